@@ -0,0 +1,40 @@
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Embeds the git commit and the build time, read by `utils::build_info`.
+/// `GIT_COMMIT` and `SOURCE_DATE_EPOCH` take precedence, e.g. for builds without a checkout.
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = std::env::var("GIT_COMMIT").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    });
+    if let Some(commit) = commit.filter(|c| !c.is_empty()) {
+        println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit);
+    }
+
+    let built_at = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs())
+        });
+    if let Some(built_at) = built_at {
+        println!("cargo:rustc-env=BUILD_TIMESTAMP={}", built_at);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod audit_event;
+pub mod audit_filter;
+pub mod audit_log;
+pub mod chain_hash;
@@ -0,0 +1,300 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::utils::{id::Id, trace_id::TraceId};
+
+use super::chain_hash::ChainHash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    Logout,
+    TotpEnabled,
+    TotpDisabled,
+    TokenCreated,
+    TokenRevoked,
+    RightsChanged,
+    FileRead,
+    FileWritten,
+    FileDeleted,
+    FileMoved,
+    DirectoryCreated,
+    VersionRestored,
+    VersionDeleted,
+    DataKeysRotated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// What was done, by whom and from where, before it has a place in the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    action: AuditAction,
+    outcome: AuditOutcome,
+    /// Why it failed, e.g. `invalid_credentials`
+    reason: Option<String>,
+    principal: Option<Id>,
+    trace_id: TraceId,
+    client_ip: Option<IpAddr>,
+    source_id: Option<Uuid>,
+    /// Path in the source, or the username, token or login the action is about
+    target: Option<String>,
+    /// Destination of a move, version of a version action or the rights granted
+    detail: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, trace_id: TraceId) -> Self {
+        Self {
+            action,
+            outcome: AuditOutcome::Success,
+            reason: None,
+            principal: None,
+            trace_id,
+            client_ip: None,
+            source_id: None,
+            target: None,
+            detail: None,
+        }
+    }
+
+    pub fn failed(mut self, reason: &str) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self.reason = Some(reason.to_owned());
+        self
+    }
+
+    pub fn with_principal(mut self, principal: Option<Id>) -> Self {
+        self.principal = principal;
+        self
+    }
+
+    pub fn with_client_ip(mut self, client_ip: Option<IpAddr>) -> Self {
+        self.client_ip = client_ip;
+        self
+    }
+
+    pub fn with_source(mut self, source_id: Uuid) -> Self {
+        self.source_id = Some(source_id);
+        self
+    }
+
+    pub fn with_target(mut self, target: impl ToString) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl ToString) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn action(&self) -> AuditAction {
+        self.action
+    }
+
+    pub fn outcome(&self) -> AuditOutcome {
+        self.outcome
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn principal(&self) -> Option<Id> {
+        self.principal
+    }
+
+    pub fn trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    pub fn source_id(&self) -> Option<Uuid> {
+        self.source_id
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+/// Entry of the append-only audit log. Its hash covers its content and the hash of
+/// the event before it, so changing or removing an event breaks every later hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent {
+    /// Position in the log, starting at 1 without gaps
+    sequence: u64,
+    timestamp: DateTime<Utc>,
+    entry: AuditEntry,
+    previous_hash: ChainHash,
+    hash: ChainHash,
+}
+
+impl AuditEvent {
+    /// The event following `previous`, or the first one. Timestamps keep milliseconds only.
+    pub fn append(
+        previous: Option<&AuditEvent>,
+        timestamp: DateTime<Utc>,
+        entry: AuditEntry,
+    ) -> Self {
+        let sequence = previous.map_or(1, |p| p.sequence + 1);
+        let previous_hash = previous.map_or(ChainHash::GENESIS, |p| p.hash);
+        let timestamp = timestamp.trunc_subsecs(3);
+        let hash = hash(sequence, timestamp, &entry, previous_hash);
+        Self {
+            sequence,
+            timestamp,
+            entry,
+            previous_hash,
+            hash,
+        }
+    }
+
+    /// As stored, the hash is checked by [verify_chain]
+    pub fn from_parts(
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        entry: AuditEntry,
+        previous_hash: ChainHash,
+        hash: ChainHash,
+    ) -> Self {
+        Self {
+            sequence,
+            timestamp,
+            entry,
+            previous_hash,
+            hash,
+        }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn entry(&self) -> &AuditEntry {
+        &self.entry
+    }
+
+    pub fn previous_hash(&self) -> ChainHash {
+        self.previous_hash
+    }
+
+    pub fn hash(&self) -> ChainHash {
+        self.hash
+    }
+
+    fn is_intact(&self) -> bool {
+        self.hash
+            == hash(
+                self.sequence,
+                self.timestamp,
+                &self.entry,
+                self.previous_hash,
+            )
+    }
+}
+
+fn hash(
+    sequence: u64,
+    timestamp: DateTime<Utc>,
+    entry: &AuditEntry,
+    previous_hash: ChainHash,
+) -> ChainHash {
+    let content =
+        serde_json::to_vec(&(sequence, timestamp.timestamp_millis(), entry, previous_hash))
+            .expect("audit entries always serialize");
+    ChainHash::new(Sha256::digest(content).into())
+}
+
+/// Checks consecutive events, starting after `previous` or at the beginning of the log.
+/// Fails with the sequence number of the first event which has been tampered with.
+pub fn verify_chain<'a>(
+    previous: Option<&AuditEvent>,
+    events: impl IntoIterator<Item = &'a AuditEvent>,
+) -> Result<(), u64> {
+    let mut expected = (
+        previous.map_or(1, |p| p.sequence + 1),
+        previous.map_or(ChainHash::GENESIS, |p| p.hash),
+    );
+    for event in events {
+        if (event.sequence, event.previous_hash) != expected || !event.is_intact() {
+            return Err(expected.0);
+        }
+        expected = (event.sequence + 1, event.hash);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn chain(count: usize) -> Vec<AuditEvent> {
+        let mut events: Vec<AuditEvent> = Vec::new();
+        for i in 0..count {
+            let entry = AuditEntry::new(AuditAction::FileRead, TraceId::default())
+                .with_target(format!("/file{}.txt", i));
+            events.push(AuditEvent::append(events.last(), utc!(2024, 5, 1), entry));
+        }
+        events
+    }
+
+    #[test]
+    fn links_events() {
+        let events = chain(3);
+
+        assert_eq!(
+            events.iter().map(|e| e.sequence()).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(events[0].previous_hash(), ChainHash::GENESIS);
+        assert_eq!(events[2].previous_hash(), events[1].hash());
+        assert_eq!(verify_chain(None, &events), Ok(()));
+        assert_eq!(verify_chain(Some(&events[0]), &events[1..]), Ok(()));
+    }
+
+    #[test]
+    fn detects_changed_and_removed_events() {
+        let events = chain(4);
+        let mut changed = events.clone();
+        let e = &changed[1];
+        changed[1] = AuditEvent::from_parts(
+            e.sequence(),
+            e.timestamp(),
+            e.entry().clone().with_target("/other.txt"),
+            e.previous_hash(),
+            e.hash(),
+        );
+        let mut removed = events.clone();
+        removed.remove(2);
+
+        assert_eq!(verify_chain(None, &changed), Err(2));
+        assert_eq!(verify_chain(None, &removed), Err(3));
+        assert_eq!(verify_chain(None, &events[1..]), Err(1));
+    }
+}
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::utils::id::Id;
+
+use super::audit_event::{AuditAction, AuditEvent, AuditOutcome};
+
+/// Events matching every criterion which is set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub principal: Option<Id>,
+    pub action: Option<AuditAction>,
+    pub outcome: Option<AuditOutcome>,
+    pub source_id: Option<Uuid>,
+    /// Start of the target, e.g. a directory for everything below it
+    pub target_prefix: Option<String>,
+    /// Inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        let entry = event.entry();
+        self.principal.is_none_or(|p| entry.principal() == Some(p))
+            && self.action.is_none_or(|a| entry.action() == a)
+            && self.outcome.is_none_or(|o| entry.outcome() == o)
+            && self.source_id.is_none_or(|s| entry.source_id() == Some(s))
+            && self.target_prefix.as_ref().is_none_or(|prefix| {
+                entry
+                    .target()
+                    .is_some_and(|t| t.starts_with(prefix.as_str()))
+            })
+            && self.from.is_none_or(|from| event.timestamp() >= from)
+            && self.to.is_none_or(|to| event.timestamp() < to)
+    }
+}
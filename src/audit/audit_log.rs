@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+
+use crate::dal::{audit_events_dal::AuditEventsDal, dal_error::DalError};
+
+use super::audit_event::{AuditEntry, AuditEvent};
+
+/// Concurrent appends race for the next sequence number, the losers chain again
+const MAX_ATTEMPTS: usize = 10;
+
+/// Chains the entry to the last event of the log and stores it
+pub async fn append<A: AuditEventsDal>(
+    events: &A,
+    now: DateTime<Utc>,
+    entry: AuditEntry,
+) -> Result<AuditEvent, DalError> {
+    for _ in 0..MAX_ATTEMPTS {
+        let last = events.last().await?;
+        let event = AuditEvent::append(last.as_ref(), now, entry.clone());
+        match events.append(event.clone()).await {
+            Ok(()) => return Ok(event),
+            Err(DalError::Conflict(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(DalError::Unexpected(format!(
+        "audit event not appended after {} attempts",
+        MAX_ATTEMPTS
+    )))
+}
@@ -1,7 +1,15 @@
+pub mod basic_auth_cache;
 pub mod content_right;
 pub mod login;
+pub mod login_lockout;
 pub mod login_right;
+pub mod login_totp;
+pub mod oidc;
+pub mod personal_access_token;
 pub mod principal;
 pub mod pwd;
 pub mod pwd_alg;
+pub mod recovery_code;
+pub mod token_scope;
 pub mod tokens;
+pub mod totp;
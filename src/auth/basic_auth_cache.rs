@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+use super::pwd::Pwd;
+
+type CacheKey = [u8; 32];
+
+/// Remembers successful Basic credential checks, so the password hash isn't computed on every request.
+///
+/// Entries are keyed by an HMAC of the credentials and of the stored password hash with a random
+/// per process key, plain passwords are never kept. A password change invalidates the entries
+/// of the login, because its stored hash changes.
+pub struct BasicAuthCache {
+    ttl: Duration,
+    capacity: usize,
+    key: [u8; 32],
+    entries: Mutex<HashMap<CacheKey, DateTime<Utc>>>,
+}
+
+impl BasicAuthCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        Self {
+            ttl,
+            capacity,
+            key,
+            entries: Default::default(),
+        }
+    }
+
+    /// Returns `true` if the password has recently been verified against the stored one
+    pub fn verified(&self, username: &str, password: &str, pwd: &Pwd, now: DateTime<Utc>) -> bool {
+        let key = self.cache_key(username, password, pwd);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(expires_at) if *expires_at > now => true,
+            Some(_) => {
+                entries.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&self, username: &str, password: &str, pwd: &Pwd, now: DateTime<Utc>) {
+        if self.capacity == 0 || self.ttl <= Duration::zero() {
+            return;
+        }
+        let key = self.cache_key(username, password, pwd);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.retain(|_, expires_at| *expires_at > now);
+        }
+        if entries.len() >= self.capacity {
+            entries.clear();
+        }
+        entries.insert(key, now + self.ttl);
+    }
+
+    fn cache_key(&self, username: &str, password: &str, pwd: &Pwd) -> CacheKey {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("any key size is valid");
+        mac.update(&(username.len() as u64).to_be_bytes());
+        mac.update(username.as_bytes());
+        mac.update(&(password.len() as u64).to_be_bytes());
+        mac.update(password.as_bytes());
+        mac.update(pwd.hash());
+        mac.finalize().into_bytes().into()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::pwd_alg::{Argon2Params, PwdAlg},
+        test::*,
+        utc,
+    };
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn pwd(password: &str) -> Pwd {
+        Pwd::new(password, PwdAlg::Argon2id(Argon2Params::new(8, 1, 1)))
+    }
+
+    #[test]
+    fn remembers_credentials_until_ttl() {
+        let cache = BasicAuthCache::new(Duration::seconds(60), 10);
+        let pwd = pwd("password");
+        cache.insert("user", "password", &pwd, utc!(2000));
+
+        assert!(cache.verified("user", "password", &pwd, utc!(2000)));
+        assert!(!cache.verified("user", "wrong", &pwd, utc!(2000)));
+        assert!(!cache.verified("userp", "assword", &pwd, utc!(2000)));
+        assert!(!cache.verified("user", "password", &pwd, utc!(2000, 1, 1, 0, 1)));
+    }
+
+    #[test]
+    fn password_change_invalidates_credentials() {
+        let cache = BasicAuthCache::new(Duration::seconds(60), 10);
+        cache.insert("user", "password", &pwd("password"), utc!(2000));
+
+        assert!(!cache.verified("user", "password", &pwd("password"), utc!(2000)));
+    }
+
+    #[test]
+    fn capacity_is_not_exceeded() {
+        let cache = BasicAuthCache::new(Duration::seconds(60), 2);
+        let pwd = pwd("password");
+        for i in 0..5 {
+            cache.insert(&i.to_string(), "password", &pwd, utc!(2000));
+        }
+
+        assert!(cache.entries.lock().unwrap().len() <= 2);
+        assert!(cache.verified("4", "password", &pwd, utc!(2000)));
+    }
+}
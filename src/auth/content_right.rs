@@ -1,6 +1,7 @@
 use bitflags::bitflags;
+use serde::{de, ser::SerializeSeq, Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ContentRight(u32);
 
 bitflags! {
@@ -11,3 +12,63 @@ bitflags! {
         const All = !0;
     }
 }
+
+const NAMES: [(&str, ContentRight); 2] =
+    [("read", ContentRight::Read), ("write", ContentRight::Write)];
+
+/// Serialized as a list of lowercase right names, e.g. `["read", "write"]`
+impl Serialize for ContentRight {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<_> = NAMES
+            .iter()
+            .filter(|(_, right)| self.contains(*right))
+            .map(|(name, _)| *name)
+            .collect();
+        let mut seq = serializer.serialize_seq(Some(names.len()))?;
+        for name in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentRight {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(ContentRight::None, |acc, name| {
+            let right = match name.as_str() {
+                "all" => ContentRight::All,
+                name => NAMES
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, right)| *right)
+                    .ok_or_else(|| de::Error::unknown_variant(name, &["read", "write", "all"]))?,
+            };
+            Ok(acc | right)
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn serde() {
+        let json = serde_json::to_string(&(ContentRight::Read | ContentRight::Write)).unwrap();
+        assert_str_eq!(json, r#"["read","write"]"#);
+
+        let json = serde_json::to_string(&ContentRight::None).unwrap();
+        assert_str_eq!(json, "[]");
+
+        let right: ContentRight = serde_json::from_str(r#"["write"]"#).unwrap();
+        assert_eq!(right, ContentRight::Write);
+
+        let right: ContentRight = serde_json::from_str(r#"["all"]"#).unwrap();
+        assert_eq!(right, ContentRight::All);
+
+        assert!(serde_json::from_str::<ContentRight>(r#"["delete"]"#).is_err());
+    }
+}
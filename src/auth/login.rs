@@ -1,9 +1,40 @@
 use super::pwd::Pwd;
 use crate::utils::{id::Id, secret::Secret};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Login {
     login_id: Id,
     username: Secret<String>,
-    password: Pwd,
+    /// Absent for logins which authenticate only through an external identity provider
+    password: Option<Pwd>,
+}
+
+impl Login {
+    pub fn new(login_id: Id, username: String, password: Pwd) -> Self {
+        Self {
+            login_id,
+            username: Secret::new(username),
+            password: Some(password),
+        }
+    }
+
+    pub fn without_password(login_id: Id, username: String) -> Self {
+        Self {
+            login_id,
+            username: Secret::new(username),
+            password: None,
+        }
+    }
+
+    pub fn login_id(&self) -> Id {
+        self.login_id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn password(&self) -> Option<&Pwd> {
+        self.password.as_ref()
+    }
 }
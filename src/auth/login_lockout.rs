@@ -0,0 +1,72 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::app_config::LockoutConfig,
+    dal::dal_error::DalError,
+    rate_limit::{rate_limit_key::RateLimitKey, rate_limit_store::RateLimitStore},
+};
+
+/// Brute-force protection of the login endpoints.
+/// Failures are counted per username and per client IP independently.
+pub struct LoginLockout<'a, S> {
+    store: &'a S,
+    config: &'a LockoutConfig,
+}
+
+impl<'a, S: RateLimitStore> LoginLockout<'a, S> {
+    pub fn new(store: &'a S, config: &'a LockoutConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// The moment the latest of the username and the IP lockouts ends
+    pub async fn locked_until(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, DalError> {
+        let mut until = self
+            .store
+            .locked_until(&RateLimitKey::LoginUsername(username), now)
+            .await?;
+        if let Some(ip) = ip {
+            let ip_until = self
+                .store
+                .locked_until(&RateLimitKey::LoginIp(ip), now)
+                .await?;
+            until = until.max(ip_until);
+        }
+        Ok(until)
+    }
+
+    pub async fn failed(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Result<(), DalError> {
+        let key = RateLimitKey::LoginUsername(username);
+        let max = self.config.max_failures_per_username();
+        self.store
+            .record_failure(&key, max, self.config, now)
+            .await?;
+        if let Some(ip) = ip {
+            let key = RateLimitKey::LoginIp(ip);
+            let max = self.config.max_failures_per_ip();
+            self.store
+                .record_failure(&key, max, self.config, now)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Forgets failures of the username. IP failures stay, so one valid account
+    /// can't be used to reset the counter while guessing others.
+    pub async fn succeeded(&self, username: &str, now: DateTime<Utc>) -> Result<(), DalError> {
+        self.store
+            .reset(&RateLimitKey::LoginUsername(username), now)
+            .await
+    }
+}
@@ -2,8 +2,22 @@ use crate::utils::id::Id;
 
 use super::content_right::ContentRight;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LoginRight {
     login_id: Id,
     right: ContentRight,
 }
+
+impl LoginRight {
+    pub fn new(login_id: Id, right: ContentRight) -> Self {
+        Self { login_id, right }
+    }
+
+    pub fn login_id(&self) -> Id {
+        self.login_id
+    }
+
+    pub fn right(&self) -> ContentRight {
+        self.right
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+
+use super::{recovery_code::RecoveryCodeHash, totp::TotpSecret};
+use crate::utils::id::Id;
+
+/// TOTP second factor of a login. It's enforced only after it has been confirmed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoginTotp {
+    login_id: Id,
+    secret: TotpSecret,
+    confirmed_at: Option<DateTime<Utc>>,
+    last_used_step: Option<u64>,
+    recovery_codes: Vec<RecoveryCodeHash>,
+}
+
+impl LoginTotp {
+    pub fn new(login_id: Id, secret: TotpSecret) -> Self {
+        Self {
+            login_id,
+            secret,
+            confirmed_at: None,
+            last_used_step: None,
+            recovery_codes: Vec::new(),
+        }
+    }
+
+    pub fn login_id(&self) -> Id {
+        self.login_id
+    }
+
+    pub fn secret(&self) -> &TotpSecret {
+        &self.secret
+    }
+
+    pub fn confirmed_at(&self) -> Option<DateTime<Utc>> {
+        self.confirmed_at
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+
+    pub fn recovery_codes_left(&self) -> usize {
+        self.recovery_codes.len()
+    }
+
+    pub fn confirm(&mut self, now: DateTime<Utc>, recovery_codes: Vec<RecoveryCodeHash>) {
+        self.confirmed_at = Some(now);
+        self.recovery_codes = recovery_codes;
+    }
+
+    /// Verifies the code and remembers its step, so the same code can't be used twice
+    pub fn verify_code(&mut self, code: &str, now: DateTime<Utc>, skew: u64) -> bool {
+        match self.secret.verify(code, now, skew, self.last_used_step) {
+            Some(step) => {
+                self.last_used_step = Some(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consumes the recovery code if it exists
+    pub fn use_recovery_code(&mut self, code: &str) -> bool {
+        let hash = RecoveryCodeHash::of(code);
+        match self.recovery_codes.iter().position(|c| *c == hash) {
+            Some(index) => {
+                self.recovery_codes.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Accepts either a TOTP code or a recovery code
+    pub fn verify_code_or_recovery_code(
+        &mut self,
+        code: &str,
+        now: DateTime<Utc>,
+        skew: u64,
+    ) -> bool {
+        self.verify_code(code, now, skew) || self.use_recovery_code(code)
+    }
+}
@@ -0,0 +1,7 @@
+pub mod external_identity;
+pub mod id_token_claims;
+pub mod oidc_auth_request;
+pub mod oidc_client;
+pub mod oidc_providers;
+pub mod pkce;
+pub mod provider_metadata;
@@ -0,0 +1,31 @@
+use crate::utils::id::Id;
+
+/// Links a subject of an identity provider to a local login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalIdentity {
+    provider: String,
+    subject: String,
+    login_id: Id,
+}
+
+impl ExternalIdentity {
+    pub fn new(provider: String, subject: String, login_id: Id) -> Self {
+        Self {
+            provider,
+            subject,
+            login_id,
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn login_id(&self) -> Id {
+        self.login_id
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+use crate::{auth::tokens::encoder::Expiring, web::common::serde_chrono::ApiDateTimeSeconds};
+
+/// Claims of an OpenID Connect ID token. `aud` is validated while decoding and is kept in `other`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub exp: ApiDateTimeSeconds,
+    pub iat: ApiDateTimeSeconds,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl IdTokenClaims {
+    pub fn string_claim(&self, name: &str) -> Option<&str> {
+        self.other.get(name).and_then(Value::as_str)
+    }
+
+    /// Accepts both a single string and an array of strings
+    pub fn string_list_claim(&self, name: &str) -> Vec<&str> {
+        match self.other.get(name) {
+            Some(Value::String(value)) => vec![value.as_str()],
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Expiring for IdTokenClaims {
+    fn exp(&self) -> DateTime<Utc> {
+        *self.exp
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn list_claim_accepts_string_and_array() {
+        let claims: IdTokenClaims = serde_json::from_value(serde_json::json!({
+            "iss": "https://idp",
+            "sub": "subject",
+            "aud": "client",
+            "exp": 1,
+            "iat": 0,
+            "single": "admins",
+            "many": ["admins", 5, "users"],
+        }))
+        .unwrap();
+
+        assert_eq!(claims.string_list_claim("single"), vec!["admins"]);
+        assert_eq!(claims.string_list_claim("many"), vec!["admins", "users"]);
+        assert!(claims.string_list_claim("missing").is_empty());
+        assert_eq!(claims.string_claim("aud"), Some("client"));
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+
+use super::pkce::PkceVerifier;
+
+/// A started sign-in which waits for the provider callback. Found by `state` and used once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcAuthRequest {
+    state: String,
+    provider: String,
+    nonce: String,
+    verifier: PkceVerifier,
+    expires_at: DateTime<Utc>,
+}
+
+impl OidcAuthRequest {
+    pub fn new(
+        state: String,
+        provider: String,
+        nonce: String,
+        verifier: PkceVerifier,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            state,
+            provider,
+            nonce,
+            verifier,
+            expires_at,
+        }
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    pub fn verifier(&self) -> &PkceVerifier {
+        &self.verifier
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Duration as StdDuration,
+};
+
+use actix_web::http::{header, Method, Uri};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::de::DeserializeOwned;
+
+use crate::config::app_config::OidcProviderConfig;
+
+use super::{
+    id_token_claims::IdTokenClaims, pkce::PkceVerifier, provider_metadata::ProviderMetadata,
+};
+
+const TIMEOUT: StdDuration = StdDuration::from_secs(10);
+/// Tokens with unknown keys reload the JWKS at most this often, so they can't make us hammer
+/// the provider
+const MIN_RELOAD_INTERVAL_SECONDS: i64 = 60;
+/// Everything except the unreserved characters of RFC 3986
+const FORM_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum OidcError {
+    /// The provider is unreachable or has answered with an error
+    #[display("provider error: {_0}")]
+    Provider(#[error(not(source))] String),
+
+    #[display("invalid token: {_0}")]
+    InvalidToken(#[error(not(source))] String),
+}
+
+#[derive(Debug)]
+struct Discovered {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// Authorization code flow with one provider. The discovery document and the JWKS are loaded lazily
+/// and reloaded when an ID token is signed by an unknown key, at most once a minute.
+pub struct OidcClient {
+    config: OidcProviderConfig,
+    clock_skew: Duration,
+    discovered: RwLock<Option<Arc<Discovered>>>,
+    last_reload: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcProviderConfig, clock_skew: Duration) -> Self {
+        Self {
+            config,
+            clock_skew,
+            discovered: RwLock::new(None),
+            last_reload: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &OidcProviderConfig {
+        &self.config
+    }
+
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        verifier: &PkceVerifier,
+    ) -> Result<String, OidcError> {
+        let discovered = self.discovered(false).await?;
+        let endpoint = &discovered.metadata.authorization_endpoint;
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        let query = form_encode(&[
+            ("response_type", "code"),
+            ("client_id", self.config.client_id()),
+            ("redirect_uri", self.config.redirect_uri()),
+            ("scope", &self.config.scopes().join(" ")),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", &verifier.challenge()),
+            ("code_challenge_method", "S256"),
+        ]);
+        Ok(format!("{}{}{}", endpoint, separator, query))
+    }
+
+    /// Returns the raw ID token, it has to be validated with [OidcClient::validate_id_token]
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        verifier: &PkceVerifier,
+    ) -> Result<String, OidcError> {
+        let discovered = self.discovered(false).await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri()),
+            ("client_id", self.config.client_id()),
+            ("code_verifier", verifier.as_str()),
+        ];
+        if let Some(secret) = self.config.client_secret() {
+            form.push(("client_secret", secret));
+        }
+        let response: TokenResponse = fetch_json(
+            Method::POST,
+            &discovered.metadata.token_endpoint,
+            Some(form_encode(&form)),
+        )
+        .await?;
+        response
+            .id_token
+            .ok_or_else(|| OidcError::Provider("token response has no id_token".to_owned()))
+    }
+
+    pub async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+        now: DateTime<Utc>,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token).map_err(invalid_token)?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::InvalidToken(format!(
+                "{:?} is not accepted",
+                header.alg
+            )));
+        }
+
+        let mut discovered = self.discovered(false).await?;
+        if find_key(&discovered.jwks, header.kid.as_deref()).is_none() && self.reload_due(now) {
+            // keys may have been rotated
+            discovered = self.discovered(true).await?;
+        }
+        let jwk = find_key(&discovered.jwks, header.kid.as_deref())
+            .ok_or_else(|| OidcError::InvalidToken("unknown signing key".to_owned()))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(invalid_token)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.validate_exp = false;
+        validation.set_audience(&[self.config.client_id()]);
+        validation.set_issuer(&[&discovered.metadata.issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(invalid_token)?
+            .claims;
+
+        if *claims.exp + self.clock_skew <= now {
+            return Err(OidcError::InvalidToken("expired".to_owned()));
+        }
+        if *claims.iat - self.clock_skew > now {
+            return Err(OidcError::InvalidToken("issued in the future".to_owned()));
+        }
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidToken("nonce mismatch".to_owned()));
+        }
+        Ok(claims)
+    }
+
+    /// Whether an unknown key may reload the JWKS now, takes the reload if so
+    fn reload_due(&self, now: DateTime<Utc>) -> bool {
+        let mut last_reload = self.last_reload.lock().unwrap();
+        if last_reload
+            .is_some_and(|last| now - last < Duration::seconds(MIN_RELOAD_INTERVAL_SECONDS))
+        {
+            return false;
+        }
+        *last_reload = Some(now);
+        true
+    }
+
+    async fn discovered(&self, reload: bool) -> Result<Arc<Discovered>, OidcError> {
+        if !reload {
+            if let Some(discovered) = self.discovered.read().unwrap().as_ref() {
+                return Ok(discovered.clone());
+            }
+        }
+
+        let issuer = self.config.issuer().trim_end_matches('/');
+        let metadata: ProviderMetadata = fetch_json(
+            Method::GET,
+            &format!("{}/.well-known/openid-configuration", issuer),
+            None,
+        )
+        .await?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(OidcError::Provider(format!(
+                "discovery document is for issuer '{}'",
+                metadata.issuer
+            )));
+        }
+        let jwks: JwkSet = fetch_json(Method::GET, &metadata.jwks_uri, None).await?;
+        tracing::info!(
+            provider = self.config.name(),
+            keys = jwks.keys.len(),
+            "provider metadata loaded"
+        );
+
+        let discovered = Arc::new(Discovered { metadata, jwks });
+        *self.discovered.write().unwrap() = Some(discovered.clone());
+        Ok(discovered)
+    }
+}
+
+/// Without `kid` only a single key is unambiguous
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+fn form_encode(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(k, FORM_VALUE),
+                utf8_percent_encode(v, FORM_VALUE)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+async fn fetch_json<T: DeserializeOwned>(
+    method: Method,
+    url: &str,
+    form: Option<String>,
+) -> Result<T, OidcError> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|e| OidcError::Provider(format!("invalid url '{}': {}", url, e)))?;
+    let mut request = awc::Client::default()
+        .request(method, uri)
+        .timeout(TIMEOUT)
+        .insert_header((header::ACCEPT, "application/json"));
+    if form.is_some() {
+        request =
+            request.insert_header((header::CONTENT_TYPE, "application/x-www-form-urlencoded"));
+    }
+    let mut response = request
+        .send_body(form.unwrap_or_default())
+        .await
+        .map_err(|e| OidcError::Provider(format!("{} failed: {}", url, e)))?;
+    let body = response
+        .body()
+        .await
+        .map_err(|e| OidcError::Provider(format!("{} failed: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(OidcError::Provider(format!(
+            "{} answered {}: {}",
+            url,
+            response.status(),
+            String::from_utf8_lossy(&body)
+        )));
+    }
+    serde_json::from_slice(&body)
+        .map_err(|e| OidcError::Provider(format!("{} returned unexpected json: {}", url, e)))
+}
+
+fn invalid_token(err: jsonwebtoken::errors::Error) -> OidcError {
+    OidcError::InvalidToken(err.to_string())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{mock_idp::MockIdp, *},
+        utc,
+    };
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    fn client(idp: &MockIdp) -> OidcClient {
+        let config = serde_json::from_value(idp.provider_config("corp")).unwrap();
+        OidcClient::new(config, Duration::seconds(60))
+    }
+
+    #[test]
+    fn validates_id_token() {
+        test(|_| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let client = client(&idp);
+            let now = utc!(2024, 5, 6);
+            let mut claims = idp.claims("subject", now);
+            claims["aud"] = json!(["other-client", "test-client"]);
+            claims["nonce"] = json!("nonce");
+            claims["email"] = json!("user@example.com");
+
+            // act
+            let claims = client
+                .validate_id_token(&idp.sign(&claims), "nonce", now)
+                .await
+                .unwrap();
+
+            // assert
+            assert_str_eq!(claims.sub, "subject");
+            assert_eq!(claims.string_claim("email"), Some("user@example.com"));
+        });
+    }
+
+    #[test]
+    fn rejects_invalid_id_tokens() {
+        test(|_| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let client = client(&idp);
+            let now = utc!(2024, 5, 6);
+            let mut valid = idp.claims("subject", now);
+            valid["aud"] = json!("test-client");
+            valid["nonce"] = json!("nonce");
+            let with = |name: &str, value| {
+                let mut claims = valid.clone();
+                claims[name] = value;
+                idp.sign(&claims)
+            };
+            let hs256 = jsonwebtoken::encode(
+                &jsonwebtoken::Header::new(Algorithm::HS256),
+                &valid,
+                &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+            )
+            .unwrap();
+            let cases = [
+                ("audience", with("aud", json!("other-client"))),
+                ("issuer", with("iss", json!("http://evil"))),
+                ("nonce", with("nonce", json!("other"))),
+                ("expired", with("exp", json!(now.timestamp() - 61))),
+                ("future", with("iat", json!(now.timestamp() + 61))),
+                ("hs256", hs256),
+            ];
+
+            for (case, token) in cases {
+                // act
+                let result = client.validate_id_token(&token, "nonce", now).await;
+
+                // assert
+                assert!(
+                    matches!(result, Err(OidcError::InvalidToken(_))),
+                    "{}: {:?}",
+                    case,
+                    result
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn unknown_keys_reload_jwks_at_most_once_a_minute() {
+        test(|_| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let client = client(&idp);
+            let now = utc!(2024, 5, 6);
+            let mut claims = idp.claims("subject", now + Duration::minutes(2));
+            claims["aud"] = json!("test-client");
+            claims["nonce"] = json!("nonce");
+            let token = idp.sign_with_key_id(&claims, "rotated");
+
+            // act
+            let mut requests = Vec::new();
+            for at in [
+                now,
+                now + Duration::seconds(30),
+                now + Duration::seconds(61),
+            ] {
+                let result = client.validate_id_token(&token, "nonce", at).await;
+                assert!(matches!(result, Err(OidcError::InvalidToken(_))));
+                requests.push(idp.jwks_requests());
+            }
+
+            // assert
+            assert_eq!(requests, vec![2, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn authorization_url_has_pkce_challenge() {
+        test(|_| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let client = client(&idp);
+            let verifier = PkceVerifier::from_string("verifier".to_owned());
+
+            // act
+            let url = client
+                .authorization_url("state", "nonce", &verifier)
+                .await
+                .unwrap();
+
+            // assert
+            assert_str_eq!(
+                url,
+                format!(
+                    "{}/authorize?response_type=code&client_id=test-client\
+                    &redirect_uri=http%3A%2F%2Flocalhost%2Foidc%2Fcallback\
+                    &scope=openid%20profile%20email&state=state&nonce=nonce\
+                    &code_challenge={}&code_challenge_method=S256",
+                    idp.issuer(),
+                    verifier.challenge()
+                )
+            );
+        });
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use crate::config::app_config::OidcConfig;
+
+use super::oidc_client::OidcClient;
+
+/// Configured identity providers by name
+#[derive(Default)]
+pub struct OidcProviders(HashMap<String, OidcClient>);
+
+impl OidcProviders {
+    pub fn from_config(config: &OidcConfig) -> Self {
+        Self(
+            config
+                .providers()
+                .iter()
+                .map(|p| {
+                    (
+                        p.name().to_owned(),
+                        OidcClient::new(p.clone(), config.clock_skew()),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcClient> {
+        self.0.get(name)
+    }
+}
@@ -0,0 +1,63 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::utils::secret::Secret;
+
+/// Random URL safe value, used for PKCE verifiers, `state` and `nonce`
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// PKCE code verifier (RFC 7636). Stays on the server, only its challenge is sent to the provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkceVerifier(Secret<String>);
+
+impl PkceVerifier {
+    pub fn generate() -> Self {
+        Self(Secret::new(random_token()))
+    }
+
+    pub fn from_string(value: String) -> Self {
+        Self(Secret::new(value))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// `S256` challenge
+    pub fn challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn rfc7636_challenge() {
+        let verifier =
+            PkceVerifier::from_string("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_owned());
+
+        assert_str_eq!(
+            verifier.challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn generated_verifiers_differ() {
+        let a = PkceVerifier::generate();
+        let b = PkceVerifier::generate();
+
+        assert_ne!(a, b);
+        assert_eq!(a.as_str().len(), 43);
+    }
+}
@@ -0,0 +1,8 @@
+/// The part of the discovery document (`/.well-known/openid-configuration`) used for the code flow
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{content_right::ContentRight, token_scope::TokenScope};
+use crate::{
+    fs::storage_path::StoragePath,
+    utils::{id::Id, secret::Secret},
+};
+
+/// Every token starts with it, so it can be told apart from a JWT without decoding
+pub const TOKEN_PREFIX: &str = "rhfs_";
+const TOKEN_BYTES: usize = 32;
+
+/// Long-lived token for automation. Only the hash of the token is stored.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PersonalAccessToken {
+    token_id: Id,
+    login_id: Id,
+    name: String,
+    hash: TokenHash,
+    rights: ContentRight,
+    sources: Option<Vec<Uuid>>,
+    /// Limits the token to this path and everything below it, e.g. for a share link
+    path: Option<StoragePath>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct TokenHash([u8; 32]);
+
+impl TokenHash {
+    pub fn of(token: &str) -> Self {
+        Self(Sha256::digest(token.as_bytes()).into())
+    }
+}
+
+/// Generates a new random token
+pub fn generate_token() -> Secret<String> {
+    let mut bytes = [0; TOKEN_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let encoded = base32::encode(base32::Alphabet::Rfc4648Lower { padding: false }, &bytes);
+    Secret::new(format!("{}{}", TOKEN_PREFIX, encoded))
+}
+
+pub fn is_personal_access_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+impl PersonalAccessToken {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        token_id: Id,
+        login_id: Id,
+        name: String,
+        token: &str,
+        rights: ContentRight,
+        sources: Option<Vec<Uuid>>,
+        created_at: DateTime<Utc>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            token_id,
+            login_id,
+            name,
+            hash: TokenHash::of(token),
+            rights,
+            sources,
+            path: None,
+            created_at,
+            expires_at,
+            last_used_at: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: Option<StoragePath>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn token_id(&self) -> Id {
+        self.token_id
+    }
+
+    pub fn login_id(&self) -> Id {
+        self.login_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn hash(&self) -> &TokenHash {
+        &self.hash
+    }
+
+    pub fn rights(&self) -> ContentRight {
+        self.rights
+    }
+
+    pub fn sources(&self) -> Option<&[Uuid]> {
+        self.sources.as_deref()
+    }
+
+    pub fn path(&self) -> Option<&StoragePath> {
+        self.path.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    pub fn last_used_at(&self) -> Option<DateTime<Utc>> {
+        self.last_used_at
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|exp| exp <= now)
+    }
+
+    pub fn scope(&self) -> TokenScope {
+        TokenScope::new(self.rights, self.sources.clone()).with_path(self.path.clone())
+    }
+
+    pub fn set_last_used_at(&mut self, now: DateTime<Utc>) {
+        self.last_used_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn generated_tokens_are_unique_and_prefixed() {
+        let first = generate_token();
+        let second = generate_token();
+
+        assert!(is_personal_access_token(&first));
+        assert_eq!(first.len(), TOKEN_PREFIX.len() + 52);
+        assert_ne!(first, second);
+        assert_ne!(TokenHash::of(&first), TokenHash::of(&second));
+    }
+}
@@ -1,11 +1,95 @@
-use super::pwd_alg::PwdAlg;
+use std::{hint::black_box, sync::LazyLock};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+
+use super::pwd_alg::{Argon2Params, PwdAlg};
 use crate::utils::secret::Secret;
 
-#[derive(Debug, PartialEq, Eq)]
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Pwd {
     alg: PwdAlg,
+    salt: PwdSalt,
     hash: PwdHash,
 }
 
-#[derive(Debug, PartialEq, Eq, derive_more::Deref)]
+#[derive(Debug, PartialEq, Eq, Clone, derive_more::Deref)]
 pub struct PwdHash(Secret<Vec<u8>>);
+
+#[derive(Debug, PartialEq, Eq, Clone, derive_more::Deref)]
+pub struct PwdSalt(Vec<u8>);
+
+impl Pwd {
+    /// Hashes the password with a fresh random salt
+    pub fn new(password: &str, alg: PwdAlg) -> Self {
+        let mut salt = vec![0; SALT_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let salt = PwdSalt(salt);
+        let hash = PwdHash(Secret::new(hash(&alg, &salt, password)));
+        Self { alg, salt, hash }
+    }
+
+    pub fn alg(&self) -> &PwdAlg {
+        &self.alg
+    }
+
+    /// The stored hash, it's different for every password change because of the fresh salt
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        let actual = hash(&self.alg, &self.salt, password);
+        actual.ct_eq(&self.hash).into()
+    }
+
+    /// Takes as long as a verification, for logins which don't exist or have no password,
+    /// so the response time doesn't tell whether they do. Never succeeds.
+    pub fn verify_missing(password: &str) -> bool {
+        static DUMMY: LazyLock<Pwd> =
+            LazyLock::new(|| Pwd::new("", PwdAlg::Argon2id(Argon2Params::default())));
+        black_box(DUMMY.verify(black_box(password)));
+        false
+    }
+}
+
+fn hash(alg: &PwdAlg, salt: &PwdSalt, password: &str) -> Vec<u8> {
+    match alg {
+        PwdAlg::Argon2id(params) => argon2id(params, salt, password),
+    }
+}
+
+fn argon2id(params: &Argon2Params, salt: &PwdSalt, password: &str) -> Vec<u8> {
+    let params = Params::new(
+        params.m() as u32,
+        params.t() as u32,
+        params.p() as u32,
+        Some(HASH_LEN),
+    )
+    .expect("invalid argon2 params");
+    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let mut out = vec![0; HASH_LEN];
+    argon
+        .hash_password_into(password.as_bytes(), salt, &mut out)
+        .expect("argon2 hashing failed");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let pwd = Pwd::new("qwerty", PwdAlg::Argon2id(Argon2Params::new(8, 1, 1)));
+
+        assert!(pwd.verify("qwerty"));
+        assert!(!pwd.verify("qwerty1"));
+        assert!(!pwd.verify(""));
+        assert!(!Pwd::verify_missing(""));
+    }
+}
@@ -1,11 +1,39 @@
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum PwdAlg {
     Argon2id(Argon2Params),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Argon2Params {
     m: usize,
     t: usize,
     p: usize,
 }
+
+impl Argon2Params {
+    pub fn new(m: usize, t: usize, p: usize) -> Self {
+        Self { m, t, p }
+    }
+
+    /// Memory size in KiB
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    /// Number of iterations
+    pub fn t(&self) -> usize {
+        self.t
+    }
+
+    /// Degree of parallelism
+    pub fn p(&self) -> usize {
+        self.p
+    }
+}
+
+impl Default for Argon2Params {
+    /// OWASP recommended minimum: 19 MiB, 2 iterations, 1 lane
+    fn default() -> Self {
+        Self::new(19 * 1024, 2, 1)
+    }
+}
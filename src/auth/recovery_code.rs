@@ -0,0 +1,70 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const GROUP_LEN: usize = 5;
+const GROUPS: usize = 2;
+
+/// One-time code which can be used instead of a TOTP code. Only the hash is stored.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecoveryCodeHash([u8; 32]);
+
+impl RecoveryCodeHash {
+    pub fn of(code: &str) -> Self {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        Self(Sha256::digest(normalized.as_bytes()).into())
+    }
+}
+
+/// Generates `count` codes formatted as `xxxxx-xxxxx` along with their hashes
+pub fn generate(count: usize) -> Vec<(String, RecoveryCodeHash)> {
+    let mut rng = rand::rngs::OsRng;
+    (0..count)
+        .map(|_| {
+            let code = (0..GROUPS)
+                .map(|_| {
+                    (0..GROUP_LEN)
+                        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("-");
+            let hash = RecoveryCodeHash::of(&code);
+            (code, hash)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn hash_ignores_case_and_separators() {
+        assert_eq!(
+            RecoveryCodeHash::of("abcde-fghjk"),
+            RecoveryCodeHash::of(" ABCDE FGHJK ")
+        );
+        assert_ne!(
+            RecoveryCodeHash::of("abcde-fghjk"),
+            RecoveryCodeHash::of("abcde-fghjm")
+        );
+    }
+
+    #[test]
+    fn generate_codes() {
+        let codes = generate(10);
+
+        assert_eq!(codes.len(), 10);
+        for (code, hash) in codes {
+            assert_eq!(code.len(), GROUPS * GROUP_LEN + 1);
+            assert_eq!(RecoveryCodeHash::of(&code), hash);
+        }
+    }
+}
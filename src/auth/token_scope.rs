@@ -0,0 +1,69 @@
+use uuid::Uuid;
+
+use super::content_right::ContentRight;
+use crate::fs::storage_path::StoragePath;
+
+/// Restrictions of the credential a request has been authenticated with.
+/// Interactive logins are unrestricted, personal access tokens carry their own scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenScope {
+    rights: ContentRight,
+    /// `None` means all sources
+    sources: Option<Vec<Uuid>>,
+    /// Limits access to this path and everything below it, `None` means whole sources
+    path: Option<StoragePath>,
+}
+
+impl TokenScope {
+    pub fn new(rights: ContentRight, sources: Option<Vec<Uuid>>) -> Self {
+        Self {
+            rights,
+            sources,
+            path: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: Option<StoragePath>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn unrestricted() -> Self {
+        Self::new(ContentRight::All, None)
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.rights == ContentRight::All && self.sources.is_none() && self.path.is_none()
+    }
+
+    pub fn rights(&self) -> ContentRight {
+        self.rights
+    }
+
+    pub fn sources(&self) -> Option<&[Uuid]> {
+        self.sources.as_deref()
+    }
+
+    pub fn path(&self) -> Option<&StoragePath> {
+        self.path.as_ref()
+    }
+
+    /// The path is allowed if it is the path of the scope or below it
+    pub fn allows_path(&self, path: &StoragePath) -> bool {
+        self.path.as_ref().is_none_or(|base| path.starts_with(base))
+    }
+
+    pub fn allows(&self, right: ContentRight, source_id: Uuid) -> bool {
+        self.rights.contains(right)
+            && self
+                .sources
+                .as_ref()
+                .is_none_or(|sources| sources.contains(&source_id))
+    }
+}
+
+impl Default for TokenScope {
+    fn default() -> Self {
+        Self::unrestricted()
+    }
+}
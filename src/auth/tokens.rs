@@ -1,3 +1,4 @@
 pub mod access_token_claims;
 pub mod encoder;
 pub mod refresh_token_claims;
+pub mod totp_challenge_claims;
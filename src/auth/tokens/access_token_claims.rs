@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 
 use crate::{utils::id::Id, web::common::serde_chrono::ApiDateTimeSeconds};
 
+use super::encoder::Expiring;
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccessTokenClaims {
     pub sub: Id,
@@ -22,3 +24,9 @@ impl AccessTokenClaims {
         *self.iat
     }
 }
+
+impl Expiring for AccessTokenClaims {
+    fn exp(&self) -> DateTime<Utc> {
+        *self.exp
+    }
+}
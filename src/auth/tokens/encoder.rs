@@ -1,11 +1,15 @@
 use std::marker::PhantomData;
 
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{errors::ErrorKind, DecodingKey, EncodingKey};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::config::app_config::TokenSecretsConfig;
 
-use super::{access_token_claims::AccessTokenClaims, refresh_token_claims::RefreshTokenClaims};
+use super::{
+    access_token_claims::AccessTokenClaims, refresh_token_claims::RefreshTokenClaims,
+    totp_challenge_claims::TotpChallengeClaims,
+};
 
 pub struct JwtTokenEncoder<T> {
     alg: jsonwebtoken::Algorithm,
@@ -24,6 +28,7 @@ const ALG: jsonwebtoken::Algorithm = jsonwebtoken::Algorithm::HS512;
 pub struct TokensEncDec {
     pub access: EncDecPair<AccessTokenClaims>,
     pub refresh: EncDecPair<RefreshTokenClaims>,
+    pub totp_challenge: EncDecPair<TotpChallengeClaims>,
 }
 
 impl TokensEncDec {
@@ -31,6 +36,7 @@ impl TokensEncDec {
         Self {
             access: EncDecPair::from_secret(config.access_secret()),
             refresh: EncDecPair::from_secret(config.refresh_secret()),
+            totp_challenge: EncDecPair::from_secret(config.totp_challenge_secret()),
         }
     }
 }
@@ -74,3 +80,26 @@ impl<T: DeserializeOwned> JwtTokenDecoder<T> {
         jsonwebtoken::decode::<T>(token, &self.key, &validation)
     }
 }
+
+/// Claims with an expiration time which is checked against [crate::utils::time::Time]
+/// instead of the system clock
+pub trait Expiring {
+    fn exp(&self) -> DateTime<Utc>;
+}
+
+impl<T: DeserializeOwned + Expiring> JwtTokenDecoder<T> {
+    pub fn decode_at<S: AsRef<str>>(
+        &self,
+        token: S,
+        now: DateTime<Utc>,
+    ) -> Result<jsonwebtoken::TokenData<T>, jsonwebtoken::errors::Error> {
+        let token = token.as_ref();
+        let mut validation = jsonwebtoken::Validation::new(self.alg);
+        validation.validate_exp = false;
+        let data = jsonwebtoken::decode::<T>(token, &self.key, &validation)?;
+        if data.claims.exp() <= now {
+            return Err(ErrorKind::ExpiredSignature.into());
+        }
+        Ok(data)
+    }
+}
@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 
 use crate::{utils::id::Id, web::common::serde_chrono::ApiDateTimeSeconds};
 
+use super::encoder::Expiring;
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct RefreshTokenClaims {
     pub sub: Id,
@@ -32,3 +34,9 @@ impl RefreshTokenClaims {
         self.sid
     }
 }
+
+impl Expiring for RefreshTokenClaims {
+    fn exp(&self) -> DateTime<Utc> {
+        *self.exp
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+
+use crate::{utils::id::Id, web::common::serde_chrono::ApiDateTimeSeconds};
+
+use super::encoder::Expiring;
+
+/// Issued after a successful password check when the login has TOTP enabled.
+/// It proves the first factor and is exchanged for a token pair together with a TOTP code.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TotpChallengeClaims {
+    pub jti: Id,
+    pub sub: Id,
+    pub exp: ApiDateTimeSeconds,
+    pub iat: ApiDateTimeSeconds,
+}
+
+impl TotpChallengeClaims {
+    pub fn jti(&self) -> Id {
+        self.jti
+    }
+
+    pub fn sub(&self) -> Id {
+        self.sub
+    }
+
+    pub fn exp(&self) -> DateTime<Utc> {
+        *self.exp
+    }
+
+    pub fn iat(&self) -> DateTime<Utc> {
+        *self.iat
+    }
+}
+
+impl Expiring for TotpChallengeClaims {
+    fn exp(&self) -> DateTime<Utc> {
+        *self.exp
+    }
+}
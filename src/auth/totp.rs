@@ -0,0 +1,161 @@
+//! RFC 6238 time-based one-time passwords (HMAC-SHA1, 6 digits, 30 second step)
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::RngCore;
+use sha1::Sha1;
+
+use crate::utils::secret::Secret;
+
+pub const SECRET_LEN: usize = 20;
+pub const STEP_SECONDS: i64 = 30;
+pub const DIGITS: usize = 6;
+
+const BASE32: base32::Alphabet = base32::Alphabet::Rfc4648 { padding: false };
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TotpSecret(Secret<Vec<u8>>);
+
+impl TotpSecret {
+    pub fn generate() -> Self {
+        let mut bytes = vec![0; SECRET_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(Secret::new(bytes))
+    }
+
+    pub fn from_base32(value: &str) -> Option<Self> {
+        base32::decode(BASE32, value).map(Self::from_bytes)
+    }
+
+    pub fn to_base32(&self) -> String {
+        base32::encode(BASE32, &self.0)
+    }
+
+    /// HOTP value (RFC 4226) for the given counter
+    pub fn hotp(&self, counter: u64) -> u32 {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.0).expect("hmac accepts any key size");
+        mac.update(&counter.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let value = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        value % 10u32.pow(DIGITS as u32)
+    }
+
+    pub fn code_at(&self, now: DateTime<Utc>) -> String {
+        format_code(self.hotp(step_at(now)))
+    }
+
+    /// Checks `code` against the steps in `[now - skew, now + skew]`.
+    ///
+    /// Steps up to and including `last_used_step` are rejected so a code can't be replayed.
+    /// Returns the matched step.
+    pub fn verify(
+        &self,
+        code: &str,
+        now: DateTime<Utc>,
+        skew: u64,
+        last_used_step: Option<u64>,
+    ) -> Option<u64> {
+        if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let current = step_at(now);
+        let from = current.saturating_sub(skew);
+        let from = match last_used_step {
+            Some(last) => from.max(last + 1),
+            None => from,
+        };
+        (from..=current.saturating_add(skew)).find(|step| format_code(self.hotp(*step)) == code)
+    }
+
+    /// Key URI understood by authenticator apps
+    pub fn otpauth_uri(&self, issuer: &str, account: &str) -> String {
+        let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC);
+        let account = utf8_percent_encode(account, NON_ALPHANUMERIC);
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+            self.to_base32(),
+        )
+    }
+}
+
+pub fn step_at(now: DateTime<Utc>) -> u64 {
+    now.timestamp().div_euclid(STEP_SECONDS).max(0) as u64
+}
+
+fn format_code(value: u32) -> String {
+    format!("{:0width$}", value, width = DIGITS)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::from_bytes(b"12345678901234567890".to_vec())
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        // RFC 6238 Appendix B (SHA1), truncated to 6 digits
+        let secret = rfc_secret();
+        let cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (ts, expected) in cases {
+            let now = DateTime::from_timestamp(ts, 0).unwrap();
+            assert_str_eq!(secret.code_at(now), expected, "ts = {}", ts);
+        }
+    }
+
+    #[test]
+    fn verify_accepts_skew_and_rejects_replay() {
+        let secret = rfc_secret();
+        let now = utc!(2024, 5, 6, 7, 8, 9);
+        let step = step_at(now);
+        let previous = format_code(secret.hotp(step - 1));
+        let too_old = format_code(secret.hotp(step - 2));
+
+        assert_eq!(secret.verify(&previous, now, 1, None), Some(step - 1));
+        assert_eq!(secret.verify(&too_old, now, 1, None), None);
+        assert_eq!(secret.verify(&previous, now, 1, Some(step - 1)), None);
+        assert_eq!(secret.verify("12345", now, 1, None), None);
+        assert_eq!(secret.verify("abcdef", now, 1, None), None);
+    }
+
+    #[test]
+    fn base32_roundtrip() {
+        let secret = rfc_secret();
+        let encoded = secret.to_base32();
+
+        assert_str_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(TotpSecret::from_base32(&encoded), Some(secret));
+    }
+
+    #[test]
+    fn otpauth_uri() {
+        let uri = rfc_secret().otpauth_uri("Rusty FS", "user@example.com");
+
+        assert_str_eq!(
+            uri,
+            "otpauth://totp/Rusty%20FS:user%40example%2Ecom?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Rusty%20FS&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -1 +1,6 @@
 pub mod app_config;
+pub mod config_error;
+pub mod config_loader;
+pub mod config_reloader;
+pub mod config_validation;
+pub mod secret_reference;
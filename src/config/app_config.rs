@@ -1,34 +1,166 @@
+use std::{collections::HashMap, net::IpAddr, path::PathBuf};
+
+use chrono::Duration;
 use config::Environment;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::content_right::ContentRight,
+    utils::{ip_network::IpNetwork, secret::Secret},
+    web::common::serde_chrono::ApiDurationSeconds,
+};
 
-use crate::utils::secret::Secret;
+use super::secret_reference;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct AppConfig {
     secrets: SecretsConfig,
+    #[serde(default)]
+    auth: AuthConfig,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+    #[serde(default)]
+    sources: Vec<SourceConfig>,
+    #[serde(default)]
+    scrubber: ScrubberConfig,
+    #[serde(default)]
+    compression: CompressionConfig,
+    #[serde(default)]
+    metrics: MetricsConfig,
+    #[serde(default)]
+    tracing: TracingConfig,
+    #[serde(default)]
+    proxies: ProxiesConfig,
+    #[serde(default)]
+    logging: LoggingConfig,
+    #[serde(default)]
+    audit: AuditConfig,
+    #[serde(default)]
+    encryption: EncryptionConfig,
+    #[serde(default)]
+    health: HealthConfig,
+    #[serde(default)]
+    uploads: UploadsConfig,
+    /// HTTPS is served only if configured
+    #[serde(default)]
+    tls: Option<TlsConfig>,
 }
 
 impl AppConfig {
     pub fn secrets(&self) -> &SecretsConfig {
         &self.secrets
     }
+
+    pub fn auth(&self) -> &AuthConfig {
+        &self.auth
+    }
+
+    pub fn rate_limit(&self) -> &RateLimitConfig {
+        &self.rate_limit
+    }
+
+    pub fn sources(&self) -> &[SourceConfig] {
+        &self.sources
+    }
+
+    pub fn scrubber(&self) -> &ScrubberConfig {
+        &self.scrubber
+    }
+
+    pub fn compression(&self) -> &CompressionConfig {
+        &self.compression
+    }
+
+    pub fn metrics(&self) -> &MetricsConfig {
+        &self.metrics
+    }
+
+    pub fn tracing(&self) -> &TracingConfig {
+        &self.tracing
+    }
+
+    pub fn proxies(&self) -> &ProxiesConfig {
+        &self.proxies
+    }
+
+    pub fn logging(&self) -> &LoggingConfig {
+        &self.logging
+    }
+
+    pub fn audit(&self) -> &AuditConfig {
+        &self.audit
+    }
+
+    pub fn encryption(&self) -> &EncryptionConfig {
+        &self.encryption
+    }
+
+    pub fn health(&self) -> &HealthConfig {
+        &self.health
+    }
+
+    pub fn uploads(&self) -> &UploadsConfig {
+        &self.uploads
+    }
+
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct SecretsConfig {
     tokens: TokenSecretsConfig,
+    /// Required if a source is encrypted
+    #[serde(default)]
+    encryption: Option<EncryptionSecretsConfig>,
 }
 
 impl SecretsConfig {
     pub fn tokens(&self) -> &TokenSecretsConfig {
         &self.tokens
     }
+
+    pub fn encryption(&self) -> Option<&EncryptionSecretsConfig> {
+        self.encryption.as_ref()
+    }
+}
+
+/// Master keys wrapping the data keys of encrypted sources, 32 bytes each in base64.
+/// Old keys stay listed until [crate::fs::encryption::key_rotation] re-wrapped everything with the active one.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EncryptionSecretsConfig {
+    active_key: String,
+    #[serde(deserialize_with = "secret_reference::deserialize_map")]
+    master_keys: HashMap<String, Secret<String>>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+impl EncryptionSecretsConfig {
+    pub fn new(active_key: String, master_keys: HashMap<String, Secret<String>>) -> Self {
+        Self {
+            active_key,
+            master_keys,
+        }
+    }
+
+    pub fn active_key(&self) -> &str {
+        &self.active_key
+    }
+
+    pub fn master_keys(&self) -> &HashMap<String, Secret<String>> {
+        &self.master_keys
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct TokenSecretsConfig {
+    #[serde(deserialize_with = "secret_reference::deserialize")]
     access_secret: Secret<String>,
+    #[serde(deserialize_with = "secret_reference::deserialize")]
     refresh_secret: Secret<String>,
+    #[serde(deserialize_with = "secret_reference::deserialize")]
+    totp_challenge_secret: Secret<String>,
 }
 
 impl TokenSecretsConfig {
@@ -39,6 +171,525 @@ impl TokenSecretsConfig {
     pub fn refresh_secret(&self) -> &str {
         &self.refresh_secret
     }
+
+    pub fn totp_challenge_secret(&self) -> &str {
+        &self.totp_challenge_secret
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AuthConfig {
+    access_token_ttl: ApiDurationSeconds,
+    refresh_token_ttl: ApiDurationSeconds,
+    totp: TotpConfig,
+    lockout: LockoutConfig,
+    oidc: OidcConfig,
+    methods: AuthMethodsConfig,
+}
+
+impl AuthConfig {
+    pub fn access_token_ttl(&self) -> Duration {
+        *self.access_token_ttl
+    }
+
+    pub fn refresh_token_ttl(&self) -> Duration {
+        *self.refresh_token_ttl
+    }
+
+    pub fn totp(&self) -> &TotpConfig {
+        &self.totp
+    }
+
+    pub fn lockout(&self) -> &LockoutConfig {
+        &self.lockout
+    }
+
+    pub fn oidc(&self) -> &OidcConfig {
+        &self.oidc
+    }
+
+    pub fn methods(&self) -> &AuthMethodsConfig {
+        &self.methods
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            access_token_ttl: Duration::minutes(15).into(),
+            refresh_token_ttl: Duration::days(30).into(),
+            totp: Default::default(),
+            lockout: Default::default(),
+            oidc: Default::default(),
+            methods: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TotpConfig {
+    /// Shown by authenticator apps next to the account name
+    issuer: String,
+    /// How long the password step stays valid while waiting for a TOTP code
+    challenge_ttl: ApiDurationSeconds,
+    /// How many 30 second steps before and after the current one are accepted
+    skew_steps: u64,
+    recovery_codes_count: usize,
+}
+
+impl TotpConfig {
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn challenge_ttl(&self) -> Duration {
+        *self.challenge_ttl
+    }
+
+    pub fn skew_steps(&self) -> u64 {
+        self.skew_steps
+    }
+
+    pub fn recovery_codes_count(&self) -> usize {
+        self.recovery_codes_count
+    }
+}
+
+impl Default for TotpConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "rusty-http-fs".to_owned(),
+            challenge_ttl: Duration::minutes(5).into(),
+            skew_steps: 1,
+            recovery_codes_count: 10,
+        }
+    }
+}
+
+/// Progressive lockout of failed logins, see [crate::rate_limit::failure_counter::FailureCounter]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LockoutConfig {
+    max_failures_per_username: u32,
+    max_failures_per_ip: u32,
+    base_lockout: ApiDurationSeconds,
+    max_lockout: ApiDurationSeconds,
+    /// Failures are forgotten after this period without new ones
+    reset_after: ApiDurationSeconds,
+}
+
+impl LockoutConfig {
+    pub fn new(
+        max_failures_per_username: u32,
+        max_failures_per_ip: u32,
+        base_lockout: Duration,
+        max_lockout: Duration,
+        reset_after: Duration,
+    ) -> Self {
+        Self {
+            max_failures_per_username,
+            max_failures_per_ip,
+            base_lockout: base_lockout.into(),
+            max_lockout: max_lockout.into(),
+            reset_after: reset_after.into(),
+        }
+    }
+
+    pub fn max_failures_per_username(&self) -> u32 {
+        self.max_failures_per_username
+    }
+
+    pub fn max_failures_per_ip(&self) -> u32 {
+        self.max_failures_per_ip
+    }
+
+    pub fn base_lockout(&self) -> Duration {
+        *self.base_lockout
+    }
+
+    pub fn max_lockout(&self) -> Duration {
+        *self.max_lockout
+    }
+
+    pub fn reset_after(&self) -> Duration {
+        *self.reset_after
+    }
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self::new(
+            5,
+            20,
+            Duration::seconds(30),
+            Duration::minutes(15),
+            Duration::minutes(15),
+        )
+    }
+}
+
+/// How requests can be authenticated. An `Authorization` header of a disabled method is ignored,
+/// the cookie is checked only when there is no `Authorization` header.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AuthMethodsConfig {
+    /// `Authorization: Bearer` with an access token or a personal access token
+    bearer: bool,
+    basic: BasicAuthConfig,
+    cookie: CookieAuthConfig,
+    client_certificate: ClientCertificateAuthConfig,
+}
+
+impl AuthMethodsConfig {
+    pub fn bearer(&self) -> bool {
+        self.bearer
+    }
+
+    pub fn basic(&self) -> &BasicAuthConfig {
+        &self.basic
+    }
+
+    pub fn cookie(&self) -> &CookieAuthConfig {
+        &self.cookie
+    }
+
+    pub fn client_certificate(&self) -> &ClientCertificateAuthConfig {
+        &self.client_certificate
+    }
+}
+
+impl Default for AuthMethodsConfig {
+    fn default() -> Self {
+        Self {
+            bearer: true,
+            basic: Default::default(),
+            cookie: Default::default(),
+            client_certificate: Default::default(),
+        }
+    }
+}
+
+/// Certificates verified during the TLS handshake, see [TlsConfig]. Used only without
+/// an `Authorization` header or a cookie.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ClientCertificateAuthConfig {
+    enabled: bool,
+    identity: ClientCertificateIdentity,
+}
+
+impl ClientCertificateAuthConfig {
+    pub fn new(enabled: bool, identity: ClientCertificateIdentity) -> Self {
+        Self { enabled, identity }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn identity(&self) -> ClientCertificateIdentity {
+        self.identity
+    }
+}
+
+/// Part of a client certificate which is the username of the [crate::auth::login::Login]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientCertificateIdentity {
+    /// Common name of the subject
+    #[default]
+    SubjectCn,
+    /// First e-mail address in the subject alternative names
+    SanEmail,
+    /// First DNS name in the subject alternative names
+    SanDns,
+}
+
+/// `Authorization: Basic` with a username and a password. Logins with TOTP can't use it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BasicAuthConfig {
+    enabled: bool,
+    /// Successful checks are remembered for this long, so the password isn't hashed on every request
+    cache_ttl: ApiDurationSeconds,
+    cache_capacity: usize,
+}
+
+impl BasicAuthConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        *self.cache_ttl
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache_capacity
+    }
+}
+
+impl Default for BasicAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_ttl: Duration::minutes(1).into(),
+            cache_capacity: 1000,
+        }
+    }
+}
+
+/// Access token in an HttpOnly cookie with a double submit CSRF token for unsafe methods
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CookieAuthConfig {
+    enabled: bool,
+    name: String,
+    /// Readable by scripts, its value has to be repeated in `csrf_header_name`
+    csrf_cookie_name: String,
+    csrf_header_name: String,
+    /// Can be turned off only for local development over plain http
+    secure: bool,
+    same_site: CookieSameSite,
+}
+
+impl CookieAuthConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn csrf_cookie_name(&self) -> &str {
+        &self.csrf_cookie_name
+    }
+
+    pub fn csrf_header_name(&self) -> &str {
+        &self.csrf_header_name
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn same_site(&self) -> CookieSameSite {
+        self.same_site
+    }
+}
+
+impl Default for CookieAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: "rhfs_access".to_owned(),
+            csrf_cookie_name: "rhfs_csrf".to_owned(),
+            csrf_header_name: "x-csrf-token".to_owned(),
+            secure: true,
+            same_site: CookieSameSite::Strict,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct OidcConfig {
+    /// How long a started sign-in waits for the provider callback
+    auth_request_ttl: ApiDurationSeconds,
+    /// Allowed clock difference with providers when checking ID token times
+    clock_skew: ApiDurationSeconds,
+    providers: Vec<OidcProviderConfig>,
+}
+
+impl OidcConfig {
+    pub fn auth_request_ttl(&self) -> Duration {
+        *self.auth_request_ttl
+    }
+
+    pub fn clock_skew(&self) -> Duration {
+        *self.clock_skew
+    }
+
+    pub fn providers(&self) -> &[OidcProviderConfig] {
+        &self.providers
+    }
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            auth_request_ttl: Duration::minutes(10).into(),
+            clock_skew: Duration::seconds(60).into(),
+            providers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct OidcProviderConfig {
+    /// Used in URLs, e.g. `/api/auth/oidc/{name}/authorize/v1`
+    name: String,
+    /// The discovery document is loaded from `{issuer}/.well-known/openid-configuration`
+    issuer: String,
+    client_id: String,
+    #[serde(default, deserialize_with = "secret_reference::deserialize_option")]
+    client_secret: Option<Secret<String>>,
+    redirect_uri: String,
+    #[serde(default = "OidcProviderConfig::default_scopes")]
+    scopes: Vec<String>,
+    #[serde(default = "OidcProviderConfig::default_username_claim")]
+    username_claim: String,
+    #[serde(default = "OidcProviderConfig::default_groups_claim")]
+    groups_claim: String,
+    /// Creates a login on the first sign-in of an unknown subject, named `<name>:<username claim>`
+    /// so it can't take the name of a local login or an admin
+    #[serde(default)]
+    auto_provision: bool,
+    /// When not empty, the login rights are replaced with the rights of its groups on every sign-in
+    #[serde(default)]
+    group_rights: HashMap<String, ContentRight>,
+}
+
+impl OidcProviderConfig {
+    fn default_scopes() -> Vec<String> {
+        ["openid", "profile", "email"].map(String::from).to_vec()
+    }
+
+    fn default_username_claim() -> String {
+        "preferred_username".to_owned()
+    }
+
+    fn default_groups_claim() -> String {
+        "groups".to_owned()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn client_secret(&self) -> Option<&str> {
+        self.client_secret.as_deref().map(String::as_str)
+    }
+
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    pub fn username_claim(&self) -> &str {
+        &self.username_claim
+    }
+
+    pub fn groups_claim(&self) -> &str {
+        &self.groups_claim
+    }
+
+    pub fn auto_provision(&self) -> bool {
+        self.auto_provision
+    }
+
+    pub fn group_rights(&self) -> &HashMap<String, ContentRight> {
+        &self.group_rights
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// The first group whose prefix matches the request path is applied
+    groups: Vec<RouteGroupConfig>,
+}
+
+impl RateLimitConfig {
+    pub fn groups(&self) -> &[RouteGroupConfig] {
+        &self.groups
+    }
+
+    /// Group with the longest prefix which matches whole path segments,
+    /// `/api/auth` matches `/api/auth` and `/api/auth/login` but not `/api/authz`
+    pub fn group_for(&self, path: &str) -> Option<&RouteGroupConfig> {
+        self.groups
+            .iter()
+            .filter(|g| {
+                let prefix = g.path_prefix.trim_end_matches('/');
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|g| g.path_prefix.trim_end_matches('/').len())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RouteGroupConfig {
+    name: String,
+    path_prefix: String,
+    #[serde(default)]
+    per_ip: Option<TokenBucketConfig>,
+    #[serde(default)]
+    per_principal: Option<TokenBucketConfig>,
+}
+
+impl RouteGroupConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path_prefix(&self) -> &str {
+        &self.path_prefix
+    }
+
+    pub fn per_ip(&self) -> Option<&TokenBucketConfig> {
+        self.per_ip.as_ref()
+    }
+
+    pub fn per_principal(&self) -> Option<&TokenBucketConfig> {
+        self.per_principal.as_ref()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct TokenBucketConfig {
+    /// Burst size
+    capacity: u32,
+    refill_per_second: f64,
+}
+
+impl TokenBucketConfig {
+    pub fn new(capacity: u32, refill_per_second: f64) -> Self {
+        Self {
+            capacity,
+            refill_per_second,
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn refill_per_second(&self) -> f64 {
+        self.refill_per_second
+    }
 }
 
 pub static ENVIRONMENT_PREFIX: &str = "RHFS";
@@ -49,3 +700,718 @@ pub fn env_source() -> Environment {
         .try_parsing(true)
         .separator(ENVIRONMENT_SEPARATOR)
 }
+
+/// Background re-hashing of stored files, see [crate::fs::scrubber]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScrubberConfig {
+    interval: ApiDurationSeconds,
+}
+
+impl ScrubberConfig {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.into(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        *self.interval
+    }
+}
+
+impl Default for ScrubberConfig {
+    fn default() -> Self {
+        Self::new(Duration::days(1))
+    }
+}
+
+/// Compression of responses negotiated with `Accept-Encoding`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CompressionConfig {
+    enabled: bool,
+    /// Smaller responses aren't worth compressing, streamed ones of unknown size always are
+    min_size: u64,
+}
+
+impl CompressionConfig {
+    pub fn new(enabled: bool, min_size: u64) -> Self {
+        Self { enabled, min_size }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn min_size(&self) -> u64 {
+        self.min_size
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self::new(true, 1024)
+    }
+}
+
+/// Prometheus metrics at `/metrics`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// The endpoint doesn't require credentials, switch it off if it can't be firewalled
+    enabled: bool,
+}
+
+impl MetricsConfig {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+/// Spans are exported only if a collector is configured
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct TracingConfig {
+    otlp: Option<OtlpConfig>,
+}
+
+impl TracingConfig {
+    pub fn otlp(&self) -> Option<&OtlpConfig> {
+        self.otlp.as_ref()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    format: LogFormat,
+    /// Comma separated `target=level` directives and a bare default level,
+    /// e.g. `info,sqlx=warn,rusty_http_fs::web=debug`
+    filter: String,
+    output: LogOutputConfig,
+    /// Usernames of the logins allowed to change the filter at runtime
+    admins: Vec<String>,
+}
+
+impl LoggingConfig {
+    pub fn new(format: LogFormat, filter: String, output: LogOutputConfig) -> Self {
+        Self {
+            format,
+            filter,
+            output,
+            admins: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn output(&self) -> &LogOutputConfig {
+        &self.output
+    }
+
+    pub fn admins(&self) -> &[String] {
+        &self.admins
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self::new(
+            LogFormat::Pretty,
+            "info".to_owned(),
+            LogOutputConfig::Stdout,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human readable, one line per event
+    Pretty,
+    /// One JSON object per line with the fields of the event and its spans
+    Json,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogOutputConfig {
+    Stdout,
+    /// Files named `{prefix}.{period}.log` in `directory`, or `{prefix}.log` if they never rotate
+    File {
+        directory: PathBuf,
+        prefix: String,
+        #[serde(default)]
+        rotation: LogRotation,
+        /// Older files are deleted on rotation, all are kept if absent
+        #[serde(default)]
+        max_files: Option<usize>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+/// Trail of logins, credential changes and file access, see [crate::audit]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AuditConfig {
+    enabled: bool,
+    /// Usernames of the logins allowed to read and export the trail
+    admins: Vec<String>,
+}
+
+impl AuditConfig {
+    pub fn new(enabled: bool, admins: Vec<String>) -> Self {
+        Self { enabled, admins }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn admins(&self) -> &[String] {
+        &self.admins
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self::new(true, Vec::new())
+    }
+}
+
+/// Administration of encrypted sources, the master keys are in [SecretsConfig]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Usernames of the logins allowed to rotate the data keys
+    admins: Vec<String>,
+}
+
+impl EncryptionConfig {
+    pub fn admins(&self) -> &[String] {
+        &self.admins
+    }
+}
+
+/// Checks of `/health/ready`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Sources on a local disk with less space left are not ready
+    min_free_bytes: u64,
+    /// A check which takes longer fails
+    check_timeout: ApiDurationSeconds,
+}
+
+impl HealthConfig {
+    pub fn new(min_free_bytes: u64, check_timeout: Duration) -> Self {
+        Self {
+            min_free_bytes,
+            check_timeout: check_timeout.into(),
+        }
+    }
+
+    pub fn min_free_bytes(&self) -> u64 {
+        self.min_free_bytes
+    }
+
+    pub fn check_timeout(&self) -> Duration {
+        *self.check_timeout
+    }
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self::new(1 << 30, Duration::seconds(5))
+    }
+}
+
+/// Files written through the file API
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct UploadsConfig {
+    /// Larger uploads are rejected with 413, unlimited if not set
+    max_file_size: Option<u64>,
+}
+
+impl UploadsConfig {
+    pub fn new(max_file_size: Option<u64>) -> Self {
+        Self { max_file_size }
+    }
+
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size
+    }
+}
+
+/// HTTPS with HTTP/2 offered over ALPN. The certificate and the key are reloaded when the files change,
+/// a pair which doesn't load keeps the previous one in use.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM chain, leaf first
+    cert_path: PathBuf,
+    /// PEM private key, PKCS#8, PKCS#1 or SEC1
+    key_path: PathBuf,
+    /// How often the files are checked for changes
+    #[serde(default = "TlsConfig::default_reload_interval")]
+    reload_interval: ApiDurationSeconds,
+    /// Requests client certificates when set
+    #[serde(default)]
+    client_auth: Option<ClientAuthConfig>,
+}
+
+impl TlsConfig {
+    pub fn new(cert_path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            cert_path,
+            key_path,
+            reload_interval: Self::default_reload_interval(),
+            client_auth: None,
+        }
+    }
+
+    fn default_reload_interval() -> ApiDurationSeconds {
+        Duration::seconds(30).into()
+    }
+
+    pub fn cert_path(&self) -> &PathBuf {
+        &self.cert_path
+    }
+
+    pub fn key_path(&self) -> &PathBuf {
+        &self.key_path
+    }
+
+    pub fn reload_interval(&self) -> Duration {
+        *self.reload_interval
+    }
+
+    pub fn client_auth(&self) -> Option<&ClientAuthConfig> {
+        self.client_auth.as_ref()
+    }
+}
+
+/// Mutual TLS, certificates are mapped to logins by [ClientCertificateAuthConfig]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ClientAuthConfig {
+    /// PEM certificates of the CAs issuing client certificates
+    ca_path: PathBuf,
+    /// Handshakes without a client certificate fail, otherwise they may use other credentials
+    #[serde(default)]
+    required: bool,
+}
+
+impl ClientAuthConfig {
+    pub fn new(ca_path: PathBuf, required: bool) -> Self {
+        Self { ca_path, required }
+    }
+
+    pub fn ca_path(&self) -> &PathBuf {
+        &self.ca_path
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+}
+
+/// Reverse proxies and load balancers in front of the server
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ProxiesConfig {
+    /// Only requests from these networks may set `x-traceid`, `X-Forwarded-For` and `Forwarded`
+    trusted: Vec<IpNetwork>,
+}
+
+impl ProxiesConfig {
+    pub fn new(trusted: Vec<IpNetwork>) -> Self {
+        Self { trusted }
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// Collector receiving spans over OTLP/HTTP with JSON encoding
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct OtlpConfig {
+    /// Traces endpoint, e.g. `http://localhost:4318/v1/traces`
+    endpoint: String,
+    /// Sent with every export, e.g. credentials of a hosted collector
+    #[serde(default, deserialize_with = "secret_reference::deserialize_map")]
+    headers: HashMap<String, Secret<String>>,
+    #[serde(default = "OtlpConfig::default_service_name")]
+    service_name: String,
+    /// Spans are sent once this many are waiting or the interval has passed
+    #[serde(default = "OtlpConfig::default_batch_size")]
+    batch_size: usize,
+    #[serde(default = "OtlpConfig::default_flush_interval")]
+    flush_interval: ApiDurationSeconds,
+    /// Spans are dropped while this many wait for the collector
+    #[serde(default = "OtlpConfig::default_queue_size")]
+    queue_size: usize,
+}
+
+impl OtlpConfig {
+    fn default_service_name() -> String {
+        env!("CARGO_PKG_NAME").to_owned()
+    }
+
+    fn default_batch_size() -> usize {
+        512
+    }
+
+    fn default_flush_interval() -> ApiDurationSeconds {
+        Duration::seconds(5).into()
+    }
+
+    fn default_queue_size() -> usize {
+        4096
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn headers(&self) -> &HashMap<String, Secret<String>> {
+        &self.headers
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size.max(1)
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        *self.flush_interval
+    }
+
+    pub fn queue_size(&self) -> usize {
+        self.queue_size.max(1)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SourceConfig {
+    id: Uuid,
+    name: String,
+    backend: BackendConfig,
+    /// Keeps prior contents of files on every write
+    #[serde(default)]
+    versioning: Option<VersioningConfig>,
+    /// Contents are encrypted before they reach the backend, which should start empty.
+    /// Not supported with the dedup backend, encrypted chunks never repeat
+    #[serde(default)]
+    encrypted: bool,
+    /// Contents are stored compressed with zstd, the backend should start empty
+    #[serde(default)]
+    compressed: Option<StoredCompressionConfig>,
+    /// Downloads are served from `.br` or `.gz` siblings of a file if the client accepts them
+    #[serde(default)]
+    precompressed: bool,
+    /// Serves the source as a static web site outside of the API
+    #[serde(default)]
+    hosting: Option<HostingConfig>,
+}
+
+impl SourceConfig {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn backend(&self) -> &BackendConfig {
+        &self.backend
+    }
+
+    pub fn versioning(&self) -> Option<&VersioningConfig> {
+        self.versioning.as_ref()
+    }
+
+    pub fn encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub fn compressed(&self) -> Option<&StoredCompressionConfig> {
+        self.compressed.as_ref()
+    }
+
+    pub fn precompressed(&self) -> bool {
+        self.precompressed
+    }
+
+    pub fn hosting(&self) -> Option<&HostingConfig> {
+        self.hosting.as_ref()
+    }
+}
+
+/// Static web site served from a source.
+/// Sites only get requests which no API route matches.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HostingConfig {
+    /// Path the site is mounted at
+    prefix: String,
+    /// Only requests for this host get the site, any host if missing.
+    /// Sites without a host are sandboxed as they share the origin of the API.
+    host: Option<String>,
+    /// Anyone may read the site, otherwise the read right on the source is required
+    public: bool,
+    /// Served for directories
+    index: String,
+    /// `/about` is served from `about.html` if it doesn't exist itself
+    clean_urls: bool,
+    /// Paths without an extension which don't exist get the root index, for client side routing
+    spa: bool,
+    /// Served with status 404 for paths which don't exist, relative to the root of the source
+    not_found_page: Option<String>,
+    /// `Cache-Control` by file extension, `*` applies to the other files
+    cache_control: HashMap<String, String>,
+}
+
+impl HostingConfig {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub fn public(&self) -> bool {
+        self.public
+    }
+
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    pub fn clean_urls(&self) -> bool {
+        self.clean_urls
+    }
+
+    pub fn spa(&self) -> bool {
+        self.spa
+    }
+
+    pub fn not_found_page(&self) -> Option<&str> {
+        self.not_found_page.as_deref()
+    }
+
+    /// Value for a file with the extension
+    pub fn cache_control(&self, extension: Option<&str>) -> Option<&str> {
+        extension
+            .and_then(|extension| self.cache_control.get(&extension.to_ascii_lowercase()))
+            .or_else(|| self.cache_control.get("*"))
+            .map(String::as_str)
+    }
+}
+
+impl Default for HostingConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "/".to_owned(),
+            host: None,
+            public: false,
+            index: "index.html".to_owned(),
+            clean_urls: true,
+            spa: false,
+            not_found_page: None,
+            cache_control: HashMap::from([
+                ("html".to_owned(), "no-cache".to_owned()),
+                ("*".to_owned(), "public, max-age=3600".to_owned()),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy)]
+#[serde(default)]
+pub struct StoredCompressionConfig {
+    /// zstd level, 1 to 22
+    level: i32,
+}
+
+impl StoredCompressionConfig {
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+}
+
+impl Default for StoredCompressionConfig {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct VersioningConfig {
+    /// Directory on the local disk holding the version contents
+    path: PathBuf,
+    #[serde(default)]
+    retention: RetentionConfig,
+}
+
+impl VersioningConfig {
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn retention(&self) -> &RetentionConfig {
+        &self.retention
+    }
+}
+
+/// Which versions of a file are kept, the current content isn't one of them
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Prior contents kept per file
+    max_versions: Option<u32>,
+    /// Versions replaced longer ago are removed
+    max_age: Option<ApiDurationSeconds>,
+}
+
+impl RetentionConfig {
+    pub fn new(max_versions: Option<u32>, max_age: Option<Duration>) -> Self {
+        Self {
+            max_versions,
+            max_age: max_age.map(Into::into),
+        }
+    }
+
+    pub fn max_versions(&self) -> Option<u32> {
+        self.max_versions
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age.map(|age| *age)
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self::new(Some(10), None)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendConfig {
+    /// Directory on the local disk
+    Local { path: PathBuf },
+    /// Starts empty and is lost on restart
+    Memory,
+    /// Read only, the tar file must not be compressed
+    Tar { path: PathBuf },
+    /// Read only
+    Zip { path: PathBuf },
+    /// Contents are split into chunks kept once under `chunks_path`, paths map to manifests in the DAL
+    Dedup {
+        chunks_path: PathBuf,
+        #[serde(default)]
+        chunking: ChunkingConfig,
+    },
+}
+
+/// Content defined chunking sizes in bytes, see `fastcdc` for the allowed ranges
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    min_size: u32,
+    avg_size: u32,
+    max_size: u32,
+}
+
+impl ChunkingConfig {
+    pub fn new(min_size: u32, avg_size: u32, max_size: u32) -> Self {
+        Self {
+            min_size,
+            avg_size,
+            max_size,
+        }
+    }
+
+    pub fn min_size(&self) -> u32 {
+        self.min_size
+    }
+
+    pub fn avg_size(&self) -> u32 {
+        self.avg_size
+    }
+
+    pub fn max_size(&self) -> u32 {
+        self.max_size
+    }
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self::new(16 * 1024, 64 * 1024, 256 * 1024)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn group_for_matches_whole_segments_and_the_longest_prefix() {
+        let config: RateLimitConfig = serde_json::from_value(serde_json::json!({
+            "groups": [
+                { "name": "api", "path_prefix": "/api" },
+                { "name": "auth", "path_prefix": "/api/auth/" },
+                { "name": "login", "path_prefix": "/api/auth/login" },
+            ]
+        }))
+        .unwrap();
+        let group = |path| config.group_for(path).map(RouteGroupConfig::name);
+
+        assert_eq!(group("/api"), Some("api"));
+        assert_eq!(group("/apis"), None);
+        assert_eq!(group("/api/authz"), Some("api"));
+        assert_eq!(group("/api/auth"), Some("auth"));
+        assert_eq!(group("/api/auth/tokens/v1"), Some("auth"));
+        assert_eq!(group("/api/auth/login/v1"), Some("login"));
+        assert_eq!(group("/api/auth/loginx"), Some("auth"));
+        assert_eq!(group("/ui"), None);
+    }
+}
@@ -0,0 +1,37 @@
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum ConfigError {
+    /// Unreadable file, bad syntax or a value of the wrong type
+    #[display("unable to load config: {_0}")]
+    Load(#[error(not(source))] String),
+
+    /// Malformed command line
+    #[display("{_0}")]
+    Args(#[error(not(source))] String),
+
+    #[display("invalid config:{}", _0.iter().map(|p| format!("\n  {}", p)).collect::<String>())]
+    Invalid(#[error(not(source))] Vec<ConfigProblem>),
+}
+
+impl From<config::ConfigError> for ConfigError {
+    fn from(value: config::ConfigError) -> Self {
+        ConfigError::Load(value.to_string())
+    }
+}
+
+/// A setting which loaded but can't be used, e.g. a path which doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+#[display("{path}: {message}")]
+pub struct ConfigProblem {
+    /// Dotted path of the setting, e.g. `sources[0].path`
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
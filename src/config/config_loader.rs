@@ -0,0 +1,383 @@
+use std::path::{Path, PathBuf};
+
+use config::{Config, File};
+
+use super::{
+    app_config::{env_source, AppConfig},
+    config_error::ConfigError,
+    config_validation::validate,
+};
+
+/// Main file used if the command line doesn't name one, the extension selects the format
+pub const DEFAULT_CONFIG_PATH: &str = "config/rusty-http-fs";
+
+/// Formats of `conf.d` fragments, by extension
+const FRAGMENT_EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+/// Builds [AppConfig] from layers, each one overriding the ones before:
+/// 1. defaults of the config types
+/// 2. the main file
+/// 3. fragments in `conf.d` next to it, in the order of their names
+/// 4. `RHFS__` environment variables, e.g. `RHFS__AUTH__ACCESS_TOKEN_TTL=600`
+/// 5. overrides from the command line, e.g. `--set auth.access_token_ttl=600`
+///
+/// The result is validated, every problem is reported at once.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    path: PathBuf,
+    conf_d: PathBuf,
+    overrides: Vec<(String, String)>,
+}
+
+impl ConfigLoader {
+    /// `path` may leave out the extension
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let conf_d = path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("conf.d");
+        Self {
+            path,
+            conf_d,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// `--config <path>` selects the main file, `--set <key>=<value>` overrides a setting
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut path = None;
+        let mut overrides = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) if name == "--config" || name == "--set" => {
+                    (name.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::Args(format!("{} requires a value", name)))
+            };
+            match name.as_str() {
+                "--config" => path = Some(value()?),
+                "--set" => {
+                    let value = value()?;
+                    let Some((key, value)) = value.split_once('=') else {
+                        return Err(ConfigError::Args(format!(
+                            "--set {}: expected <key>=<value>",
+                            value
+                        )));
+                    };
+                    overrides.push((key.trim().to_owned(), value.to_owned()));
+                }
+                _ => return Err(ConfigError::Args(format!("unknown argument {}", name))),
+            }
+        }
+        let loader = Self::new(path.unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_owned()));
+        Ok(overrides.into_iter().fold(loader, |loader, (key, value)| {
+            loader.with_override(key, value)
+        }))
+    }
+
+    pub fn with_conf_d(self, conf_d: impl Into<PathBuf>) -> Self {
+        Self {
+            conf_d: conf_d.into(),
+            ..self
+        }
+    }
+
+    /// `key` is dotted, e.g. `logging.filter`
+    pub fn with_override(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn load(&self) -> Result<AppConfig, ConfigError> {
+        let config: AppConfig = self.layers()?.try_deserialize()?;
+        validate(&config).map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    /// The layered settings as json, before secret references are resolved.
+    /// Unlike a serialized [AppConfig] it keeps the secrets.
+    pub fn load_value(&self) -> Result<serde_json::Value, ConfigError> {
+        Ok(self.layers()?.try_deserialize()?)
+    }
+
+    fn layers(&self) -> Result<Config, ConfigError> {
+        let mut builder = Config::builder().add_source(File::from(self.main_file()?));
+        for fragment in self.fragments()? {
+            builder = builder.add_source(File::from(fragment));
+        }
+        builder = builder.add_source(env_source());
+        for (key, value) in &self.overrides {
+            builder = builder.set_override(key.as_str(), value.as_str())?;
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Files the config is read from, a change to one of them calls for a reload
+    pub fn files(&self) -> Vec<PathBuf> {
+        let main = self.main_file().ok();
+        main.into_iter()
+            .chain(self.fragments().unwrap_or_default())
+            .collect()
+    }
+
+    fn main_file(&self) -> Result<PathBuf, ConfigError> {
+        if self.path.is_file() {
+            return Ok(self.path.clone());
+        }
+        FRAGMENT_EXTENSIONS
+            .iter()
+            .map(|extension| self.path.with_extension(extension))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                ConfigError::Load(format!("config file {} not found", self.path.display()))
+            })
+    }
+
+    /// Sorted by name, a missing directory has none
+    fn fragments(&self) -> Result<Vec<PathBuf>, ConfigError> {
+        let entries = match std::fs::read_dir(&self.conf_d) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(ConfigError::Load(format!(
+                    "{}: {}",
+                    self.conf_d.display(),
+                    e
+                )))
+            }
+        };
+        let mut fragments: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| FRAGMENT_EXTENSIONS.contains(&e))
+            })
+            .collect();
+        fragments.sort();
+        Ok(fragments)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    const MAIN: &str = r#"{
+        "secrets": {
+            "tokens": {
+                "access_secret": "access_secret_0123456789abcdefghij",
+                "refresh_secret": "refresh_secret_0123456789abcdefghij",
+                "totp_challenge_secret": "totp_challenge_secret_0123456789abcdef"
+            }
+        },
+        "logging": { "filter": "info" },
+        "auth": { "access_token_ttl": 600 }
+    }"#;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn layers_fragments_in_order_and_overrides_last() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.json"), MAIN).unwrap();
+        std::fs::create_dir(dir.path().join("conf.d")).unwrap();
+        std::fs::write(
+            dir.path().join("conf.d/20-logging.toml"),
+            "[logging]\nfilter = \"warn\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("conf.d/10-logging.yaml"),
+            "logging:\n  filter: debug\nauth:\n  access_token_ttl: 300\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("conf.d/ignored.txt"), "logging").unwrap();
+
+        let loader = ConfigLoader::new(dir.path().join("main"));
+        let config = loader.load().unwrap();
+        assert_eq!(config.logging().filter(), "warn");
+        assert_eq!(
+            config.auth().access_token_ttl(),
+            chrono::Duration::seconds(300)
+        );
+        assert_eq!(
+            loader.files(),
+            vec![
+                dir.path().join("main.json"),
+                dir.path().join("conf.d/10-logging.yaml"),
+                dir.path().join("conf.d/20-logging.toml"),
+            ]
+        );
+
+        let config = loader
+            .with_override("logging.filter", "error")
+            .with_override("auth.access_token_ttl", "120")
+            .load()
+            .unwrap();
+        assert_eq!(config.logging().filter(), "error");
+        assert_eq!(
+            config.auth().access_token_ttl(),
+            chrono::Duration::seconds(120)
+        );
+    }
+
+    #[test]
+    fn resolves_secret_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("access_secret");
+        std::fs::write(&secret, "access_secret_from_a_file_0123456789\n").unwrap();
+        std::fs::write(
+            dir.path().join("main.json"),
+            MAIN.replace(
+                r#""access_secret_0123456789abcdefghij""#,
+                &format!(r#"{{ "file": {:?} }}"#, secret),
+            ),
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new(dir.path().join("main")).load().unwrap();
+
+        assert_eq!(
+            config.secrets().tokens().access_secret(),
+            "access_secret_from_a_file_0123456789"
+        );
+    }
+
+    #[test]
+    fn reads_the_command_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.json");
+        std::fs::write(&path, MAIN).unwrap();
+
+        let config = ConfigLoader::from_args(args(&[
+            "--config",
+            path.to_str().unwrap(),
+            "--set",
+            "logging.filter=trace",
+            "--set=compression.min_size=10",
+        ]))
+        .unwrap()
+        .load()
+        .unwrap();
+
+        assert_eq!(config.logging().filter(), "trace");
+        assert_eq!(config.compression().min_size(), 10);
+    }
+
+    #[test]
+    fn rejects_malformed_command_lines() {
+        for (line, expected) in [
+            (vec!["--verbose"], "unknown argument --verbose"),
+            (vec!["--config"], "--config requires a value"),
+            (
+                vec!["--set", "filter"],
+                "--set filter: expected <key>=<value>",
+            ),
+        ] {
+            let error = ConfigLoader::from_args(args(&line)).unwrap_err();
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn reports_a_missing_file_and_every_invalid_setting() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = ConfigLoader::new(dir.path().join("main"))
+            .load()
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Load(_)), "{}", error);
+
+        std::fs::write(dir.path().join("main.json"), MAIN).unwrap();
+        let error = ConfigLoader::new(dir.path().join("main"))
+            .with_override("secrets.tokens.access_secret", "short")
+            .with_override("logging.filter", "web=loud")
+            .with_override("tls.cert_path", "/nonexistent/cert.pem")
+            .with_override("tls.key_path", "/nonexistent/key.pem")
+            .load()
+            .unwrap_err();
+        let ConfigError::Invalid(problems) = error else {
+            panic!("{}", error);
+        };
+        let paths: Vec<_> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "secrets.tokens.access_secret",
+                "tls.cert_path",
+                "tls.key_path",
+                "logging.filter"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_sources_sharing_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunks = dir.path().join("chunks");
+        std::fs::create_dir(&chunks).unwrap();
+        let source = |id: u128, chunks_path: PathBuf| {
+            serde_json::json!({
+                "id": uuid::Uuid::from_u128(id),
+                "name": id.to_string(),
+                "backend": { "type": "dedup", "chunks_path": chunks_path },
+            })
+        };
+        let mut main: serde_json::Value = serde_json::from_str(MAIN).unwrap();
+        main["sources"] = serde_json::json!([
+            source(1, chunks.clone()),
+            source(2, chunks.join("../chunks")),
+        ]);
+        std::fs::write(dir.path().join("main.json"), main.to_string()).unwrap();
+
+        let error = ConfigLoader::new(dir.path().join("main"))
+            .load()
+            .unwrap_err();
+
+        let ConfigError::Invalid(problems) = error else {
+            panic!("{}", error);
+        };
+        let paths: Vec<_> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, vec!["sources[1].backend.chunks_path"]);
+    }
+
+    #[test]
+    fn rejects_encrypted_dedup_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut main: serde_json::Value = serde_json::from_str(MAIN).unwrap();
+        main["sources"] = serde_json::json!([{
+            "id": uuid::Uuid::from_u128(1),
+            "name": "dedup",
+            "backend": { "type": "dedup", "chunks_path": dir.path() },
+            "encrypted": true,
+        }]);
+        std::fs::write(dir.path().join("main.json"), main.to_string()).unwrap();
+
+        let error = ConfigLoader::new(dir.path().join("main"))
+            .load()
+            .unwrap_err();
+
+        let ConfigError::Invalid(problems) = error else {
+            panic!("{}", error);
+        };
+        assert!(
+            problems.iter().any(|p| p.path == "sources[0].encrypted"),
+            "{:?}",
+            problems
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::SystemTime,
+};
+
+use chrono::Duration;
+use serde_json::Value;
+
+use crate::{
+    dal::Dal,
+    fs::{encryption::key_ring::KeyRing, source::Source, sources::Sources},
+    logging::log_filter::LogFilter,
+    metrics::app_metrics::AppMetrics,
+    rate_limit::rate_limit_rules::RateLimitRules,
+};
+
+use super::{
+    app_config::{AppConfig, SourceConfig},
+    config_error::{ConfigError, ConfigProblem},
+    config_loader::ConfigLoader,
+};
+
+/// Applies a changed config to the running server without dropping connections.
+///
+/// The log filter, rate limits and sources are replaced, the other settings are used
+/// until a restart, which is logged. A config which doesn't load or validate is logged
+/// and nothing of it is applied.
+pub struct ConfigReloader<D> {
+    loader: ConfigLoader,
+    current: RwLock<Arc<AppConfig>>,
+    modified: Mutex<Vec<(PathBuf, Option<SystemTime>)>>,
+    log_filter: LogFilter,
+    rate_limit_rules: RateLimitRules,
+    sources: Sources,
+    dal: D,
+    keys: Option<Arc<KeyRing>>,
+    metrics: AppMetrics,
+}
+
+impl<D> ConfigReloader<D>
+where
+    D: Dal + Clone + Send + Sync + 'static,
+{
+    /// `config` is the one the server started with, `keys` the master keys the sources were opened with
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        loader: ConfigLoader,
+        config: Arc<AppConfig>,
+        log_filter: LogFilter,
+        rate_limit_rules: RateLimitRules,
+        sources: Sources,
+        dal: D,
+        keys: Option<Arc<KeyRing>>,
+        metrics: AppMetrics,
+    ) -> Self {
+        let modified = modified(&loader);
+        Self {
+            loader,
+            current: RwLock::new(config),
+            modified: Mutex::new(modified),
+            log_filter,
+            rate_limit_rules,
+            sources,
+            dal,
+            keys,
+            metrics,
+        }
+    }
+
+    pub fn config(&self) -> Arc<AppConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Loads the config again and applies what can change at runtime, all or nothing
+    pub fn reload(&self) -> Result<Arc<AppConfig>, ConfigError> {
+        let config = Arc::new(self.loader.load()?);
+        let previous = self.config();
+
+        // sources open before anything is applied, one which fails keeps the old config in use
+        let previous_sources = previous.sources();
+        let mut opened = Vec::new();
+        let mut problems = Vec::new();
+        for (i, source) in config.sources().iter().enumerate() {
+            let unchanged = previous_sources
+                .iter()
+                .find(|p| p.id() == source.id())
+                // secrets included, a source with a changed one is opened again
+                .is_some_and(|p| p == source);
+            if unchanged && self.sources.get(source.id()).is_some() {
+                continue;
+            }
+            match Source::open(source, &self.dal, self.keys.as_ref()) {
+                Ok(opened_source) => opened.push(opened_source.with_metrics(self.metrics.clone())),
+                Err(e) => {
+                    problems.push(ConfigProblem::new(format!("sources[{}]", i), e.to_string()))
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+
+        let ids: HashSet<_> = config.sources().iter().map(SourceConfig::id).collect();
+        for source in previous_sources.iter().filter(|s| !ids.contains(&s.id())) {
+            self.sources.remove(source.id());
+            tracing::info!(source = source.name(), "source removed");
+        }
+        for source in opened {
+            tracing::info!(source = source.name(), "source opened");
+            self.sources.insert(source);
+        }
+        // a filter set at runtime stays until the config changes it
+        if config.logging().filter() != previous.logging().filter() {
+            if let Err(e) = self.log_filter.set(config.logging().filter()) {
+                tracing::error!("log filter not changed: {}", e);
+            }
+        }
+        self.rate_limit_rules.set(config.rate_limit().clone());
+
+        let restart = requires_restart(&previous, &config);
+        if !restart.is_empty() {
+            tracing::warn!(
+                settings = restart.join(", "),
+                "changed settings take effect after a restart"
+            );
+        }
+        *self.current.write().unwrap() = config.clone();
+        tracing::info!("config reloaded");
+        Ok(config)
+    }
+
+    /// Reloads if a config file was modified, added or removed since the last check
+    pub fn reload_if_changed(&self) -> bool {
+        let modified = modified(&self.loader);
+        {
+            let mut previous = self.modified.lock().unwrap();
+            if *previous == modified {
+                return false;
+            }
+            // a config which fails isn't retried until the files change again
+            *previous = modified;
+        }
+        self.reload_logged()
+    }
+
+    fn reload_logged(&self) -> bool {
+        match self.reload() {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::error!("config not reloaded, the previous one stays in use: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Checks the files on a thread of its own until the reloader is dropped
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let reloader: Weak<Self> = Arc::downgrade(self);
+        let interval = interval
+            .to_std()
+            .unwrap_or(std::time::Duration::from_secs(5));
+        std::thread::Builder::new()
+            .name("config-reloader".to_owned())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                match reloader.upgrade() {
+                    Some(reloader) => _ = reloader.reload_if_changed(),
+                    None => break,
+                }
+            })
+            .expect("unable to start the config reloader");
+    }
+
+    /// Reloads on every SIGHUP, runs until the runtime stops
+    #[cfg(unix)]
+    pub async fn reload_on_hangup(self: Arc<Self>) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = signal(SignalKind::hangup())?;
+        while hangups.recv().await.is_some() {
+            let reloader = self.clone();
+            // sources open with blocking IO
+            _ = tokio::task::spawn_blocking(move || reloader.reload_logged()).await;
+        }
+        Ok(())
+    }
+}
+
+fn modified(loader: &ConfigLoader) -> Vec<(PathBuf, Option<SystemTime>)> {
+    loader
+        .files()
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Top level settings which changed and are only read on startup, including secrets
+fn requires_restart(previous: &AppConfig, config: &AppConfig) -> Vec<String> {
+    // secrets serialize redacted, the sections holding them are compared as they are
+    let secrets = [
+        ("secrets", previous.secrets() != config.secrets()),
+        ("auth", previous.auth().oidc() != config.auth().oidc()),
+        ("tracing", previous.tracing() != config.tracing()),
+    ];
+    let (Ok(Value::Object(mut previous)), Ok(Value::Object(mut config))) =
+        (serde_json::to_value(previous), serde_json::to_value(config))
+    else {
+        return Vec::new();
+    };
+    for settings in [&mut previous, &mut config] {
+        settings.remove("sources");
+        settings.remove("rate_limit");
+        if let Some(Value::Object(logging)) = settings.get_mut("logging") {
+            logging.remove("filter");
+        }
+    }
+    let mut changed: Vec<String> = previous
+        .keys()
+        .chain(config.keys())
+        .filter(|key| previous.get(*key) != config.get(*key))
+        .cloned()
+        .chain(
+            secrets
+                .into_iter()
+                .filter(|(_, changed)| *changed)
+                .map(|(key, _)| key.to_owned()),
+        )
+        .collect();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::dal::memory::MemoryDal;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn write(dir: &std::path::Path, filter: &str, sources: &str, extra: &str) {
+        let config = format!(
+            r#"{{
+                "secrets": {{
+                    "tokens": {{
+                        "access_secret": "access_secret_0123456789abcdefghij",
+                        "refresh_secret": "refresh_secret_0123456789abcdefghij",
+                        "totp_challenge_secret": "totp_challenge_secret_0123456789abcdef"
+                    }}
+                }},
+                "logging": {{ "filter": "{}" }},
+                "sources": [{}]
+                {}
+            }}"#,
+            filter, sources, extra
+        );
+        std::fs::write(dir.join("server.json"), config).unwrap();
+    }
+
+    fn memory_source(id: u128, name: &str) -> String {
+        format!(
+            r#"{{ "id": "{}", "name": "{}", "backend": {{ "type": "memory" }} }}"#,
+            uuid::Uuid::from_u128(id),
+            name
+        )
+    }
+
+    fn reloader(dir: &std::path::Path) -> ConfigReloader<MemoryDal> {
+        let loader = ConfigLoader::new(dir.join("server"));
+        let config = loader.load().unwrap();
+        let dal = MemoryDal::default();
+        let metrics = AppMetrics::default();
+        let sources = Sources::open(config.sources(), &dal, None, &metrics).unwrap();
+        ConfigReloader::new(
+            loader,
+            Arc::new(config.clone()),
+            LogFilter::new(config.logging().filter()).unwrap(),
+            RateLimitRules::new(config.rate_limit().clone()),
+            sources,
+            dal,
+            None,
+            metrics,
+        )
+    }
+
+    fn names(sources: &Sources) -> Vec<String> {
+        sources.list().iter().map(|s| s.name().to_owned()).collect()
+    }
+
+    #[test]
+    fn applies_log_filter_rate_limits_and_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "info",
+            &[memory_source(1, "kept"), memory_source(2, "removed")].join(","),
+            "",
+        );
+        let reloader = reloader(dir.path());
+        let kept = reloader.sources.get(uuid::Uuid::from_u128(1)).unwrap();
+
+        write(
+            dir.path(),
+            "debug",
+            &[memory_source(1, "kept"), memory_source(3, "added")].join(","),
+            r#", "rate_limit": { "groups": [{ "name": "api", "path_prefix": "/api",
+                "per_ip": { "capacity": 5, "refill_per_second": 1.0 } }] }"#,
+        );
+        reloader.reload().unwrap();
+
+        assert_eq!(reloader.log_filter.directives(), "debug");
+        assert_eq!(reloader.rate_limit_rules.get().groups().len(), 1);
+        assert_eq!(names(&reloader.sources), vec!["added", "kept"]);
+        let still_kept = reloader.sources.get(uuid::Uuid::from_u128(1)).unwrap();
+        assert!(Arc::ptr_eq(&kept, &still_kept));
+        assert_eq!(reloader.config().logging().filter(), "debug");
+    }
+
+    #[test]
+    fn keeps_the_previous_config_if_the_new_one_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "info", &memory_source(1, "memory"), "");
+        let reloader = reloader(dir.path());
+
+        // passes validation, but doesn't open
+        let archive = dir.path().join("archive.tar");
+        std::fs::write(&archive, "not a tar file").unwrap();
+        let tar = format!(
+            r#"{{ "id": "{}", "name": "tar", "backend": {{ "type": "tar", "path": {:?} }} }}"#,
+            uuid::Uuid::from_u128(2),
+            archive
+        );
+        write(
+            dir.path(),
+            "debug",
+            &[memory_source(1, "memory"), tar].join(","),
+            "",
+        );
+        let error = reloader.reload().unwrap_err();
+
+        assert!(matches!(error, ConfigError::Invalid(_)), "{}", error);
+        assert_eq!(reloader.log_filter.directives(), "info");
+        assert_eq!(names(&reloader.sources), vec!["memory"]);
+        assert_eq!(reloader.config().logging().filter(), "info");
+    }
+
+    #[test]
+    fn keeps_a_filter_set_at_runtime_until_the_config_changes_it() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "info", "", "");
+        let reloader = reloader(dir.path());
+        reloader.log_filter.set("trace").unwrap();
+
+        reloader.reload().unwrap();
+        assert_eq!(reloader.log_filter.directives(), "trace");
+
+        write(dir.path(), "warn", "", "");
+        reloader.reload().unwrap();
+        assert_eq!(reloader.log_filter.directives(), "warn");
+    }
+
+    #[test]
+    fn reloads_only_if_a_file_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "info", "", "");
+        let reloader = reloader(dir.path());
+        assert!(!reloader.reload_if_changed());
+
+        std::fs::create_dir(dir.path().join("conf.d")).unwrap();
+        std::fs::write(
+            dir.path().join("conf.d/logging.json"),
+            r#"{ "logging": { "filter": "debug" } }"#,
+        )
+        .unwrap();
+        assert!(reloader.reload_if_changed());
+        assert_eq!(reloader.log_filter.directives(), "debug");
+        assert!(!reloader.reload_if_changed());
+    }
+
+    #[test]
+    fn lists_settings_which_require_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "info", "", "");
+        let previous = ConfigLoader::new(dir.path().join("server")).load().unwrap();
+        write(
+            dir.path(),
+            "debug",
+            &memory_source(1, "memory"),
+            r#", "compression": { "enabled": false }, "metrics": { "enabled": false }"#,
+        );
+        let config = ConfigLoader::new(dir.path().join("server")).load().unwrap();
+
+        assert_eq!(
+            requires_restart(&previous, &config),
+            vec!["compression", "metrics"]
+        );
+    }
+
+    #[test]
+    fn changed_secrets_require_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "info", "", "");
+        let loader = ConfigLoader::new(dir.path().join("server"));
+        let previous = loader.load().unwrap();
+        let config = ConfigLoader::new(dir.path().join("server"))
+            .with_override(
+                "secrets.tokens.access_secret",
+                "rotated_access_secret_0123456789abcdef",
+            )
+            .load()
+            .unwrap();
+
+        assert_eq!(
+            requires_restart(&previous, &loader.load().unwrap()),
+            Vec::<String>::new()
+        );
+        assert_eq!(requires_restart(&previous, &config), vec!["secrets"]);
+    }
+}
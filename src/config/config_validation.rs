@@ -0,0 +1,201 @@
+use std::{collections::HashSet, path::Path};
+
+use chrono::Duration;
+
+use crate::{fs::encryption::key_ring::KeyRing, logging::log_filter::LogFilter};
+
+use super::{
+    app_config::{AppConfig, BackendConfig, TokenBucketConfig},
+    config_error::ConfigProblem,
+};
+
+/// Shorter token secrets could be brute forced from a single token
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+/// Checks what deserializing can't, returns every problem found
+pub fn validate(config: &AppConfig) -> Result<(), Vec<ConfigProblem>> {
+    let mut problems = Problems::default();
+    secrets(config, &mut problems);
+    durations(config, &mut problems);
+    rate_limit(config, &mut problems);
+    sources(config, &mut problems);
+    files(config, &mut problems);
+    if let Err(e) = LogFilter::new(config.logging().filter()) {
+        problems.add("logging.filter", e.to_string());
+    }
+    if config.uploads().max_file_size() == Some(0) {
+        problems.add(
+            "uploads.max_file_size",
+            "must be positive, leave it out for no limit",
+        );
+    }
+    match problems.0.is_empty() {
+        true => Ok(()),
+        false => Err(problems.0),
+    }
+}
+
+#[derive(Default)]
+struct Problems(Vec<ConfigProblem>);
+
+impl Problems {
+    fn add(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.0.push(ConfigProblem::new(path, message));
+    }
+
+    fn positive(&mut self, path: &str, duration: Duration) {
+        if duration <= Duration::zero() {
+            self.add(path, "must be positive");
+        }
+    }
+
+    fn exists(&mut self, path: &str, file: &Path, directory: bool) {
+        let found = match directory {
+            true => file.is_dir(),
+            false => file.is_file(),
+        };
+        if !found {
+            let kind = if directory { "directory" } else { "file" };
+            self.add(path, format!("{} {} doesn't exist", kind, file.display()));
+        }
+    }
+
+    fn bucket(&mut self, path: &str, bucket: Option<&TokenBucketConfig>) {
+        let Some(bucket) = bucket else {
+            return;
+        };
+        if bucket.capacity() == 0 {
+            self.add(format!("{}.capacity", path), "must be positive");
+        }
+        if !(bucket.refill_per_second() > 0.0 && bucket.refill_per_second().is_finite()) {
+            self.add(format!("{}.refill_per_second", path), "must be positive");
+        }
+    }
+}
+
+fn secrets(config: &AppConfig, problems: &mut Problems) {
+    let tokens = config.secrets().tokens();
+    for (name, secret) in [
+        ("access_secret", tokens.access_secret()),
+        ("refresh_secret", tokens.refresh_secret()),
+        ("totp_challenge_secret", tokens.totp_challenge_secret()),
+    ] {
+        if secret.len() < MIN_SECRET_LENGTH {
+            problems.add(
+                format!("secrets.tokens.{}", name),
+                format!("must be at least {} characters", MIN_SECRET_LENGTH),
+            );
+        }
+    }
+    match config.secrets().encryption() {
+        Some(encryption) => {
+            if let Err(e) = KeyRing::from_config(encryption) {
+                problems.add("secrets.encryption", e.to_string());
+            }
+        }
+        None => {
+            for (i, source) in config.sources().iter().enumerate() {
+                if source.encrypted() {
+                    problems.add(
+                        format!("sources[{}].encrypted", i),
+                        "requires secrets.encryption",
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn durations(config: &AppConfig, problems: &mut Problems) {
+    let auth = config.auth();
+    problems.positive("auth.access_token_ttl", auth.access_token_ttl());
+    if auth.refresh_token_ttl() <= auth.access_token_ttl() {
+        problems.add(
+            "auth.refresh_token_ttl",
+            "must be longer than auth.access_token_ttl",
+        );
+    }
+    problems.positive("auth.totp.challenge_ttl", auth.totp().challenge_ttl());
+    let lockout = auth.lockout();
+    problems.positive("auth.lockout.base_lockout", lockout.base_lockout());
+    if lockout.max_lockout() < lockout.base_lockout() {
+        problems.add(
+            "auth.lockout.max_lockout",
+            "must not be shorter than auth.lockout.base_lockout",
+        );
+    }
+    problems.positive("auth.lockout.reset_after", lockout.reset_after());
+    problems.positive("scrubber.interval", config.scrubber().interval());
+    problems.positive("health.check_timeout", config.health().check_timeout());
+    if let Some(tls) = config.tls() {
+        problems.positive("tls.reload_interval", tls.reload_interval());
+    }
+    if let Some(otlp) = config.tracing().otlp() {
+        problems.positive("tracing.otlp.flush_interval", otlp.flush_interval());
+    }
+}
+
+fn rate_limit(config: &AppConfig, problems: &mut Problems) {
+    for (i, group) in config.rate_limit().groups().iter().enumerate() {
+        let path = format!("rate_limit.groups[{}]", i);
+        problems.bucket(&format!("{}.per_ip", path), group.per_ip());
+        problems.bucket(&format!("{}.per_principal", path), group.per_principal());
+    }
+}
+
+fn sources(config: &AppConfig, problems: &mut Problems) {
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    let mut chunks_paths = HashSet::new();
+    for (i, source) in config.sources().iter().enumerate() {
+        let path = format!("sources[{}]", i);
+        if !ids.insert(source.id()) {
+            problems.add(format!("{}.id", path), "is used by another source");
+        }
+        if !names.insert(source.name()) {
+            problems.add(format!("{}.name", path), "is used by another source");
+        }
+        match source.backend() {
+            BackendConfig::Local { path: dir } => {
+                problems.exists(&format!("{}.backend.path", path), dir, true)
+            }
+            BackendConfig::Tar { path: file } | BackendConfig::Zip { path: file } => {
+                problems.exists(&format!("{}.backend.path", path), file, false)
+            }
+            BackendConfig::Dedup { chunks_path, .. } => {
+                let field = format!("{}.backend.chunks_path", path);
+                problems.exists(&field, chunks_path, true);
+                // garbage collection only knows the chunks referenced by its own source
+                let canonical = chunks_path.canonicalize().unwrap_or(chunks_path.clone());
+                if !chunks_paths.insert(canonical) {
+                    problems.add(field, "is used by another source");
+                }
+            }
+            BackendConfig::Memory => {}
+        }
+        if source.encrypted() && matches!(source.backend(), BackendConfig::Dedup { .. }) {
+            // every file gets a random nonce, so encrypted chunks would never repeat
+            problems.add(
+                format!("{}.encrypted", path),
+                "can't be combined with a dedup backend",
+            );
+        }
+        if let Some(versioning) = source.versioning() {
+            problems.exists(
+                &format!("{}.versioning.path", path),
+                versioning.path(),
+                true,
+            );
+        }
+    }
+}
+
+fn files(config: &AppConfig, problems: &mut Problems) {
+    if let Some(tls) = config.tls() {
+        problems.exists("tls.cert_path", tls.cert_path(), false);
+        problems.exists("tls.key_path", tls.key_path(), false);
+        if let Some(client_auth) = tls.client_auth() {
+            problems.exists("tls.client_auth.ca_path", client_auth.ca_path(), false);
+        }
+    }
+}
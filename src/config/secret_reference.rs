@@ -0,0 +1,122 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::utils::secret::Secret;
+
+/// Secret setting given as the value itself, `{ "file": "/run/secrets/x" }` or `{ "env": "NAME" }`.
+/// Files and variables are read when the config loads and surrounding whitespace is trimmed.
+///
+/// Only config fields resolve references, a request body must never make the server read a file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SecretReference {
+    Value(String),
+    File { file: PathBuf },
+    Env { env: String },
+}
+
+impl SecretReference {
+    fn resolve(self) -> Result<Secret<String>, String> {
+        let value = match self {
+            SecretReference::Value(value) => return Ok(Secret::new(value)),
+            SecretReference::File { file } => std::fs::read_to_string(&file)
+                .map_err(|e| format!("secret file {}: {}", file.display(), e))?,
+            SecretReference::Env { env } => {
+                std::env::var(&env).map_err(|e| format!("secret variable {}: {}", env, e))?
+            }
+        };
+        let value = Secret::new(value);
+        match value.trim() {
+            "" => Err("secret reference is empty".to_owned()),
+            trimmed => Ok(Secret::new(trimmed.to_owned())),
+        }
+    }
+}
+
+/// `#[serde(deserialize_with = "secret_reference::deserialize")]`
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Secret<String>, D::Error> {
+    SecretReference::deserialize(deserializer)?
+        .resolve()
+        .map_err(D::Error::custom)
+}
+
+/// [deserialize] for an optional secret, the field also needs `#[serde(default)]`
+pub fn deserialize_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Secret<String>>, D::Error> {
+    Option::<SecretReference>::deserialize(deserializer)?
+        .map(SecretReference::resolve)
+        .transpose()
+        .map_err(D::Error::custom)
+}
+
+/// [deserialize] for the values of a map
+pub fn deserialize_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Secret<String>>, D::Error> {
+    HashMap::<String, SecretReference>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, reference)| Ok((key, reference.resolve()?)))
+        .collect::<Result<_, String>>()
+        .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[derive(Deserialize)]
+    struct Settings {
+        #[serde(deserialize_with = "deserialize")]
+        secret: Secret<String>,
+        #[serde(default, deserialize_with = "deserialize_option")]
+        optional: Option<Secret<String>>,
+        #[serde(default, deserialize_with = "deserialize_map")]
+        map: HashMap<String, Secret<String>>,
+    }
+
+    fn settings(json: serde_json::Value) -> Result<Settings, String> {
+        serde_json::from_value(json).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn reads_files_and_variables_trimmed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("secret");
+        std::fs::write(&file, "from a file\n").unwrap();
+        std::env::set_var("RHFS_TEST_SECRET_REFERENCE", " from a variable ");
+
+        let settings = settings(serde_json::json!({
+            "secret": { "file": file },
+            "optional": { "env": "RHFS_TEST_SECRET_REFERENCE" },
+            "map": { "inline": " kept as is " },
+        }))
+        .unwrap();
+
+        assert_eq!(settings.secret.as_str(), "from a file");
+        assert_eq!(settings.optional.unwrap().as_str(), "from a variable");
+        assert_eq!(settings.map["inline"].as_str(), " kept as is ");
+    }
+
+    #[test]
+    fn fails_if_a_reference_doesnt_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty");
+        std::fs::write(&empty, " \n").unwrap();
+
+        let error = settings(serde_json::json!({ "secret": { "env": "RHFS_TEST_UNSET" } }));
+        assert_eq!(
+            error.err().unwrap(),
+            "secret variable RHFS_TEST_UNSET: environment variable not found"
+        );
+        let error = settings(serde_json::json!({ "secret": { "file": empty } }));
+        assert_eq!(error.err().unwrap(), "secret reference is empty");
+        let error = settings(
+            serde_json::json!({ "secret": "x", "map": { "a": { "file": dir.path().join("missing") } } }),
+        );
+        assert!(error.err().unwrap().starts_with("secret file "));
+    }
+}
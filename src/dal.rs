@@ -1 +1,78 @@
+pub mod audit_events_dal;
+pub mod chunk_refs_dal;
+pub mod dal_error;
+pub mod data_keys_dal;
+pub mod external_identities_dal;
+pub mod file_checksums_dal;
+pub mod file_manifests_dal;
+pub mod file_versions_dal;
+pub mod login_rights_dal;
+pub mod login_totps_dal;
+pub mod logins_dal;
+pub mod memory;
+pub mod oidc_auth_requests_dal;
+pub mod personal_access_tokens_dal;
+pub mod rate_limits_dal;
 
+use audit_events_dal::AuditEventsDal;
+use chunk_refs_dal::ChunkRefsDal;
+use dal_error::DalError;
+use data_keys_dal::DataKeysDal;
+use external_identities_dal::ExternalIdentitiesDal;
+use file_checksums_dal::FileChecksumsDal;
+use file_manifests_dal::FileManifestsDal;
+use file_versions_dal::FileVersionsDal;
+use login_rights_dal::LoginRightsDal;
+use login_totps_dal::LoginTotpsDal;
+use logins_dal::LoginsDal;
+use oidc_auth_requests_dal::OidcAuthRequestsDal;
+use personal_access_tokens_dal::PersonalAccessTokensDal;
+use rate_limits_dal::RateLimitsDal;
+
+use crate::metrics::pool_stats::PoolStats;
+
+#[allow(async_fn_in_trait)]
+pub trait Dal {
+    type Logins: LoginsDal;
+    type LoginTotps: LoginTotpsDal;
+    type RateLimits: RateLimitsDal;
+    type LoginRights: LoginRightsDal;
+    type PersonalAccessTokens: PersonalAccessTokensDal;
+    type OidcAuthRequests: OidcAuthRequestsDal;
+    type ExternalIdentities: ExternalIdentitiesDal;
+    type FileManifests: FileManifestsDal;
+    type ChunkRefs: ChunkRefsDal;
+    type FileVersions: FileVersionsDal;
+    type FileChecksums: FileChecksumsDal;
+    type DataKeys: DataKeysDal;
+    type AuditEvents: AuditEventsDal;
+
+    fn logins(&self) -> &Self::Logins;
+    fn login_totps(&self) -> &Self::LoginTotps;
+    fn rate_limits(&self) -> &Self::RateLimits;
+    fn login_rights(&self) -> &Self::LoginRights;
+    fn personal_access_tokens(&self) -> &Self::PersonalAccessTokens;
+    fn oidc_auth_requests(&self) -> &Self::OidcAuthRequests;
+    fn external_identities(&self) -> &Self::ExternalIdentities;
+    fn file_manifests(&self) -> &Self::FileManifests;
+    fn chunk_refs(&self) -> &Self::ChunkRefs;
+    fn file_versions(&self) -> &Self::FileVersions;
+    fn file_checksums(&self) -> &Self::FileChecksums;
+    fn data_keys(&self) -> &Self::DataKeys;
+    fn audit_events(&self) -> &Self::AuditEvents;
+
+    /// Connections of the pool, `None` if the DAL doesn't pool connections
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    /// Fails if the database can't be reached, a DAL without one always succeeds
+    async fn ping(&self) -> Result<(), DalError> {
+        Ok(())
+    }
+
+    /// Names of the schema migrations which haven't been applied yet
+    async fn pending_migrations(&self) -> Result<Vec<String>, DalError> {
+        Ok(Vec::new())
+    }
+}
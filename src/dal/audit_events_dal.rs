@@ -0,0 +1,20 @@
+use crate::audit::{audit_event::AuditEvent, audit_filter::AuditFilter};
+
+use super::dal_error::DalError;
+
+/// Append only, events are never changed or removed
+#[allow(async_fn_in_trait)]
+pub trait AuditEventsDal {
+    /// Fails with [DalError::Conflict] unless the event directly follows the last one
+    async fn append(&self, event: AuditEvent) -> Result<(), DalError>;
+
+    async fn last(&self) -> Result<Option<AuditEvent>, DalError>;
+
+    /// Matching events with a sequence number above `after`, oldest first
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<AuditEvent>, DalError>;
+}
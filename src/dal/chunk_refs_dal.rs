@@ -0,0 +1,21 @@
+use uuid::Uuid;
+
+use crate::fs::dedup::chunk_ref::{ChunkRef, ChunkUsage};
+
+use super::dal_error::DalError;
+
+/// Reference counts of the chunks of each deduplicating source
+#[allow(async_fn_in_trait)]
+pub trait ChunkRefsDal {
+    /// Adds a reference per element, so a chunk listed twice gets two
+    async fn acquire(&self, source_id: Uuid, chunks: &[ChunkRef]) -> Result<(), DalError>;
+
+    /// Removes a reference per element, counts never go below zero
+    async fn release(&self, source_id: Uuid, chunks: &[ChunkRef]) -> Result<(), DalError>;
+
+    /// Including chunks without references which haven't been collected yet
+    async fn list(&self, source_id: Uuid) -> Result<Vec<ChunkUsage>, DalError>;
+
+    /// Forgets chunks without references, returns how many
+    async fn remove_unreferenced(&self, source_id: Uuid) -> Result<usize, DalError>;
+}
@@ -0,0 +1,8 @@
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum DalError {
+    #[display("conflict: {_0}")]
+    Conflict(#[error(not(source))] String),
+
+    #[display("unexpected: {_0}")]
+    Unexpected(#[error(not(source))] String),
+}
@@ -0,0 +1,19 @@
+use uuid::Uuid;
+
+use crate::fs::encryption::wrapped_key::WrappedKey;
+
+use super::dal_error::DalError;
+
+/// Data keys of encrypted sources, wrapped with a master key
+#[allow(async_fn_in_trait)]
+pub trait DataKeysDal {
+    async fn get(&self, source_id: Uuid) -> Result<Option<WrappedKey>, DalError>;
+
+    /// Fails with a conflict if the source already has a data key
+    async fn insert(&self, key: WrappedKey) -> Result<(), DalError>;
+
+    /// Replaces the wrapping of an existing data key
+    async fn update(&self, key: WrappedKey) -> Result<(), DalError>;
+
+    async fn list(&self) -> Result<Vec<WrappedKey>, DalError>;
+}
@@ -0,0 +1,15 @@
+use crate::auth::oidc::external_identity::ExternalIdentity;
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait ExternalIdentitiesDal {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<ExternalIdentity>, DalError>;
+
+    /// Fails with [DalError::Conflict] if the subject is already linked
+    async fn insert(&self, identity: ExternalIdentity) -> Result<(), DalError>;
+}
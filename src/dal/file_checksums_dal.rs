@@ -0,0 +1,21 @@
+use uuid::Uuid;
+
+use crate::fs::{file_checksum::FileChecksum, storage_path::StoragePath};
+
+use super::dal_error::DalError;
+
+/// One checksum per path, callers check it still matches the modification time and size
+#[allow(async_fn_in_trait)]
+pub trait FileChecksumsDal {
+    async fn get(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileChecksum>, DalError>;
+
+    /// Replaces the checksum of the path
+    async fn save(&self, checksum: FileChecksum) -> Result<(), DalError>;
+
+    /// Removes the checksums of the path and everything below it, returns how many
+    async fn delete_under(&self, source_id: Uuid, path: &StoragePath) -> Result<usize, DalError>;
+}
@@ -0,0 +1,40 @@
+use uuid::Uuid;
+
+use crate::fs::{dedup::file_manifest::FileManifest, storage_path::StoragePath};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait FileManifestsDal {
+    async fn get(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileManifest>, DalError>;
+
+    /// Direct children of the directory, sorted by path
+    async fn list_children(
+        &self,
+        source_id: Uuid,
+        dir: &StoragePath,
+    ) -> Result<Vec<FileManifest>, DalError>;
+
+    async fn list(&self, source_id: Uuid) -> Result<Vec<FileManifest>, DalError>;
+
+    /// Inserts or replaces, returns the replaced manifest
+    async fn save(&self, manifest: FileManifest) -> Result<Option<FileManifest>, DalError>;
+
+    /// Moves the manifest and everything below it, fails if the target exists
+    async fn rename(
+        &self,
+        source_id: Uuid,
+        from: &StoragePath,
+        to: &StoragePath,
+    ) -> Result<(), DalError>;
+
+    async fn delete(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileManifest>, DalError>;
+}
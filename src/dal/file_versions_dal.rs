@@ -0,0 +1,21 @@
+use uuid::Uuid;
+
+use crate::{
+    fs::{file_version::FileVersion, storage_path::StoragePath},
+    utils::id::Id,
+};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait FileVersionsDal {
+    async fn insert(&self, version: FileVersion) -> Result<(), DalError>;
+
+    async fn get(&self, id: Id) -> Result<Option<FileVersion>, DalError>;
+
+    /// Newest first, versions created at the same time in reverse insertion order
+    async fn list(&self, source_id: Uuid, path: &StoragePath)
+        -> Result<Vec<FileVersion>, DalError>;
+
+    async fn delete(&self, id: Id) -> Result<Option<FileVersion>, DalError>;
+}
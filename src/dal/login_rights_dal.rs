@@ -0,0 +1,11 @@
+use crate::{auth::login_right::LoginRight, utils::id::Id};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait LoginRightsDal {
+    async fn get(&self, login_id: Id) -> Result<Option<LoginRight>, DalError>;
+
+    /// Inserts or replaces the rights of the login
+    async fn save(&self, right: LoginRight) -> Result<(), DalError>;
+}
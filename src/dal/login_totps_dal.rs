@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+
+use crate::{auth::login_totp::LoginTotp, utils::id::Id};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait LoginTotpsDal {
+    async fn get(&self, login_id: Id) -> Result<Option<LoginTotp>, DalError>;
+
+    /// Inserts or replaces the TOTP of the login
+    async fn save(&self, totp: LoginTotp) -> Result<(), DalError>;
+
+    /// Replaces the TOTP only if the stored one still equals `expected`, so a code or
+    /// a recovery code can't be used by two concurrent requests.
+    /// Returns `false` if it has been changed in the meantime
+    async fn compare_and_save(
+        &self,
+        expected: &LoginTotp,
+        totp: LoginTotp,
+    ) -> Result<bool, DalError>;
+
+    /// Returns `false` if there was nothing to delete
+    async fn delete(&self, login_id: Id) -> Result<bool, DalError>;
+
+    /// Marks the login challenge as used until it expires.
+    /// Returns `false` if it has already been used
+    async fn consume_challenge(
+        &self,
+        jti: Id,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, DalError>;
+}
@@ -0,0 +1,13 @@
+use crate::{auth::login::Login, utils::id::Id};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait LoginsDal {
+    async fn get(&self, login_id: Id) -> Result<Option<Login>, DalError>;
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Login>, DalError>;
+
+    /// Fails with [DalError::Conflict] if the id or the username is already taken
+    async fn insert(&self, login: Login) -> Result<(), DalError>;
+}
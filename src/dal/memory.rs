@@ -0,0 +1,115 @@
+pub mod audit_events;
+pub mod chunk_refs;
+pub mod data_keys;
+pub mod external_identities;
+pub mod file_checksums;
+pub mod file_manifests;
+pub mod file_versions;
+pub mod login_rights;
+pub mod login_totps;
+pub mod logins;
+pub mod oidc_auth_requests;
+pub mod personal_access_tokens;
+pub mod rate_limits;
+
+use audit_events::MemoryAuditEvents;
+use chunk_refs::MemoryChunkRefs;
+use data_keys::MemoryDataKeys;
+use external_identities::MemoryExternalIdentities;
+use file_checksums::MemoryFileChecksums;
+use file_manifests::MemoryFileManifests;
+use file_versions::MemoryFileVersions;
+use login_rights::MemoryLoginRights;
+use login_totps::MemoryLoginTotps;
+use logins::MemoryLogins;
+use oidc_auth_requests::MemoryOidcAuthRequests;
+use personal_access_tokens::MemoryPersonalAccessTokens;
+use rate_limits::MemoryRateLimits;
+
+use super::Dal;
+
+/// Keeps everything in process memory. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct MemoryDal {
+    logins: MemoryLogins,
+    login_totps: MemoryLoginTotps,
+    rate_limits: MemoryRateLimits,
+    login_rights: MemoryLoginRights,
+    personal_access_tokens: MemoryPersonalAccessTokens,
+    oidc_auth_requests: MemoryOidcAuthRequests,
+    external_identities: MemoryExternalIdentities,
+    file_manifests: MemoryFileManifests,
+    chunk_refs: MemoryChunkRefs,
+    file_versions: MemoryFileVersions,
+    file_checksums: MemoryFileChecksums,
+    data_keys: MemoryDataKeys,
+    audit_events: MemoryAuditEvents,
+}
+
+impl Dal for MemoryDal {
+    type Logins = MemoryLogins;
+    type LoginTotps = MemoryLoginTotps;
+    type RateLimits = MemoryRateLimits;
+    type LoginRights = MemoryLoginRights;
+    type PersonalAccessTokens = MemoryPersonalAccessTokens;
+    type OidcAuthRequests = MemoryOidcAuthRequests;
+    type ExternalIdentities = MemoryExternalIdentities;
+    type FileManifests = MemoryFileManifests;
+    type ChunkRefs = MemoryChunkRefs;
+    type FileVersions = MemoryFileVersions;
+    type FileChecksums = MemoryFileChecksums;
+    type DataKeys = MemoryDataKeys;
+    type AuditEvents = MemoryAuditEvents;
+
+    fn logins(&self) -> &Self::Logins {
+        &self.logins
+    }
+
+    fn login_totps(&self) -> &Self::LoginTotps {
+        &self.login_totps
+    }
+
+    fn rate_limits(&self) -> &Self::RateLimits {
+        &self.rate_limits
+    }
+
+    fn login_rights(&self) -> &Self::LoginRights {
+        &self.login_rights
+    }
+
+    fn personal_access_tokens(&self) -> &Self::PersonalAccessTokens {
+        &self.personal_access_tokens
+    }
+
+    fn oidc_auth_requests(&self) -> &Self::OidcAuthRequests {
+        &self.oidc_auth_requests
+    }
+
+    fn external_identities(&self) -> &Self::ExternalIdentities {
+        &self.external_identities
+    }
+
+    fn file_manifests(&self) -> &Self::FileManifests {
+        &self.file_manifests
+    }
+
+    fn chunk_refs(&self) -> &Self::ChunkRefs {
+        &self.chunk_refs
+    }
+
+    fn file_versions(&self) -> &Self::FileVersions {
+        &self.file_versions
+    }
+
+    fn file_checksums(&self) -> &Self::FileChecksums {
+        &self.file_checksums
+    }
+
+    fn data_keys(&self) -> &Self::DataKeys {
+        &self.data_keys
+    }
+
+    fn audit_events(&self) -> &Self::AuditEvents {
+        &self.audit_events
+    }
+}
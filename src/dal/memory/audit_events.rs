@@ -0,0 +1,48 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    audit::{audit_event::AuditEvent, audit_filter::AuditFilter},
+    dal::{audit_events_dal::AuditEventsDal, dal_error::DalError},
+};
+
+/// In sequence order, the sequence number of an event is its index plus one
+#[derive(Clone, Default)]
+pub struct MemoryAuditEvents(Arc<RwLock<Vec<AuditEvent>>>);
+
+impl AuditEventsDal for MemoryAuditEvents {
+    async fn append(&self, event: AuditEvent) -> Result<(), DalError> {
+        let mut events = self.0.write().unwrap();
+        let next = events.len() as u64 + 1;
+        if event.sequence() != next {
+            return Err(DalError::Conflict(format!(
+                "audit event {} is not the next one, {} is",
+                event.sequence(),
+                next
+            )));
+        }
+        events.push(event);
+        Ok(())
+    }
+
+    async fn last(&self) -> Result<Option<AuditEvent>, DalError> {
+        Ok(self.0.read().unwrap().last().cloned())
+    }
+
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<AuditEvent>, DalError> {
+        let events = self.0.read().unwrap();
+        let start = usize::try_from(after)
+            .unwrap_or(usize::MAX)
+            .min(events.len());
+        Ok(events[start..]
+            .iter()
+            .filter(|e| filter.matches(e))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
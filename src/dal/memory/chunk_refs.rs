@@ -0,0 +1,56 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    dal::{chunk_refs_dal::ChunkRefsDal, dal_error::DalError},
+    fs::dedup::{
+        chunk_hash::ChunkHash,
+        chunk_ref::{ChunkRef, ChunkUsage},
+    },
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryChunkRefs(Arc<RwLock<HashMap<(Uuid, ChunkHash), ChunkUsage>>>);
+
+impl ChunkRefsDal for MemoryChunkRefs {
+    async fn acquire(&self, source_id: Uuid, chunks: &[ChunkRef]) -> Result<(), DalError> {
+        let mut usages = self.0.write().unwrap();
+        for chunk in chunks {
+            let usage = usages
+                .entry((source_id, chunk.hash()))
+                .or_insert(ChunkUsage::new(*chunk, 0));
+            *usage = ChunkUsage::new(*chunk, usage.refs() + 1);
+        }
+        Ok(())
+    }
+
+    async fn release(&self, source_id: Uuid, chunks: &[ChunkRef]) -> Result<(), DalError> {
+        let mut usages = self.0.write().unwrap();
+        for chunk in chunks {
+            if let Some(usage) = usages.get_mut(&(source_id, chunk.hash())) {
+                *usage = ChunkUsage::new(usage.chunk(), usage.refs().saturating_sub(1));
+            }
+        }
+        Ok(())
+    }
+
+    async fn list(&self, source_id: Uuid) -> Result<Vec<ChunkUsage>, DalError> {
+        let usages = self.0.read().unwrap();
+        Ok(usages
+            .iter()
+            .filter(|((id, _), _)| *id == source_id)
+            .map(|(_, usage)| *usage)
+            .collect())
+    }
+
+    async fn remove_unreferenced(&self, source_id: Uuid) -> Result<usize, DalError> {
+        let mut usages = self.0.write().unwrap();
+        let before = usages.len();
+        usages.retain(|(id, _), usage| *id != source_id || usage.refs() > 0);
+        Ok(before - usages.len())
+    }
+}
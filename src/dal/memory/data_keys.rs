@@ -0,0 +1,50 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    dal::{dal_error::DalError, data_keys_dal::DataKeysDal},
+    fs::encryption::wrapped_key::WrappedKey,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryDataKeys(Arc<RwLock<HashMap<Uuid, WrappedKey>>>);
+
+impl DataKeysDal for MemoryDataKeys {
+    async fn get(&self, source_id: Uuid) -> Result<Option<WrappedKey>, DalError> {
+        Ok(self.0.read().unwrap().get(&source_id).cloned())
+    }
+
+    async fn insert(&self, key: WrappedKey) -> Result<(), DalError> {
+        let mut keys = self.0.write().unwrap();
+        if keys.contains_key(&key.source_id()) {
+            return Err(DalError::Conflict(format!(
+                "source {} already has a data key",
+                key.source_id()
+            )));
+        }
+        keys.insert(key.source_id(), key);
+        Ok(())
+    }
+
+    async fn update(&self, key: WrappedKey) -> Result<(), DalError> {
+        let mut keys = self.0.write().unwrap();
+        match keys.get_mut(&key.source_id()) {
+            Some(existing) => {
+                *existing = key;
+                Ok(())
+            }
+            None => Err(DalError::Unexpected(format!(
+                "source {} has no data key",
+                key.source_id()
+            ))),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<WrappedKey>, DalError> {
+        Ok(self.0.read().unwrap().values().cloned().collect())
+    }
+}
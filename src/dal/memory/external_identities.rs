@@ -0,0 +1,38 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    auth::oidc::external_identity::ExternalIdentity,
+    dal::{dal_error::DalError, external_identities_dal::ExternalIdentitiesDal},
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryExternalIdentities(Arc<RwLock<HashMap<(String, String), ExternalIdentity>>>);
+
+impl ExternalIdentitiesDal for MemoryExternalIdentities {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<ExternalIdentity>, DalError> {
+        let key = (provider.to_owned(), subject.to_owned());
+        Ok(self.0.read().unwrap().get(&key).cloned())
+    }
+
+    async fn insert(&self, identity: ExternalIdentity) -> Result<(), DalError> {
+        let mut identities = self.0.write().unwrap();
+        let key = (
+            identity.provider().to_owned(),
+            identity.subject().to_owned(),
+        );
+        if identities.contains_key(&key) {
+            return Err(DalError::Conflict(
+                "identity is already linked to a login".to_owned(),
+            ));
+        }
+        identities.insert(key, identity);
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    dal::{dal_error::DalError, file_checksums_dal::FileChecksumsDal},
+    fs::{file_checksum::FileChecksum, storage_path::StoragePath},
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryFileChecksums(Arc<RwLock<HashMap<(Uuid, StoragePath), FileChecksum>>>);
+
+impl FileChecksumsDal for MemoryFileChecksums {
+    async fn get(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileChecksum>, DalError> {
+        let checksums = self.0.read().unwrap();
+        Ok(checksums.get(&(source_id, path.clone())).cloned())
+    }
+
+    async fn save(&self, checksum: FileChecksum) -> Result<(), DalError> {
+        let mut checksums = self.0.write().unwrap();
+        let key = (checksum.source_id(), checksum.path().clone());
+        checksums.insert(key, checksum);
+        Ok(())
+    }
+
+    async fn delete_under(&self, source_id: Uuid, path: &StoragePath) -> Result<usize, DalError> {
+        let mut checksums = self.0.write().unwrap();
+        let before = checksums.len();
+        checksums.retain(|(id, p), _| *id != source_id || !p.starts_with(path));
+        Ok(before - checksums.len())
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    dal::{dal_error::DalError, file_manifests_dal::FileManifestsDal},
+    fs::{dedup::file_manifest::FileManifest, storage_path::StoragePath},
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryFileManifests(Arc<RwLock<BTreeMap<(Uuid, StoragePath), FileManifest>>>);
+
+impl FileManifestsDal for MemoryFileManifests {
+    async fn get(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileManifest>, DalError> {
+        let manifests = self.0.read().unwrap();
+        Ok(manifests.get(&(source_id, path.clone())).cloned())
+    }
+
+    async fn list_children(
+        &self,
+        source_id: Uuid,
+        dir: &StoragePath,
+    ) -> Result<Vec<FileManifest>, DalError> {
+        let manifests = self.0.read().unwrap();
+        Ok(manifests
+            .iter()
+            .filter(|((id, path), _)| *id == source_id && path.is_child_of(dir))
+            .map(|(_, m)| m.clone())
+            .collect())
+    }
+
+    async fn list(&self, source_id: Uuid) -> Result<Vec<FileManifest>, DalError> {
+        let manifests = self.0.read().unwrap();
+        Ok(manifests
+            .iter()
+            .filter(|((id, _), _)| *id == source_id)
+            .map(|(_, m)| m.clone())
+            .collect())
+    }
+
+    async fn save(&self, manifest: FileManifest) -> Result<Option<FileManifest>, DalError> {
+        let mut manifests = self.0.write().unwrap();
+        let key = (manifest.source_id(), manifest.path().clone());
+        Ok(manifests.insert(key, manifest))
+    }
+
+    async fn rename(
+        &self,
+        source_id: Uuid,
+        from: &StoragePath,
+        to: &StoragePath,
+    ) -> Result<(), DalError> {
+        let mut manifests = self.0.write().unwrap();
+        if manifests.contains_key(&(source_id, to.clone())) {
+            return Err(DalError::Conflict(format!("'{}' already exists", to)));
+        }
+        let moved: Vec<_> = manifests
+            .keys()
+            .filter(|(id, path)| *id == source_id && path.starts_with(from))
+            .cloned()
+            .collect();
+        for key in moved {
+            let manifest = manifests.remove(&key).unwrap();
+            let suffix = &key.1.as_str()[from.as_str().len()..];
+            let path = StoragePath::parse(&format!("{}{}", to.as_str(), suffix))
+                .map_err(|e| DalError::Unexpected(e.to_string()))?;
+            manifests.insert((source_id, path.clone()), manifest.with_path(path));
+        }
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Option<FileManifest>, DalError> {
+        let mut manifests = self.0.write().unwrap();
+        Ok(manifests.remove(&(source_id, path.clone())))
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    cmp::Reverse,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    dal::{dal_error::DalError, file_versions_dal::FileVersionsDal},
+    fs::{file_version::FileVersion, storage_path::StoragePath},
+    utils::id::Id,
+};
+
+/// In insertion order
+#[derive(Clone, Default)]
+pub struct MemoryFileVersions(Arc<RwLock<Vec<FileVersion>>>);
+
+impl FileVersionsDal for MemoryFileVersions {
+    async fn insert(&self, version: FileVersion) -> Result<(), DalError> {
+        let mut versions = self.0.write().unwrap();
+        if versions.iter().any(|v| v.id() == version.id()) {
+            return Err(DalError::Conflict(format!(
+                "version {} already exists",
+                version.id()
+            )));
+        }
+        versions.push(version);
+        Ok(())
+    }
+
+    async fn get(&self, id: Id) -> Result<Option<FileVersion>, DalError> {
+        let versions = self.0.read().unwrap();
+        Ok(versions.iter().find(|v| v.id() == id).cloned())
+    }
+
+    async fn list(
+        &self,
+        source_id: Uuid,
+        path: &StoragePath,
+    ) -> Result<Vec<FileVersion>, DalError> {
+        let versions = self.0.read().unwrap();
+        let mut found: Vec<_> = versions
+            .iter()
+            .rev()
+            .filter(|v| v.source_id() == source_id && v.path() == path)
+            .cloned()
+            .collect();
+        found.sort_by_key(|v| Reverse(v.created()));
+        Ok(found)
+    }
+
+    async fn delete(&self, id: Id) -> Result<Option<FileVersion>, DalError> {
+        let mut versions = self.0.write().unwrap();
+        Ok(versions
+            .iter()
+            .position(|v| v.id() == id)
+            .map(|index| versions.remove(index)))
+    }
+}
@@ -0,0 +1,24 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    auth::login_right::LoginRight,
+    dal::{dal_error::DalError, login_rights_dal::LoginRightsDal},
+    utils::id::Id,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryLoginRights(Arc<RwLock<HashMap<Id, LoginRight>>>);
+
+impl LoginRightsDal for MemoryLoginRights {
+    async fn get(&self, login_id: Id) -> Result<Option<LoginRight>, DalError> {
+        Ok(self.0.read().unwrap().get(&login_id).cloned())
+    }
+
+    async fn save(&self, right: LoginRight) -> Result<(), DalError> {
+        self.0.write().unwrap().insert(right.login_id(), right);
+        Ok(())
+    }
+}
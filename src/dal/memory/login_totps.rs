@@ -0,0 +1,57 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    auth::login_totp::LoginTotp,
+    dal::{dal_error::DalError, login_totps_dal::LoginTotpsDal},
+    utils::id::Id,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryLoginTotps {
+    totps: Arc<RwLock<HashMap<Id, LoginTotp>>>,
+    consumed_challenges: Arc<RwLock<HashMap<Id, DateTime<Utc>>>>,
+}
+
+impl LoginTotpsDal for MemoryLoginTotps {
+    async fn get(&self, login_id: Id) -> Result<Option<LoginTotp>, DalError> {
+        Ok(self.totps.read().unwrap().get(&login_id).cloned())
+    }
+
+    async fn save(&self, totp: LoginTotp) -> Result<(), DalError> {
+        self.totps.write().unwrap().insert(totp.login_id(), totp);
+        Ok(())
+    }
+
+    async fn compare_and_save(
+        &self,
+        expected: &LoginTotp,
+        totp: LoginTotp,
+    ) -> Result<bool, DalError> {
+        let mut totps = self.totps.write().unwrap();
+        if totps.get(&expected.login_id()) != Some(expected) {
+            return Ok(false);
+        }
+        totps.insert(totp.login_id(), totp);
+        Ok(true)
+    }
+
+    async fn delete(&self, login_id: Id) -> Result<bool, DalError> {
+        Ok(self.totps.write().unwrap().remove(&login_id).is_some())
+    }
+
+    async fn consume_challenge(
+        &self,
+        jti: Id,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, DalError> {
+        let mut consumed = self.consumed_challenges.write().unwrap();
+        consumed.retain(|_, expires_at| *expires_at > now);
+        Ok(consumed.insert(jti, expires_at).is_none())
+    }
+}
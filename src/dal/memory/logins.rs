@@ -0,0 +1,39 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    auth::login::Login,
+    dal::{dal_error::DalError, logins_dal::LoginsDal},
+    utils::id::Id,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryLogins(Arc<RwLock<HashMap<Id, Login>>>);
+
+impl LoginsDal for MemoryLogins {
+    async fn get(&self, login_id: Id) -> Result<Option<Login>, DalError> {
+        Ok(self.0.read().unwrap().get(&login_id).cloned())
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Login>, DalError> {
+        let logins = self.0.read().unwrap();
+        Ok(logins.values().find(|l| l.username() == username).cloned())
+    }
+
+    async fn insert(&self, login: Login) -> Result<(), DalError> {
+        let mut logins = self.0.write().unwrap();
+        if logins.contains_key(&login.login_id()) {
+            return Err(DalError::Conflict(format!(
+                "login '{}' already exists",
+                login.login_id()
+            )));
+        }
+        if logins.values().any(|l| l.username() == login.username()) {
+            return Err(DalError::Conflict("username is already taken".to_owned()));
+        }
+        logins.insert(login.login_id(), login);
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    auth::oidc::oidc_auth_request::OidcAuthRequest,
+    dal::{dal_error::DalError, oidc_auth_requests_dal::OidcAuthRequestsDal},
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryOidcAuthRequests(Arc<RwLock<HashMap<String, OidcAuthRequest>>>);
+
+impl OidcAuthRequestsDal for MemoryOidcAuthRequests {
+    async fn insert(&self, request: OidcAuthRequest) -> Result<(), DalError> {
+        let mut requests = self.0.write().unwrap();
+        if requests.contains_key(request.state()) {
+            return Err(DalError::Conflict("state is already used".to_owned()));
+        }
+        requests.insert(request.state().to_owned(), request);
+        Ok(())
+    }
+
+    async fn take(&self, state: &str) -> Result<Option<OidcAuthRequest>, DalError> {
+        Ok(self.0.write().unwrap().remove(state))
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<(), DalError> {
+        self.0.write().unwrap().retain(|_, r| !r.is_expired(now));
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    auth::personal_access_token::{PersonalAccessToken, TokenHash},
+    dal::{dal_error::DalError, personal_access_tokens_dal::PersonalAccessTokensDal},
+    utils::id::Id,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryPersonalAccessTokens(Arc<RwLock<HashMap<Id, PersonalAccessToken>>>);
+
+impl PersonalAccessTokensDal for MemoryPersonalAccessTokens {
+    async fn insert(&self, token: PersonalAccessToken) -> Result<(), DalError> {
+        let mut tokens = self.0.write().unwrap();
+        if tokens.contains_key(&token.token_id()) {
+            return Err(DalError::Conflict(format!(
+                "token '{}' already exists",
+                token.token_id()
+            )));
+        }
+        tokens.insert(token.token_id(), token);
+        Ok(())
+    }
+
+    async fn find_by_hash(
+        &self,
+        hash: &TokenHash,
+    ) -> Result<Option<PersonalAccessToken>, DalError> {
+        let tokens = self.0.read().unwrap();
+        Ok(tokens.values().find(|t| t.hash() == hash).cloned())
+    }
+
+    async fn list_by_login(&self, login_id: Id) -> Result<Vec<PersonalAccessToken>, DalError> {
+        let tokens = self.0.read().unwrap();
+        let mut result: Vec<_> = tokens
+            .values()
+            .filter(|t| t.login_id() == login_id)
+            .cloned()
+            .collect();
+        result.sort_by_key(|t| t.token_id());
+        Ok(result)
+    }
+
+    async fn delete(&self, login_id: Id, token_id: Id) -> Result<bool, DalError> {
+        let mut tokens = self.0.write().unwrap();
+        match tokens.get(&token_id) {
+            Some(t) if t.login_id() == login_id => Ok(tokens.remove(&token_id).is_some()),
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_last_used_at(&self, token_id: Id, now: DateTime<Utc>) -> Result<(), DalError> {
+        if let Some(token) = self.0.write().unwrap().get_mut(&token_id) {
+            token.set_last_used_at(now);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    dal::{dal_error::DalError, rate_limits_dal::RateLimitsDal},
+    rate_limit::rate_limit_entry::RateLimitEntry,
+};
+
+/// Versions come from one counter for the whole store, so a key which is removed and created
+/// again never gets a version an earlier reader still expects
+#[derive(Clone, Default)]
+pub struct MemoryRateLimits(Arc<RwLock<Entries>>);
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, (RateLimitEntry, u64)>,
+    last_version: u64,
+}
+
+impl RateLimitsDal for MemoryRateLimits {
+    async fn get(&self, key: &str) -> Result<Option<(RateLimitEntry, u64)>, DalError> {
+        Ok(self.0.read().unwrap().entries.get(key).copied())
+    }
+
+    async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: Option<u64>,
+        entry: Option<RateLimitEntry>,
+    ) -> Result<bool, DalError> {
+        let mut store = self.0.write().unwrap();
+        let current = store.entries.get(key).map(|(_, v)| *v);
+        if current != expected {
+            return Ok(false);
+        }
+        match entry {
+            Some(entry) => {
+                store.last_version += 1;
+                let version = store.last_version;
+                store.entries.insert(key.to_owned(), (entry, version));
+            }
+            None => {
+                store.entries.remove(key);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<(), DalError> {
+        self.0
+            .write()
+            .unwrap()
+            .entries
+            .retain(|_, (e, _)| e.expires_at > now);
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+
+use crate::auth::oidc::oidc_auth_request::OidcAuthRequest;
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait OidcAuthRequestsDal {
+    /// Fails with [DalError::Conflict] if the state is already used
+    async fn insert(&self, request: OidcAuthRequest) -> Result<(), DalError>;
+
+    /// Removes and returns the request, so every request can be completed only once
+    async fn take(&self, state: &str) -> Result<Option<OidcAuthRequest>, DalError>;
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<(), DalError>;
+}
@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    auth::personal_access_token::{PersonalAccessToken, TokenHash},
+    utils::id::Id,
+};
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait PersonalAccessTokensDal {
+    async fn insert(&self, token: PersonalAccessToken) -> Result<(), DalError>;
+
+    async fn find_by_hash(&self, hash: &TokenHash)
+        -> Result<Option<PersonalAccessToken>, DalError>;
+
+    async fn list_by_login(&self, login_id: Id) -> Result<Vec<PersonalAccessToken>, DalError>;
+
+    /// Returns `false` if the login has no such token
+    async fn delete(&self, login_id: Id, token_id: Id) -> Result<bool, DalError>;
+
+    async fn set_last_used_at(&self, token_id: Id, now: DateTime<Utc>) -> Result<(), DalError>;
+}
@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+
+use crate::rate_limit::rate_limit_entry::RateLimitEntry;
+
+use super::dal_error::DalError;
+
+#[allow(async_fn_in_trait)]
+pub trait RateLimitsDal {
+    /// Returns the entry along with its version. Versions of a key are never reused,
+    /// even after it's removed.
+    async fn get(&self, key: &str) -> Result<Option<(RateLimitEntry, u64)>, DalError>;
+
+    /// Stores `entry` (or removes the key if `None`) only if the current version equals `expected`.
+    /// `expected == None` means the key must not exist.
+    async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: Option<u64>,
+        entry: Option<RateLimitEntry>,
+    ) -> Result<bool, DalError>;
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<(), DalError>;
+}
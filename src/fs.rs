@@ -1 +1,17 @@
+pub mod archive_backend;
+pub mod compressed_backend;
+pub mod dedup;
+pub mod digests;
+pub mod encryption;
+pub mod file_checksum;
+pub mod file_version;
+pub mod local_backend;
+pub mod memory_backend;
+pub mod metered_backend;
+pub mod scrubber;
 pub mod source;
+pub mod sources;
+pub mod storage_backend;
+pub mod storage_error;
+pub mod storage_path;
+pub mod version_store;
@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::FutureExt;
+
+use super::{
+    storage_backend::{
+        file_stream, once_stream, resolve_range, ByteStream, EntryMetadata, FileRead,
+        StorageBackend, StorageResult,
+    },
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    /// Directories have no content
+    Directory,
+    /// Uncompressed content at an offset of the archive file
+    Raw { offset: u64 },
+    /// Compressed zip entry, has to be inflated to be read
+    Zip { index: usize },
+}
+
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    metadata: EntryMetadata,
+    location: Location,
+}
+
+/// Read only view of an uncompressed tar or a zip file.
+/// The archive is indexed once when opened and has to stay unchanged afterwards.
+#[derive(Debug)]
+pub struct ArchiveBackend {
+    archive: PathBuf,
+    entries: BTreeMap<StoragePath, ArchiveEntry>,
+    modified: DateTime<Utc>,
+}
+
+impl ArchiveBackend {
+    pub fn open_tar(archive: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let archive = archive.into();
+        let modified = file_modified(&archive)?;
+        let mut entries = BTreeMap::new();
+
+        let mut tar = tar::Archive::new(std::fs::File::open(&archive)?);
+        for entry in tar.entries()? {
+            let entry = entry?;
+            let Some(path) = archive_path(&entry.path()?.to_string_lossy()) else {
+                continue;
+            };
+            let header = entry.header();
+            let entry_modified = header
+                .mtime()
+                .ok()
+                .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                .unwrap_or(modified);
+            let entry = match header.entry_type() {
+                tar::EntryType::Regular | tar::EntryType::Continuous => ArchiveEntry {
+                    metadata: EntryMetadata::file(path.clone(), entry.size(), entry_modified),
+                    location: Location::Raw {
+                        offset: entry.raw_file_position(),
+                    },
+                },
+                tar::EntryType::Directory => ArchiveEntry {
+                    metadata: EntryMetadata::directory(path.clone(), entry_modified),
+                    location: Location::Directory,
+                },
+                _ => continue,
+            };
+            insert_entry(&mut entries, path, entry, modified);
+        }
+
+        Ok(Self {
+            archive,
+            entries,
+            modified,
+        })
+    }
+
+    pub fn open_zip(archive: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let archive = archive.into();
+        let modified = file_modified(&archive)?;
+        let mut entries = BTreeMap::new();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive)?).map_err(zip_error)?;
+        for index in 0..zip.len() {
+            let file = zip.by_index_raw(index).map_err(zip_error)?;
+            if file.encrypted() {
+                continue;
+            }
+            let Some(path) = archive_path(file.name()) else {
+                continue;
+            };
+            let entry_modified = file
+                .last_modified()
+                .and_then(zip_datetime)
+                .unwrap_or(modified);
+            let entry = if file.is_dir() {
+                ArchiveEntry {
+                    metadata: EntryMetadata::directory(path.clone(), entry_modified),
+                    location: Location::Directory,
+                }
+            } else {
+                let location = match file.compression() {
+                    zip::CompressionMethod::Stored => Location::Raw {
+                        offset: file.data_start(),
+                    },
+                    _ => Location::Zip { index },
+                };
+                ArchiveEntry {
+                    metadata: EntryMetadata::file(path.clone(), file.size(), entry_modified),
+                    location,
+                }
+            };
+            insert_entry(&mut entries, path, entry, modified);
+        }
+
+        Ok(Self {
+            archive,
+            entries,
+            modified,
+        })
+    }
+
+    fn entry(&self, path: &StoragePath) -> Result<EntryMetadata, StorageError> {
+        if path.is_root() {
+            return Ok(EntryMetadata::directory(StoragePath::root(), self.modified));
+        }
+        self.entries
+            .get(path)
+            .map(|e| e.metadata.clone())
+            .ok_or_else(|| StorageError::NotFound(path.to_string()))
+    }
+
+    /// Inflates the entry up to the end of the range and keeps only the range
+    async fn read_zip_entry(&self, index: usize, range: Range<u64>) -> Result<Bytes, StorageError> {
+        let archive = self.archive.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut zip =
+                zip::ZipArchive::new(std::fs::File::open(&archive)?).map_err(zip_error)?;
+            let mut file = zip.by_index(index).map_err(zip_error)?.take(range.end);
+            std::io::copy(&mut (&mut file).take(range.start), &mut std::io::sink())?;
+            let mut content = Vec::with_capacity((range.end - range.start) as usize);
+            file.read_to_end(&mut content)?;
+            Ok(Bytes::from(content))
+        })
+        .await
+        .map_err(|e| StorageError::Io(e.to_string()))?
+    }
+}
+
+impl StorageBackend for ArchiveBackend {
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        let result = self.entry(path);
+        async move { result }.boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        let result = self.entry(path).and_then(|dir| {
+            if !dir.is_dir() {
+                return Err(StorageError::NotADirectory(path.to_string()));
+            }
+            Ok(self
+                .entries
+                .iter()
+                .filter(|(p, _)| p.is_child_of(path))
+                .map(|(_, e)| e.metadata.clone())
+                .collect())
+        });
+        async move { result }.boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        async move {
+            let metadata = self.entry(path)?;
+            if metadata.is_dir() {
+                return Err(StorageError::IsADirectory(path.to_string()));
+            }
+            let range = resolve_range(range, metadata.size())?;
+            let stream: ByteStream = match self.entries[path].location {
+                Location::Raw { offset } => {
+                    let file = tokio::fs::File::open(&self.archive).await?;
+                    file_stream(file, offset + range.start, range.end - range.start)
+                }
+                Location::Zip { index } => {
+                    once_stream(self.read_zip_entry(index, range.clone()).await?)
+                }
+                Location::Directory => unreachable!("directories are rejected above"),
+            };
+            Ok(FileRead {
+                metadata,
+                range,
+                stream,
+            })
+        }
+        .boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        _path: &'a StoragePath,
+        _content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async { Err(StorageError::ReadOnly) }.boxed_local()
+    }
+
+    fn rename<'a>(&'a self, _from: &'a StoragePath, _to: &'a StoragePath) -> StorageResult<'a, ()> {
+        async { Err(StorageError::ReadOnly) }.boxed_local()
+    }
+
+    fn delete<'a>(&'a self, _path: &'a StoragePath) -> StorageResult<'a, ()> {
+        async { Err(StorageError::ReadOnly) }.boxed_local()
+    }
+
+    fn mkdir<'a>(&'a self, _path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async { Err(StorageError::ReadOnly) }.boxed_local()
+    }
+}
+
+/// Entries with `..` or absolute paths are skipped, `./` prefixes are dropped
+fn archive_path(name: &str) -> Option<StoragePath> {
+    StoragePath::parse(name).ok().filter(|p| !p.is_root())
+}
+
+/// Adds the entry and the directories above it which have no entry of their own
+fn insert_entry(
+    entries: &mut BTreeMap<StoragePath, ArchiveEntry>,
+    path: StoragePath,
+    entry: ArchiveEntry,
+    modified: DateTime<Utc>,
+) {
+    let mut parent = path.parent();
+    while let Some(dir) = parent.filter(|p| !p.is_root()) {
+        parent = dir.parent();
+        entries.entry(dir.clone()).or_insert_with(|| ArchiveEntry {
+            metadata: EntryMetadata::directory(dir, modified),
+            location: Location::Directory,
+        });
+    }
+    entries.insert(path, entry);
+}
+
+fn file_modified(path: &Path) -> Result<DateTime<Utc>, StorageError> {
+    Ok(std::fs::metadata(path)?.modified()?.into())
+}
+
+fn zip_datetime(value: zip::DateTime) -> Option<DateTime<Utc>> {
+    NaiveDate::from_ymd_opt(
+        value.year().into(),
+        value.month().into(),
+        value.day().into(),
+    )?
+    .and_hms_opt(
+        value.hour().into(),
+        value.minute().into(),
+        value.second().into(),
+    )
+    .map(|t| t.and_utc())
+}
+
+fn zip_error(error: zip::result::ZipError) -> StorageError {
+    match error {
+        zip::result::ZipError::Io(e) => e.into(),
+        e => StorageError::Io(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::{collect_stream, EntryKind};
+    use crate::test::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use std::io::Write;
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    fn make_tar(path: &Path) {
+        let mut builder = tar::Builder::new(std::fs::File::create(path).unwrap());
+        for (name, content) in [("docs/a.txt", "hello tar"), ("b.txt", "b")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn make_zip(path: &Path) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        let deflated = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        writer.add_directory("empty/", stored).unwrap();
+        writer.start_file("docs/stored.txt", stored).unwrap();
+        writer.write_all(b"hello stored").unwrap();
+        writer.start_file("docs/deflated.txt", deflated).unwrap();
+        writer.write_all(b"hello deflated").unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn tar_entries() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            make_tar(&dir.path().join("a.tar"));
+
+            // act
+            let backend = ArchiveBackend::open_tar(dir.path().join("a.tar")).unwrap();
+
+            // assert
+            let root: Vec<_> = backend
+                .list(&StoragePath::root())
+                .await
+                .unwrap()
+                .iter()
+                .map(|e| (e.name().to_owned(), e.kind()))
+                .collect();
+            assert_eq!(
+                root,
+                vec![
+                    ("b.txt".to_owned(), EntryKind::File),
+                    ("docs".to_owned(), EntryKind::Directory)
+                ]
+            );
+            let read = backend.read(&path("docs/a.txt"), Some(6..9)).await.unwrap();
+            assert_eq!(read.metadata.modified(), utc!(2023, 11, 14, 22, 13, 20));
+            assert_eq!(collect_stream(read.stream).await.unwrap(), "tar");
+        })
+    }
+
+    #[test]
+    fn zip_entries() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            make_zip(&dir.path().join("a.zip"));
+
+            // act
+            let backend = ArchiveBackend::open_zip(dir.path().join("a.zip")).unwrap();
+
+            // assert
+            assert!(backend.stat(&path("empty")).await.unwrap().is_dir());
+            assert_eq!(backend.list(&path("docs")).await.unwrap().len(), 2);
+            let stored = backend
+                .read(&path("docs/stored.txt"), Some(6..12))
+                .await
+                .unwrap();
+            assert_eq!(collect_stream(stored.stream).await.unwrap(), "stored");
+            let deflated = backend
+                .read(&path("docs/deflated.txt"), Some(6..14))
+                .await
+                .unwrap();
+            assert_eq!(collect_stream(deflated.stream).await.unwrap(), "deflated");
+        })
+    }
+
+    #[test]
+    fn archives_are_read_only() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            make_zip(&dir.path().join("a.zip"));
+            let backend = ArchiveBackend::open_zip(dir.path().join("a.zip")).unwrap();
+
+            // act
+            let result = backend.mkdir(&path("new")).await;
+
+            // assert
+            assert!(backend.is_read_only());
+            assert!(matches!(result, Err(StorageError::ReadOnly)));
+        })
+    }
+}
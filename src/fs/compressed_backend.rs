@@ -0,0 +1,393 @@
+use std::{io::Write, ops::Range};
+
+use bytes::Bytes;
+use futures::{FutureExt, StreamExt};
+
+use super::{
+    dedup::dedup_stats::{DedupStats, GarbageReport},
+    storage_backend::{
+        collect_stream, resolve_range, ByteStream, EntryMetadata, FileRead, StorageBackend,
+        StorageCheck, StorageResult,
+    },
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+const TRAILER_MAGIC: &[u8; 4] = b"RZST";
+/// Start of every zstd frame
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// Size of the content as little endian u64, then the magic
+const TRAILER_SIZE: u64 = 8 + TRAILER_MAGIC.len() as u64;
+
+/// Stores file contents compressed with zstd in another backend. A trailer keeps the size
+/// of the content, so stat and list don't decompress anything. Ranges are decompressed
+/// from the start of the file. Files stored without compression are read as they are.
+pub struct CompressedBackend {
+    inner: Box<dyn StorageBackend>,
+    level: i32,
+}
+
+impl CompressedBackend {
+    pub fn new(inner: impl StorageBackend + 'static, level: i32) -> Self {
+        Self {
+            inner: Box::new(inner),
+            level,
+        }
+    }
+
+    /// Size of the content of a compressed file, `None` for files stored as they are,
+    /// e.g. written before compression was enabled. Compressed files start with the zstd
+    /// magic and end with the trailer.
+    async fn content_size(&self, metadata: &EntryMetadata) -> Result<Option<u64>, StorageError> {
+        let path = metadata.path();
+        let Some(start) = metadata
+            .size()
+            .checked_sub(TRAILER_SIZE + ZSTD_MAGIC.len() as u64)
+            .map(|start| start + ZSTD_MAGIC.len() as u64)
+        else {
+            return Ok(None);
+        };
+        let trailer = self.inner.read(path, Some(start..metadata.size())).await?;
+        let trailer = collect_stream(trailer.stream).await?;
+        let Some(size) = trailer
+            .strip_suffix(TRAILER_MAGIC)
+            .and_then(|size| size.try_into().ok())
+            .map(u64::from_le_bytes)
+        else {
+            return Ok(None);
+        };
+        let magic = self
+            .inner
+            .read(path, Some(0..ZSTD_MAGIC.len() as u64))
+            .await?;
+        Ok((collect_stream(magic.stream).await? == ZSTD_MAGIC.as_slice()).then_some(size))
+    }
+
+    async fn plain_metadata(&self, metadata: EntryMetadata) -> Result<EntryMetadata, StorageError> {
+        if metadata.is_dir() {
+            return Ok(metadata);
+        }
+        match self.content_size(&metadata).await? {
+            Some(size) => Ok(EntryMetadata::file(
+                metadata.path().clone(),
+                size,
+                metadata.modified(),
+            )),
+            None => Ok(metadata),
+        }
+    }
+
+    async fn read_file(
+        &self,
+        path: &StoragePath,
+        range: Option<Range<u64>>,
+    ) -> Result<FileRead, StorageError> {
+        let stored = self.inner.stat(path).await?;
+        if stored.is_dir() {
+            return Err(StorageError::IsADirectory(path.to_string()));
+        }
+        let stored_size = stored.size();
+        let Some(size) = self.content_size(&stored).await? else {
+            return self.inner.read(path, range).await;
+        };
+        let metadata = EntryMetadata::file(path.clone(), size, stored.modified());
+        let range = resolve_range(range, size)?;
+        let stream = if range.is_empty() {
+            futures::stream::empty().boxed_local()
+        } else {
+            let compressed = self
+                .inner
+                .read(path, Some(0..stored_size - TRAILER_SIZE))
+                .await?;
+            decompress_stream(compressed.stream, range.clone())?
+        };
+        Ok(FileRead {
+            metadata,
+            range,
+            stream,
+        })
+    }
+}
+
+fn io_error(e: std::io::Error) -> StorageError {
+    StorageError::Io(format!("zstd: {}", e))
+}
+
+/// Compresses the content while it passes through and appends the trailer
+fn compress_stream(content: ByteStream, level: i32) -> Result<ByteStream, StorageError> {
+    let encoder = zstd::stream::write::Encoder::new(Vec::new(), level).map_err(io_error)?;
+    let state = (content, encoder, 0u64);
+    let stream = futures::stream::unfold(Some(state), |state| async move {
+        let (mut content, mut encoder, mut size) = state?;
+        loop {
+            match content.next().await {
+                Some(Ok(data)) => {
+                    if let Err(e) = encoder.write_all(&data) {
+                        return Some((Err(io_error(e)), None));
+                    }
+                    size += data.len() as u64;
+                    let compressed = std::mem::take(encoder.get_mut());
+                    if !compressed.is_empty() {
+                        let next = Some((content, encoder, size));
+                        return Some((Ok(Bytes::from(compressed)), next));
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    let mut rest = match encoder.finish() {
+                        Ok(rest) => rest,
+                        Err(e) => return Some((Err(io_error(e)), None)),
+                    };
+                    rest.extend_from_slice(&size.to_le_bytes());
+                    rest.extend_from_slice(TRAILER_MAGIC);
+                    return Some((Ok(Bytes::from(rest)), None));
+                }
+            }
+        }
+    });
+    Ok(stream.boxed_local())
+}
+
+/// Decompresses from the start and yields the part within the range, stops once it's complete
+fn decompress_stream(
+    compressed: ByteStream,
+    range: Range<u64>,
+) -> Result<ByteStream, StorageError> {
+    let decoder = zstd::stream::write::Decoder::new(Vec::new()).map_err(io_error)?;
+    let state = (compressed, decoder, 0u64);
+    let stream = futures::stream::unfold(Some(state), move |state| {
+        let range = range.clone();
+        async move {
+            let (mut compressed, mut decoder, mut position) = state?;
+            loop {
+                let data = match compressed.next().await {
+                    Some(Ok(data)) => Some(data),
+                    Some(Err(e)) => return Some((Err(e), None)),
+                    None => None,
+                };
+                let written = match &data {
+                    Some(data) => decoder.write_all(data).and_then(|_| decoder.flush()),
+                    None => decoder.flush(),
+                };
+                if let Err(e) = written {
+                    return Some((Err(io_error(e)), None));
+                }
+                let plain = std::mem::take(decoder.get_mut());
+                let end = position + plain.len() as u64;
+                let (from, to) = (
+                    range.start.clamp(position, end),
+                    range.end.clamp(position, end),
+                );
+                let part =
+                    Bytes::from(plain).slice((from - position) as usize..(to - position) as usize);
+                if end >= range.end {
+                    return Some((Ok(part), None));
+                }
+                if data.is_none() {
+                    let error = StorageError::Io("compressed file ended early".to_owned());
+                    return Some((Err(error), None));
+                }
+                position = end;
+                if !part.is_empty() {
+                    return Some((Ok(part), Some((compressed, decoder, position))));
+                }
+            }
+        }
+    });
+    Ok(stream.boxed_local())
+}
+
+impl StorageBackend for CompressedBackend {
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async move { self.plain_metadata(self.inner.stat(path).await?).await }.boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        async move {
+            let mut entries = Vec::new();
+            for entry in self.inner.list(path).await? {
+                entries.push(self.plain_metadata(entry).await?);
+            }
+            Ok(entries)
+        }
+        .boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        self.read_file(path, range).boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            let compressed = compress_stream(content, self.level)?;
+            let metadata = self.inner.write(path, compressed).await?;
+            self.plain_metadata(metadata).await
+        }
+        .boxed_local()
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.inner.rename(from, to)
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.inner.delete(path)
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.inner.mkdir(path)
+    }
+
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        self.inner.dedup_stats()
+    }
+
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        self.inner.collect_garbage()
+    }
+
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.inner.check()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::memory_backend::MemoryBackend;
+    use crate::fs::storage_backend::once_stream;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn stores_compressed_and_reads_ranges() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let backend = CompressedBackend::new(inner.clone(), 3);
+            let content: String = (0..20_000).map(|i| format!("line {}\n", i % 100)).collect();
+            let chunks: Vec<_> = content
+                .as_bytes()
+                .chunks(1000)
+                .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                .collect();
+
+            // act
+            let written = backend
+                .write(&path("a.txt"), futures::stream::iter(chunks).boxed_local())
+                .await
+                .unwrap();
+            let part = backend
+                .read(&path("a.txt"), Some(50_000..50_020))
+                .await
+                .unwrap();
+
+            // assert
+            let stored = inner.stat(&path("a.txt")).await.unwrap();
+            assert_eq!(written.size(), content.len() as u64);
+            assert!(stored.size() < content.len() as u64 / 10);
+            assert_eq!(part.metadata.size(), content.len() as u64);
+            assert_eq!(
+                collect_stream(part.stream).await.unwrap(),
+                content[50_000..50_020]
+            );
+            let listed = backend.list(&StoragePath::root()).await.unwrap();
+            assert_eq!(listed[0].size(), content.len() as u64);
+            let whole = backend.read(&path("a.txt"), None).await.unwrap();
+            assert_eq!(collect_stream(whole.stream).await.unwrap(), content);
+        })
+    }
+
+    #[test]
+    fn empty_and_foreign_files() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let backend = CompressedBackend::new(inner.clone(), 3);
+            inner
+                .write(
+                    &path("plain.txt"),
+                    once_stream(Bytes::from("not compressed")),
+                )
+                .await
+                .unwrap();
+
+            // act
+            backend
+                .write(&path("empty.txt"), once_stream(Bytes::new()))
+                .await
+                .unwrap();
+            let empty = backend.read(&path("empty.txt"), None).await.unwrap();
+            let foreign = backend.read(&path("plain.txt"), Some(4..10)).await.unwrap();
+
+            // assert
+            assert_eq!(empty.metadata.size(), 0);
+            assert_eq!(collect_stream(empty.stream).await.unwrap(), "");
+            assert_eq!(foreign.metadata.size(), 14);
+            assert_eq!(collect_stream(foreign.stream).await.unwrap(), "compre");
+        })
+    }
+
+    #[test]
+    fn reads_files_stored_before_compression_was_enabled() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let content = dir! {
+                "old.txt" => "stored as it is",
+                "tiny.txt" => "a",
+                "sub" => dir! { "old.bin" => "ends like a trailer 12345678RZST" },
+            };
+            content.write(&inner, &StoragePath::root()).await.unwrap();
+            let backend = CompressedBackend::new(inner.clone(), 3);
+
+            // act
+            backend
+                .write(&path("new.txt"), once_stream(Bytes::from("compressed")))
+                .await
+                .unwrap();
+            let listed = backend.list(&StoragePath::root()).await.unwrap();
+
+            // assert
+            let sizes: Vec<_> = listed
+                .iter()
+                .map(|e| (e.name().to_owned(), e.size()))
+                .collect();
+            assert_eq!(
+                sizes,
+                vec![
+                    ("new.txt".to_owned(), 10),
+                    ("old.txt".to_owned(), 15),
+                    ("sub".to_owned(), 0),
+                    ("tiny.txt".to_owned(), 1),
+                ]
+            );
+            assert_eq!(
+                FsTree::read(&backend, &StoragePath::root()).await.unwrap(),
+                dir! {
+                    "new.txt" => "compressed",
+                    "old.txt" => "stored as it is",
+                    "tiny.txt" => "a",
+                    "sub" => dir! { "old.bin" => "ends like a trailer 12345678RZST" },
+                }
+            );
+        })
+    }
+}
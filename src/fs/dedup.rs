@@ -0,0 +1,7 @@
+pub mod chunk_hash;
+pub mod chunk_ref;
+pub mod chunk_store;
+pub mod chunker;
+pub mod dedup_backend;
+pub mod dedup_stats;
+pub mod file_manifest;
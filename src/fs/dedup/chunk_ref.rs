@@ -0,0 +1,43 @@
+use super::chunk_hash::ChunkHash;
+
+/// Chunk as part of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef {
+    hash: ChunkHash,
+    size: u64,
+}
+
+impl ChunkRef {
+    pub fn new(hash: ChunkHash, size: u64) -> Self {
+        Self { hash, size }
+    }
+
+    pub fn hash(&self) -> ChunkHash {
+        self.hash
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Stored chunk with the number of places in file manifests referring to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkUsage {
+    chunk: ChunkRef,
+    refs: u64,
+}
+
+impl ChunkUsage {
+    pub fn new(chunk: ChunkRef, refs: u64) -> Self {
+        Self { chunk, refs }
+    }
+
+    pub fn chunk(&self) -> ChunkRef {
+        self.chunk
+    }
+
+    pub fn refs(&self) -> u64 {
+        self.refs
+    }
+}
@@ -0,0 +1,86 @@
+use bytes::Bytes;
+
+use crate::fs::{
+    storage_backend::{collect_stream, once_stream, StorageBackend, StorageCheck, StorageResult},
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+use super::chunk_hash::ChunkHash;
+
+/// Chunk contents by hash, kept as `ab/abcdef…` files of another backend
+pub struct ChunkStore {
+    backend: Box<dyn StorageBackend>,
+}
+
+impl ChunkStore {
+    pub fn new(backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+
+    fn dir(hash: &ChunkHash) -> StoragePath {
+        StoragePath::parse(&hash.to_string()[..2]).unwrap()
+    }
+
+    fn path(hash: &ChunkHash) -> StoragePath {
+        Self::dir(hash).join(&hash.to_string()).unwrap()
+    }
+
+    pub fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.backend.check()
+    }
+
+    pub async fn contains(&self, hash: &ChunkHash) -> Result<bool, StorageError> {
+        match self.backend.stat(&Self::path(hash)).await {
+            Ok(_) => Ok(true),
+            Err(StorageError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fails if the stored content doesn't match the hash
+    pub async fn get(&self, hash: &ChunkHash) -> Result<Bytes, StorageError> {
+        let read = self.backend.read(&Self::path(hash), None).await?;
+        let content = collect_stream(read.stream).await?;
+        if ChunkHash::of(&content) != *hash {
+            return Err(StorageError::Io(format!("chunk {} is corrupted", hash)));
+        }
+        Ok(content)
+    }
+
+    pub async fn put(&self, hash: &ChunkHash, content: Bytes) -> Result<(), StorageError> {
+        match self.backend.mkdir(&Self::dir(hash)).await {
+            Ok(_) | Err(StorageError::AlreadyExists(_)) => {}
+            Err(e) => return Err(e),
+        }
+        self.backend
+            .write(&Self::path(hash), once_stream(content))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, hash: &ChunkHash) -> Result<(), StorageError> {
+        match self.backend.delete(&Self::path(hash)).await {
+            Ok(()) | Err(StorageError::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Every stored chunk with its size, files which are not chunks are skipped
+    pub async fn list(&self) -> Result<Vec<(ChunkHash, u64)>, StorageError> {
+        let mut chunks = Vec::new();
+        for dir in self.backend.list(&StoragePath::root()).await? {
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in self.backend.list(dir.path()).await? {
+                if let Ok(hash) = file.name().parse::<ChunkHash>() {
+                    chunks.push((hash, file.size()));
+                }
+            }
+        }
+        Ok(chunks)
+    }
+}
@@ -0,0 +1,129 @@
+use bytes::{Bytes, BytesMut};
+use fastcdc::v2020::{self, FastCDC};
+
+use crate::{config::app_config::ChunkingConfig, fs::storage_error::StorageError};
+
+/// Splits a stream into content defined chunks. Cut points depend only on the content,
+/// so equal parts of different files produce equal chunks wherever they are.
+pub struct Chunker {
+    config: ChunkingConfig,
+    buffer: BytesMut,
+}
+
+impl Chunker {
+    pub fn new(config: ChunkingConfig) -> Result<Self, StorageError> {
+        validate(&config)?;
+        Ok(Self {
+            config,
+            buffer: BytesMut::new(),
+        })
+    }
+
+    /// Chunks which are complete after the data
+    pub fn push(&mut self, data: &[u8]) -> Vec<Bytes> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() < self.config.max_size() as usize {
+            return Vec::new();
+        }
+        self.cut(false)
+    }
+
+    /// The rest of the stream
+    pub fn finish(mut self) -> Vec<Bytes> {
+        self.cut(true)
+    }
+
+    fn cut(&mut self, last: bool) -> Vec<Bytes> {
+        let max_size = self.config.max_size() as usize;
+        let mut ranges = Vec::new();
+        let mut consumed = 0;
+        for chunk in FastCDC::new(
+            &self.buffer,
+            self.config.min_size(),
+            self.config.avg_size(),
+            self.config.max_size(),
+        ) {
+            let end = chunk.offset + chunk.length;
+            // more data may move the cut point of the chunk at the end of the buffer
+            if !last && end == self.buffer.len() && chunk.length < max_size {
+                break;
+            }
+            ranges.push(chunk.offset..end);
+            consumed = end;
+        }
+        let consumed = self.buffer.split_to(consumed).freeze();
+        ranges.into_iter().map(|r| consumed.slice(r)).collect()
+    }
+}
+
+fn validate(config: &ChunkingConfig) -> Result<(), StorageError> {
+    let valid = (v2020::MINIMUM_MIN..=v2020::MINIMUM_MAX).contains(&config.min_size())
+        && (v2020::AVERAGE_MIN..=v2020::AVERAGE_MAX).contains(&config.avg_size())
+        && (v2020::MAXIMUM_MIN..=v2020::MAXIMUM_MAX).contains(&config.max_size())
+        && config.min_size() <= config.avg_size()
+        && config.avg_size() <= config.max_size();
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::Io(format!(
+            "invalid chunk sizes {:?}",
+            config
+        )))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use rand::{RngCore, SeedableRng};
+
+    fn config() -> ChunkingConfig {
+        ChunkingConfig::new(64, 256, 1024)
+    }
+
+    fn random(len: usize, seed: u64) -> Vec<u8> {
+        let mut data = vec![0; len];
+        rand::rngs::StdRng::seed_from_u64(seed).fill_bytes(&mut data);
+        data
+    }
+
+    fn chunk_all(data: &[u8], piece: usize) -> Vec<Bytes> {
+        let mut chunker = Chunker::new(config()).unwrap();
+        let mut chunks: Vec<_> = data.chunks(piece).flat_map(|p| chunker.push(p)).collect();
+        chunks.extend(chunker.finish());
+        chunks
+    }
+
+    #[test]
+    fn cut_points_do_not_depend_on_stream_pieces() {
+        let data = random(20_000, 1);
+
+        let whole = chunk_all(&data, data.len());
+        let small_pieces = chunk_all(&data, 7);
+
+        assert_eq!(whole, small_pieces);
+        assert_eq!(whole.concat(), data);
+        assert!(whole.iter().all(|c| c.len() <= 1024));
+    }
+
+    #[test]
+    fn shared_content_gives_shared_chunks() {
+        let shared = random(10_000, 2);
+        let a = [random(3_000, 3), shared.clone()].concat();
+        let b = [random(5_000, 4), shared].concat();
+
+        let a = chunk_all(&a, 100);
+        let b = chunk_all(&b, 100);
+
+        let common = a.iter().filter(|c| b.contains(c)).count();
+        assert!(common >= a.len() / 2, "{} of {}", common, a.len());
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(Chunker::new(ChunkingConfig::new(64, 256, 128)).is_err());
+        assert!(Chunker::new(ChunkingConfig::new(1, 256, 1024)).is_err());
+    }
+}
@@ -0,0 +1,526 @@
+use std::{collections::HashSet, ops::Range, sync::Arc};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{FutureExt, StreamExt};
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::ChunkingConfig,
+    dal::{chunk_refs_dal::ChunkRefsDal, file_manifests_dal::FileManifestsDal, Dal},
+    fs::{
+        storage_backend::{
+            require_not_root, resolve_range, ByteStream, EntryMetadata, FileRead, StorageBackend,
+            StorageCheck, StorageResult,
+        },
+        storage_error::StorageError,
+        storage_path::StoragePath,
+    },
+    utils::time::Time,
+};
+
+use super::{
+    chunk_hash::ChunkHash,
+    chunk_ref::ChunkRef,
+    chunk_store::ChunkStore,
+    chunker::Chunker,
+    dedup_stats::{DedupStats, GarbageReport},
+    file_manifest::FileManifest,
+};
+
+/// Splits files into content defined chunks and stores every distinct chunk once.
+/// The tree lives in the DAL as manifests, chunks are reference counted there
+/// and removed by `collect_garbage` once nothing refers to them.
+pub struct DedupBackend<D, T> {
+    source_id: Uuid,
+    dal: D,
+    time: T,
+    chunks: Arc<ChunkStore>,
+    chunking: ChunkingConfig,
+    /// Shared by writers and taken exclusively by the garbage collector,
+    /// so a chunk is never collected between being stored and being referenced
+    gc_lock: tokio::sync::RwLock<()>,
+    /// The root directory is implicit
+    created: DateTime<Utc>,
+}
+
+impl<D: Dal, T: Time> DedupBackend<D, T> {
+    pub fn new(
+        source_id: Uuid,
+        dal: D,
+        time: T,
+        chunks: ChunkStore,
+        chunking: ChunkingConfig,
+    ) -> Result<Self, StorageError> {
+        Chunker::new(chunking)?;
+        let created = time.now();
+        Ok(Self {
+            source_id,
+            dal,
+            time,
+            chunks: Arc::new(chunks),
+            chunking,
+            gc_lock: Default::default(),
+            created,
+        })
+    }
+
+    async fn manifest(&self, path: &StoragePath) -> Result<FileManifest, StorageError> {
+        self.dal
+            .file_manifests()
+            .get(self.source_id, path)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(path.to_string()))
+    }
+
+    async fn metadata(&self, path: &StoragePath) -> Result<EntryMetadata, StorageError> {
+        if path.is_root() {
+            return Ok(EntryMetadata::directory(StoragePath::root(), self.created));
+        }
+        Ok(self.manifest(path).await?.metadata())
+    }
+
+    async fn require_parent_dir(&self, path: &StoragePath) -> Result<(), StorageError> {
+        let parent = path.parent().unwrap_or_default();
+        if self.metadata(&parent).await?.is_dir() {
+            Ok(())
+        } else {
+            Err(StorageError::NotADirectory(parent.to_string()))
+        }
+    }
+
+    async fn exists(&self, path: &StoragePath) -> Result<bool, StorageError> {
+        Ok(path.is_root()
+            || self
+                .dal
+                .file_manifests()
+                .get(self.source_id, path)
+                .await?
+                .is_some())
+    }
+
+    /// Stores the chunks of the content which aren't known yet
+    async fn store_chunks(
+        &self,
+        path: &StoragePath,
+        mut content: ByteStream,
+    ) -> Result<Vec<ChunkRef>, StorageError> {
+        let mut chunker = Chunker::new(self.chunking)?;
+        let mut chunks = Vec::new();
+        let mut reused = 0;
+        while let Some(data) = content.next().await {
+            for chunk in chunker.push(&data?) {
+                reused += self.store_chunk(chunk, &mut chunks).await? as usize;
+            }
+        }
+        for chunk in chunker.finish() {
+            reused += self.store_chunk(chunk, &mut chunks).await? as usize;
+        }
+        tracing::debug!(
+            source_id = %self.source_id,
+            path = %path,
+            chunks = chunks.len(),
+            reused = reused,
+            "file chunked"
+        );
+        Ok(chunks)
+    }
+
+    /// Returns whether the chunk was already stored
+    async fn store_chunk(
+        &self,
+        content: Bytes,
+        chunks: &mut Vec<ChunkRef>,
+    ) -> Result<bool, StorageError> {
+        let hash = ChunkHash::of(&content);
+        chunks.push(ChunkRef::new(hash, content.len() as u64));
+        if self.chunks.contains(&hash).await? {
+            return Ok(true);
+        }
+        self.chunks.put(&hash, content).await?;
+        Ok(false)
+    }
+}
+
+/// Parts of the chunks which hold the range
+fn chunk_slices(chunks: &[ChunkRef], range: Range<u64>) -> Vec<(ChunkHash, Range<usize>)> {
+    let mut slices = Vec::new();
+    let mut offset = 0;
+    for chunk in chunks {
+        let (start, end) = (offset, offset + chunk.size());
+        offset = end;
+        if end <= range.start || start >= range.end {
+            continue;
+        }
+        let from = range.start.saturating_sub(start);
+        let to = range.end.min(end) - start;
+        slices.push((chunk.hash(), from as usize..to as usize));
+    }
+    slices
+}
+
+impl<D: Dal + Send + Sync, T: Time + Send + Sync> StorageBackend for DedupBackend<D, T> {
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.metadata(path).boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        async move {
+            if !self.metadata(path).await?.is_dir() {
+                return Err(StorageError::NotADirectory(path.to_string()));
+            }
+            let children = self
+                .dal
+                .file_manifests()
+                .list_children(self.source_id, path)
+                .await?;
+            Ok(children.iter().map(FileManifest::metadata).collect())
+        }
+        .boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        async move {
+            if path.is_root() {
+                return Err(StorageError::IsADirectory(path.to_string()));
+            }
+            let manifest = self.manifest(path).await?;
+            if manifest.is_dir() {
+                return Err(StorageError::IsADirectory(path.to_string()));
+            }
+            let range = resolve_range(range, manifest.size())?;
+            let chunks = self.chunks.clone();
+            let stream = futures::stream::iter(chunk_slices(manifest.chunks(), range.clone()))
+                .then(move |(hash, slice)| {
+                    let chunks = chunks.clone();
+                    async move { Ok(chunks.get(&hash).await?.slice(slice)) }
+                })
+                .boxed_local();
+            Ok(FileRead {
+                metadata: manifest.metadata(),
+                range,
+                stream,
+            })
+        }
+        .boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            require_not_root(path)?;
+            let _gc = self.gc_lock.read().await;
+            self.require_parent_dir(path).await?;
+            if let Ok(existing) = self.manifest(path).await {
+                if existing.is_dir() {
+                    return Err(StorageError::IsADirectory(path.to_string()));
+                }
+            }
+
+            let chunks = self.store_chunks(path, content).await?;
+            let refs = self.dal.chunk_refs();
+            refs.acquire(self.source_id, &chunks).await?;
+            let manifest =
+                FileManifest::file(self.source_id, path.clone(), chunks, self.time.now());
+            let metadata = manifest.metadata();
+            match self.dal.file_manifests().save(manifest.clone()).await {
+                Ok(Some(replaced)) => refs.release(self.source_id, replaced.chunks()).await?,
+                Ok(None) => {}
+                Err(e) => {
+                    refs.release(self.source_id, manifest.chunks()).await?;
+                    return Err(e.into());
+                }
+            }
+            Ok(metadata)
+        }
+        .boxed_local()
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        async move {
+            require_not_root(from)?;
+            require_not_root(to)?;
+            if to.starts_with(from) {
+                return Err(StorageError::InvalidPath(to.to_string()));
+            }
+            self.manifest(from).await?;
+            if self.exists(to).await? {
+                return Err(StorageError::AlreadyExists(to.to_string()));
+            }
+            self.require_parent_dir(to).await?;
+            self.dal
+                .file_manifests()
+                .rename(self.source_id, from, to)
+                .await?;
+            Ok(())
+        }
+        .boxed_local()
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        async move {
+            require_not_root(path)?;
+            let manifests = self.dal.file_manifests();
+            if self.manifest(path).await?.is_dir()
+                && !manifests
+                    .list_children(self.source_id, path)
+                    .await?
+                    .is_empty()
+            {
+                return Err(StorageError::NotEmpty(path.to_string()));
+            }
+            if let Some(deleted) = manifests.delete(self.source_id, path).await? {
+                self.dal
+                    .chunk_refs()
+                    .release(self.source_id, deleted.chunks())
+                    .await?;
+            }
+            Ok(())
+        }
+        .boxed_local()
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            require_not_root(path)?;
+            if self.exists(path).await? {
+                return Err(StorageError::AlreadyExists(path.to_string()));
+            }
+            self.require_parent_dir(path).await?;
+            let manifest = FileManifest::directory(self.source_id, path.clone(), self.time.now());
+            let metadata = manifest.metadata();
+            self.dal.file_manifests().save(manifest).await?;
+            Ok(metadata)
+        }
+        .boxed_local()
+    }
+
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        async move {
+            let mut stats = DedupStats::default();
+            for manifest in self.dal.file_manifests().list(self.source_id).await? {
+                if !manifest.is_dir() {
+                    stats.files += 1;
+                    stats.logical_size += manifest.size();
+                }
+            }
+            for usage in self.dal.chunk_refs().list(self.source_id).await? {
+                if usage.refs() > 0 {
+                    stats.chunks += 1;
+                    stats.stored_size += usage.chunk().size();
+                }
+            }
+            Ok(Some(stats))
+        }
+        .boxed_local()
+    }
+
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        async move {
+            let _gc = self.gc_lock.write().await;
+            let refs = self.dal.chunk_refs();
+            refs.remove_unreferenced(self.source_id).await?;
+            let referenced: HashSet<_> = refs
+                .list(self.source_id)
+                .await?
+                .iter()
+                .map(|usage| usage.chunk().hash())
+                .collect();
+
+            // anything else in the store is garbage, including chunks of failed writes
+            let mut report = GarbageReport::default();
+            for (hash, size) in self.chunks.list().await? {
+                if !referenced.contains(&hash) {
+                    self.chunks.delete(&hash).await?;
+                    report.removed_chunks += 1;
+                    report.removed_size += size;
+                }
+            }
+            tracing::info!(
+                source_id = %self.source_id,
+                removed_chunks = report.removed_chunks,
+                removed_size = report.removed_size,
+                "garbage collected"
+            );
+            Ok(report)
+        }
+        .boxed_local()
+    }
+
+    /// Files are in the DAL, the chunk store holds the data
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.chunks.check()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::{collect_stream, once_stream};
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use rand::{RngCore, SeedableRng};
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    fn random(len: usize, seed: u64) -> Vec<u8> {
+        let mut data = vec![0; len];
+        rand::rngs::StdRng::seed_from_u64(seed).fill_bytes(&mut data);
+        data
+    }
+
+    #[test]
+    fn identical_files_share_chunks() {
+        test(|ctx| async move {
+            // arrange
+            let content = random(10_000, 1);
+            let source = ctx
+                .dedup_source(
+                    "dedup",
+                    dir! {
+                        "a.bin" => content.clone(),
+                        "copies" => dir! { "b.bin" => content.clone() },
+                    },
+                )
+                .await;
+
+            // act
+            let stats = source.backend().dedup_stats().await.unwrap().unwrap();
+
+            // assert
+            assert_eq!(stats.files, 2);
+            assert_eq!(stats.logical_size, 20_000);
+            assert_eq!(stats.stored_size, 10_000);
+            assert_eq!(stats.ratio(), 2.0);
+            ctx.assert_tree(
+                &source,
+                dir! {
+                    "a.bin" => content.clone(),
+                    "copies" => dir! { "b.bin" => content },
+                },
+            )
+            .await;
+        })
+    }
+
+    #[test]
+    fn range_read_spans_chunks() {
+        test(|ctx| async move {
+            // arrange
+            let content = random(5_000, 2);
+            let source = ctx
+                .dedup_source("dedup", dir! { "a.bin" => content.clone() })
+                .await;
+
+            // act
+            let read = source
+                .backend()
+                .read(&path("a.bin"), Some(100..4_000))
+                .await
+                .unwrap();
+
+            // assert
+            assert_eq!(read.range, 100..4_000);
+            assert_eq!(read.metadata.size(), 5_000);
+            assert_eq!(
+                collect_stream(read.stream).await.unwrap(),
+                content[100..4_000]
+            );
+        })
+    }
+
+    #[test]
+    fn garbage_collection_keeps_referenced_chunks() {
+        test(|ctx| async move {
+            // arrange
+            let shared = random(4_000, 3);
+            let a = [random(2_000, 4), shared.clone()].concat();
+            let b = [random(3_000, 5), shared].concat();
+            let source = ctx
+                .dedup_source("dedup", dir! { "a.bin" => a, "b.bin" => b.clone() })
+                .await;
+            let backend = source.backend();
+            let before = backend.dedup_stats().await.unwrap().unwrap();
+
+            // act
+            backend.delete(&path("a.bin")).await.unwrap();
+            let report = backend.collect_garbage().await.unwrap();
+            let again = backend.collect_garbage().await.unwrap();
+
+            // assert
+            let after = backend.dedup_stats().await.unwrap().unwrap();
+            assert!(before.ratio() > 1.0);
+            assert_eq!(after.logical_size, 7_000);
+            assert_eq!(after.stored_size, 7_000);
+            assert!(report.removed_chunks > 0);
+            assert_eq!(report.removed_size, before.stored_size - after.stored_size);
+            assert_eq!(again, GarbageReport::default());
+            ctx.assert_tree(&source, dir! { "b.bin" => b }).await;
+        })
+    }
+
+    #[test]
+    fn replacing_file_releases_old_chunks() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .dedup_source("dedup", dir! { "a.bin" => random(3_000, 6) })
+                .await;
+            let backend = source.backend();
+
+            // act
+            backend
+                .write(&path("a.bin"), once_stream(Bytes::from("small")))
+                .await
+                .unwrap();
+            backend.collect_garbage().await.unwrap();
+
+            // assert
+            let stats = backend.dedup_stats().await.unwrap().unwrap();
+            assert_eq!(stats.stored_size, 5);
+            assert_eq!(stats.chunks, 1);
+            ctx.assert_tree(&source, dir! { "a.bin" => "small" }).await;
+        })
+    }
+
+    #[test]
+    fn rename_and_delete_directories() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .dedup_source(
+                    "dedup",
+                    dir! { "a" => dir! { "b" => dir! { "c.txt" => "c" } }, "empty" => dir! {} },
+                )
+                .await;
+            let backend = source.backend();
+
+            // act
+            backend.rename(&path("a"), &path("z")).await.unwrap();
+            let not_empty = backend.delete(&path("z")).await;
+            backend.delete(&path("empty")).await.unwrap();
+
+            // assert
+            assert!(matches!(not_empty, Err(StorageError::NotEmpty(_))));
+            assert!(matches!(
+                backend.rename(&path("z"), &path("z/b/inner")).await,
+                Err(StorageError::InvalidPath(_))
+            ));
+            ctx.assert_tree(
+                &source,
+                dir! { "z" => dir! { "b" => dir! { "c.txt" => "c" } } },
+            )
+            .await;
+        })
+    }
+}
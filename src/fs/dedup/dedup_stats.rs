@@ -0,0 +1,28 @@
+/// Space used by a deduplicating source
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DedupStats {
+    pub files: u64,
+    /// Sum of the file sizes
+    pub logical_size: u64,
+    pub chunks: u64,
+    /// Sum of the sizes of the stored chunks
+    pub stored_size: u64,
+}
+
+impl DedupStats {
+    /// How many times more data the files hold than the storage, 1 for an empty source
+    pub fn ratio(&self) -> f64 {
+        if self.stored_size == 0 {
+            1.0
+        } else {
+            self.logical_size as f64 / self.stored_size as f64
+        }
+    }
+}
+
+/// Result of a garbage collection run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GarbageReport {
+    pub removed_chunks: u64,
+    pub removed_size: u64,
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::fs::{
+    storage_backend::{EntryKind, EntryMetadata},
+    storage_path::StoragePath,
+};
+
+use super::chunk_ref::ChunkRef;
+
+/// Entry of a deduplicating source. Files list their chunks in order, directories have none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileManifest {
+    source_id: Uuid,
+    path: StoragePath,
+    kind: EntryKind,
+    modified: DateTime<Utc>,
+    chunks: Vec<ChunkRef>,
+}
+
+impl FileManifest {
+    pub fn file(
+        source_id: Uuid,
+        path: StoragePath,
+        chunks: Vec<ChunkRef>,
+        modified: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            source_id,
+            path,
+            kind: EntryKind::File,
+            modified,
+            chunks,
+        }
+    }
+
+    pub fn directory(source_id: Uuid, path: StoragePath, modified: DateTime<Utc>) -> Self {
+        Self {
+            source_id,
+            path,
+            kind: EntryKind::Directory,
+            modified,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn source_id(&self) -> Uuid {
+        self.source_id
+    }
+
+    pub fn path(&self) -> &StoragePath {
+        &self.path
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    pub fn modified(&self) -> DateTime<Utc> {
+        self.modified
+    }
+
+    pub fn chunks(&self) -> &[ChunkRef] {
+        &self.chunks
+    }
+
+    pub fn size(&self) -> u64 {
+        self.chunks.iter().map(ChunkRef::size).sum()
+    }
+
+    pub fn with_path(self, path: StoragePath) -> Self {
+        Self { path, ..self }
+    }
+
+    pub fn metadata(&self) -> EntryMetadata {
+        match self.kind {
+            EntryKind::File => EntryMetadata::file(self.path.clone(), self.size(), self.modified),
+            EntryKind::Directory => EntryMetadata::directory(self.path.clone(), self.modified),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::{cell::RefCell, rc::Rc};
+
+use futures::StreamExt;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    storage_backend::{ByteStream, StorageBackend},
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestAlgorithm {
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+impl DigestAlgorithm {
+    pub const ALL: [DigestAlgorithm; 3] = [Self::Sha256, Self::Blake3, Self::Md5];
+
+    /// Name in `Content-Digest` and `Repr-Digest`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha-256",
+            Self::Blake3 => "blake3",
+            Self::Md5 => "md5",
+        }
+    }
+
+    /// Name in the legacy `Digest` header
+    pub fn legacy_name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "SHA-256",
+            Self::Blake3 => "BLAKE3",
+            Self::Md5 => "MD5",
+        }
+    }
+
+    /// Either name, ignoring case
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|alg| alg.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Every supported digest of a file, computed in a single pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileDigests {
+    sha256: [u8; 32],
+    blake3: [u8; 32],
+    md5: [u8; 16],
+}
+
+impl FileDigests {
+    pub fn of(content: &[u8]) -> Self {
+        let mut hasher = DigestHasher::default();
+        hasher.update(content);
+        hasher.finish()
+    }
+
+    pub fn get(&self, algorithm: DigestAlgorithm) -> &[u8] {
+        match algorithm {
+            DigestAlgorithm::Sha256 => &self.sha256,
+            DigestAlgorithm::Blake3 => &self.blake3,
+            DigestAlgorithm::Md5 => &self.md5,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DigestHasher {
+    sha256: Sha256,
+    blake3: blake3::Hasher,
+    md5: Md5,
+}
+
+impl DigestHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.blake3.update(data);
+        self.md5.update(data);
+    }
+
+    pub fn finish(self) -> FileDigests {
+        FileDigests {
+            sha256: self.sha256.finalize().into(),
+            blake3: *self.blake3.finalize().as_bytes(),
+            md5: self.md5.finalize().into(),
+        }
+    }
+}
+
+/// Digest a client sent with the content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedDigest {
+    pub algorithm: DigestAlgorithm,
+    pub value: Vec<u8>,
+}
+
+/// Digests of a [verified_stream], available once the stream ended successfully
+pub type DigestsHandle = Rc<RefCell<Option<FileDigests>>>;
+
+/// Hashes the content while it passes through. The end of the stream is turned into an error
+/// if a digest doesn't match, so backends discard the content like any other failed write.
+pub fn verified_stream(
+    content: ByteStream,
+    expected: Vec<ExpectedDigest>,
+) -> (ByteStream, DigestsHandle) {
+    let handle = DigestsHandle::default();
+    let result = handle.clone();
+    let expected = Rc::new(expected);
+    let stream = futures::stream::unfold(Some((content, DigestHasher::default())), move |state| {
+        let (expected, result) = (expected.clone(), result.clone());
+        async move {
+            let (mut content, mut hasher) = state?;
+            match content.next().await {
+                Some(Ok(data)) => {
+                    hasher.update(&data);
+                    Some((Ok(data), Some((content, hasher))))
+                }
+                Some(Err(e)) => Some((Err(e), None)),
+                None => {
+                    let digests = hasher.finish();
+                    if let Some(mismatch) = expected
+                        .iter()
+                        .find(|e| digests.get(e.algorithm) != e.value.as_slice())
+                    {
+                        let name = mismatch.algorithm.name().to_owned();
+                        return Some((Err(StorageError::DigestMismatch(name)), None));
+                    }
+                    *result.borrow_mut() = Some(digests);
+                    None
+                }
+            }
+        }
+    })
+    .boxed_local();
+    (stream, handle)
+}
+
+/// Reads the whole file
+pub async fn compute_digests(
+    backend: &dyn StorageBackend,
+    path: &StoragePath,
+) -> Result<FileDigests, StorageError> {
+    let mut stream = backend.read(path, None).await?.stream;
+    let mut hasher = DigestHasher::default();
+    while let Some(data) = stream.next().await {
+        hasher.update(&data?);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::{collect_stream, once_stream};
+    use crate::test::*;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn known_digests() {
+        let digests = FileDigests::of(b"hello");
+
+        assert_str_eq!(
+            hex(digests.get(DigestAlgorithm::Sha256)),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_str_eq!(
+            hex(digests.get(DigestAlgorithm::Md5)),
+            "5d41402abc4b2a76b9719d911017c592"
+        );
+        assert_eq!(
+            digests.get(DigestAlgorithm::Blake3),
+            blake3::hash(b"hello").as_bytes()
+        );
+    }
+
+    #[test]
+    fn verified_stream_rejects_mismatch() {
+        test(|_| async move {
+            // arrange
+            let expected = vec![ExpectedDigest {
+                algorithm: DigestAlgorithm::Md5,
+                value: FileDigests::of(b"hello").get(DigestAlgorithm::Md5).to_vec(),
+            }];
+            let (good, digests) =
+                verified_stream(once_stream(Bytes::from("hello")), expected.clone());
+            let (bad, _) = verified_stream(once_stream(Bytes::from("hellO")), expected);
+
+            // act
+            let good = collect_stream(good).await;
+            let bad = collect_stream(bad).await;
+
+            // assert
+            assert_eq!(good.unwrap(), "hello");
+            assert_eq!(*digests.borrow(), Some(FileDigests::of(b"hello")));
+            assert!(matches!(bad, Err(StorageError::DigestMismatch(alg)) if alg == "md5"));
+        })
+    }
+}
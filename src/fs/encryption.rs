@@ -0,0 +1,6 @@
+pub mod data_key;
+pub mod encrypted_backend;
+pub mod key_ring;
+pub mod key_rotation;
+pub mod segments;
+pub mod wrapped_key;
@@ -0,0 +1,26 @@
+use chacha20poly1305::{aead::OsRng, KeyInit, XChaCha20Poly1305};
+
+use crate::utils::secret::Secret;
+
+/// Key encrypting the file contents of one source. It never changes,
+/// so rotating master keys doesn't touch the files.
+#[derive(Debug, Clone)]
+pub struct DataKey(Secret<[u8; 32]>);
+
+impl DataKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(Secret::new(key))
+    }
+
+    pub fn generate() -> Self {
+        Self::new(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    pub fn bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(self.bytes().into())
+    }
+}
@@ -0,0 +1,419 @@
+use std::{ops::Range, sync::Arc};
+
+use chacha20poly1305::XChaCha20Poly1305;
+use futures::FutureExt;
+use uuid::Uuid;
+
+use crate::{
+    dal::{dal_error::DalError, data_keys_dal::DataKeysDal, Dal},
+    fs::{
+        dedup::dedup_stats::{DedupStats, GarbageReport},
+        storage_backend::{
+            collect_stream, require_not_root, resolve_range, ByteStream, EntryMetadata, FileRead,
+            StorageBackend, StorageCheck, StorageResult,
+        },
+        storage_error::StorageError,
+        storage_path::StoragePath,
+    },
+};
+
+use super::{
+    data_key::DataKey,
+    key_ring::KeyRing,
+    segments::{parse_header, plain_size, seal_stream, SegmentPlan, HEADER_SIZE},
+};
+
+/// Encrypts file contents before they reach another backend. Names and the tree stay
+/// readable there. The data key of the source is created on first use and kept in the DAL
+/// wrapped with the active master key.
+pub struct EncryptedBackend<D> {
+    source_id: Uuid,
+    inner: Box<dyn StorageBackend>,
+    dal: D,
+    keys: Arc<KeyRing>,
+    cipher: tokio::sync::OnceCell<XChaCha20Poly1305>,
+}
+
+impl<D: Dal> EncryptedBackend<D> {
+    pub fn new(
+        source_id: Uuid,
+        inner: impl StorageBackend + 'static,
+        dal: D,
+        keys: Arc<KeyRing>,
+    ) -> Self {
+        Self {
+            source_id,
+            inner: Box::new(inner),
+            dal,
+            keys,
+            cipher: Default::default(),
+        }
+    }
+
+    async fn cipher(&self) -> Result<&XChaCha20Poly1305, StorageError> {
+        self.cipher
+            .get_or_try_init(|| async {
+                let key = match self.data_key().await? {
+                    Some(key) => key,
+                    None => self.create_data_key().await?,
+                };
+                Ok(key.cipher())
+            })
+            .await
+    }
+
+    async fn data_key(&self) -> Result<Option<DataKey>, StorageError> {
+        match self.dal.data_keys().get(self.source_id).await? {
+            Some(wrapped) => self.keys.unwrap(&wrapped).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn create_data_key(&self) -> Result<DataKey, StorageError> {
+        let key = DataKey::generate();
+        let wrapped = self.keys.wrap(self.source_id, &key)?;
+        match self.dal.data_keys().insert(wrapped).await {
+            Ok(()) => {
+                tracing::info!(source_id = %self.source_id, master_key = self.keys.active_key(), "data key created");
+                Ok(key)
+            }
+            // created concurrently by another instance
+            Err(DalError::Conflict(_)) => self.data_key().await?.ok_or_else(|| {
+                StorageError::Encryption(format!("data key of source {} vanished", self.source_id))
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Contents are bound to their path, so moved files are encrypted again for the new one.
+    /// Every file is written at the new path before the old one is removed.
+    fn move_entry<'a>(
+        &'a self,
+        from: &'a StoragePath,
+        to: &'a StoragePath,
+    ) -> StorageResult<'a, ()> {
+        async move {
+            if self.inner.stat(from).await?.is_dir() {
+                self.inner.mkdir(to).await?;
+                for child in self.inner.list(from).await? {
+                    self.move_entry(child.path(), &to.join(child.name())?)
+                        .await?;
+                }
+            } else {
+                let cipher = self.cipher().await?.clone();
+                let content = self.read_file(from, None).await?.stream;
+                self.inner
+                    .write(to, seal_stream(cipher, to, content))
+                    .await?;
+            }
+            self.inner.delete(from).await
+        }
+        .boxed_local()
+    }
+
+    fn plain_metadata(metadata: EntryMetadata) -> Result<EntryMetadata, StorageError> {
+        if metadata.is_dir() {
+            return Ok(metadata);
+        }
+        Ok(EntryMetadata::file(
+            metadata.path().clone(),
+            plain_size(metadata.size())?,
+            metadata.modified(),
+        ))
+    }
+
+    async fn read_file(
+        &self,
+        path: &StoragePath,
+        range: Option<Range<u64>>,
+    ) -> Result<FileRead, StorageError> {
+        let cipher = self.cipher().await?.clone();
+        let header = match self.inner.read(path, Some(0..HEADER_SIZE)).await {
+            Err(StorageError::InvalidRange) => {
+                return Err(StorageError::Encryption(format!(
+                    "{} is too short to be encrypted",
+                    path
+                )))
+            }
+            header => header?,
+        };
+        let metadata = Self::plain_metadata(header.metadata)?;
+        let prefix = parse_header(&collect_stream(header.stream).await?)?;
+        let range = resolve_range(range, metadata.size())?;
+        let plan = SegmentPlan::new(&range, metadata.size());
+        let sealed: ByteStream = match plan.sealed_range.clone() {
+            Some(sealed_range) => self.inner.read(path, Some(sealed_range)).await?.stream,
+            None => Box::pin(futures::stream::empty()),
+        };
+        Ok(FileRead {
+            metadata,
+            range,
+            stream: plan.open(cipher, prefix, path, sealed),
+        })
+    }
+}
+
+impl<D: Dal + Send + Sync> StorageBackend for EncryptedBackend<D> {
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async move { Self::plain_metadata(self.inner.stat(path).await?) }.boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        async move {
+            self.inner
+                .list(path)
+                .await?
+                .into_iter()
+                .map(Self::plain_metadata)
+                .collect()
+        }
+        .boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        self.read_file(path, range).boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            let cipher = self.cipher().await?.clone();
+            let metadata = self
+                .inner
+                .write(path, seal_stream(cipher, path, content))
+                .await?;
+            Self::plain_metadata(metadata)
+        }
+        .boxed_local()
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        async move {
+            require_not_root(from)?;
+            require_not_root(to)?;
+            if to.starts_with(from) {
+                return Err(StorageError::InvalidPath(to.to_string()));
+            }
+            if self.inner.stat(to).await.is_ok() {
+                return Err(StorageError::AlreadyExists(to.to_string()));
+            }
+            self.move_entry(from, to).await
+        }
+        .boxed_local()
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.inner.delete(path)
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.inner.mkdir(path)
+    }
+
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        self.inner.dedup_stats()
+    }
+
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        self.inner.collect_garbage()
+    }
+
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.inner.check()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::memory_backend::MemoryBackend;
+    use crate::fs::storage_backend::once_stream;
+    use crate::test::*;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn stores_only_ciphertext() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source(
+                    "secret",
+                    inner.clone(),
+                    dir! { "a.txt" => "attack at dawn", "sub" => dir! {} },
+                )
+                .await;
+
+            // act
+            let stored = collect_stream(inner.read(&path("a.txt"), None).await.unwrap().stream)
+                .await
+                .unwrap();
+            let listed = source.backend().list(&StoragePath::root()).await.unwrap();
+
+            // assert
+            assert!(!stored.windows(6).any(|w| w == b"attack"));
+            assert_eq!(listed[0].size(), 14);
+            assert!(listed[1].is_dir());
+            ctx.assert_tree(
+                &source,
+                dir! { "a.txt" => "attack at dawn", "sub" => dir! {} },
+            )
+            .await;
+            assert!(ctx
+                .dal()
+                .data_keys()
+                .get(source.id())
+                .await
+                .unwrap()
+                .is_some());
+        })
+    }
+
+    #[test]
+    fn reads_ranges() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx.encrypted_source("secret", inner, dir! {}).await;
+            let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+            source
+                .backend()
+                .write(&path("big.bin"), once_stream(Bytes::from(content.clone())))
+                .await
+                .unwrap();
+
+            // act
+            let read = source
+                .backend()
+                .read(&path("big.bin"), Some(65_000..140_000))
+                .await
+                .unwrap();
+
+            // assert
+            assert_eq!(read.metadata.size(), 200_000);
+            assert_eq!(read.range, 65_000..140_000);
+            assert_eq!(
+                collect_stream(read.stream).await.unwrap(),
+                content[65_000..140_000]
+            );
+        })
+    }
+
+    #[test]
+    fn rejects_tampered_content() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source("secret", inner.clone(), dir! { "a.txt" => "content" })
+                .await;
+            let stored = collect_stream(inner.read(&path("a.txt"), None).await.unwrap().stream)
+                .await
+                .unwrap();
+            let mut tampered = stored.to_vec();
+            *tampered.last_mut().unwrap() ^= 1;
+            inner
+                .write(&path("a.txt"), once_stream(Bytes::from(tampered)))
+                .await
+                .unwrap();
+            inner
+                .write(
+                    &path("plain.txt"),
+                    once_stream(Bytes::from("not encrypted")),
+                )
+                .await
+                .unwrap();
+
+            // act
+            let tampered = collect_stream(
+                source
+                    .backend()
+                    .read(&path("a.txt"), None)
+                    .await
+                    .unwrap()
+                    .stream,
+            )
+            .await;
+            let plain = source.backend().read(&path("plain.txt"), None).await;
+
+            // assert
+            assert!(matches!(tampered, Err(StorageError::Encryption(_))));
+            assert!(matches!(plain, Err(StorageError::Encryption(_))));
+        })
+    }
+
+    #[test]
+    fn rename_keeps_files_readable() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source(
+                    "secret",
+                    inner,
+                    dir! { "a" => dir! { "b.txt" => "b", "c" => dir! { "d.txt" => "d" } } },
+                )
+                .await;
+
+            // act
+            source
+                .backend()
+                .rename(&path("a"), &path("z"))
+                .await
+                .unwrap();
+            let existing = source
+                .backend()
+                .rename(&path("z/b.txt"), &path("z/c"))
+                .await;
+
+            // assert
+            assert!(matches!(existing, Err(StorageError::AlreadyExists(_))));
+            ctx.assert_tree(
+                &source,
+                dir! { "z" => dir! { "b.txt" => "b", "c" => dir! { "d.txt" => "d" } } },
+            )
+            .await;
+        })
+    }
+
+    #[test]
+    fn rejects_content_moved_to_another_path() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source(
+                    "secret",
+                    inner.clone(),
+                    dir! { "a.txt" => "content", "b.txt" => "other" },
+                )
+                .await;
+            let stored = inner.read(&path("a.txt"), None).await.unwrap().stream;
+            inner.write(&path("b.txt"), stored).await.unwrap();
+
+            // act
+            let read = source.backend().read(&path("b.txt"), None).await.unwrap();
+            let content = collect_stream(read.stream).await;
+
+            // assert
+            assert!(matches!(content, Err(StorageError::Encryption(_))));
+        })
+    }
+}
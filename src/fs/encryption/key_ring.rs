@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, KeyInit, XChaCha20Poly1305,
+};
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::EncryptionSecretsConfig, fs::storage_error::StorageError,
+    utils::secret::Secret,
+};
+
+use super::{data_key::DataKey, wrapped_key::WrappedKey};
+
+/// Master keys by id. New data keys are wrapped with the active one,
+/// the others only unwrap data keys which weren't rotated yet.
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    active_key: String,
+    master_keys: HashMap<String, Secret<[u8; 32]>>,
+}
+
+impl KeyRing {
+    pub fn new(
+        active_key: String,
+        master_keys: HashMap<String, Secret<[u8; 32]>>,
+    ) -> Result<Self, StorageError> {
+        if !master_keys.contains_key(&active_key) {
+            return Err(StorageError::Encryption(format!(
+                "active master key {} is not configured",
+                active_key
+            )));
+        }
+        Ok(Self {
+            active_key,
+            master_keys,
+        })
+    }
+
+    pub fn from_config(config: &EncryptionSecretsConfig) -> Result<Self, StorageError> {
+        let mut master_keys = HashMap::new();
+        for (id, key) in config.master_keys() {
+            let key: [u8; 32] = STANDARD
+                .decode(key.as_bytes())
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| {
+                    StorageError::Encryption(format!(
+                        "master key {} must be 32 bytes in base64",
+                        id
+                    ))
+                })?;
+            master_keys.insert(id.clone(), Secret::new(key));
+        }
+        Self::new(config.active_key().to_owned(), master_keys)
+    }
+
+    pub fn active_key(&self) -> &str {
+        &self.active_key
+    }
+
+    /// The source id is authenticated too, so a wrapped key can't be moved to another source
+    pub fn wrap(&self, source_id: Uuid, key: &DataKey) -> Result<WrappedKey, StorageError> {
+        let cipher = self.master_cipher(&self.active_key)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: key.bytes(),
+            aad: source_id.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| StorageError::Encryption("data key can't be wrapped".to_owned()))?;
+        Ok(WrappedKey::new(
+            source_id,
+            self.active_key.clone(),
+            nonce.into(),
+            ciphertext,
+        ))
+    }
+
+    pub fn unwrap(&self, wrapped: &WrappedKey) -> Result<DataKey, StorageError> {
+        let cipher = self.master_cipher(wrapped.master_key_id())?;
+        let source_id = wrapped.source_id();
+        let payload = Payload {
+            msg: wrapped.ciphertext(),
+            aad: source_id.as_bytes(),
+        };
+        cipher
+            .decrypt(wrapped.nonce().into(), payload)
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .map(DataKey::new)
+            .ok_or_else(|| {
+                StorageError::Encryption(format!(
+                    "data key of source {} can't be unwrapped with master key {}",
+                    wrapped.source_id(),
+                    wrapped.master_key_id()
+                ))
+            })
+    }
+
+    fn master_cipher(&self, id: &str) -> Result<XChaCha20Poly1305, StorageError> {
+        let key = self.master_keys.get(id).ok_or_else(|| {
+            StorageError::Encryption(format!("master key {} is not configured", id))
+        })?;
+        Ok(XChaCha20Poly1305::new((&**key).into()))
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    dal::{data_keys_dal::DataKeysDal, Dal},
+    fs::storage_error::StorageError,
+};
+
+use super::key_ring::KeyRing;
+
+/// Re-wraps every data key which isn't wrapped with the active master key yet and returns
+/// how many were. File contents stay as they are, since the data keys themselves don't change.
+/// Afterwards the old master keys can be removed from the config.
+pub async fn rotate_data_keys<D: Dal>(dal: &D, keys: &KeyRing) -> Result<usize, StorageError> {
+    let mut rotated = 0;
+    for wrapped in dal.data_keys().list().await? {
+        if wrapped.master_key_id() == keys.active_key() {
+            continue;
+        }
+        let key = keys.unwrap(&wrapped)?;
+        dal.data_keys()
+            .update(keys.wrap(wrapped.source_id(), &key)?)
+            .await?;
+        tracing::info!(
+            source_id = %wrapped.source_id(),
+            from = wrapped.master_key_id(),
+            to = keys.active_key(),
+            "data key rotated"
+        );
+        rotated += 1;
+    }
+    Ok(rotated)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::config::app_config::EncryptionSecretsConfig;
+    use crate::fs::encryption::encrypted_backend::EncryptedBackend;
+    use crate::fs::memory_backend::MemoryBackend;
+    use crate::fs::storage_path::StoragePath;
+    use crate::test::*;
+    use crate::utils::secret::Secret;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use std::{collections::HashMap, sync::Arc};
+
+    #[test]
+    fn rotation_keeps_files_readable() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source("secret", inner.clone(), dir! { "a.txt" => "content" })
+                .await;
+            let new_key = Secret::new(STANDARD.encode([9; 32]));
+            let mut master_keys = ctx
+                .config()
+                .secrets()
+                .encryption()
+                .unwrap()
+                .master_keys()
+                .clone();
+            master_keys.insert("new".to_owned(), new_key.clone());
+            let rotating =
+                KeyRing::from_config(&EncryptionSecretsConfig::new("new".to_owned(), master_keys))
+                    .unwrap();
+
+            // act
+            let rotated = rotate_data_keys(ctx.dal(), &rotating).await.unwrap();
+            let again = rotate_data_keys(ctx.dal(), &rotating).await.unwrap();
+
+            // assert
+            assert_eq!((rotated, again), (1, 0));
+            let only_new = KeyRing::from_config(&EncryptionSecretsConfig::new(
+                "new".to_owned(),
+                HashMap::from([("new".to_owned(), new_key)]),
+            ))
+            .unwrap();
+            let reopened =
+                EncryptedBackend::new(source.id(), inner, ctx.dal().clone(), Arc::new(only_new));
+            let tree = FsTree::read(&reopened, &StoragePath::root()).await.unwrap();
+            assert_eq!(tree, dir! { "a.txt" => "content" });
+        })
+    }
+}
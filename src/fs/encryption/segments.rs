@@ -0,0 +1,312 @@
+//! Encrypted file format: a header with a random nonce prefix followed by the content
+//! in segments of [SEGMENT_SIZE] bytes, each sealed on its own. The nonce of a segment is
+//! the prefix, its index and whether it is the last one, so segments can't be reordered,
+//! dropped or cut off at the end. Every segment also authenticates the storage path of the
+//! file, so the content of one file can't be passed off as another's. A range only needs the
+//! segments covering it.
+
+use std::{collections::VecDeque, ops::Range};
+
+use bytes::{Bytes, BytesMut};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use futures::StreamExt;
+
+use crate::fs::{
+    storage_backend::ByteStream, storage_error::StorageError, storage_path::StoragePath,
+};
+
+const MAGIC: &[u8; 8] = b"RHFSENC1";
+const PREFIX_SIZE: usize = 19;
+pub const HEADER_SIZE: u64 = (MAGIC.len() + PREFIX_SIZE) as u64;
+pub const SEGMENT_SIZE: u64 = 64 * 1024;
+const TAG_SIZE: u64 = 16;
+const SEALED_SEGMENT_SIZE: u64 = SEGMENT_SIZE + TAG_SIZE;
+
+type NoncePrefix = [u8; PREFIX_SIZE];
+
+fn nonce(prefix: &NoncePrefix, index: u32, last: bool) -> XNonce {
+    let mut nonce = XNonce::default();
+    nonce[..PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[PREFIX_SIZE..PREFIX_SIZE + 4].copy_from_slice(&index.to_be_bytes());
+    nonce[PREFIX_SIZE + 4] = last as u8;
+    nonce
+}
+
+fn corrupted(message: &str) -> StorageError {
+    StorageError::Encryption(message.to_owned())
+}
+
+/// Size of the content stored in an encrypted file of the given size
+pub fn plain_size(sealed_size: u64) -> Result<u64, StorageError> {
+    let body = sealed_size
+        .checked_sub(HEADER_SIZE)
+        .ok_or_else(|| corrupted("file is too short to be encrypted"))?;
+    let segments = body.div_ceil(SEALED_SEGMENT_SIZE).max(1);
+    body.checked_sub(segments * TAG_SIZE)
+        .filter(|_| body % SEALED_SEGMENT_SIZE == 0 || body % SEALED_SEGMENT_SIZE >= TAG_SIZE)
+        .ok_or_else(|| corrupted("file size doesn't match the segments"))
+}
+
+fn segment_count(plain_size: u64) -> u64 {
+    plain_size.div_ceil(SEGMENT_SIZE).max(1)
+}
+
+pub fn parse_header(header: &[u8]) -> Result<NoncePrefix, StorageError> {
+    header
+        .strip_prefix(MAGIC)
+        .and_then(|prefix| prefix.try_into().ok())
+        .ok_or_else(|| corrupted("file isn't encrypted"))
+}
+
+/// Encrypts the content of the file at `path` while it passes through, the header comes first
+pub fn seal_stream(
+    cipher: XChaCha20Poly1305,
+    path: &StoragePath,
+    content: ByteStream,
+) -> ByteStream {
+    let mut prefix = NoncePrefix::default();
+    OsRng.fill_bytes(&mut prefix);
+    let header = Bytes::from([MAGIC.as_slice(), &prefix].concat());
+    let sealer = Sealer {
+        cipher,
+        path: path.to_string(),
+        prefix,
+        index: 0,
+        buffer: BytesMut::new(),
+    };
+    let segments = futures::stream::unfold(Some((content, sealer)), |state| async move {
+        let (mut content, mut sealer) = state?;
+        loop {
+            match content.next().await {
+                Some(Ok(data)) => match sealer.push(&data) {
+                    Ok(sealed) if sealed.is_empty() => continue,
+                    Ok(sealed) => return Some((Ok(sealed), Some((content, sealer)))),
+                    Err(e) => return Some((Err(e), None)),
+                },
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => return Some((sealer.finish(), None)),
+            }
+        }
+    });
+    futures::stream::once(async move { Ok(header) })
+        .chain(segments)
+        .boxed_local()
+}
+
+struct Sealer {
+    cipher: XChaCha20Poly1305,
+    path: String,
+    prefix: NoncePrefix,
+    index: u32,
+    buffer: BytesMut,
+}
+
+impl Sealer {
+    /// Seals the full segments in the buffer. One is always held back, as it may be the last.
+    fn push(&mut self, data: &[u8]) -> Result<Bytes, StorageError> {
+        self.buffer.extend_from_slice(data);
+        let mut sealed = BytesMut::new();
+        while self.buffer.len() as u64 > SEGMENT_SIZE {
+            let segment = self.buffer.split_to(SEGMENT_SIZE as usize);
+            sealed.extend_from_slice(&self.seal(&segment, false)?);
+        }
+        Ok(sealed.freeze())
+    }
+
+    fn finish(mut self) -> Result<Bytes, StorageError> {
+        let segment = std::mem::take(&mut self.buffer);
+        self.seal(&segment, true).map(Bytes::from)
+    }
+
+    fn seal(&mut self, segment: &[u8], last: bool) -> Result<Vec<u8>, StorageError> {
+        let nonce = nonce(&self.prefix, self.index, last);
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| corrupted("file is too large to be encrypted"))?;
+        let payload = Payload {
+            msg: segment,
+            aad: self.path.as_bytes(),
+        };
+        self.cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| corrupted("segment can't be encrypted"))
+    }
+}
+
+/// Segments covering a part of the content
+pub struct SegmentPlan {
+    segments: VecDeque<PlannedSegment>,
+    /// Part of the encrypted file to read, `None` if no segment is needed
+    pub sealed_range: Option<Range<u64>>,
+}
+
+struct PlannedSegment {
+    index: u32,
+    sealed_size: usize,
+    last: bool,
+    /// Part of the decrypted segment within the range
+    keep: Range<usize>,
+}
+
+impl SegmentPlan {
+    pub fn new(range: &Range<u64>, plain_size: u64) -> Self {
+        if range.is_empty() {
+            return Self {
+                segments: VecDeque::new(),
+                sealed_range: None,
+            };
+        }
+        let (first, last) = (range.start / SEGMENT_SIZE, (range.end - 1) / SEGMENT_SIZE);
+        let count = segment_count(plain_size);
+        let segments: VecDeque<_> = (first..=last)
+            .map(|index| {
+                let start = index * SEGMENT_SIZE;
+                let size = SEGMENT_SIZE.min(plain_size - start);
+                PlannedSegment {
+                    index: index as u32,
+                    sealed_size: (size + TAG_SIZE) as usize,
+                    last: index == count - 1,
+                    keep: (range.start.max(start) - start) as usize
+                        ..(range.end.min(start + size) - start) as usize,
+                }
+            })
+            .collect();
+        let start = HEADER_SIZE + first * SEALED_SEGMENT_SIZE;
+        let end =
+            HEADER_SIZE + last * SEALED_SEGMENT_SIZE + segments.back().unwrap().sealed_size as u64;
+        Self {
+            segments,
+            sealed_range: Some(start..end),
+        }
+    }
+
+    /// Decrypts the planned segments from the sealed range of the file at `path`
+    pub fn open(
+        self,
+        cipher: XChaCha20Poly1305,
+        prefix: NoncePrefix,
+        path: &StoragePath,
+        sealed: ByteStream,
+    ) -> ByteStream {
+        let path = path.to_string();
+        let state = (self.segments, sealed, BytesMut::new());
+        futures::stream::unfold(Some(state), move |state| {
+            let cipher = cipher.clone();
+            let path = path.clone();
+            async move {
+                let (mut segments, mut sealed, mut buffer) = state?;
+                let segment = segments.pop_front()?;
+                while buffer.len() < segment.sealed_size {
+                    match sealed.next().await {
+                        Some(Ok(data)) => buffer.extend_from_slice(&data),
+                        Some(Err(e)) => return Some((Err(e), None)),
+                        None => return Some((Err(corrupted("file ended early")), None)),
+                    }
+                }
+                let content = buffer.split_to(segment.sealed_size);
+                let nonce = nonce(&prefix, segment.index, segment.last);
+                let payload = Payload {
+                    msg: content.as_ref(),
+                    aad: path.as_bytes(),
+                };
+                match cipher.decrypt(&nonce, payload) {
+                    Ok(plain) => {
+                        let plain = Bytes::from(plain).slice(segment.keep);
+                        Some((Ok(plain), Some((segments, sealed, buffer))))
+                    }
+                    Err(_) => Some((Err(corrupted("segment fails authentication")), None)),
+                }
+            }
+        })
+        .boxed_local()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::encryption::data_key::DataKey;
+    use crate::fs::storage_backend::{collect_stream, once_stream};
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn sizes_round_trip() {
+        test(|_| async move {
+            let cipher = DataKey::generate().cipher();
+            for size in [
+                0,
+                1,
+                SEGMENT_SIZE - 1,
+                SEGMENT_SIZE,
+                SEGMENT_SIZE + 1,
+                3 * SEGMENT_SIZE,
+            ] {
+                let content = Bytes::from(vec![7; size as usize]);
+
+                let sealed = collect_stream(seal_stream(
+                    cipher.clone(),
+                    &StoragePath::root(),
+                    once_stream(content),
+                ))
+                .await
+                .unwrap();
+
+                assert_eq!(plain_size(sealed.len() as u64).unwrap(), size);
+            }
+            assert!(plain_size(HEADER_SIZE + 15).is_err());
+            assert!(plain_size(HEADER_SIZE + SEALED_SEGMENT_SIZE + 3).is_err());
+        })
+    }
+
+    #[test]
+    fn opens_ranges_across_segments() {
+        test(|_| async move {
+            // arrange
+            let cipher = DataKey::generate().cipher();
+            let content: Vec<u8> = (0..2 * SEGMENT_SIZE + 100).map(|i| i as u8).collect();
+            let path = StoragePath::parse("a.bin").unwrap();
+            let sealed = collect_stream(seal_stream(
+                cipher.clone(),
+                &path,
+                once_stream(Bytes::from(content.clone())),
+            ))
+            .await
+            .unwrap();
+            let prefix = parse_header(&sealed[..HEADER_SIZE as usize]).unwrap();
+            let range = SEGMENT_SIZE - 10..2 * SEGMENT_SIZE + 5;
+
+            // act
+            let plan = SegmentPlan::new(&range, content.len() as u64);
+            let sealed_range = plan.sealed_range.clone().unwrap();
+            let part = sealed.slice(sealed_range.start as usize..sealed_range.end as usize);
+            let opened =
+                collect_stream(plan.open(cipher.clone(), prefix, &path, once_stream(part.clone())))
+                    .await;
+            let elsewhere = SegmentPlan::new(&range, content.len() as u64);
+            let moved = collect_stream(elsewhere.open(
+                cipher,
+                prefix,
+                &StoragePath::parse("b.bin").unwrap(),
+                once_stream(part),
+            ))
+            .await;
+
+            // assert
+            assert_eq!(
+                sealed_range,
+                HEADER_SIZE..HEADER_SIZE + 2 * SEALED_SEGMENT_SIZE + 100 + TAG_SIZE
+            );
+            assert_eq!(
+                opened.unwrap(),
+                content[range.start as usize..range.end as usize]
+            );
+            assert!(matches!(moved, Err(StorageError::Encryption(_))));
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use uuid::Uuid;
+
+/// Data key of a source encrypted with a master key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    source_id: Uuid,
+    master_key_id: String,
+    nonce: [u8; 24],
+    ciphertext: Vec<u8>,
+}
+
+impl WrappedKey {
+    pub fn new(
+        source_id: Uuid,
+        master_key_id: String,
+        nonce: [u8; 24],
+        ciphertext: Vec<u8>,
+    ) -> Self {
+        Self {
+            source_id,
+            master_key_id,
+            nonce,
+            ciphertext,
+        }
+    }
+
+    pub fn source_id(&self) -> Uuid {
+        self.source_id
+    }
+
+    pub fn master_key_id(&self) -> &str {
+        &self.master_key_id
+    }
+
+    pub fn nonce(&self) -> &[u8; 24] {
+        &self.nonce
+    }
+
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.ciphertext
+    }
+}
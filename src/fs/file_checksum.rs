@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::dal::{file_checksums_dal::FileChecksumsDal, Dal};
+
+use super::{
+    digests::{compute_digests, FileDigests},
+    source::Source,
+    storage_backend::EntryMetadata,
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+/// Digests of a file as it was at the given modification time and size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChecksum {
+    source_id: Uuid,
+    path: StoragePath,
+    modified: DateTime<Utc>,
+    size: u64,
+    digests: FileDigests,
+}
+
+impl FileChecksum {
+    pub fn new(source_id: Uuid, metadata: &EntryMetadata, digests: FileDigests) -> Self {
+        Self {
+            source_id,
+            path: metadata.path().clone(),
+            modified: metadata.modified(),
+            size: metadata.size(),
+            digests,
+        }
+    }
+
+    pub fn source_id(&self) -> Uuid {
+        self.source_id
+    }
+
+    pub fn path(&self) -> &StoragePath {
+        &self.path
+    }
+
+    pub fn modified(&self) -> DateTime<Utc> {
+        self.modified
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn digests(&self) -> &FileDigests {
+        &self.digests
+    }
+
+    /// Whether the checksum was computed for the file in this state
+    pub fn matches(&self, metadata: &EntryMetadata) -> bool {
+        self.path == *metadata.path()
+            && self.modified == metadata.modified()
+            && self.size == metadata.size()
+    }
+}
+
+/// Stored digests if they are still valid for the file, otherwise they are computed and stored
+pub async fn file_digests<D: Dal>(
+    dal: &D,
+    source: &Source,
+    metadata: &EntryMetadata,
+) -> Result<FileDigests, StorageError> {
+    let checksums = dal.file_checksums();
+    if let Some(checksum) = checksums.get(source.id(), metadata.path()).await? {
+        if checksum.matches(metadata) {
+            return Ok(checksum.digests);
+        }
+    }
+    let digests = compute_digests(source.backend(), metadata.path()).await?;
+    checksums
+        .save(FileChecksum::new(source.id(), metadata, digests))
+        .await?;
+    Ok(digests)
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::utils::id::Id;
+
+use super::storage_path::StoragePath;
+
+/// Content a file had at some point, kept by the [super::version_store::VersionStore] of its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersion {
+    id: Id,
+    source_id: Uuid,
+    path: StoragePath,
+    /// Login whose write replaced the content, `None` if it's unknown
+    replaced_by: Option<Id>,
+    /// When the content was replaced
+    created: DateTime<Utc>,
+    size: u64,
+}
+
+impl FileVersion {
+    pub fn new(
+        id: Id,
+        source_id: Uuid,
+        path: StoragePath,
+        replaced_by: Option<Id>,
+        created: DateTime<Utc>,
+        size: u64,
+    ) -> Self {
+        Self {
+            id,
+            source_id,
+            path,
+            replaced_by,
+            created,
+            size,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn source_id(&self) -> Uuid {
+        self.source_id
+    }
+
+    pub fn path(&self) -> &StoragePath {
+        &self.path
+    }
+
+    pub fn replaced_by(&self) -> Option<Id> {
+        self.replaced_by
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
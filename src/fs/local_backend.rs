@@ -0,0 +1,431 @@
+use std::{
+    io::ErrorKind,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use futures::{FutureExt, StreamExt};
+use rand::RngCore;
+use tokio::io::AsyncWriteExt;
+
+use super::{
+    storage_backend::{
+        file_stream, require_not_root, resolve_range, ByteStream, EntryMetadata, FileRead,
+        StorageBackend, StorageCheck, StorageResult,
+    },
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+/// Prefix of files receiving uploads, hidden from listings
+const TEMP_PREFIX: &str = ".~rhfs-";
+
+/// Directory on the local disk. Symbolic links are never followed,
+/// so nothing outside of the root is reachable.
+#[derive(Debug, Clone)]
+pub struct LocalBackend {
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Disk path of a storage path, fails if an existing part of it is a symbolic link
+    async fn resolve(&self, path: &StoragePath) -> Result<PathBuf, StorageError> {
+        let mut full = self.root.clone();
+        let mut exists = true;
+        for segment in path.segments() {
+            full.push(segment);
+            if !exists {
+                continue;
+            }
+            match tokio::fs::symlink_metadata(&full).await {
+                Ok(m) if m.file_type().is_symlink() => {
+                    return Err(StorageError::NotFound(path.to_string()))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => exists = false,
+                Err(e) => return Err(io_error(path, e)),
+            }
+        }
+        Ok(full)
+    }
+
+    async fn metadata(
+        &self,
+        path: &StoragePath,
+        full: &Path,
+    ) -> Result<EntryMetadata, StorageError> {
+        let m = tokio::fs::symlink_metadata(full)
+            .await
+            .map_err(|e| io_error(path, e))?;
+        to_metadata(path.clone(), &m)
+    }
+
+    async fn require_dir(&self, path: &StoragePath) -> Result<(), StorageError> {
+        let full = self.resolve(path).await?;
+        if self.metadata(path, &full).await?.is_dir() {
+            Ok(())
+        } else {
+            Err(StorageError::NotADirectory(path.to_string()))
+        }
+    }
+
+    async fn require_absent(&self, path: &StoragePath, full: &Path) -> Result<(), StorageError> {
+        match tokio::fs::symlink_metadata(full).await {
+            Ok(_) => Err(StorageError::AlreadyExists(path.to_string())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error(path, e)),
+        }
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            let full = self.resolve(path).await?;
+            self.metadata(path, &full).await
+        }
+        .boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        async move {
+            self.require_dir(path).await?;
+            let full = self.resolve(path).await?;
+            let mut dir = tokio::fs::read_dir(&full)
+                .await
+                .map_err(|e| io_error(path, e))?;
+            let mut entries = Vec::new();
+            while let Some(entry) = dir.next_entry().await.map_err(|e| io_error(path, e))? {
+                let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+                    continue;
+                };
+                if name.starts_with(TEMP_PREFIX) {
+                    continue;
+                }
+                let Ok(child) = path.join(&name) else {
+                    continue;
+                };
+                let m = entry.metadata().await.map_err(|e| io_error(&child, e))?;
+                if m.file_type().is_symlink() {
+                    continue;
+                }
+                entries.push(to_metadata(child, &m)?);
+            }
+            entries.sort_by(|a, b| a.path().cmp(b.path()));
+            Ok(entries)
+        }
+        .boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        async move {
+            let full = self.resolve(path).await?;
+            let metadata = self.metadata(path, &full).await?;
+            if metadata.is_dir() {
+                return Err(StorageError::IsADirectory(path.to_string()));
+            }
+            let range = resolve_range(range, metadata.size())?;
+            let file = tokio::fs::File::open(&full)
+                .await
+                .map_err(|e| io_error(path, e))?;
+            Ok(FileRead {
+                metadata,
+                stream: file_stream(file, range.start, range.end - range.start),
+                range,
+            })
+        }
+        .boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        mut content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            require_not_root(path)?;
+            let parent = path.parent().unwrap_or_default();
+            self.require_dir(&parent).await?;
+            let full = self.resolve(path).await?;
+            match self.metadata(path, &full).await {
+                Ok(m) if m.is_dir() => return Err(StorageError::IsADirectory(path.to_string())),
+                Ok(_) | Err(StorageError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+
+            let temp = full.with_file_name(temp_name());
+            let written = async {
+                let mut file = tokio::fs::File::create_new(&temp).await?;
+                while let Some(chunk) = content.next().await {
+                    file.write_all(&chunk?).await?;
+                }
+                file.sync_all().await?;
+                tokio::fs::rename(&temp, &full).await?;
+                Ok::<_, StorageError>(())
+            }
+            .await;
+            if let Err(e) = written {
+                _ = tokio::fs::remove_file(&temp).await;
+                return Err(e);
+            }
+            self.metadata(path, &full).await
+        }
+        .boxed_local()
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        async move {
+            require_not_root(from)?;
+            require_not_root(to)?;
+            if to.starts_with(from) {
+                return Err(StorageError::InvalidPath(to.to_string()));
+            }
+            let from_full = self.resolve(from).await?;
+            self.metadata(from, &from_full).await?;
+            self.require_dir(&to.parent().unwrap_or_default()).await?;
+            let to_full = self.resolve(to).await?;
+            self.require_absent(to, &to_full).await?;
+            tokio::fs::rename(&from_full, &to_full)
+                .await
+                .map_err(|e| io_error(from, e))
+        }
+        .boxed_local()
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        async move {
+            require_not_root(path)?;
+            let full = self.resolve(path).await?;
+            if self.metadata(path, &full).await?.is_dir() {
+                let mut dir = tokio::fs::read_dir(&full)
+                    .await
+                    .map_err(|e| io_error(path, e))?;
+                if dir
+                    .next_entry()
+                    .await
+                    .map_err(|e| io_error(path, e))?
+                    .is_some()
+                {
+                    return Err(StorageError::NotEmpty(path.to_string()));
+                }
+                tokio::fs::remove_dir(&full).await
+            } else {
+                tokio::fs::remove_file(&full).await
+            }
+            .map_err(|e| io_error(path, e))
+        }
+        .boxed_local()
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            require_not_root(path)?;
+            self.require_dir(&path.parent().unwrap_or_default()).await?;
+            let full = self.resolve(path).await?;
+            tokio::fs::create_dir(&full)
+                .await
+                .map_err(|e| io_error(path, e))?;
+            self.metadata(path, &full).await
+        }
+        .boxed_local()
+    }
+
+    /// Writes and removes a hidden probe file in the root
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        async move {
+            let root = StoragePath::root();
+            self.require_dir(&root).await?;
+            let probe = self.root.join(temp_name());
+            tokio::fs::File::create_new(&probe)
+                .await
+                .map_err(|e| io_error(&root, e))?;
+            tokio::fs::remove_file(&probe)
+                .await
+                .map_err(|e| io_error(&root, e))?;
+            let stats = rustix::fs::statvfs(&self.root).map_err(|e| io_error(&root, e.into()))?;
+            Ok(StorageCheck {
+                free_bytes: Some(stats.f_bavail.saturating_mul(stats.f_frsize)),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+fn temp_name() -> String {
+    let mut bytes = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let suffix: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", TEMP_PREFIX, suffix)
+}
+
+/// Io errors carry no paths, so the storage path is added to keep disk paths private
+fn io_error(path: &StoragePath, error: std::io::Error) -> StorageError {
+    match error.kind() {
+        ErrorKind::NotFound => StorageError::NotFound(path.to_string()),
+        ErrorKind::AlreadyExists => StorageError::AlreadyExists(path.to_string()),
+        ErrorKind::NotADirectory => StorageError::NotADirectory(path.to_string()),
+        ErrorKind::IsADirectory => StorageError::IsADirectory(path.to_string()),
+        ErrorKind::DirectoryNotEmpty => StorageError::NotEmpty(path.to_string()),
+        _ => StorageError::Io(format!("{}: {}", path, error)),
+    }
+}
+
+fn to_metadata(path: StoragePath, m: &std::fs::Metadata) -> Result<EntryMetadata, StorageError> {
+    let modified: DateTime<Utc> = m.modified().map_err(|e| io_error(&path, e))?.into();
+    Ok(if m.is_dir() {
+        EntryMetadata::directory(path, modified)
+    } else {
+        EntryMetadata::file(path, m.len(), modified)
+    })
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::{collect_stream, once_stream};
+    use crate::test::*;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn check_probes_root() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            let backend = LocalBackend::new(dir.path());
+            let missing = LocalBackend::new(dir.path().join("missing"));
+
+            // act
+            let check = backend.check().await.unwrap();
+            let failed = missing.check().await;
+
+            // assert
+            assert!(check.free_bytes.is_some_and(|free| free > 0));
+            assert!(matches!(failed, Err(StorageError::NotFound(_))));
+            assert_eq!(backend.list(&StoragePath::root()).await.unwrap(), vec![]);
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        });
+    }
+
+    #[test]
+    fn write_read_list() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            let backend = LocalBackend::new(dir.path());
+            backend.mkdir(&path("docs")).await.unwrap();
+
+            // act
+            let written = backend
+                .write(&path("docs/a.txt"), once_stream(Bytes::from("hello world")))
+                .await
+                .unwrap();
+            let read = backend.read(&path("docs/a.txt"), Some(0..5)).await.unwrap();
+            let listed = backend.list(&path("docs")).await.unwrap();
+
+            // assert
+            assert_eq!(written.size(), 11);
+            assert_eq!(collect_stream(read.stream).await.unwrap(), "hello");
+            assert_eq!(listed, vec![written]);
+            assert_str_eq!(
+                std::fs::read_to_string(dir.path().join("docs/a.txt")).unwrap(),
+                "hello world"
+            );
+        })
+    }
+
+    #[test]
+    fn failed_write_keeps_old_content() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            let backend = LocalBackend::new(dir.path());
+            backend
+                .write(&path("a.txt"), once_stream(Bytes::from("old")))
+                .await
+                .unwrap();
+            let content: ByteStream = futures::stream::iter([
+                Ok(Bytes::from("new")),
+                Err(StorageError::Io("connection reset".to_owned())),
+            ])
+            .boxed_local();
+
+            // act
+            let result = backend.write(&path("a.txt"), content).await;
+
+            // assert
+            assert!(result.is_err());
+            let read = backend.read(&path("a.txt"), None).await.unwrap();
+            assert_eq!(collect_stream(read.stream).await.unwrap(), "old");
+            assert_eq!(backend.list(&StoragePath::root()).await.unwrap().len(), 1);
+        })
+    }
+
+    #[test]
+    fn rename_and_delete() {
+        test(|_ctx| async move {
+            // arrange
+            let dir = tempfile::tempdir().unwrap();
+            let backend = LocalBackend::new(dir.path());
+            backend.mkdir(&path("a")).await.unwrap();
+            backend
+                .write(&path("a/b.txt"), once_stream(Bytes::from("b")))
+                .await
+                .unwrap();
+
+            // act
+            backend.rename(&path("a"), &path("c")).await.unwrap();
+
+            // assert
+            assert!(matches!(
+                backend.stat(&path("a")).await,
+                Err(StorageError::NotFound(_))
+            ));
+            assert!(matches!(
+                backend.delete(&path("c")).await,
+                Err(StorageError::NotEmpty(_))
+            ));
+            backend.delete(&path("c/b.txt")).await.unwrap();
+            backend.delete(&path("c")).await.unwrap();
+            assert!(backend.list(&StoragePath::root()).await.unwrap().is_empty());
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed() {
+        test(|_ctx| async move {
+            // arrange
+            let outside = tempfile::tempdir().unwrap();
+            std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+            let dir = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+            let backend = LocalBackend::new(dir.path());
+
+            // act
+            let result = backend.read(&path("link/secret.txt"), None).await;
+
+            // assert
+            assert!(matches!(result, Err(StorageError::NotFound(_))));
+            assert!(backend.list(&StoragePath::root()).await.unwrap().is_empty());
+        })
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    sync::{Arc, RwLock},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+
+use crate::utils::time::Time;
+
+use super::{
+    storage_backend::{
+        collect_stream, once_stream, require_not_root, resolve_range, ByteStream, EntryMetadata,
+        FileRead, StorageBackend, StorageResult,
+    },
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+#[derive(Debug, Clone)]
+enum Node {
+    File {
+        content: Bytes,
+        modified: DateTime<Utc>,
+    },
+    Directory {
+        modified: DateTime<Utc>,
+    },
+}
+
+impl Node {
+    fn metadata(&self, path: StoragePath) -> EntryMetadata {
+        match self {
+            Node::File { content, modified } => {
+                EntryMetadata::file(path, content.len() as u64, *modified)
+            }
+            Node::Directory { modified } => EntryMetadata::directory(path, *modified),
+        }
+    }
+}
+
+/// Keeps everything in memory, clones share the same content
+#[derive(Debug, Clone)]
+pub struct MemoryBackend<T> {
+    time: T,
+    /// The root directory is implicit
+    nodes: Arc<RwLock<BTreeMap<StoragePath, Node>>>,
+    created: DateTime<Utc>,
+}
+
+impl<T: Time> MemoryBackend<T> {
+    pub fn new(time: T) -> Self {
+        let created = time.now();
+        Self {
+            time,
+            nodes: Default::default(),
+            created,
+        }
+    }
+
+    fn metadata(
+        &self,
+        nodes: &BTreeMap<StoragePath, Node>,
+        path: &StoragePath,
+    ) -> Result<EntryMetadata, StorageError> {
+        if path.is_root() {
+            return Ok(EntryMetadata::directory(StoragePath::root(), self.created));
+        }
+        nodes
+            .get(path)
+            .map(|node| node.metadata(path.clone()))
+            .ok_or_else(|| StorageError::NotFound(path.to_string()))
+    }
+
+    fn require_parent_dir(
+        &self,
+        nodes: &BTreeMap<StoragePath, Node>,
+        path: &StoragePath,
+    ) -> Result<(), StorageError> {
+        let parent = path.parent().unwrap_or_default();
+        if self.metadata(nodes, &parent)?.is_dir() {
+            Ok(())
+        } else {
+            Err(StorageError::NotADirectory(parent.to_string()))
+        }
+    }
+}
+
+impl<T: Time + Send + Sync> StorageBackend for MemoryBackend<T> {
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        let nodes = self.nodes.read().unwrap();
+        let result = self.metadata(&nodes, path);
+        async move { result }.boxed_local()
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        let nodes = self.nodes.read().unwrap();
+        let result = self.metadata(&nodes, path).and_then(|dir| {
+            if !dir.is_dir() {
+                return Err(StorageError::NotADirectory(path.to_string()));
+            }
+            Ok(nodes
+                .iter()
+                .filter(|(p, _)| p.is_child_of(path))
+                .map(|(p, node)| node.metadata(p.clone()))
+                .collect())
+        });
+        async move { result }.boxed_local()
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        let nodes = self.nodes.read().unwrap();
+        let result = match nodes.get(path) {
+            Some(Node::File { content, modified }) => resolve_range(range, content.len() as u64)
+                .map(|range| FileRead {
+                    metadata: EntryMetadata::file(path.clone(), content.len() as u64, *modified),
+                    stream: once_stream(content.slice(range.start as usize..range.end as usize)),
+                    range,
+                }),
+            Some(Node::Directory { .. }) => Err(StorageError::IsADirectory(path.to_string())),
+            None if path.is_root() => Err(StorageError::IsADirectory(path.to_string())),
+            None => Err(StorageError::NotFound(path.to_string())),
+        };
+        async move { result }.boxed_local()
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        async move {
+            require_not_root(path)?;
+            let content = collect_stream(content).await?;
+            let mut nodes = self.nodes.write().unwrap();
+            self.require_parent_dir(&nodes, path)?;
+            if let Some(Node::Directory { .. }) = nodes.get(path) {
+                return Err(StorageError::IsADirectory(path.to_string()));
+            }
+            let node = Node::File {
+                content,
+                modified: self.time.now(),
+            };
+            let metadata = node.metadata(path.clone());
+            nodes.insert(path.clone(), node);
+            Ok(metadata)
+        }
+        .boxed_local()
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        let result = (|| {
+            require_not_root(from)?;
+            require_not_root(to)?;
+            if to.starts_with(from) {
+                return Err(StorageError::InvalidPath(to.to_string()));
+            }
+            let mut nodes = self.nodes.write().unwrap();
+            self.metadata(&nodes, from)?;
+            if nodes.contains_key(to) {
+                return Err(StorageError::AlreadyExists(to.to_string()));
+            }
+            self.require_parent_dir(&nodes, to)?;
+
+            let moved: Vec<_> = nodes
+                .keys()
+                .filter(|p| p.starts_with(from))
+                .cloned()
+                .collect();
+            for old in moved {
+                let node = nodes.remove(&old).unwrap();
+                let suffix = &old.as_str()[from.as_str().len()..];
+                let new = StoragePath::parse(&format!("{}{}", to.as_str(), suffix))?;
+                nodes.insert(new, node);
+            }
+            Ok(())
+        })();
+        async move { result }.boxed_local()
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        let result = (|| {
+            require_not_root(path)?;
+            let mut nodes = self.nodes.write().unwrap();
+            if self.metadata(&nodes, path)?.is_dir() && nodes.keys().any(|p| p.is_child_of(path)) {
+                return Err(StorageError::NotEmpty(path.to_string()));
+            }
+            nodes.remove(path);
+            Ok(())
+        })();
+        async move { result }.boxed_local()
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        let result = (|| {
+            require_not_root(path)?;
+            let mut nodes = self.nodes.write().unwrap();
+            if nodes.contains_key(path) {
+                return Err(StorageError::AlreadyExists(path.to_string()));
+            }
+            self.require_parent_dir(&nodes, path)?;
+            let node = Node::Directory {
+                modified: self.time.now(),
+            };
+            let metadata = node.metadata(path.clone());
+            nodes.insert(path.clone(), node);
+            Ok(metadata)
+        })();
+        async move { result }.boxed_local()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn write_read_list() {
+        test(|ctx| async move {
+            // arrange
+            ctx.time().set(utc!(2024, 1, 1));
+            let backend = MemoryBackend::new(ctx.time().clone());
+            backend.mkdir(&path("docs")).await.unwrap();
+
+            // act
+            let written = backend
+                .write(&path("docs/a.txt"), once_stream(Bytes::from("hello world")))
+                .await
+                .unwrap();
+            let read = backend
+                .read(&path("docs/a.txt"), Some(6..11))
+                .await
+                .unwrap();
+            let listed = backend.list(&path("docs")).await.unwrap();
+
+            // assert
+            assert_eq!(written.size(), 11);
+            assert_eq!(written.modified(), utc!(2024, 1, 1));
+            assert_eq!(read.range, 6..11);
+            assert_eq!(collect_stream(read.stream).await.unwrap(), "world");
+            assert_eq!(listed, vec![written]);
+        })
+    }
+
+    #[test]
+    fn write_requires_parent_directory() {
+        test(|ctx| async move {
+            // arrange
+            let backend = MemoryBackend::new(ctx.time().clone());
+
+            // act
+            let result = backend
+                .write(&path("missing/a.txt"), once_stream(Bytes::new()))
+                .await;
+
+            // assert
+            assert!(matches!(result, Err(StorageError::NotFound(_))));
+        })
+    }
+
+    #[test]
+    fn read_out_of_range() {
+        test(|ctx| async move {
+            // arrange
+            let backend = MemoryBackend::new(ctx.time().clone());
+            backend
+                .write(&path("a.txt"), once_stream(Bytes::from("abc")))
+                .await
+                .unwrap();
+
+            // act
+            let result = backend.read(&path("a.txt"), Some(2..4)).await;
+
+            // assert
+            assert!(matches!(result, Err(StorageError::InvalidRange)));
+        })
+    }
+
+    #[test]
+    fn rename_moves_subtree() {
+        test(|ctx| async move {
+            // arrange
+            let backend = MemoryBackend::new(ctx.time().clone());
+            backend.mkdir(&path("a")).await.unwrap();
+            backend.mkdir(&path("a/b")).await.unwrap();
+            backend
+                .write(&path("a/b/c.txt"), once_stream(Bytes::from("c")))
+                .await
+                .unwrap();
+
+            // act
+            backend.rename(&path("a"), &path("z")).await.unwrap();
+
+            // assert
+            assert!(backend.stat(&path("a")).await.is_err());
+            assert_eq!(backend.stat(&path("z/b/c.txt")).await.unwrap().size(), 1);
+            assert!(matches!(
+                backend.rename(&path("z"), &path("z/b/inner")).await,
+                Err(StorageError::InvalidPath(_))
+            ));
+        })
+    }
+
+    #[test]
+    fn delete_refuses_non_empty_directory() {
+        test(|ctx| async move {
+            // arrange
+            let backend = MemoryBackend::new(ctx.time().clone());
+            backend.mkdir(&path("a")).await.unwrap();
+            backend
+                .write(&path("a/b.txt"), once_stream(Bytes::new()))
+                .await
+                .unwrap();
+
+            // act
+            let result = backend.delete(&path("a")).await;
+
+            // assert
+            assert!(matches!(result, Err(StorageError::NotEmpty(_))));
+            backend.delete(&path("a/b.txt")).await.unwrap();
+            backend.delete(&path("a")).await.unwrap();
+            assert!(backend.list(&StoragePath::root()).await.unwrap().is_empty());
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use std::{ops::Range, sync::Arc, time::Instant};
+
+use futures::FutureExt;
+
+use crate::metrics::app_metrics::AppMetrics;
+
+use super::{
+    dedup::dedup_stats::{DedupStats, GarbageReport},
+    storage_backend::{
+        ByteStream, EntryMetadata, FileRead, StorageBackend, StorageCheck, StorageResult,
+    },
+    storage_path::StoragePath,
+};
+
+/// Records the latency of every operation of another backend by the name of its source.
+/// Reads are measured until the content starts streaming.
+pub struct MeteredBackend {
+    inner: Arc<dyn StorageBackend>,
+    source: String,
+    metrics: AppMetrics,
+}
+
+impl MeteredBackend {
+    pub fn new(inner: Arc<dyn StorageBackend>, source: String, metrics: AppMetrics) -> Self {
+        Self {
+            inner,
+            source,
+            metrics,
+        }
+    }
+
+    fn measure<'a, T: 'a>(
+        &'a self,
+        operation: &'static str,
+        future: StorageResult<'a, T>,
+    ) -> StorageResult<'a, T> {
+        let started = Instant::now();
+        async move {
+            let result = future.await;
+            self.metrics.operation_finished(
+                &self.source,
+                operation,
+                result.is_ok(),
+                started.elapsed(),
+            );
+            result
+        }
+        .boxed_local()
+    }
+}
+
+impl StorageBackend for MeteredBackend {
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.measure("stat", self.inner.stat(path))
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        self.measure("list", self.inner.list(path))
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        self.measure("read", self.inner.read(path, range))
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        self.measure("write", self.inner.write(path, content))
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.measure("rename", self.inner.rename(from, to))
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.measure("delete", self.inner.delete(path))
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.measure("mkdir", self.inner.mkdir(path))
+    }
+
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        self.inner.dedup_stats()
+    }
+
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        self.measure("collect_garbage", self.inner.collect_garbage())
+    }
+
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.inner.check()
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::Duration;
+use uuid::Uuid;
+
+use crate::dal::{file_checksums_dal::FileChecksumsDal, Dal};
+
+use super::{
+    digests::compute_digests, file_checksum::FileChecksum, source::Source, sources::Sources,
+    storage_backend::EntryMetadata, storage_error::StorageError, storage_path::StoragePath,
+};
+
+/// Outcome of a pass over the sources
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrubReport {
+    /// Files whose content still matches the stored checksum
+    pub verified: u64,
+    /// Files without a checksum for their modification time and size, hashed for later passes
+    pub hashed: u64,
+    /// Files whose content changed while modification time and size stayed the same
+    pub corrupted: Vec<(Uuid, StoragePath)>,
+    /// Files and directories which couldn't be read
+    pub failed: u64,
+}
+
+/// Re-hashes every file of every source and compares it with the stored checksum
+pub async fn scrub<D: Dal>(dal: &D, sources: &Sources) -> ScrubReport {
+    let mut report = ScrubReport::default();
+    for source in sources.list() {
+        scrub_source(dal, &source, &mut report).await;
+    }
+    tracing::info!(
+        verified = report.verified,
+        hashed = report.hashed,
+        corrupted = report.corrupted.len(),
+        failed = report.failed,
+        "scrub finished"
+    );
+    report
+}
+
+/// Runs [scrub] every interval, the first pass starts after one interval
+pub async fn run<D: Dal>(dal: D, sources: Sources, interval: Duration) {
+    let period = interval
+        .to_std()
+        .unwrap_or(std::time::Duration::from_secs(1));
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        ticks.tick().await;
+        scrub(&dal, &sources).await;
+    }
+}
+
+async fn scrub_source<D: Dal>(dal: &D, source: &Source, report: &mut ScrubReport) {
+    let mut pending = vec![StoragePath::root()];
+    while let Some(dir) = pending.pop() {
+        let entries = match source.backend().list(&dir).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!(source_id = %source.id(), path = %dir, error = %e, "directory can't be scrubbed");
+                report.failed += 1;
+                continue;
+            }
+        };
+        for entry in entries {
+            if entry.is_dir() {
+                pending.push(entry.path().clone());
+            } else {
+                scrub_file(dal, source, &entry, report).await;
+            }
+        }
+    }
+}
+
+async fn scrub_file<D: Dal>(
+    dal: &D,
+    source: &Source,
+    entry: &EntryMetadata,
+    report: &mut ScrubReport,
+) {
+    let checksums = dal.file_checksums();
+    let result = async {
+        let stored = checksums.get(source.id(), entry.path()).await?;
+        let digests = compute_digests(source.backend(), entry.path()).await?;
+        Ok::<_, StorageError>((stored, digests))
+    };
+    let (stored, digests) = match result.await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!(source_id = %source.id(), path = %entry.path(), error = %e, "file can't be scrubbed");
+            report.failed += 1;
+            return;
+        }
+    };
+
+    match stored {
+        Some(checksum) if checksum.matches(entry) => {
+            if *checksum.digests() == digests {
+                report.verified += 1;
+            } else {
+                tracing::error!(source_id = %source.id(), path = %entry.path(), "file is corrupted");
+                report.corrupted.push((source.id(), entry.path().clone()));
+            }
+        }
+        _ => {
+            let saved = checksums
+                .save(FileChecksum::new(source.id(), entry, digests))
+                .await;
+            match saved {
+                Ok(()) => report.hashed += 1,
+                Err(e) => {
+                    tracing::error!(source_id = %source.id(), path = %entry.path(), error = %e, "checksum can't be saved");
+                    report.failed += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::once_stream;
+    use crate::test::*;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn detects_silent_changes() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .memory_source(
+                    "docs",
+                    dir! { "a.txt" => "abc", "sub" => dir! { "b.txt" => "b" } },
+                )
+                .await;
+            let first = scrub(ctx.dal(), ctx.sources()).await;
+            // same size and the test clock doesn't move, so the modification time stays too
+            let path = StoragePath::parse("sub/b.txt").unwrap();
+            source
+                .backend()
+                .write(&path, once_stream(Bytes::from("x")))
+                .await
+                .unwrap();
+
+            // act
+            let second = scrub(ctx.dal(), ctx.sources()).await;
+
+            // assert
+            assert_eq!(
+                first,
+                ScrubReport {
+                    hashed: 2,
+                    ..Default::default()
+                }
+            );
+            assert_eq!(
+                second,
+                ScrubReport {
+                    verified: 1,
+                    corrupted: vec![(source.id(), path.clone())],
+                    ..Default::default()
+                }
+            );
+            let logged = ctx.logs().get(|e| e.message() == "file is corrupted");
+            assert_eq!(logged.must_have_field_value::<String>("path"), "/sub/b.txt");
+        })
+    }
+}
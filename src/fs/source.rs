@@ -1,9 +1,236 @@
-use std::path::PathBuf;
+use std::{fmt::Debug, sync::Arc};
 
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::{
+    config::app_config::{BackendConfig, HostingConfig, SourceConfig},
+    dal::Dal,
+    metrics::app_metrics::AppMetrics,
+    utils::time::TimeNow,
+};
+
+use super::{
+    archive_backend::ArchiveBackend,
+    compressed_backend::CompressedBackend,
+    dedup::{chunk_store::ChunkStore, dedup_backend::DedupBackend},
+    encryption::{encrypted_backend::EncryptedBackend, key_ring::KeyRing},
+    local_backend::LocalBackend,
+    memory_backend::MemoryBackend,
+    metered_backend::MeteredBackend,
+    storage_backend::{StorageBackend, StorageCheck},
+    storage_error::StorageError,
+    version_store::VersionStore,
+};
+
+/// Named root of files served over HTTP
+#[derive(Clone)]
 pub struct Source {
     id: Uuid,
-    path: PathBuf,
+    name: String,
+    backend: Arc<dyn StorageBackend>,
+    versions: Option<Arc<VersionStore>>,
+    precompressed: bool,
+    encrypted: bool,
+    hosting: Option<HostingConfig>,
+}
+
+impl Source {
+    pub fn new(id: Uuid, name: String, backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            id,
+            name,
+            backend: Arc::new(backend),
+            versions: None,
+            precompressed: false,
+            encrypted: false,
+            hosting: None,
+        }
+    }
+
+    /// Keeps prior contents of files written through the API
+    pub fn with_versions(self, versions: VersionStore) -> Self {
+        Self {
+            versions: Some(Arc::new(versions)),
+            ..self
+        }
+    }
+
+    /// Downloads are served from `.br` or `.gz` siblings of a file if the client accepts them
+    pub fn with_precompressed(self) -> Self {
+        Self {
+            precompressed: true,
+            ..self
+        }
+    }
+
+    /// Marks the backend as encrypting contents at rest, it's wrapped by the caller
+    pub fn with_encryption(self) -> Self {
+        Self {
+            encrypted: true,
+            ..self
+        }
+    }
+
+    /// Serves the source as a static web site
+    pub fn with_hosting(self, hosting: HostingConfig) -> Self {
+        Self {
+            hosting: Some(hosting),
+            ..self
+        }
+    }
+
+    /// Records the latencies of backend operations
+    pub fn with_metrics(self, metrics: AppMetrics) -> Self {
+        let backend = MeteredBackend::new(self.backend, self.name.clone(), metrics);
+        Self {
+            backend: Arc::new(backend),
+            ..self
+        }
+    }
+
+    /// Archives are indexed here, so a broken archive fails at startup.
+    /// Deduplicating sources keep their manifests in the DAL, encrypted ones their data key.
+    pub fn open<D>(
+        config: &SourceConfig,
+        dal: &D,
+        keys: Option<&Arc<KeyRing>>,
+    ) -> Result<Self, StorageError>
+    where
+        D: Dal + Clone + Send + Sync + 'static,
+    {
+        match config.backend() {
+            BackendConfig::Local { path } => {
+                Self::assemble(config, dal, keys, LocalBackend::new(path))
+            }
+            BackendConfig::Memory => {
+                Self::assemble(config, dal, keys, MemoryBackend::new(TimeNow {}))
+            }
+            BackendConfig::Tar { path } => {
+                Self::assemble(config, dal, keys, ArchiveBackend::open_tar(path)?)
+            }
+            BackendConfig::Zip { path } => {
+                Self::assemble(config, dal, keys, ArchiveBackend::open_zip(path)?)
+            }
+            BackendConfig::Dedup {
+                chunks_path,
+                chunking,
+            } => {
+                let chunks = ChunkStore::new(LocalBackend::new(chunks_path));
+                let backend =
+                    DedupBackend::new(config.id(), dal.clone(), TimeNow {}, chunks, *chunking)?;
+                Self::assemble(config, dal, keys, backend)
+            }
+        }
+    }
+
+    /// Adds encryption, compression and versions to the backend as configured
+    fn assemble<D>(
+        config: &SourceConfig,
+        dal: &D,
+        keys: Option<&Arc<KeyRing>>,
+        backend: impl StorageBackend + 'static,
+    ) -> Result<Self, StorageError>
+    where
+        D: Dal + Clone + Send + Sync + 'static,
+    {
+        let id = config.id();
+        let keys = match (config.encrypted(), keys) {
+            (true, None) => {
+                return Err(StorageError::Encryption(format!(
+                    "source {} is encrypted but no master keys are configured",
+                    config.name()
+                )))
+            }
+            (encrypted, keys) => keys.filter(|_| encrypted),
+        };
+        let encrypt = |backend: Box<dyn StorageBackend>| -> Box<dyn StorageBackend> {
+            match keys {
+                Some(keys) => Box::new(EncryptedBackend::new(
+                    id,
+                    backend,
+                    dal.clone(),
+                    keys.clone(),
+                )),
+                None => backend,
+            }
+        };
+        // compressed before being encrypted, ciphertext doesn't compress
+        let mut backend = encrypt(Box::new(backend));
+        if let Some(compressed) = config.compressed() {
+            backend = Box::new(CompressedBackend::new(backend, compressed.level()));
+        }
+        let mut source = Self::new(id, config.name().to_owned(), backend);
+        if keys.is_some() {
+            source = source.with_encryption();
+        }
+        if config.precompressed() {
+            source = source.with_precompressed();
+        }
+        if let Some(hosting) = config.hosting() {
+            source = source.with_hosting(hosting.clone());
+        }
+        Ok(match config.versioning() {
+            // versions share the data key of the source
+            Some(versioning) => source.with_versions(VersionStore::new(
+                encrypt(Box::new(LocalBackend::new(versioning.path()))),
+                versioning.retention().clone(),
+            )),
+            None => source,
+        })
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn backend(&self) -> &dyn StorageBackend {
+        self.backend.as_ref()
+    }
+
+    pub fn versions(&self) -> Option<&VersionStore> {
+        self.versions.as_deref()
+    }
+
+    pub fn precompressed(&self) -> bool {
+        self.precompressed
+    }
+
+    pub fn encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub fn hosting(&self) -> Option<&HostingConfig> {
+        self.hosting.as_ref()
+    }
+
+    /// Checks the backend and the version store, the free space is the lower of both
+    pub async fn check(&self) -> Result<StorageCheck, StorageError> {
+        let mut check = self.backend.check().await?;
+        if let Some(versions) = &self.versions {
+            let versions = versions.check().await?;
+            check.free_bytes = match (check.free_bytes, versions.free_bytes) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        Ok(check)
+    }
+}
+
+impl Debug for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Source")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("read_only", &self.backend.is_read_only())
+            .field("versioned", &self.versions.is_some())
+            .field("precompressed", &self.precompressed)
+            .field("encrypted", &self.encrypted)
+            .field("hosted", &self.hosting.is_some())
+            .finish()
+    }
 }
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::{config::app_config::SourceConfig, dal::Dal, metrics::app_metrics::AppMetrics};
+
+use super::{encryption::key_ring::KeyRing, source::Source, storage_error::StorageError};
+
+/// Sources by id, clones share the same registry
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    sources: Arc<RwLock<HashMap<Uuid, Arc<Source>>>>,
+}
+
+impl Sources {
+    pub fn open<D>(
+        configs: &[SourceConfig],
+        dal: &D,
+        keys: Option<&Arc<KeyRing>>,
+        metrics: &AppMetrics,
+    ) -> Result<Self, StorageError>
+    where
+        D: Dal + Clone + Send + Sync + 'static,
+    {
+        let sources = Self::default();
+        for config in configs {
+            sources.insert(Source::open(config, dal, keys)?.with_metrics(metrics.clone()));
+        }
+        Ok(sources)
+    }
+
+    /// Replaces a source with the same id
+    pub fn insert(&self, source: Source) -> Arc<Source> {
+        let source = Arc::new(source);
+        self.sources
+            .write()
+            .unwrap()
+            .insert(source.id(), source.clone());
+        source
+    }
+
+    /// Requests which already got the source keep using it
+    pub fn remove(&self, id: Uuid) -> Option<Arc<Source>> {
+        self.sources.write().unwrap().remove(&id)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Arc<Source>> {
+        self.sources.read().unwrap().get(&id).cloned()
+    }
+
+    /// Sorted by name
+    pub fn list(&self) -> Vec<Arc<Source>> {
+        let mut sources: Vec<_> = self.sources.read().unwrap().values().cloned().collect();
+        sources.sort_by(|a, b| a.name().cmp(b.name()).then(a.id().cmp(&b.id())));
+        sources
+    }
+}
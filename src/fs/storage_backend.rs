@@ -0,0 +1,297 @@
+use std::ops::Range;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{future::LocalBoxFuture, stream::LocalBoxStream, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dedup::dedup_stats::{DedupStats, GarbageReport},
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+pub type ByteStream = LocalBoxStream<'static, Result<Bytes, StorageError>>;
+
+pub type StorageResult<'a, T> = LocalBoxFuture<'a, Result<T, StorageError>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    path: StoragePath,
+    kind: EntryKind,
+    /// Zero for directories
+    size: u64,
+    modified: DateTime<Utc>,
+}
+
+impl EntryMetadata {
+    pub fn file(path: StoragePath, size: u64, modified: DateTime<Utc>) -> Self {
+        Self {
+            path,
+            kind: EntryKind::File,
+            size,
+            modified,
+        }
+    }
+
+    pub fn directory(path: StoragePath, modified: DateTime<Utc>) -> Self {
+        Self {
+            path,
+            kind: EntryKind::Directory,
+            size: 0,
+            modified,
+        }
+    }
+
+    pub fn path(&self) -> &StoragePath {
+        &self.path
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.name().unwrap_or_default()
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn modified(&self) -> DateTime<Utc> {
+        self.modified
+    }
+}
+
+/// Outcome of [StorageBackend::check]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StorageCheck {
+    /// Space left on the local disk holding the data, `None` if it isn't on one
+    pub free_bytes: Option<u64>,
+}
+
+pub struct FileRead {
+    pub metadata: EntryMetadata,
+    /// Part of the file the stream yields
+    pub range: Range<u64>,
+    pub stream: ByteStream,
+}
+
+/// Storage of a source. Routes only talk to this trait, so a new kind of storage
+/// needs no route changes. Futures are boxed to keep the trait object safe.
+pub trait StorageBackend: Send + Sync {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata>;
+
+    /// Direct children of a directory, sorted by name
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>>;
+
+    /// Reads the whole file or the given part of it
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead>;
+
+    /// Creates or replaces a file, the parent directory must exist.
+    /// Readers see either the old or the complete new content.
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata>;
+
+    /// Moves a file or a whole directory, fails if the target exists
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()>;
+
+    /// Removes a file or an empty directory
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()>;
+
+    /// Creates a directory, the parent directory must exist
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata>;
+
+    /// Space saved by deduplication, `None` if the backend doesn't deduplicate
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        async { Ok(None) }.boxed_local()
+    }
+
+    /// Removes stored data which no file refers to anymore
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        async { Ok(GarbageReport::default()) }.boxed_local()
+    }
+
+    /// Fails unless the root can be read, and written if the backend isn't read only
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        async {
+            self.stat(&StoragePath::root()).await?;
+            Ok(StorageCheck::default())
+        }
+        .boxed_local()
+    }
+}
+
+/// Lets wrapping backends be stacked as configured
+impl StorageBackend for Box<dyn StorageBackend> {
+    fn is_read_only(&self) -> bool {
+        self.as_ref().is_read_only()
+    }
+
+    fn stat<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.as_ref().stat(path)
+    }
+
+    fn list<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, Vec<EntryMetadata>> {
+        self.as_ref().list(path)
+    }
+
+    fn read<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<'a, FileRead> {
+        self.as_ref().read(path, range)
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a StoragePath,
+        content: ByteStream,
+    ) -> StorageResult<'a, EntryMetadata> {
+        self.as_ref().write(path, content)
+    }
+
+    fn rename<'a>(&'a self, from: &'a StoragePath, to: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.as_ref().rename(from, to)
+    }
+
+    fn delete<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, ()> {
+        self.as_ref().delete(path)
+    }
+
+    fn mkdir<'a>(&'a self, path: &'a StoragePath) -> StorageResult<'a, EntryMetadata> {
+        self.as_ref().mkdir(path)
+    }
+
+    fn dedup_stats(&self) -> StorageResult<'_, Option<DedupStats>> {
+        self.as_ref().dedup_stats()
+    }
+
+    fn collect_garbage(&self) -> StorageResult<'_, GarbageReport> {
+        self.as_ref().collect_garbage()
+    }
+
+    fn check(&self) -> StorageResult<'_, StorageCheck> {
+        self.as_ref().check()
+    }
+}
+
+/// Checks a requested range against the file size, `None` means the whole file
+pub fn resolve_range(range: Option<Range<u64>>, size: u64) -> Result<Range<u64>, StorageError> {
+    match range {
+        None => Ok(0..size),
+        Some(range) if range.start <= range.end && range.end <= size => Ok(range),
+        Some(_) => Err(StorageError::InvalidRange),
+    }
+}
+
+pub fn once_stream(content: Bytes) -> ByteStream {
+    futures::stream::once(async move { Ok(content) }).boxed_local()
+}
+
+pub async fn collect_stream(mut stream: ByteStream) -> Result<Bytes, StorageError> {
+    let mut content = bytes::BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk?);
+    }
+    Ok(content.freeze())
+}
+
+/// Fails once more than `max` bytes went through, before the rest is read
+pub fn limit_stream(stream: ByteStream, max: u64) -> ByteStream {
+    let mut total = 0u64;
+    stream
+        .map(move |chunk| {
+            let chunk = chunk?;
+            total += chunk.len() as u64;
+            if total > max {
+                return Err(StorageError::TooLarge(max));
+            }
+            Ok(chunk)
+        })
+        .boxed_local()
+}
+
+/// Fails if the path is the root, which can't be created, replaced or removed
+pub fn require_not_root(path: &StoragePath) -> Result<(), StorageError> {
+    if path.is_root() {
+        return Err(StorageError::InvalidPath(path.to_string()));
+    }
+    Ok(())
+}
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams `len` bytes of an open file starting at `start`
+pub fn file_stream(file: tokio::fs::File, start: u64, len: u64) -> ByteStream {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let state = (Some(file), start, len);
+    futures::stream::unfold(state, |(file, start, remaining)| async move {
+        let mut file = file?;
+        if remaining == 0 {
+            return None;
+        }
+        if start > 0 {
+            if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+                return Some((Err(e.into()), (None, 0, 0)));
+            }
+        }
+        let mut buf = vec![0; READ_CHUNK_SIZE.min(remaining as usize)];
+        match file.read(&mut buf).await {
+            Ok(0) => Some((
+                Err(StorageError::Io("file ended early".to_owned())),
+                (None, 0, 0),
+            )),
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), (Some(file), 0, remaining - n as u64)))
+            }
+            Err(e) => Some((Err(e.into()), (None, 0, 0))),
+        }
+    })
+    .boxed_local()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[tokio::test]
+    async fn limit_stream_fails_past_max() {
+        let chunks =
+            || futures::stream::iter(["abc", "def"].map(|c| Ok(Bytes::from(c)))).boxed_local();
+
+        let within = collect_stream(limit_stream(chunks(), 6)).await;
+        let over = collect_stream(limit_stream(chunks(), 5)).await;
+
+        assert_eq!(within.unwrap(), "abcdef");
+        assert!(matches!(over, Err(StorageError::TooLarge(5))));
+    }
+}
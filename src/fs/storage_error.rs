@@ -0,0 +1,62 @@
+use crate::dal::dal_error::DalError;
+
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum StorageError {
+    #[display("not found: {_0}")]
+    NotFound(#[error(not(source))] String),
+
+    #[display("already exists: {_0}")]
+    AlreadyExists(#[error(not(source))] String),
+
+    #[display("not a directory: {_0}")]
+    NotADirectory(#[error(not(source))] String),
+
+    #[display("is a directory: {_0}")]
+    IsADirectory(#[error(not(source))] String),
+
+    #[display("directory is not empty: {_0}")]
+    NotEmpty(#[error(not(source))] String),
+
+    #[display("invalid path: {_0}")]
+    InvalidPath(#[error(not(source))] String),
+
+    #[display("range is outside of the file")]
+    InvalidRange,
+
+    /// The content doesn't match the digest sent with it
+    #[display("{_0} digest doesn't match the content")]
+    DigestMismatch(#[error(not(source))] String),
+
+    /// Wrong or missing key, or content which was changed or isn't encrypted
+    #[display("encryption: {_0}")]
+    Encryption(#[error(not(source))] String),
+
+    /// The content is larger than the configured limit
+    #[display("content is larger than {_0} bytes")]
+    TooLarge(#[error(not(source))] u64),
+
+    #[display("source is read only")]
+    ReadOnly,
+
+    #[display("io: {_0}")]
+    Io(#[error(not(source))] String),
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::NotFound => StorageError::NotFound(value.to_string()),
+            std::io::ErrorKind::AlreadyExists => StorageError::AlreadyExists(value.to_string()),
+            _ => StorageError::Io(value.to_string()),
+        }
+    }
+}
+
+impl From<DalError> for StorageError {
+    fn from(value: DalError) -> Self {
+        match value {
+            DalError::Conflict(message) => StorageError::AlreadyExists(message),
+            DalError::Unexpected(message) => StorageError::Io(format!("dal: {}", message)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::storage_error::StorageError;
+
+/// Normalized path inside a source, relative to its root.
+/// Segments are separated by `/`, the root is an empty path.
+/// `..` segments are rejected, so a path can never escape the source root.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StoragePath(String);
+
+impl StoragePath {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Empty and `.` segments are dropped, leading and trailing slashes are ignored
+    pub fn parse(path: &str) -> Result<Self, StorageError> {
+        let mut normalized = String::with_capacity(path.len());
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => return Err(StorageError::InvalidPath(path.to_owned())),
+                s if s.contains(['\\', '\0']) => {
+                    return Err(StorageError::InvalidPath(path.to_owned()))
+                }
+                s => {
+                    if !normalized.is_empty() {
+                        normalized.push('/');
+                    }
+                    normalized.push_str(s);
+                }
+            }
+        }
+        Ok(Self(normalized))
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/').filter(|s| !s.is_empty())
+    }
+
+    /// Last segment, `None` for the root
+    pub fn name(&self) -> Option<&str> {
+        self.segments().last()
+    }
+
+    /// `None` for the root
+    pub fn parent(&self) -> Option<StoragePath> {
+        if self.is_root() {
+            return None;
+        }
+        Some(match self.0.rfind('/') {
+            Some(index) => Self(self.0[..index].to_owned()),
+            None => Self::root(),
+        })
+    }
+
+    pub fn join(&self, name: &str) -> Result<StoragePath, StorageError> {
+        Self::parse(&format!("{}/{}", self.0, name))
+    }
+
+    /// True for the path itself and everything below it
+    pub fn starts_with(&self, base: &StoragePath) -> bool {
+        base.is_root()
+            || self.0 == base.0
+            || (self.0.starts_with(&base.0) && self.0.as_bytes()[base.0.len()] == b'/')
+    }
+
+    /// Direct children only
+    pub fn is_child_of(&self, parent: &StoragePath) -> bool {
+        self.parent().as_ref() == Some(parent)
+    }
+}
+
+impl Display for StoragePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/{}", self.0)
+    }
+}
+
+impl Serialize for StoragePath {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoragePath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        Self::parse(&path).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn parse_normalizes() {
+        let path = StoragePath::parse("/a//b/./c/").unwrap();
+        assert_str_eq!(path.as_str(), "a/b/c");
+        assert_str_eq!(path.to_string(), "/a/b/c");
+        assert!(StoragePath::parse("/").unwrap().is_root());
+        assert!(StoragePath::parse("").unwrap().is_root());
+    }
+
+    #[test]
+    fn parse_rejects_escapes() {
+        assert!(StoragePath::parse("a/../../etc").is_err());
+        assert!(StoragePath::parse("..").is_err());
+        assert!(StoragePath::parse("a\\..\\b").is_err());
+    }
+
+    #[test]
+    fn parent_and_name() {
+        let path = StoragePath::parse("a/b/c.txt").unwrap();
+        assert_eq!(path.name(), Some("c.txt"));
+        assert_eq!(path.parent(), Some(StoragePath::parse("a/b").unwrap()));
+        assert_eq!(
+            StoragePath::parse("a").unwrap().parent(),
+            Some(StoragePath::root())
+        );
+        assert_eq!(StoragePath::root().parent(), None);
+        assert_eq!(StoragePath::root().name(), None);
+    }
+
+    #[test]
+    fn starts_with() {
+        let base = StoragePath::parse("a/b").unwrap();
+        assert!(StoragePath::parse("a/b").unwrap().starts_with(&base));
+        assert!(StoragePath::parse("a/b/c").unwrap().starts_with(&base));
+        assert!(!StoragePath::parse("a/bc").unwrap().starts_with(&base));
+        assert!(!StoragePath::parse("a").unwrap().starts_with(&base));
+        assert!(base.starts_with(&StoragePath::root()));
+    }
+}
@@ -0,0 +1,315 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::app_config::RetentionConfig,
+    dal::{file_versions_dal::FileVersionsDal, Dal},
+    utils::id::Id,
+};
+
+use super::{
+    file_version::FileVersion,
+    source::Source,
+    storage_backend::{EntryMetadata, FileRead, StorageBackend, StorageCheck},
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+/// Prior contents of the files of a source, one file per version named by its id.
+/// Versions stay with their path, so renamed or deleted files keep their history there.
+pub struct VersionStore {
+    backend: Box<dyn StorageBackend>,
+    retention: RetentionConfig,
+}
+
+impl VersionStore {
+    pub fn new(backend: impl StorageBackend + 'static, retention: RetentionConfig) -> Self {
+        Self {
+            backend: Box::new(backend),
+            retention,
+        }
+    }
+
+    pub fn retention(&self) -> &RetentionConfig {
+        &self.retention
+    }
+
+    pub async fn check(&self) -> Result<StorageCheck, StorageError> {
+        self.backend.check().await
+    }
+
+    fn content_path(id: Id) -> StoragePath {
+        StoragePath::parse(&id.to_string()).unwrap()
+    }
+
+    /// Copies the current content of the file into a new version before it's replaced,
+    /// nothing is recorded for files which don't exist
+    pub async fn record<D: Dal>(
+        &self,
+        dal: &D,
+        source: &Source,
+        path: &StoragePath,
+        id: Id,
+        replaced_by: Option<Id>,
+        replaced: DateTime<Utc>,
+    ) -> Result<Option<FileVersion>, StorageError> {
+        match source.backend().stat(path).await {
+            Ok(metadata) if !metadata.is_dir() => {}
+            Ok(_) | Err(StorageError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let read = source.backend().read(path, None).await?;
+        let size = read.metadata.size();
+        self.backend
+            .write(&Self::content_path(id), read.stream)
+            .await?;
+        let version = FileVersion::new(id, source.id(), path.clone(), replaced_by, replaced, size);
+        if let Err(e) = dal.file_versions().insert(version.clone()).await {
+            self.delete_content(id).await?;
+            return Err(e.into());
+        }
+        Ok(Some(version))
+    }
+
+    /// Removes the versions of the path the retention doesn't keep
+    pub async fn prune<D: Dal>(
+        &self,
+        dal: &D,
+        source: &Source,
+        path: &StoragePath,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<FileVersion>, StorageError> {
+        let versions = dal.file_versions().list(source.id(), path).await?;
+        let mut removed = Vec::new();
+        for (index, version) in versions.into_iter().enumerate() {
+            let too_many = self
+                .retention
+                .max_versions()
+                .is_some_and(|max| index >= max as usize);
+            let too_old = self
+                .retention
+                .max_age()
+                .is_some_and(|age| version.created() < now - age);
+            if too_many || too_old {
+                self.delete(dal, &version).await?;
+                removed.push(version);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Content of the version, the metadata describes it as the file it was
+    pub async fn read(
+        &self,
+        version: &FileVersion,
+        range: Option<Range<u64>>,
+    ) -> Result<FileRead, StorageError> {
+        let read = self
+            .backend
+            .read(&Self::content_path(version.id()), range)
+            .await?;
+        Ok(FileRead {
+            metadata: EntryMetadata::file(
+                version.path().clone(),
+                version.size(),
+                version.created(),
+            ),
+            ..read
+        })
+    }
+
+    pub async fn delete<D: Dal>(&self, dal: &D, version: &FileVersion) -> Result<(), StorageError> {
+        dal.file_versions().delete(version.id()).await?;
+        self.delete_content(version.id()).await
+    }
+
+    async fn delete_content(&self, id: Id) -> Result<(), StorageError> {
+        match self.backend.delete(&Self::content_path(id)).await {
+            Ok(()) | Err(StorageError::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::storage_backend::{collect_stream, once_stream};
+    use crate::test::*;
+    use crate::utc;
+    use crate::utils::time::Time;
+    use bytes::Bytes;
+    use chrono::Duration;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    use test_context::TestContext;
+
+    fn path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    async fn record_and_write(ctx: &TestContext, source: &Source, content: &'static str) {
+        let versions = source.versions().unwrap();
+        versions
+            .record(
+                ctx.dal(),
+                source,
+                &path("a.txt"),
+                Id::from_uuid(uuid::Uuid::new_v4()),
+                None,
+                ctx.time().now(),
+            )
+            .await
+            .unwrap();
+        source
+            .backend()
+            .write(&path("a.txt"), once_stream(Bytes::from(content)))
+            .await
+            .unwrap();
+        versions
+            .prune(ctx.dal(), source, &path("a.txt"), ctx.time().now())
+            .await
+            .unwrap();
+    }
+
+    async fn contents(ctx: &TestContext, source: &Source) -> Vec<Bytes> {
+        let versions = source.versions().unwrap();
+        let mut contents = Vec::new();
+        let list = ctx
+            .dal()
+            .file_versions()
+            .list(source.id(), &path("a.txt"))
+            .await
+            .unwrap();
+        for version in list {
+            let read = versions.read(&version, None).await.unwrap();
+            contents.push(collect_stream(read.stream).await.unwrap());
+        }
+        contents
+    }
+
+    #[test]
+    fn keeps_newest_versions_by_count() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .versioned_source("docs", dir! {}, RetentionConfig::new(Some(2), None))
+                .await;
+
+            // act
+            for content in ["one", "two", "three", "four"] {
+                record_and_write(&ctx, &source, content).await;
+            }
+
+            // assert
+            assert_eq!(contents(&ctx, &source).await, vec!["three", "two"]);
+        })
+    }
+
+    #[test]
+    fn drops_old_versions() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .versioned_source(
+                    "docs",
+                    dir! {},
+                    RetentionConfig::new(None, Some(Duration::days(7))),
+                )
+                .await;
+            ctx.time().set(utc!(2024, 1, 1));
+            record_and_write(&ctx, &source, "one").await;
+            ctx.time().set(utc!(2024, 1, 5));
+            record_and_write(&ctx, &source, "two").await;
+            ctx.time().set(utc!(2024, 1, 6));
+            record_and_write(&ctx, &source, "three").await;
+
+            // act
+            ctx.time().set(utc!(2024, 1, 10));
+            let removed = source
+                .versions()
+                .unwrap()
+                .prune(ctx.dal(), &source, &path("a.txt"), ctx.time().now())
+                .await
+                .unwrap();
+            let kept = contents(&ctx, &source).await;
+            ctx.time().set(utc!(2024, 2, 1));
+            source
+                .versions()
+                .unwrap()
+                .prune(ctx.dal(), &source, &path("a.txt"), ctx.time().now())
+                .await
+                .unwrap();
+
+            // assert
+            assert_eq!(removed.len(), 0);
+            assert_eq!(kept, vec!["two", "one"]);
+            assert_eq!(contents(&ctx, &source).await, Vec::<Bytes>::new());
+        })
+    }
+
+    #[test]
+    fn records_only_existing_files() {
+        test(|ctx| async move {
+            // arrange
+            ctx.time().set(utc!(2024, 1, 1));
+            let source = ctx
+                .versioned_source(
+                    "docs",
+                    dir! { "a.txt" => "seeded", "dir" => dir! {} },
+                    RetentionConfig::default(),
+                )
+                .await;
+            let versions = source.versions().unwrap();
+            let id = || Id::from_uuid(uuid::Uuid::new_v4());
+            let author = Some(Id::from_u128(1));
+
+            // act
+            ctx.time().set(utc!(2024, 1, 2));
+            let seeded = versions
+                .record(
+                    ctx.dal(),
+                    &source,
+                    &path("a.txt"),
+                    id(),
+                    author,
+                    ctx.time().now(),
+                )
+                .await
+                .unwrap();
+            let missing = versions
+                .record(
+                    ctx.dal(),
+                    &source,
+                    &path("b.txt"),
+                    id(),
+                    author,
+                    ctx.time().now(),
+                )
+                .await
+                .unwrap();
+            let dir = versions
+                .record(
+                    ctx.dal(),
+                    &source,
+                    &path("dir"),
+                    id(),
+                    author,
+                    ctx.time().now(),
+                )
+                .await
+                .unwrap();
+
+            // assert
+            let seeded = seeded.unwrap();
+            assert_eq!(seeded.replaced_by(), author);
+            assert_eq!(seeded.created(), utc!(2024, 1, 2));
+            assert_eq!(seeded.size(), 6);
+            assert_eq!(missing, None);
+            assert_eq!(dir, None);
+            assert_eq!(contents(&ctx, &source).await, vec!["seeded"]);
+        })
+    }
+}
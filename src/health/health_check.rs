@@ -0,0 +1,114 @@
+use std::{future::Future, time::Instant};
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+/// Outcome of one dependency check of the readiness probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    name: String,
+    status: CheckStatus,
+    duration: Duration,
+    /// Why it failed, or what was found
+    message: Option<String>,
+}
+
+impl HealthCheck {
+    /// Runs the check, which fails with its message or if it takes longer than `timeout`
+    pub async fn run<F>(name: impl Into<String>, timeout: Duration, check: F) -> Self
+    where
+        F: Future<Output = Result<Option<String>, String>>,
+    {
+        let start = Instant::now();
+        let result = tokio::time::timeout(timeout.to_std().unwrap_or_default(), check)
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {}ms", timeout.num_milliseconds())));
+        let duration = Duration::from_std(start.elapsed()).unwrap_or(Duration::MAX);
+        let name = name.into();
+        match result {
+            Ok(message) => Self {
+                name,
+                status: CheckStatus::Pass,
+                duration,
+                message,
+            },
+            Err(message) => {
+                tracing::warn!(check = %name, "health check failed: {}", message);
+                Self {
+                    name,
+                    status: CheckStatus::Fail,
+                    duration,
+                    message: Some(message),
+                }
+            }
+        }
+    }
+
+    pub fn failed(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            duration: Duration::zero(),
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> CheckStatus {
+        self.status
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[tokio::test]
+    async fn reports_outcome_and_timeout() {
+        let passed = HealthCheck::run("a", Duration::seconds(1), async {
+            Ok(Some("fine".to_owned()))
+        })
+        .await;
+        let failed =
+            HealthCheck::run("b", Duration::seconds(1), async { Err("down".to_owned()) }).await;
+        let slow = HealthCheck::run("c", Duration::milliseconds(10), async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok(None)
+        })
+        .await;
+
+        assert_eq!(
+            (passed.status(), passed.message()),
+            (CheckStatus::Pass, Some("fine"))
+        );
+        assert_eq!(
+            (failed.status(), failed.message()),
+            (CheckStatus::Fail, Some("down"))
+        );
+        assert_eq!(
+            (slow.status(), slow.message()),
+            (CheckStatus::Fail, Some("timed out after 10ms"))
+        );
+        assert!(slow.duration() < Duration::seconds(5));
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Whether the server still wants traffic. Flipped when a graceful shutdown begins,
+/// so load balancers stop sending requests while the open ones finish.
+/// Clones share the state.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn begin_shutdown(&self) {
+        if !self.0.swap(true, Ordering::SeqCst) {
+            tracing::info!("shutting down, no longer ready");
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Listens for SIGTERM and SIGINT right away, the returned future begins the shutdown
+    /// on the first of them. Must be called within the runtime.
+    #[cfg(unix)]
+    pub fn on_terminate(&self) -> std::io::Result<impl std::future::Future<Output = ()> + 'static> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let readiness = self.clone();
+        Ok(async move {
+            tokio::select! {
+                _ = terminate.recv() => {}
+                _ = interrupt.recv() => {}
+            }
+            readiness.begin_shutdown();
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn terminate_signal_begins_shutdown() {
+        // arrange
+        let readiness = Readiness::default();
+        let terminated = readiness.on_terminate().unwrap();
+
+        // act
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), terminated)
+            .await
+            .unwrap();
+
+        // assert
+        assert!(status.success());
+        assert!(readiness.is_shutting_down());
+    }
+}
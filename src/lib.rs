@@ -1,7 +1,14 @@
+pub mod audit;
 pub mod auth;
 pub mod config;
 pub mod dal;
 pub mod fs;
+pub mod health;
+pub mod logging;
+pub mod metrics;
+pub mod rate_limit;
+pub mod telemetry;
+pub mod tls;
 pub mod ui;
 pub mod utils;
 pub mod web;
@@ -0,0 +1,4 @@
+pub mod log_filter;
+pub mod log_layer;
+pub mod redacted_headers;
+pub mod rolling_file;
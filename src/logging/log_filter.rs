@@ -0,0 +1,99 @@
+use std::sync::{Arc, RwLock};
+
+use tracing::{subscriber::Interest, Metadata};
+use tracing_subscriber::{
+    filter::{ParseError, Targets},
+    layer::{Context, Filter},
+};
+
+/// Filter of the log output which can be replaced while the server runs.
+/// Clones share the directives, so a change applies to the layers of every worker.
+#[derive(Debug, Clone)]
+pub struct LogFilter(Arc<RwLock<Directives>>);
+
+#[derive(Debug)]
+struct Directives {
+    text: String,
+    targets: Targets,
+}
+
+impl LogFilter {
+    pub fn new(directives: &str) -> Result<Self, ParseError> {
+        Ok(Self(Arc::new(RwLock::new(Directives::parse(directives)?))))
+    }
+
+    pub fn directives(&self) -> String {
+        self.0.read().unwrap().text.clone()
+    }
+
+    /// Keeps the current directives if the new ones are invalid
+    pub fn set(&self, directives: &str) -> Result<(), ParseError> {
+        let directives = Directives::parse(directives)?;
+        *self.0.write().unwrap() = directives;
+        Ok(())
+    }
+}
+
+impl Directives {
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        let text = text.trim().to_owned();
+        let targets = text.parse()?;
+        Ok(Self { text, targets })
+    }
+}
+
+impl<S> Filter<S> for LogFilter {
+    fn enabled(&self, meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        self.0
+            .read()
+            .unwrap()
+            .targets
+            .would_enable(meta.target(), meta.level())
+    }
+
+    /// Never cached, the answer changes with the directives
+    fn callsite_enabled(&self, _meta: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use tracing::Level;
+
+    fn would_enable(filter: &LogFilter, target: &str, level: Level) -> bool {
+        filter
+            .0
+            .read()
+            .unwrap()
+            .targets
+            .would_enable(target, &level)
+    }
+
+    #[test]
+    fn replaces_directives() {
+        let filter = LogFilter::new("info,sqlx=warn").unwrap();
+        let clone = filter.clone();
+
+        assert!(would_enable(&filter, "rusty_http_fs", Level::INFO));
+        assert!(!would_enable(&filter, "sqlx::query", Level::INFO));
+
+        clone.set(" warn,rusty_http_fs::web=debug ").unwrap();
+
+        assert_eq!(filter.directives(), "warn,rusty_http_fs::web=debug");
+        assert!(!would_enable(&filter, "rusty_http_fs", Level::INFO));
+        assert!(would_enable(&filter, "rusty_http_fs::web", Level::DEBUG));
+    }
+
+    #[test]
+    fn keeps_directives_if_invalid() {
+        let filter = LogFilter::new("info").unwrap();
+
+        assert!(filter.set("sqlx=loud").is_err());
+
+        assert_eq!(filter.directives(), "info");
+    }
+}
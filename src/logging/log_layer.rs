@@ -0,0 +1,130 @@
+use std::io;
+
+use tracing::Subscriber;
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    registry::LookupSpan,
+    Layer,
+};
+
+use crate::config::app_config::{LogFormat, LogOutputConfig, LoggingConfig};
+
+use super::{log_filter::LogFilter, rolling_file::RollingFile};
+
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Writes the events the filter lets through to the configured output.
+/// Fails if the log directory can't be created.
+pub fn from_config<S>(config: &LoggingConfig, filter: LogFilter) -> io::Result<BoxedLayer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    Ok(match config.output() {
+        LogOutputConfig::Stdout => log_layer(config.format(), filter, io::stdout, true),
+        LogOutputConfig::File {
+            directory,
+            prefix,
+            rotation,
+            max_files,
+        } => {
+            let file = RollingFile::new(directory.clone(), prefix.clone(), *rotation, *max_files)?;
+            log_layer(config.format(), filter, file, false)
+        }
+    })
+}
+
+/// Colors are only for terminals, JSON never has them
+pub fn log_layer<S, W>(format: LogFormat, filter: LogFilter, writer: W, ansi: bool) -> BoxedLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Pretty => layer.with_ansi(ansi).with_filter(filter).boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_filter(filter)
+            .boxed(),
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        }
+    }
+
+    fn log_with(format: LogFormat, filter: &LogFilter, log: impl FnOnce()) -> Vec<String> {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(log_layer(
+            format,
+            filter.clone(),
+            move || writer.clone(),
+            false,
+        ));
+        tracing::subscriber::with_default(subscriber, log);
+        buffer.lines()
+    }
+
+    #[test]
+    fn writes_json_lines_with_spans() {
+        let filter = LogFilter::new("info").unwrap();
+
+        let lines = log_with(LogFormat::Json, &filter, || {
+            let _span = tracing::info_span!("req", path = "/a").entered();
+            tracing::info!(size = 3, "written");
+            tracing::debug!("hidden");
+        });
+
+        assert_eq!(lines.len(), 1);
+        let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "written");
+        assert_eq!(line["fields"]["size"], 3);
+        assert_eq!(line["span"]["path"], "/a");
+    }
+
+    #[test]
+    fn applies_changed_filter() {
+        let filter = LogFilter::new("warn").unwrap();
+
+        let lines = log_with(LogFormat::Pretty, &filter, || {
+            for attempt in 0..2 {
+                tracing::info!(attempt = attempt, "logged");
+                filter.set("info").unwrap();
+            }
+        });
+
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("INFO") && lines[0].contains("attempt=1"));
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::Debug;
+
+use actix_http::header::{self, HeaderMap, HeaderName};
+
+use crate::utils::secret::REDACTED;
+
+/// Carry credentials, only their presence is logged
+fn is_sensitive(name: &HeaderName) -> bool {
+    [
+        header::AUTHORIZATION,
+        header::PROXY_AUTHORIZATION,
+        header::COOKIE,
+        header::SET_COOKIE,
+    ]
+    .contains(name)
+}
+
+/// Headers for a log field, values of credentials are replaced
+pub struct RedactedHeaders<'a>(pub &'a HeaderMap);
+
+impl Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value = if is_sensitive(name) {
+                    REDACTED
+                } else {
+                    value.to_str().unwrap_or("[binary]")
+                };
+                (name.as_str(), value)
+            }))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use actix_http::header::HeaderValue;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn redacts_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc.def"),
+        );
+        headers.insert(header::COOKIE, HeaderValue::from_static("session=xyz"));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/plain"));
+
+        let text = format!("{:?}", RedactedHeaders(&headers));
+
+        assert!(
+            text.contains(r#""authorization": "[redacted]""#),
+            "{}",
+            text
+        );
+        assert!(text.contains(r#""cookie": "[redacted]""#), "{}", text);
+        assert!(text.contains(r#""accept": "text/plain""#), "{}", text);
+        assert!(
+            !text.contains("abc.def") && !text.contains("xyz"),
+            "{}",
+            text
+        );
+    }
+}
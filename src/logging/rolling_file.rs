@@ -0,0 +1,179 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::app_config::LogRotation;
+
+/// Log file which starts over every period, see [crate::config::app_config::LogOutputConfig::File]
+#[derive(Debug, Clone)]
+pub struct RollingFile(Arc<Mutex<State>>);
+
+#[derive(Debug)]
+struct State {
+    directory: PathBuf,
+    prefix: String,
+    rotation: LogRotation,
+    max_files: Option<usize>,
+    /// Name and handle of the file of the current period
+    current: Option<(String, File)>,
+}
+
+impl RollingFile {
+    pub fn new(
+        directory: PathBuf,
+        prefix: String,
+        rotation: LogRotation,
+        max_files: Option<usize>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        Ok(Self(Arc::new(Mutex::new(State {
+            directory,
+            prefix,
+            rotation,
+            max_files,
+            current: None,
+        }))))
+    }
+
+    fn write_at(&self, now: DateTime<Utc>, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.0.lock().unwrap();
+        let name = state.file_name(now);
+        if state
+            .current
+            .as_ref()
+            .is_none_or(|(current, _)| *current != name)
+        {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(state.directory.join(&name))?;
+            state.current = Some((name, file));
+            state.prune()?;
+        }
+        let (_, file) = state.current.as_mut().unwrap();
+        file.write_all(buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl State {
+    fn file_name(&self, now: DateTime<Utc>) -> String {
+        let period = match self.rotation {
+            LogRotation::Never => return format!("{}.log", self.prefix),
+            LogRotation::Hourly => now.format("%Y-%m-%d-%H"),
+            LogRotation::Daily => now.format("%Y-%m-%d"),
+        };
+        format!("{}.{}.log", self.prefix, period)
+    }
+
+    /// Deletes the oldest files of the prefix beyond `max_files`, periods sort by name
+    fn prune(&self) -> io::Result<()> {
+        let Some(max_files) = self.max_files else {
+            return Ok(());
+        };
+        let prefix = format!("{}.", self.prefix);
+        let mut names: Vec<_> = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with(&prefix) && name.ends_with(".log"))
+            .collect();
+        names.sort();
+        let excess = names.len().saturating_sub(max_files.max(1));
+        for name in &names[..excess] {
+            fs::remove_file(self.directory.join(name))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct RollingFileWriter<'a>(&'a RollingFile);
+
+impl Write for RollingFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_at(Utc::now(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 .0.lock().unwrap().current {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFile {
+    type Writer = RollingFileWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RollingFileWriter(self)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn names(directory: &std::path::Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_and_keeps_the_newest_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = RollingFile::new(
+            directory.path().to_owned(),
+            "server".to_owned(),
+            LogRotation::Daily,
+            Some(2),
+        )
+        .unwrap();
+
+        file.write_at(utc!(2024, 3, 1, 10, 0, 0), b"a\n").unwrap();
+        file.write_at(utc!(2024, 3, 1, 23, 0, 0), b"b\n").unwrap();
+        file.write_at(utc!(2024, 3, 2, 0, 0, 1), b"c\n").unwrap();
+        file.write_at(utc!(2024, 3, 3, 8, 0, 0), b"d\n").unwrap();
+
+        assert_eq!(
+            names(directory.path()),
+            vec!["server.2024-03-02.log", "server.2024-03-03.log"]
+        );
+        let read = |name: &str| fs::read_to_string(directory.path().join(name)).unwrap();
+        assert_eq!(read("server.2024-03-02.log"), "c\n");
+        assert_eq!(read("server.2024-03-03.log"), "d\n");
+    }
+
+    #[test]
+    fn appends_to_a_single_file_if_it_never_rotates() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("server.log"), "old\n").unwrap();
+        let file = RollingFile::new(
+            directory.path().to_owned(),
+            "server".to_owned(),
+            LogRotation::Never,
+            Some(1),
+        )
+        .unwrap();
+
+        file.write_at(utc!(2024, 3, 1), b"a\n").unwrap();
+        file.write_at(utc!(2024, 3, 2), b"b\n").unwrap();
+
+        assert_eq!(names(directory.path()), vec!["server.log"]);
+        assert_eq!(
+            fs::read_to_string(directory.path().join("server.log")).unwrap(),
+            "old\na\nb\n"
+        );
+    }
+}
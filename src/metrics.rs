@@ -0,0 +1,5 @@
+pub mod app_metrics;
+pub mod histogram;
+pub mod pool_stats;
+pub mod text_encoder;
+pub mod transfer_guard;
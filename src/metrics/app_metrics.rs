@@ -0,0 +1,258 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use super::{
+    histogram::{Histogram, OPERATION_BUCKETS, REQUEST_BUCKETS},
+    pool_stats::PoolStats,
+    text_encoder::TextEncoder,
+    transfer_guard::TransferGuard,
+};
+
+/// Route of requests which matched no route, so unknown paths don't add label values
+pub const OTHER_ROUTE: &str = "other";
+
+/// Counters, gauges and histograms of the server, clones share them.
+/// Labels only take values from bounded sets: route patterns, sources and fixed reasons.
+#[derive(Debug, Clone, Default)]
+pub struct AppMetrics(Arc<Registry>);
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// By route, method and status
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+    /// By route and method
+    received_bytes: Mutex<BTreeMap<(String, String), u64>>,
+    sent_bytes: Mutex<BTreeMap<(String, String), u64>>,
+    uploads: Arc<AtomicI64>,
+    downloads: Arc<AtomicI64>,
+    /// By method
+    auth_successes: Mutex<BTreeMap<&'static str, u64>>,
+    /// By method and reason
+    auth_failures: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    /// By source, operation and result
+    operations: Mutex<BTreeMap<(String, &'static str, &'static str), Histogram>>,
+}
+
+impl AppMetrics {
+    pub fn request_finished(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        self.0
+            .requests
+            .lock()
+            .unwrap()
+            .entry((route.to_owned(), method.to_owned(), status))
+            .or_insert_with(|| Histogram::new(REQUEST_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Bytes of request bodies as they were received
+    pub fn received(&self, route: &str, method: &str, bytes: u64) {
+        add(
+            &self.0.received_bytes,
+            (route.to_owned(), method.to_owned()),
+            bytes,
+        );
+    }
+
+    /// Bytes of response bodies as they were sent, after compression
+    pub fn sent(&self, route: &str, method: &str, bytes: u64) {
+        add(
+            &self.0.sent_bytes,
+            (route.to_owned(), method.to_owned()),
+            bytes,
+        );
+    }
+
+    pub fn upload_started(&self) -> TransferGuard {
+        TransferGuard::start(&self.0.uploads)
+    }
+
+    pub fn download_started(&self) -> TransferGuard {
+        TransferGuard::start(&self.0.downloads)
+    }
+
+    pub fn auth_succeeded(&self, method: &'static str) {
+        add(&self.0.auth_successes, method, 1);
+    }
+
+    pub fn auth_failed(&self, method: &'static str, reason: &'static str) {
+        add(&self.0.auth_failures, (method, reason), 1);
+    }
+
+    /// Latency of a call to the storage backend of a source
+    pub fn operation_finished(
+        &self,
+        source: &str,
+        operation: &'static str,
+        succeeded: bool,
+        elapsed: Duration,
+    ) {
+        let result = if succeeded { "ok" } else { "error" };
+        self.0
+            .operations
+            .lock()
+            .unwrap()
+            .entry((source.to_owned(), operation, result))
+            .or_insert_with(|| Histogram::new(OPERATION_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Everything in the Prometheus text format. Pool stats are left out if there is no pool.
+    pub fn render(&self, pool: Option<PoolStats>) -> String {
+        let mut out = TextEncoder::new();
+
+        out.family(
+            "rhfs_http_request_duration_seconds",
+            "histogram",
+            "Time to answer requests by route pattern, method and status",
+        );
+        for ((route, method, status), histogram) in self.0.requests.lock().unwrap().iter() {
+            let status = status.to_string();
+            out.histogram(
+                "rhfs_http_request_duration_seconds",
+                &[("route", route), ("method", method), ("status", &status)],
+                histogram,
+            );
+        }
+
+        for (name, help, bytes) in [
+            (
+                "rhfs_http_received_bytes_total",
+                "Bytes of request bodies by route pattern and method",
+                &self.0.received_bytes,
+            ),
+            (
+                "rhfs_http_sent_bytes_total",
+                "Bytes of response bodies by route pattern and method",
+                &self.0.sent_bytes,
+            ),
+        ] {
+            out.family(name, "counter", help);
+            for ((route, method), bytes) in bytes.lock().unwrap().iter() {
+                out.sample(name, &[("route", route), ("method", method)], bytes);
+            }
+        }
+
+        out.family(
+            "rhfs_active_transfers",
+            "gauge",
+            "File uploads and downloads in progress",
+        );
+        for (direction, active) in [("upload", &self.0.uploads), ("download", &self.0.downloads)] {
+            out.sample(
+                "rhfs_active_transfers",
+                &[("direction", direction)],
+                active.load(Ordering::Relaxed),
+            );
+        }
+
+        out.family(
+            "rhfs_auth_successes_total",
+            "counter",
+            "Successful authentications by method",
+        );
+        for (method, count) in self.0.auth_successes.lock().unwrap().iter() {
+            out.sample("rhfs_auth_successes_total", &[("method", method)], count);
+        }
+        out.family(
+            "rhfs_auth_failures_total",
+            "counter",
+            "Failed authentications by method and reason",
+        );
+        for ((method, reason), count) in self.0.auth_failures.lock().unwrap().iter() {
+            out.sample(
+                "rhfs_auth_failures_total",
+                &[("method", method), ("reason", reason)],
+                count,
+            );
+        }
+
+        out.family(
+            "rhfs_fs_operation_duration_seconds",
+            "histogram",
+            "Time of storage operations by source, operation and result",
+        );
+        for ((source, operation, result), histogram) in self.0.operations.lock().unwrap().iter() {
+            out.histogram(
+                "rhfs_fs_operation_duration_seconds",
+                &[
+                    ("source", source),
+                    ("operation", operation),
+                    ("result", result),
+                ],
+                histogram,
+            );
+        }
+
+        if let Some(pool) = pool {
+            out.family(
+                "rhfs_db_pool_connections",
+                "gauge",
+                "Open database connections by state",
+            );
+            for (state, count) in [
+                ("idle", pool.idle),
+                ("active", pool.open.saturating_sub(pool.idle)),
+            ] {
+                out.sample("rhfs_db_pool_connections", &[("state", state)], count);
+            }
+            out.family(
+                "rhfs_db_pool_max_connections",
+                "gauge",
+                "Size limit of the database pool",
+            );
+            out.sample("rhfs_db_pool_max_connections", &[], pool.max);
+        }
+
+        out.finish()
+    }
+}
+
+fn add<K: Ord>(counters: &Mutex<BTreeMap<K, u64>>, key: K, value: u64) {
+    *counters.lock().unwrap().entry(key).or_default() += value;
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn transfers_are_active_while_guarded() {
+        let metrics = AppMetrics::default();
+
+        let upload = metrics.upload_started();
+        let _download = metrics.download_started();
+        let during = metrics.render(None);
+        drop(upload);
+        let after = metrics.render(None);
+
+        assert!(during.contains("rhfs_active_transfers{direction=\"upload\"} 1\n"));
+        assert!(after.contains("rhfs_active_transfers{direction=\"upload\"} 0\n"));
+        assert!(after.contains("rhfs_active_transfers{direction=\"download\"} 1\n"));
+    }
+
+    #[test]
+    fn pool_stats_only_with_a_pool() {
+        let metrics = AppMetrics::default();
+        let pool = PoolStats {
+            max: 10,
+            open: 4,
+            idle: 1,
+        };
+
+        let without = metrics.render(None);
+        let with = metrics.render(Some(pool));
+
+        assert!(!without.contains("rhfs_db_pool"));
+        assert!(with.contains("rhfs_db_pool_connections{state=\"idle\"} 1\n"));
+        assert!(with.contains("rhfs_db_pool_connections{state=\"active\"} 3\n"));
+        assert!(with.contains("rhfs_db_pool_max_connections 10\n"));
+    }
+}
@@ -0,0 +1,82 @@
+/// Request latencies in seconds
+pub const REQUEST_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Storage operation latencies in seconds, most of them are local
+pub const OPERATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+/// Counts of observations under fixed upper bounds, with their sum
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per bound, not cumulative, the last one counts what is above every bound
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Upper bounds with the cumulative count of observations, `None` is `+Inf`
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<f64>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .copied()
+            .map(Some)
+            .chain([None])
+            .zip(self.counts.iter().scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            }))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn cumulative_buckets() {
+        let mut histogram = Histogram::new(&[0.25, 1.0]);
+
+        for value in [0.125, 0.25, 0.5, 3.0] {
+            histogram.observe(value);
+        }
+
+        assert_eq!(
+            histogram.buckets().collect::<Vec<_>>(),
+            vec![(Some(0.25), 2), (Some(1.0), 3), (None, 4)]
+        );
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.sum(), 3.875);
+    }
+}
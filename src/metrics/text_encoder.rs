@@ -0,0 +1,101 @@
+use std::fmt::{Display, Write};
+
+use super::histogram::Histogram;
+
+/// Writes metrics in the Prometheus text exposition format
+#[derive(Debug, Default)]
+pub struct TextEncoder {
+    out: String,
+}
+
+impl TextEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a metric family, its samples have to follow
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.out, "# HELP {} {}", name, help).unwrap();
+        writeln!(self.out, "# TYPE {} {}", name, kind).unwrap();
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.out.push_str(name);
+        write_labels(&mut self.out, labels, None);
+        writeln!(self.out, " {}", value).unwrap();
+    }
+
+    /// `_bucket`, `_sum` and `_count` samples of the histogram
+    pub fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        for (bound, count) in histogram.buckets() {
+            let le = bound.map_or_else(|| "+Inf".to_owned(), |b| b.to_string());
+            write!(self.out, "{}_bucket", name).unwrap();
+            write_labels(&mut self.out, labels, Some(&le));
+            writeln!(self.out, " {}", count).unwrap();
+        }
+        self.sample(&format!("{}_sum", name), labels, histogram.sum());
+        self.sample(&format!("{}_count", name), labels, histogram.count());
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn write_labels(out: &mut String, labels: &[(&str, &str)], le: Option<&str>) {
+    if labels.is_empty() && le.is_none() {
+        return;
+    }
+    out.push('{');
+    let le = le.map(|le| ("le", le));
+    for (i, (name, value)) in labels.iter().copied().chain(le).enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, "{}=\"{}\"", name, escape(value)).unwrap();
+    }
+    out.push('}');
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn samples_and_histograms() {
+        let mut histogram = Histogram::new(&[0.5]);
+        histogram.observe(0.25);
+        let mut encoder = TextEncoder::new();
+
+        encoder.family("requests_total", "counter", "Requests");
+        encoder.sample("requests_total", &[("path", "/a\"b\\c\nd")], 3);
+        encoder.sample("requests_total", &[], 1);
+        encoder.family("latency_seconds", "histogram", "Latency");
+        encoder.histogram("latency_seconds", &[("method", "GET")], &histogram);
+
+        assert_str_eq!(
+            encoder.finish(),
+            concat!(
+                "# HELP requests_total Requests\n",
+                "# TYPE requests_total counter\n",
+                "requests_total{path=\"/a\\\"b\\\\c\\nd\"} 3\n",
+                "requests_total 1\n",
+                "# HELP latency_seconds Latency\n",
+                "# TYPE latency_seconds histogram\n",
+                "latency_seconds_bucket{method=\"GET\",le=\"0.5\"} 1\n",
+                "latency_seconds_bucket{method=\"GET\",le=\"+Inf\"} 1\n",
+                "latency_seconds_sum{method=\"GET\"} 0.25\n",
+                "latency_seconds_count{method=\"GET\"} 1\n",
+            )
+        );
+    }
+}
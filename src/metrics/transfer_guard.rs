@@ -0,0 +1,21 @@
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
+
+/// Counts a transfer as active until it's dropped
+#[derive(Debug)]
+pub struct TransferGuard(Arc<AtomicI64>);
+
+impl TransferGuard {
+    pub(super) fn start(active: &Arc<AtomicI64>) -> Self {
+        active.fetch_add(1, Ordering::Relaxed);
+        Self(active.clone())
+    }
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod dal_rate_limit_store;
+pub mod failure_counter;
+pub mod memory_rate_limit_store;
+pub mod rate_limit_entry;
+pub mod rate_limit_key;
+pub mod rate_limit_rules;
+pub mod rate_limit_store;
+pub mod token_bucket;
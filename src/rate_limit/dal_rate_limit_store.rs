@@ -0,0 +1,158 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::dal::{dal_error::DalError, rate_limits_dal::RateLimitsDal, Dal};
+
+use super::{
+    rate_limit_entry::RateLimitEntry, rate_limit_key::RateLimitKey,
+    rate_limit_store::RateLimitStore,
+};
+
+const MAX_ATTEMPTS: usize = 16;
+const PURGE_EVERY: u64 = 1024;
+
+/// Limiter state shared through the DAL, so all instances of the service see the same limits
+#[derive(Clone)]
+pub struct DalRateLimitStore<D> {
+    dal: D,
+    updates: Arc<AtomicU64>,
+}
+
+impl<D> DalRateLimitStore<D> {
+    pub fn new(dal: D) -> Self {
+        Self {
+            dal,
+            updates: Default::default(),
+        }
+    }
+}
+
+impl<D: Dal> RateLimitStore for DalRateLimitStore<D> {
+    async fn update<R>(
+        &self,
+        key: &RateLimitKey<'_>,
+        now: DateTime<Utc>,
+        f: impl Fn(Option<RateLimitEntry>) -> (Option<RateLimitEntry>, R),
+    ) -> Result<R, DalError> {
+        let dal = self.dal.rate_limits();
+        if self.updates.fetch_add(1, Ordering::Relaxed) % PURGE_EVERY == PURGE_EVERY - 1 {
+            dal.purge_expired(now).await?;
+        }
+
+        let key = key.to_string();
+        for _ in 0..MAX_ATTEMPTS {
+            let current = dal.get(&key).await?;
+            let version = current.as_ref().map(|(_, v)| *v);
+            let entry = current.map(|(e, _)| e).filter(|e| e.expires_at > now);
+            let (entry, result) = f(entry);
+            if dal.compare_and_set(&key, version, entry).await? {
+                return Ok(result);
+            }
+        }
+        Err(DalError::Unexpected(format!(
+            "too much contention on rate limit key '{}'",
+            key
+        )))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::app_config::TokenBucketConfig,
+        dal::memory::MemoryDal,
+        rate_limit::{rate_limit_entry::RateLimitState, token_bucket::TokenBucket},
+        test::*,
+        utc,
+    };
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn instances_share_state() {
+        test(|_ctx| async move {
+            // arrange
+            let dal = MemoryDal::default();
+            let first = DalRateLimitStore::new(dal.clone());
+            let second = DalRateLimitStore::new(dal.clone());
+            let config = TokenBucketConfig::new(2, 1.0);
+            let key = RateLimitKey::LoginUsername("user");
+            let now = utc!(2024);
+
+            // act
+            let a = first.acquire(&key, &config, now).await.unwrap();
+            let b = second.acquire(&key, &config, now).await.unwrap();
+            let c = first.acquire(&key, &config, now).await.unwrap();
+
+            // assert
+            assert_eq!((a.allowed, b.allowed, c.allowed), (true, true, false));
+            assert_eq!((a.remaining, b.remaining), (1, 0));
+        });
+    }
+
+    #[test]
+    fn reset_removes_entry() {
+        test(|_ctx| async move {
+            // arrange
+            let dal = MemoryDal::default();
+            let store = DalRateLimitStore::new(dal.clone());
+            let key = RateLimitKey::LoginUsername("user");
+            let now = utc!(2024);
+            store
+                .acquire(&key, &TokenBucketConfig::new(1, 1.0), now)
+                .await
+                .unwrap();
+
+            // act
+            store.reset(&key, now).await.unwrap();
+
+            // assert
+            let entry = dal.rate_limits().get(&key.to_string()).await.unwrap();
+            assert_eq!(entry, None);
+        });
+    }
+
+    #[test]
+    fn versions_are_not_reused_after_removal() {
+        test(|_ctx| async move {
+            // arrange
+            let dal = MemoryDal::default();
+            let rate_limits = dal.rate_limits();
+            let entry = RateLimitEntry {
+                state: RateLimitState::Bucket(TokenBucket::full(
+                    &TokenBucketConfig::new(1, 1.0),
+                    utc!(2024),
+                )),
+                expires_at: utc!(2025),
+            };
+            rate_limits
+                .compare_and_set("key", None, Some(entry))
+                .await
+                .unwrap();
+            let (_, stale) = rate_limits.get("key").await.unwrap().unwrap();
+
+            // act
+            rate_limits
+                .compare_and_set("key", Some(stale), None)
+                .await
+                .unwrap();
+            rate_limits
+                .compare_and_set("key", None, Some(entry))
+                .await
+                .unwrap();
+            let stale_write = rate_limits
+                .compare_and_set("key", Some(stale), None)
+                .await
+                .unwrap();
+
+            // assert
+            assert!(!stale_write);
+            assert!(rate_limits.get("key").await.unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::app_config::LockoutConfig;
+
+/// Consecutive failures with progressive lockout.
+///
+/// Once `max_failures` is reached each further failure doubles the lockout,
+/// starting from `base_lockout` and capped by `max_lockout`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FailureCounter {
+    failures: u32,
+    last_failure_at: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl FailureCounter {
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn locked_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.locked_until.filter(|until| *until > now)
+    }
+
+    /// Counter is forgotten after `reset_after` without failures, but not while locked
+    pub fn reset_at(&self, config: &LockoutConfig) -> DateTime<Utc> {
+        let reset_at = self.last_failure_at + config.reset_after();
+        self.locked_until
+            .map_or(reset_at, |until| until.max(reset_at))
+    }
+
+    pub fn record(
+        current: Option<Self>,
+        max_failures: u32,
+        config: &LockoutConfig,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let failures = match current {
+            Some(c) if c.reset_at(config) > now => c.failures + 1,
+            _ => 1,
+        };
+        let locked_until = (failures >= max_failures).then(|| {
+            let exp = (failures - max_failures).min(30);
+            let lockout = config
+                .base_lockout()
+                .checked_mul(1 << exp)
+                .unwrap_or(Duration::MAX)
+                .min(config.max_lockout());
+            now + lockout
+        });
+        Self {
+            failures,
+            last_failure_at: now,
+            locked_until,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn config() -> LockoutConfig {
+        LockoutConfig::new(
+            3,
+            10,
+            Duration::seconds(10),
+            Duration::seconds(60),
+            Duration::minutes(15),
+        )
+    }
+
+    #[test]
+    fn lockout_grows_progressively() {
+        let config = config();
+        let now = utc!(2024);
+
+        let mut counter = None;
+        let mut lockouts = vec![];
+        for _ in 0..6 {
+            let c = FailureCounter::record(counter, 3, &config, now);
+            lockouts.push(c.locked_until(now).map(|u| (u - now).num_seconds()));
+            counter = Some(c);
+        }
+
+        assert_eq!(
+            lockouts,
+            vec![None, None, Some(10), Some(20), Some(40), Some(60)]
+        );
+    }
+
+    #[test]
+    fn counter_resets_after_quiet_period() {
+        let config = config();
+        let now = utc!(2024);
+        let counter = FailureCounter::record(None, 3, &config, now);
+        let counter = FailureCounter::record(Some(counter), 3, &config, now);
+
+        let later = now + Duration::minutes(16);
+        let counter = FailureCounter::record(Some(counter), 3, &config, later);
+
+        assert_eq!(counter.failures(), 1);
+        assert_eq!(counter.locked_until(later), None);
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::dal::dal_error::DalError;
+
+use super::{
+    rate_limit_entry::RateLimitEntry, rate_limit_key::RateLimitKey,
+    rate_limit_store::RateLimitStore,
+};
+
+/// How many updates happen between sweeps of expired entries
+const SWEEP_EVERY: u64 = 1024;
+
+/// Process local limiter state. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct MemoryRateLimitStore(Arc<Mutex<State>>);
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, RateLimitEntry>,
+    updates: u64,
+}
+
+impl MemoryRateLimitStore {
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn update<R>(
+        &self,
+        key: &RateLimitKey<'_>,
+        now: DateTime<Utc>,
+        f: impl Fn(Option<RateLimitEntry>) -> (Option<RateLimitEntry>, R),
+    ) -> Result<R, DalError> {
+        let mut state = self.0.lock().unwrap();
+        state.updates += 1;
+        if state.updates.is_multiple_of(SWEEP_EVERY) {
+            state.entries.retain(|_, e| e.expires_at > now);
+        }
+
+        let key = key.to_string();
+        let current = state
+            .entries
+            .get(&key)
+            .copied()
+            .filter(|e| e.expires_at > now);
+        let (entry, result) = f(current);
+        match entry {
+            Some(entry) => state.entries.insert(key, entry),
+            None => state.entries.remove(&key),
+        };
+        Ok(result)
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{failure_counter::FailureCounter, token_bucket::TokenBucket};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateLimitState {
+    Bucket(TokenBucket),
+    Failures(FailureCounter),
+}
+
+/// Limiter state stored under a [super::rate_limit_key::RateLimitKey]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitEntry {
+    pub state: RateLimitState,
+    /// The entry has no effect after this moment and may be removed
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,24 @@
+use std::{fmt::Display, net::IpAddr};
+
+use crate::utils::id::Id;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey<'a> {
+    Ip { group: &'a str, ip: IpAddr },
+    Principal { group: &'a str, id: Id },
+    LoginUsername(&'a str),
+    LoginIp(IpAddr),
+}
+
+impl Display for RateLimitKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitKey::Ip { group, ip } => write!(f, "ip:{}:{}", group, ip),
+            RateLimitKey::Principal { group, id } => write!(f, "principal:{}:{}", group, id),
+            RateLimitKey::LoginUsername(username) => {
+                write!(f, "login_username:{}", username.to_lowercase())
+            }
+            RateLimitKey::LoginIp(ip) => write!(f, "login_ip:{}", ip),
+        }
+    }
+}
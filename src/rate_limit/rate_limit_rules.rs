@@ -0,0 +1,23 @@
+use std::sync::{Arc, RwLock};
+
+use crate::config::app_config::RateLimitConfig;
+
+/// Route groups of the rate limit middleware which can be replaced while the server runs.
+/// Clones share the groups, buckets of the store outlive a replacement.
+#[derive(Debug, Clone)]
+pub struct RateLimitRules(Arc<RwLock<Arc<RateLimitConfig>>>);
+
+impl RateLimitRules {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// The groups in effect, a request keeps using them when they're replaced
+    pub fn get(&self) -> Arc<RateLimitConfig> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, config: RateLimitConfig) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
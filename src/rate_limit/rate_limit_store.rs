@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::app_config::{LockoutConfig, TokenBucketConfig},
+    dal::dal_error::DalError,
+};
+
+use super::{
+    failure_counter::FailureCounter,
+    rate_limit_entry::{RateLimitEntry, RateLimitState},
+    rate_limit_key::RateLimitKey,
+    token_bucket::{RateLimitDecision, TokenBucket},
+};
+
+#[allow(async_fn_in_trait)]
+pub trait RateLimitStore {
+    /// Atomically replaces the entry stored under `key` with the one returned by `f`.
+    ///
+    /// `f` may be called more than once if there are concurrent updates.
+    async fn update<R>(
+        &self,
+        key: &RateLimitKey<'_>,
+        now: DateTime<Utc>,
+        f: impl Fn(Option<RateLimitEntry>) -> (Option<RateLimitEntry>, R),
+    ) -> Result<R, DalError>;
+
+    async fn acquire(
+        &self,
+        key: &RateLimitKey<'_>,
+        config: &TokenBucketConfig,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitDecision, DalError> {
+        self.update(key, now, |entry| {
+            let mut bucket = match entry.map(|e| e.state) {
+                Some(RateLimitState::Bucket(bucket)) => bucket,
+                _ => TokenBucket::full(config, now),
+            };
+            let decision = bucket.try_take(config, now);
+            let entry = RateLimitEntry {
+                state: RateLimitState::Bucket(bucket),
+                expires_at: bucket.full_at(config),
+            };
+            (Some(entry), decision)
+        })
+        .await
+    }
+
+    /// Returns when the lockout ends if the key is locked out
+    async fn locked_until(
+        &self,
+        key: &RateLimitKey<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, DalError> {
+        self.update(key, now, |entry| match entry.map(|e| e.state) {
+            Some(RateLimitState::Failures(counter)) => (entry, counter.locked_until(now)),
+            _ => (entry, None),
+        })
+        .await
+    }
+
+    async fn record_failure(
+        &self,
+        key: &RateLimitKey<'_>,
+        max_failures: u32,
+        config: &LockoutConfig,
+        now: DateTime<Utc>,
+    ) -> Result<FailureCounter, DalError> {
+        self.update(key, now, |entry| {
+            let current = match entry.map(|e| e.state) {
+                Some(RateLimitState::Failures(counter)) => Some(counter),
+                _ => None,
+            };
+            let counter = FailureCounter::record(current, max_failures, config, now);
+            let entry = RateLimitEntry {
+                state: RateLimitState::Failures(counter),
+                expires_at: counter.reset_at(config),
+            };
+            (Some(entry), counter)
+        })
+        .await
+    }
+
+    async fn reset(&self, key: &RateLimitKey<'_>, now: DateTime<Utc>) -> Result<(), DalError> {
+        self.update(key, now, |_| (None, ())).await
+    }
+}
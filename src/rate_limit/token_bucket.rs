@@ -0,0 +1,129 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::app_config::TokenBucketConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again
+    pub reset_after: Duration,
+    /// Time until the next token is available, set when the request is denied
+    pub retry_after: Option<Duration>,
+}
+
+impl TokenBucket {
+    pub fn full(config: &TokenBucketConfig, now: DateTime<Utc>) -> Self {
+        Self {
+            tokens: config.capacity() as f64,
+            updated_at: now,
+        }
+    }
+
+    pub fn tokens(&self) -> f64 {
+        self.tokens
+    }
+
+    /// Refills the bucket up to `now` and takes one token if there is any
+    pub fn try_take(
+        &mut self,
+        config: &TokenBucketConfig,
+        now: DateTime<Utc>,
+    ) -> RateLimitDecision {
+        self.refill(config, now);
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        RateLimitDecision {
+            allowed,
+            limit: config.capacity(),
+            remaining: self.tokens.floor() as u32,
+            reset_after: time_to(config, config.capacity() as f64 - self.tokens),
+            retry_after: (!allowed).then(|| time_to(config, 1.0 - self.tokens)),
+        }
+    }
+
+    /// Moment after which the bucket is full, so it can be forgotten
+    pub fn full_at(&self, config: &TokenBucketConfig) -> DateTime<Utc> {
+        self.updated_at + time_to(config, config.capacity() as f64 - self.tokens)
+    }
+
+    fn refill(&mut self, config: &TokenBucketConfig, now: DateTime<Utc>) {
+        let elapsed = (now - self.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        self.tokens =
+            (self.tokens + elapsed * config.refill_per_second()).min(config.capacity() as f64);
+        self.updated_at = now.max(self.updated_at);
+    }
+}
+
+fn time_to(config: &TokenBucketConfig, tokens: f64) -> Duration {
+    if tokens <= 0.0 {
+        return Duration::zero();
+    }
+    if config.refill_per_second() <= 0.0 {
+        return Duration::MAX;
+    }
+    Duration::milliseconds((tokens / config.refill_per_second() * 1000.0).ceil() as i64)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::utc;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn takes_until_empty_and_refills() {
+        let config = TokenBucketConfig::new(2, 0.5);
+        let now = utc!(2024);
+        let mut bucket = TokenBucket::full(&config, now);
+
+        let first = bucket.try_take(&config, now);
+        let second = bucket.try_take(&config, now);
+        let third = bucket.try_take(&config, now);
+
+        assert_eq!(
+            (
+                first.allowed,
+                first.remaining,
+                second.allowed,
+                second.remaining
+            ),
+            (true, 1, true, 0)
+        );
+        assert!(!third.allowed);
+        assert_eq!(third.retry_after, Some(Duration::seconds(2)));
+        assert_eq!(third.reset_after, Duration::seconds(4));
+
+        let later = now + Duration::seconds(2);
+        let fourth = bucket.try_take(&config, later);
+        assert!(fourth.allowed);
+        assert_eq!(fourth.remaining, 0);
+    }
+
+    #[test]
+    fn never_exceeds_capacity() {
+        let config = TokenBucketConfig::new(3, 1.0);
+        let now = utc!(2024);
+        let mut bucket = TokenBucket::full(&config, now);
+        bucket.try_take(&config, now);
+
+        let decision = bucket.try_take(&config, now + Duration::hours(1));
+
+        assert_eq!(decision.remaining, 2);
+        assert_eq!(
+            bucket.full_at(&config),
+            now + Duration::hours(1) + Duration::seconds(1)
+        );
+    }
+}
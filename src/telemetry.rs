@@ -0,0 +1,3 @@
+pub mod otlp_exporter;
+pub mod otlp_layer;
+pub mod trace_context;
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use actix_web::http::header;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::config::app_config::OtlpConfig;
+
+use super::otlp_layer::{attribute, AttributeValue, FinishedSpan};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends finished spans to the collector in batches. The export runs on a thread of its own,
+/// so a slow collector never holds up requests, it only loses spans once the queue is full.
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    sender: Sender<FinishedSpan>,
+}
+
+impl OtlpExporter {
+    /// The thread stops after the last clone is dropped and the queue is sent
+    pub fn start(config: OtlpConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_size());
+        std::thread::Builder::new()
+            .name("otlp-exporter".to_owned())
+            .spawn(move || actix_rt::System::new().block_on(run(config, receiver)))
+            .expect("unable to start the otlp exporter");
+        Self { sender }
+    }
+
+    pub fn export(&self, span: FinishedSpan) {
+        // a full queue drops the span, nothing may be logged from inside the subscriber
+        _ = self.sender.try_send(span);
+    }
+}
+
+async fn run(config: OtlpConfig, mut receiver: Receiver<FinishedSpan>) {
+    let client = awc::Client::default();
+    let interval = config.flush_interval().to_std().unwrap_or(TIMEOUT);
+    let mut batch = Vec::with_capacity(config.batch_size());
+    let mut open = true;
+    while open {
+        match receiver.recv().await {
+            Some(span) => batch.push(span),
+            None => break,
+        }
+        let deadline = tokio::time::Instant::now() + interval;
+        while batch.len() < config.batch_size() {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(span)) => batch.push(span),
+                Ok(None) => {
+                    open = false;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        send(&client, &config, &batch).await;
+        batch.clear();
+    }
+}
+
+async fn send(client: &awc::Client, config: &OtlpConfig, spans: &[FinishedSpan]) {
+    let mut request = client
+        .post(config.endpoint())
+        .timeout(TIMEOUT)
+        .insert_header((header::CONTENT_TYPE, "application/json"));
+    for (name, value) in config.headers() {
+        request = request.insert_header((name.as_str(), value.as_str()));
+    }
+    let body = export_request(config.service_name(), spans).to_string();
+    match request.send_body(body).await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => tracing::warn!(
+            "collector refused {} spans with status {}",
+            spans.len(),
+            response.status()
+        ),
+        Err(e) => tracing::warn!("unable to export {} spans: {}", spans.len(), e),
+    }
+}
+
+/// `ExportTraceServiceRequest` in the OTLP JSON encoding
+fn export_request(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    attribute("service.name", &AttributeValue::String(service_name.to_owned())),
+                    attribute(
+                        "service.version",
+                        &AttributeValue::String(env!("CARGO_PKG_VERSION").to_owned()),
+                    ),
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans.iter().map(FinishedSpan::to_json).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
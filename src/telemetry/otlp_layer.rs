@@ -0,0 +1,271 @@
+use std::{fmt::Debug, str::FromStr, time::SystemTime};
+
+use serde_json::{json, Value};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use uuid::Uuid;
+
+use crate::utils::{span_id::SpanId, trace_id::TraceId};
+
+use super::otlp_exporter::OtlpExporter;
+
+/// Fields which place a span in a trace instead of becoming attributes
+const TRACE_ID: &str = "trace_id";
+const SPAN_ID: &str = "span_id";
+const PARENT_SPAN_ID: &str = "parent_span_id";
+const SAMPLED: &str = "sampled";
+
+/// Exports spans as OpenTelemetry spans. A span with a `trace_id` field starts the part
+/// of a trace handled here, under the remote `parent_span_id` if there is one, and may
+/// choose its `span_id` and whether it's `sampled`. Other spans join the trace of their
+/// parent, or start a trace of their own. An error event marks its span as failed.
+#[derive(Debug, Clone)]
+pub struct OtlpLayer {
+    exporter: OtlpExporter,
+}
+
+impl OtlpLayer {
+    pub fn new(exporter: OtlpExporter) -> Self {
+        Self { exporter }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+
+        let parent = span.parent().and_then(|parent| {
+            parent
+                .extensions()
+                .get::<OpenSpan>()
+                .map(|open| (open.trace_id, open.span_id, open.sampled))
+        });
+        let (trace_id, parent_id, sampled, server) = match (fields.trace_id, parent) {
+            (Some(trace_id), _) => (
+                trace_id,
+                fields.parent_span_id,
+                fields.sampled.unwrap_or(true),
+                true,
+            ),
+            (None, Some((trace_id, span_id, sampled))) => (trace_id, Some(span_id), sampled, false),
+            (None, None) => (TraceId::from_uuid(Uuid::new_v4()), None, true, false),
+        };
+        span.extensions_mut().insert(OpenSpan {
+            trace_id,
+            span_id: fields.span_id.unwrap_or_else(SpanId::random),
+            parent_id,
+            sampled,
+            server,
+            start: SystemTime::now(),
+            attributes: fields.attributes,
+            error: None,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        values.record(&mut fields);
+        let mut extensions = span.extensions_mut();
+        if let Some(open) = extensions.get_mut::<OpenSpan>() {
+            for (name, value) in fields.attributes {
+                open.attributes.retain(|(existing, _)| *existing != name);
+                open.attributes.push((name, value));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        event.record(&mut fields);
+        let mut extensions = span.extensions_mut();
+        if let Some(open) = extensions.get_mut::<OpenSpan>() {
+            open.error = Some(fields.message.unwrap_or_default());
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+        if open.sampled {
+            self.exporter.export(FinishedSpan {
+                name: span.name(),
+                end: SystemTime::now(),
+                open,
+            });
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OpenSpan {
+    trace_id: TraceId,
+    span_id: SpanId,
+    parent_id: Option<SpanId>,
+    sampled: bool,
+    /// Handles a request, the other spans are internal
+    server: bool,
+    start: SystemTime,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct FinishedSpan {
+    name: &'static str,
+    end: SystemTime,
+    open: OpenSpan,
+}
+
+impl FinishedSpan {
+    /// Span of the OTLP JSON encoding, ids are hex and timestamps are strings
+    pub fn to_json(&self) -> Value {
+        let open = &self.open;
+        let mut span = json!({
+            "traceId": open.trace_id.to_string(),
+            "spanId": open.span_id.to_string(),
+            "name": self.name,
+            "kind": if open.server { 2 } else { 1 },
+            "startTimeUnixNano": unix_nanos(open.start),
+            "endTimeUnixNano": unix_nanos(self.end),
+            "attributes": open
+                .attributes
+                .iter()
+                .map(|(name, value)| attribute(name, value))
+                .collect::<Vec<_>>(),
+            "status": match &open.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 0 }),
+            },
+        });
+        if let Some(parent_id) = open.parent_id {
+            span["parentSpanId"] = json!(parent_id.to_string());
+        }
+        span
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+pub fn attribute(key: &str, value: &AttributeValue) -> Value {
+    let value = match value {
+        AttributeValue::String(value) => json!({ "stringValue": value }),
+        // 64 bit integers are strings in JSON
+        AttributeValue::Int(value) => json!({ "intValue": value.to_string() }),
+        AttributeValue::Double(value) => json!({ "doubleValue": value }),
+        AttributeValue::Bool(value) => json!({ "boolValue": value }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+#[derive(Default)]
+struct SpanFields {
+    trace_id: Option<TraceId>,
+    span_id: Option<SpanId>,
+    parent_span_id: Option<SpanId>,
+    sampled: Option<bool>,
+    message: Option<String>,
+    attributes: Vec<(&'static str, AttributeValue)>,
+}
+
+impl Visit for SpanFields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.attributes
+            .push((field.name(), AttributeValue::Int(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = match i64::try_from(value) {
+            Ok(value) => AttributeValue::Int(value),
+            Err(_) => AttributeValue::String(value.to_string()),
+        };
+        self.attributes.push((field.name(), value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.attributes
+            .push((field.name(), AttributeValue::Double(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        match field.name() {
+            SAMPLED => self.sampled = Some(value),
+            name => self.attributes.push((name, AttributeValue::Bool(value))),
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            TRACE_ID => self.trace_id = TraceId::from_str(value).ok(),
+            SPAN_ID => self.span_id = SpanId::from_str(value).ok(),
+            PARENT_SPAN_ID => self.parent_span_id = SpanId::from_str(value).ok(),
+            "message" => self.message = Some(value.to_owned()),
+            name => self
+                .attributes
+                .push((name, AttributeValue::String(value.to_owned()))),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn attribute_values() {
+        assert_eq!(
+            attribute("size", &AttributeValue::Int(-3)),
+            json!({ "key": "size", "value": { "intValue": "-3" } })
+        );
+        assert_eq!(
+            attribute("path", &AttributeValue::String("/a".to_owned())),
+            json!({ "key": "path", "value": { "stringValue": "/a" } })
+        );
+        assert_eq!(
+            attribute("hit", &AttributeValue::Bool(true)),
+            json!({ "key": "hit", "value": { "boolValue": true } })
+        );
+    }
+}
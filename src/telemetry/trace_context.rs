@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use actix_http::header::{HeaderMap, HeaderName};
+
+use crate::utils::{span_id::SpanId, trace_id::TraceId};
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+#[allow(clippy::declare_interior_mutable_const)]
+pub const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+const SAMPLED: u8 = 0x01;
+
+/// Trace a request belongs to according to its W3C `traceparent` and `tracestate` headers,
+/// see <https://www.w3.org/TR/trace-context/>. The trace id is our [TraceId].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: TraceId,
+    /// Span of the caller
+    pub parent_id: SpanId,
+    pub sampled: bool,
+    /// Vendor entries, passed on as they came
+    pub state: Option<String>,
+}
+
+impl TraceContext {
+    /// `None` if `traceparent` is missing or invalid, `tracestate` is ignored then
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let mut parents = headers.get_all(TRACEPARENT);
+        let traceparent = parents.next()?.to_str().ok()?;
+        if parents.next().is_some() {
+            return None;
+        }
+        let (trace_id, parent_id, flags) = parse_traceparent(traceparent)?;
+        let state = headers
+            .get_all(TRACESTATE)
+            .filter_map(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        Some(Self {
+            trace_id,
+            parent_id,
+            sampled: flags & SAMPLED != 0,
+            state: Some(state).filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+/// `traceparent` of a span of ours
+pub fn traceparent(trace_id: TraceId, span_id: SpanId, sampled: bool) -> String {
+    format!(
+        "00-{}-{}-{:02x}",
+        trace_id,
+        span_id,
+        if sampled { SAMPLED } else { 0 }
+    )
+}
+
+/// Version 00 has exactly four fields, later versions may append more
+fn parse_traceparent(value: &str) -> Option<(TraceId, SpanId, u8)> {
+    let mut fields = value.trim().split('-');
+    let version = fields.next().filter(|v| is_lower_hex(v, 2))?;
+    let trace_id = fields.next().filter(|v| is_lower_hex(v, 32))?;
+    let parent_id = fields.next()?;
+    let flags = fields.next().filter(|v| is_lower_hex(v, 2))?;
+    if version == "ff" || (version == "00" && fields.next().is_some()) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    Some((
+        TraceId::from_str(trace_id).ok()?,
+        SpanId::from_str(parent_id).ok()?,
+        u8::from_str_radix(flags, 16).ok()?,
+    ))
+}
+
+fn is_lower_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use actix_http::header::HeaderValue;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    fn headers(traceparent: &str, tracestate: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, HeaderValue::from_str(traceparent).unwrap());
+        for state in tracestate {
+            headers.append(TRACESTATE, HeaderValue::from_str(state).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_traceparent_and_tracestate() {
+        let headers = headers(
+            &format!("00-{}-{}-01", TRACE_ID, PARENT_ID),
+            &["congo=t61rcWkgMzE", "rojo=00f067aa0ba902b7"],
+        );
+
+        let context = TraceContext::from_headers(&headers).unwrap();
+
+        assert_eq!(context.trace_id.to_string(), TRACE_ID);
+        assert_eq!(context.parent_id.to_string(), PARENT_ID);
+        assert!(context.sampled);
+        assert_eq!(
+            context.state.as_deref(),
+            Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_traceparent() {
+        let invalid = [
+            format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID),
+            format!("ff-{}-{}-01", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), PARENT_ID),
+            format!("00-{}-{}-01", "0".repeat(32), PARENT_ID),
+            format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)),
+            format!("00-{}-{}", TRACE_ID, PARENT_ID),
+            format!("0-{}-{}-01", TRACE_ID, PARENT_ID),
+        ];
+
+        for traceparent in invalid {
+            assert_eq!(
+                TraceContext::from_headers(&headers(&traceparent, &["a=b"])),
+                None,
+                "{}",
+                traceparent
+            );
+        }
+    }
+
+    #[test]
+    fn later_versions_may_have_more_fields() {
+        let headers = headers(&format!("01-{}-{}-00-what-ever", TRACE_ID, PARENT_ID), &[]);
+
+        let context = TraceContext::from_headers(&headers).unwrap();
+
+        assert!(!context.sampled);
+        assert_eq!(context.state, None);
+    }
+
+    #[test]
+    fn formats_traceparent() {
+        let trace_id = TRACE_ID.parse().unwrap();
+        let span_id = PARENT_ID.parse().unwrap();
+
+        assert_eq!(
+            traceparent(trace_id, span_id, true),
+            format!("00-{}-{}-01", TRACE_ID, PARENT_ID)
+        );
+        assert_eq!(
+            traceparent(trace_id, span_id, false),
+            format!("00-{}-{}-00", TRACE_ID, PARENT_ID)
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, sync::Arc};
 
 use actix_http::{
     header::{HeaderMap, HeaderValue, TryIntoHeaderPair},
-    Method, StatusCode, Uri,
+    Method, StatusCode, Uri, Version,
 };
 use actix_web::http::header;
 use bytes::Bytes;
@@ -51,6 +51,12 @@ impl<'a, T: Serialize> Body for JsonBody<'a, T> {
     }
 }
 
+impl Body for Bytes {
+    fn get_body(&self) -> Bytes {
+        self.clone()
+    }
+}
+
 impl Body for () {
     fn get_body(&self) -> Bytes {
         Bytes::from("")
@@ -71,6 +77,15 @@ impl TestHttpClient {
         }
     }
 
+    /// HTTPS client, HTTP/2 is used if the config offers it over ALPN
+    pub fn with_tls(port: u16, config: Arc<rustls::ClientConfig>) -> TestHttpClient {
+        let connector = awc::Connector::new().rustls_0_23(config);
+        TestHttpClient {
+            client: awc::Client::builder().connector(connector).finish(),
+            base_uri: format!("https://127.0.0.1:{}/", port).parse().unwrap(),
+        }
+    }
+
     pub fn request(&self, method: Method, uri: &str) -> TestHttpRequest {
         TestHttpRequest::new(self).method(method).uri(uri)
     }
@@ -86,6 +101,9 @@ impl TestHttpClient {
     pub fn patch(&self, uri: &str) -> TestHttpRequest {
         self.request(Method::PATCH, uri)
     }
+    pub fn delete(&self, uri: &str) -> TestHttpRequest {
+        self.request(Method::DELETE, uri)
+    }
 }
 
 pub struct TestHttpRequest<B: Body = ()> {
@@ -126,6 +144,9 @@ impl<B: Body> TestHttpRequest<B> {
     pub fn patch(self) -> Self {
         self.method(Method::PATCH)
     }
+    pub fn delete(self) -> Self {
+        self.method(Method::DELETE)
+    }
 
     pub fn uri(mut self, uri: &str) -> Self {
         self.uri = uri.to_string();
@@ -152,7 +173,26 @@ impl<B: Body> TestHttpRequest<B> {
         ))
     }
 
-    pub fn json<T: Serialize>(self, data: &T) -> TestHttpRequest<JsonBody<T>> {
+    pub fn basic_auth(self, username: &str, password: &str) -> Self {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let credentials = STANDARD.encode(format!("{}:{}", username, password));
+        self.insert_header((
+            actix_http::header::AUTHORIZATION,
+            format!("Basic {}", credentials),
+        ))
+    }
+
+    pub fn cookies(self, cookies: &[(&str, &str)]) -> Self {
+        let value = cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.insert_header((header::COOKIE, value))
+    }
+
+    pub fn json<T: Serialize>(self, data: &T) -> TestHttpRequest<JsonBody<'_, T>> {
         self.insert_header((
             header::CONTENT_TYPE,
             HeaderValue::from_static("application/json"),
@@ -160,7 +200,10 @@ impl<B: Body> TestHttpRequest<B> {
         .body(JsonBody(data))
     }
 
-    pub fn form_data<'a>(self, data: HashMap<&'a str, &'a str>) -> TestHttpRequest<FormDataBody> {
+    pub fn form_data<'a>(
+        self,
+        data: HashMap<&'a str, &'a str>,
+    ) -> TestHttpRequest<FormDataBody<'a>> {
         self.insert_header((
             header::CONTENT_TYPE,
             HeaderValue::from_static("application/x-www-form-urlencoded"),
@@ -195,7 +238,9 @@ impl<B: Body> TestHttpRequest<B> {
             .client
             .client
             .request(self.method.clone(), url)
-            .timeout(std::time::Duration::from_secs(10));
+            .timeout(std::time::Duration::from_secs(10))
+            // tests check encoded bodies as the server sent them
+            .no_decompress();
         for h in self.headers {
             req = req.insert_header(h);
         }
@@ -223,6 +268,7 @@ impl<B: Body> TestHttpRequest<B> {
 
         TestHttpResponse {
             status: response.status(),
+            version: response.version(),
             headers: response.headers().to_owned(),
             body,
         }
@@ -251,11 +297,26 @@ impl Display for LogHeaders {
 
 pub struct TestHttpResponse {
     pub status: StatusCode,
+    pub version: Version,
     pub headers: HeaderMap,
     pub body: Bytes,
 }
 
 impl TestHttpResponse {
+    /// Body as UTF-8 text
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("body is not UTF-8")
+    }
+
+    /// `Set-Cookie` header of the named cookie
+    pub fn set_cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .get_all(header::SET_COOKIE)
+            .filter_map(|v| v.to_str().ok())
+            .find(|v| v.starts_with(&format!("{}=", name)))
+            .map(ToOwned::to_owned)
+    }
+
     /// Returns
     ///
     /// Ok, if 2xx
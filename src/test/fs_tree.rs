@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use bytes::Bytes;
+use futures::{future::LocalBoxFuture, FutureExt};
+
+use crate::fs::{
+    storage_backend::{collect_stream, once_stream, StorageBackend},
+    storage_error::StorageError,
+    storage_path::StoragePath,
+};
+
+/// Declarative content of a directory, built with `dir!`
+/// ```ignore
+/// dir! {
+///     "a.txt" => "hello",
+///     "sub" => dir! { "b.bin" => vec![0u8, 1, 2] },
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub enum FsTree {
+    File(Bytes),
+    Dir(BTreeMap<String, FsTree>),
+}
+
+#[macro_export]
+macro_rules! dir {
+    ($($name:expr => $content:expr),* $(,)?) => {
+        $crate::test::fs_tree::FsTree::dir(::std::vec![
+            $((::std::string::String::from($name), $crate::test::fs_tree::FsTree::from($content))),*
+        ])
+    };
+}
+
+impl FsTree {
+    pub fn dir(entries: impl IntoIterator<Item = (String, FsTree)>) -> Self {
+        Self::Dir(entries.into_iter().collect())
+    }
+
+    pub fn file(content: impl Into<Bytes>) -> Self {
+        Self::File(content.into())
+    }
+
+    /// Creates the tree at the path, existing directories are reused and files replaced
+    pub fn write<'a>(
+        &'a self,
+        backend: &'a dyn StorageBackend,
+        path: &'a StoragePath,
+    ) -> LocalBoxFuture<'a, Result<(), StorageError>> {
+        async move {
+            match self {
+                FsTree::File(content) => {
+                    backend.write(path, once_stream(content.clone())).await?;
+                }
+                FsTree::Dir(entries) => {
+                    match backend.mkdir(path).await {
+                        Ok(_) | Err(StorageError::AlreadyExists(_)) => {}
+                        Err(StorageError::InvalidPath(_)) if path.is_root() => {}
+                        Err(e) => return Err(e),
+                    }
+                    for (name, entry) in entries {
+                        entry.write(backend, &path.join(name)?).await?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        .boxed_local()
+    }
+
+    /// Reads everything below the path
+    pub fn read<'a>(
+        backend: &'a dyn StorageBackend,
+        path: &'a StoragePath,
+    ) -> LocalBoxFuture<'a, Result<FsTree, StorageError>> {
+        async move {
+            if !backend.stat(path).await?.is_dir() {
+                let read = backend.read(path, None).await?;
+                return Ok(FsTree::File(collect_stream(read.stream).await?));
+            }
+            let mut entries = BTreeMap::new();
+            for entry in backend.list(path).await? {
+                let tree = FsTree::read(backend, entry.path()).await?;
+                entries.insert(entry.name().to_owned(), tree);
+            }
+            Ok(FsTree::Dir(entries))
+        }
+        .boxed_local()
+    }
+}
+
+/// Text files are shown as strings to keep assertion diffs readable
+impl Debug for FsTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsTree::File(content) => match std::str::from_utf8(content) {
+                Ok(text) => write!(f, "{:?}", text),
+                Err(_) => write!(f, "{:?}", content),
+            },
+            FsTree::Dir(entries) => f.debug_map().entries(entries).finish(),
+        }
+    }
+}
+
+impl From<&str> for FsTree {
+    fn from(value: &str) -> Self {
+        Self::file(value.to_owned())
+    }
+}
+
+impl From<String> for FsTree {
+    fn from(value: String) -> Self {
+        Self::file(value)
+    }
+}
+
+impl From<&[u8]> for FsTree {
+    fn from(value: &[u8]) -> Self {
+        Self::file(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for FsTree {
+    fn from(value: Vec<u8>) -> Self {
+        Self::file(value)
+    }
+}
+
+impl From<Bytes> for FsTree {
+    fn from(value: Bytes) -> Self {
+        Self::file(value)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::memory_backend::MemoryBackend;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn write_and_read_back() {
+        test(|ctx| async move {
+            // arrange
+            let backend = MemoryBackend::new(ctx.time().clone());
+            let tree = dir! {
+                "a.txt" => "hello",
+                "empty" => dir! {},
+                "sub" => dir! { "b.bin" => vec![0u8, 159, 146, 150] },
+            };
+
+            // act
+            tree.write(&backend, &StoragePath::root()).await.unwrap();
+            let read = FsTree::read(&backend, &StoragePath::root()).await.unwrap();
+
+            // assert
+            assert_eq!(read, tree);
+            assert_str_eq!(
+                format!("{:?}", read),
+                r#"{"a.txt": "hello", "empty": {}, "sub": {"b.bin": b"\0\x9f\x92\x96"}}"#
+            );
+        })
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::{http::header::HeaderMap, web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+use super::{get_free_port, server::TestServer};
+
+#[derive(Clone, Default)]
+struct State {
+    requests: Arc<Mutex<Vec<(HeaderMap, Value)>>>,
+}
+
+/// OTLP/HTTP collector which keeps the export requests it receives
+pub struct MockOtlp {
+    endpoint: String,
+    state: State,
+    _server: TestServer,
+}
+
+impl MockOtlp {
+    pub async fn start() -> Self {
+        let port = get_free_port();
+        let state = State::default();
+        let server_state = state.clone();
+        let server = actix_web::HttpServer::new(move || {
+            actix_web::App::new()
+                .app_data(web::Data::new(server_state.clone()))
+                .route("/v1/traces", web::post().to(traces))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", *port))
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        tokio::task::spawn(server);
+        Self {
+            endpoint: format!("http://127.0.0.1:{}/v1/traces", *port),
+            state,
+            _server: TestServer::new(port, handle),
+        }
+    }
+
+    /// `tracing.otlp` of [crate::config::app_config::AppConfig], spans are sent one by one
+    pub fn config(&self) -> Value {
+        json!({
+            "endpoint": self.endpoint,
+            "headers": { "x-api-key": "secret" },
+            "batch_size": 1,
+        })
+    }
+
+    /// Headers and JSON bodies of the requests received so far
+    pub fn requests(&self) -> Vec<(HeaderMap, Value)> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Spans of every request received so far
+    pub fn spans(&self) -> Vec<Value> {
+        self.requests()
+            .iter()
+            .flat_map(|(_, body)| {
+                body["resourceSpans"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .flat_map(|resource| {
+                resource["scopeSpans"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .flat_map(|scope| scope["spans"].as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+
+    /// Waits up to 5 seconds for a span with the name
+    pub async fn wait_for_span(&self, name: &str) -> Value {
+        for _ in 0..100 {
+            if let Some(span) = self.spans().into_iter().find(|s| s["name"] == name) {
+                return span;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no span '{}' has been exported", name);
+    }
+}
+
+async fn traces(state: web::Data<State>, req: HttpRequest, body: web::Json<Value>) -> HttpResponse {
+    state
+        .requests
+        .lock()
+        .unwrap()
+        .push((req.headers().clone(), body.into_inner()));
+    HttpResponse::Ok().json(json!({}))
+}
@@ -1,14 +1,20 @@
 pub mod client;
+pub mod fs_tree;
+pub mod mock_idp;
+pub mod mock_otlp;
 pub mod pool;
 pub mod ports;
 pub mod server;
 pub mod test_context;
 pub mod test_environment;
+pub mod test_pki;
 pub mod test_subscriber;
 pub mod test_time;
 pub mod value_generator;
 pub mod web_server;
 
+pub use crate::dir;
+pub use fs_tree::FsTree;
 pub use inner::{get_free_port, start_test, test};
 pub use web_server::{RunServer, TestResponse};
 
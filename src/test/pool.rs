@@ -5,7 +5,12 @@ use tokio::sync::{
     Mutex,
 };
 
-pub struct Pool<T> {
+/// Resets a value before it is handed to the next user of the pool
+pub trait Recycle {
+    fn recycle(&mut self);
+}
+
+pub struct Pool<T: Recycle> {
     sender: UnboundedSender<PoolValue<T>>,
     state: Mutex<State<T>>,
 
@@ -15,14 +20,14 @@ pub struct Pool<T> {
     number_start_from: usize,
 }
 
-struct State<T> {
+struct State<T: Recycle> {
     recv: UnboundedReceiver<PoolValue<T>>,
 
     /// How many items have been received from pool
     count: usize,
 }
 
-impl<T> Pool<T> {
+impl<T: Recycle> Pool<T> {
     pub fn new(max_capacity: usize, number_start_from: usize) -> Pool<T> {
         let (sender, recv) = tokio::sync::mpsc::unbounded_channel();
         Pool {
@@ -56,21 +61,22 @@ impl<T> Pool<T> {
     }
 }
 
-impl<T> Default for Pool<T> {
+impl<T: Recycle> Default for Pool<T> {
     fn default() -> Self {
         Self::new(16, 0)
     }
 }
 
-pub struct PoolValue<T> {
+pub struct PoolValue<T: Recycle> {
     sender: UnboundedSender<PoolValue<T>>,
     value: Option<T>,
     number: usize,
 }
 
-impl<T> Drop for PoolValue<T> {
+impl<T: Recycle> Drop for PoolValue<T> {
     fn drop(&mut self) {
-        if let Some(value) = self.value.take() {
+        if let Some(mut value) = self.value.take() {
+            value.recycle();
             let _ = self.sender.send(PoolValue {
                 sender: self.sender.clone(),
                 number: self.number,
@@ -80,7 +86,7 @@ impl<T> Drop for PoolValue<T> {
     }
 }
 
-impl<T> Deref for PoolValue<T> {
+impl<T: Recycle> Deref for PoolValue<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -88,7 +94,7 @@ impl<T> Deref for PoolValue<T> {
     }
 }
 
-impl<T> PoolValue<T> {
+impl<T: Recycle> PoolValue<T> {
     pub fn number(&self) -> usize {
         self.number
     }
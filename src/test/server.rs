@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use actix_web::dev::ServerHandle;
 
 use super::{client::TestHttpClient, ports::UsingPort};
@@ -12,9 +14,18 @@ impl TestServer {
         Self { port, http_handle }
     }
 
+    pub fn port(&self) -> u16 {
+        *self.port
+    }
+
     pub fn client(&self) -> TestHttpClient {
         TestHttpClient::new(*self.port)
     }
+
+    /// For servers with `tls` in their config
+    pub fn tls_client(&self, config: Arc<rustls::ClientConfig>) -> TestHttpClient {
+        TestHttpClient::with_tls(*self.port, config)
+    }
 }
 
 impl Drop for TestServer {
@@ -1,17 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+use crate::auth::content_right::ContentRight;
+use crate::auth::login::Login;
+use crate::auth::login_right::LoginRight;
+use crate::auth::personal_access_token::{generate_token, PersonalAccessToken};
+use crate::auth::pwd::Pwd;
+use crate::auth::pwd_alg::{Argon2Params, PwdAlg};
 use crate::auth::tokens::access_token_claims::AccessTokenClaims;
-use crate::auth::tokens::encoder::{EncDecPair, JwtTokenEncoder};
+use crate::auth::tokens::encoder::{EncDecPair, JwtTokenDecoder, JwtTokenEncoder};
+use crate::config::app_config::{AppConfig, ChunkingConfig, HostingConfig, RetentionConfig};
+use crate::dal::login_rights_dal::LoginRightsDal;
+use crate::dal::logins_dal::LoginsDal;
+use crate::dal::memory::MemoryDal;
+use crate::dal::personal_access_tokens_dal::PersonalAccessTokensDal;
+use crate::dal::Dal;
+use crate::fs::dedup::chunk_store::ChunkStore;
+use crate::fs::dedup::dedup_backend::DedupBackend;
+use crate::fs::encryption::encrypted_backend::EncryptedBackend;
+use crate::fs::encryption::key_ring::KeyRing;
+use crate::fs::local_backend::LocalBackend;
+use crate::fs::memory_backend::MemoryBackend;
+use crate::fs::source::Source;
+use crate::fs::sources::Sources;
+use crate::fs::storage_backend::StorageBackend;
+use crate::fs::storage_path::StoragePath;
+use crate::fs::version_store::VersionStore;
+use crate::health::readiness::Readiness;
+use crate::metrics::app_metrics::AppMetrics;
+use crate::rate_limit::memory_rate_limit_store::MemoryRateLimitStore;
+use crate::utils::id_generator::IdGenerator;
 
+use super::fs_tree::FsTree;
 use super::test_environment::TestEnvironment;
+use super::test_pki::TestPki;
 use super::test_subscriber::LogCollector;
 use super::{pool::PoolValue, test_time::TestTime};
 
 use super::value_generator::ValueGenerator;
+use crate::utc;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
 
 pub struct TestContext {
     time: TestTime,
     value_generator: ValueGenerator,
     environment: PoolValue<TestEnvironment>,
     logs: LogCollector,
+    dal: MemoryDal,
+    rate_limits: MemoryRateLimitStore,
+    config: Mutex<Arc<AppConfig>>,
+    config_json: Mutex<serde_json::Value>,
+    sources: Sources,
+    metrics: AppMetrics,
+    readiness: Readiness,
 }
 
 impl TestContext {
@@ -19,10 +60,58 @@ impl TestContext {
         &self.environment
     }
 
+    /// Config of servers started by this test
+    pub fn config(&self) -> Arc<AppConfig> {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Changes the config of servers started afterwards. The config is edited as json.
+    pub fn update_config(&self, update: impl FnOnce(&mut serde_json::Value)) {
+        let mut json = self.config_json.lock().unwrap();
+        update(&mut json);
+        *self.config.lock().unwrap() = Arc::new(serde_json::from_value(json.clone()).unwrap());
+    }
+
+    /// Serves HTTPS with a certificate of the PKI. Client certificates are requested
+    /// if `client_certificate_required` is set, and the handshake fails without one if it's true.
+    pub fn enable_tls(&self, pki: &TestPki, client_certificate_required: Option<bool>) {
+        let dir = self.env().temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path, ca_path) = (
+            dir.join("server.pem"),
+            dir.join("server.key"),
+            dir.join("ca.pem"),
+        );
+        pki.write_server_certificate(&cert_path, &key_path);
+        std::fs::write(&ca_path, pki.ca_pem()).unwrap();
+        self.update_config(|config| {
+            config["tls"] = serde_json::json!({ "cert_path": cert_path, "key_path": key_path });
+            if let Some(required) = client_certificate_required {
+                config["tls"]["client_auth"] =
+                    serde_json::json!({ "ca_path": ca_path, "required": required });
+            }
+        });
+    }
+
     pub fn access_token_encoder(&self) -> JwtTokenEncoder<AccessTokenClaims> {
         EncDecPair::from_secret(self.env().config().secrets().tokens().access_secret()).encoder
     }
 
+    pub fn access_token_decoder(&self) -> JwtTokenDecoder<AccessTokenClaims> {
+        EncDecPair::from_secret(self.env().config().secrets().tokens().access_secret()).decoder
+    }
+
+    /// Access token for the login which expires far in the future
+    pub fn access_token_for(&self, login: &Login) -> String {
+        self.access_token_encoder()
+            .encode(&AccessTokenClaims {
+                sub: login.login_id(),
+                exp: utc!(2100).into(),
+                iat: utc!(1900).into(),
+            })
+            .unwrap()
+    }
+
     pub fn refresh_token_encoder(&self) -> JwtTokenEncoder<AccessTokenClaims> {
         EncDecPair::from_secret(self.env().config().secrets().tokens().refresh_secret()).encoder
     }
@@ -39,6 +128,177 @@ impl TestContext {
         &self.time
     }
 
+    pub fn dal(&self) -> &MemoryDal {
+        &self.dal
+    }
+
+    pub fn rate_limits(&self) -> &MemoryRateLimitStore {
+        &self.rate_limits
+    }
+
+    /// Sources of servers started by this test
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    pub fn metrics(&self) -> &AppMetrics {
+        &self.metrics
+    }
+
+    pub fn readiness(&self) -> &Readiness {
+        &self.readiness
+    }
+
+    /// Registers an in-memory source with the content, its timestamps follow the test time
+    pub async fn memory_source(&self, name: &str, content: FsTree) -> Arc<Source> {
+        let backend = MemoryBackend::new(self.time.clone());
+        self.add_source(
+            Source::new(Uuid::new_v4(), name.to_owned(), backend),
+            content,
+        )
+        .await
+    }
+
+    /// Registers an in-memory source which keeps versions in memory too
+    pub async fn versioned_source(
+        &self,
+        name: &str,
+        content: FsTree,
+        retention: RetentionConfig,
+    ) -> Arc<Source> {
+        let backend = MemoryBackend::new(self.time.clone());
+        let versions = VersionStore::new(MemoryBackend::new(self.time.clone()), retention);
+        let source = Source::new(Uuid::new_v4(), name.to_owned(), backend).with_versions(versions);
+        self.add_source(source, content).await
+    }
+
+    /// Registers an in-memory source which serves `.br` and `.gz` siblings of files
+    pub async fn precompressed_source(&self, name: &str, content: FsTree) -> Arc<Source> {
+        let backend = MemoryBackend::new(self.time.clone());
+        let source = Source::new(Uuid::new_v4(), name.to_owned(), backend).with_precompressed();
+        self.add_source(source, content).await
+    }
+
+    /// Registers an in-memory source served as a static web site
+    pub async fn hosted_source(
+        &self,
+        name: &str,
+        content: FsTree,
+        hosting: HostingConfig,
+    ) -> Arc<Source> {
+        let backend = MemoryBackend::new(self.time.clone());
+        let source = Source::new(Uuid::new_v4(), name.to_owned(), backend).with_hosting(hosting);
+        self.add_source(source, content).await
+    }
+
+    /// Registers a source on a new directory of the local disk.
+    /// The directory is removed when the test environment goes back to the pool.
+    pub async fn temp_dir_source(&self, name: &str, content: FsTree) -> Arc<Source> {
+        let root = self.env().temp_dir().join(Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        let source = Source::new(Uuid::new_v4(), name.to_owned(), LocalBackend::new(root));
+        self.add_source(source, content).await
+    }
+
+    /// Registers a deduplicating source with small chunks, manifests go to the test DAL
+    /// and chunks to memory
+    pub async fn dedup_source(&self, name: &str, content: FsTree) -> Arc<Source> {
+        let id = Uuid::new_v4();
+        let chunks = ChunkStore::new(MemoryBackend::new(self.time.clone()));
+        let backend = DedupBackend::new(
+            id,
+            self.dal.clone(),
+            self.time.clone(),
+            chunks,
+            ChunkingConfig::new(64, 256, 1024),
+        )
+        .unwrap();
+        self.add_source(Source::new(id, name.to_owned(), backend), content)
+            .await
+    }
+
+    /// Master keys of the test config
+    pub fn key_ring(&self) -> Arc<KeyRing> {
+        let config = self.config();
+        Arc::new(KeyRing::from_config(config.secrets().encryption().unwrap()).unwrap())
+    }
+
+    /// Registers a source encrypting into the given backend, the data key goes to the test DAL
+    pub async fn encrypted_source(
+        &self,
+        name: &str,
+        inner: impl StorageBackend + 'static,
+        content: FsTree,
+    ) -> Arc<Source> {
+        let id = Uuid::new_v4();
+        let backend = EncryptedBackend::new(id, inner, self.dal.clone(), self.key_ring());
+        self.add_source(Source::new(id, name.to_owned(), backend), content)
+            .await
+    }
+
+    async fn add_source(&self, source: Source, content: FsTree) -> Arc<Source> {
+        content
+            .write(source.backend(), &StoragePath::root())
+            .await
+            .unwrap();
+        self.sources
+            .insert(source.with_metrics(self.metrics.clone()))
+    }
+
+    /// Whole content of the source
+    pub async fn read_tree(&self, source: &Source) -> FsTree {
+        FsTree::read(source.backend(), &StoragePath::root())
+            .await
+            .unwrap()
+    }
+
+    pub async fn assert_tree(&self, source: &Source, expected: FsTree) {
+        pretty_assertions::assert_eq!(self.read_tree(source).await, expected);
+    }
+
+    /// Creates a login with cheap password hashing parameters
+    pub async fn create_login(&self, username: &str, password: &str) -> Login {
+        let pwd = Pwd::new(password, PwdAlg::Argon2id(Argon2Params::new(8, 1, 1)));
+        let login = Login::new(self.value_generator.next_id(), username.to_owned(), pwd);
+        self.dal.logins().insert(login.clone()).await.unwrap();
+        login
+    }
+
+    pub async fn grant_rights(&self, login: &Login, right: ContentRight) {
+        self.dal
+            .login_rights()
+            .save(LoginRight::new(login.login_id(), right))
+            .await
+            .unwrap();
+    }
+
+    /// Stores a personal access token for the login and returns its plain text
+    pub async fn create_personal_access_token(
+        &self,
+        login: &Login,
+        rights: ContentRight,
+        sources: Option<Vec<Uuid>>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> (PersonalAccessToken, String) {
+        let token = generate_token();
+        let pat = PersonalAccessToken::new(
+            self.value_generator.next_id(),
+            login.login_id(),
+            "test".to_owned(),
+            &token,
+            rights,
+            sources,
+            utc!(2000),
+            expires_at,
+        );
+        self.dal
+            .personal_access_tokens()
+            .insert(pat.clone())
+            .await
+            .unwrap();
+        (pat, token.to_string())
+    }
+
     pub fn enable_log_output(&self) {
         _ = tracing_subscriber::fmt()
             .json()
@@ -51,11 +311,20 @@ impl TestContext {
 impl PoolValue<TestEnvironment> {
     pub async fn start_test(self, logs: LogCollector) -> TestContext {
         let value_generator: ValueGenerator = Default::default();
+        let config = Mutex::new(self.config().clone());
+        let config_json = Mutex::new(self.config_json().clone());
         TestContext {
+            config,
+            config_json,
             value_generator: value_generator.clone(),
             time: TestTime::default(),
             environment: self,
             logs,
+            dal: MemoryDal::default(),
+            rate_limits: MemoryRateLimitStore::default(),
+            sources: Sources::default(),
+            metrics: AppMetrics::default(),
+            readiness: Readiness::default(),
         }
     }
 }
@@ -1,25 +1,34 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
-use config::Config;
+use crate::config::{app_config::AppConfig, config_loader::ConfigLoader};
 
-use crate::config::app_config::{env_source, AppConfig};
+use super::pool::Recycle;
 
 #[derive(Clone)]
 pub struct TestEnvironment {
     config: Arc<AppConfig>,
+    /// Source of [Self::config], serializing it would redact the secrets
+    config_json: Arc<serde_json::Value>,
     number: usize,
+    /// Files of the running test, removed when the environment goes back to the pool
+    temp_dir: PathBuf,
 }
 
 impl TestEnvironment {
     pub async fn make(number: usize) -> Self {
-        let builder = Config::builder()
-            .add_source(config::File::with_name("config/test").required(true))
-            .add_source(env_source());
-        let config = builder.build().unwrap();
-        let config = config.try_deserialize::<AppConfig>().unwrap();
+        let loader = ConfigLoader::new("config/test");
+        let config = loader.load().unwrap();
+        let config_json = loader.load_value().unwrap();
+        let temp_dir = std::env::temp_dir().join(format!(
+            "rusty-http-fs-test-{}-{}",
+            std::process::id(),
+            number
+        ));
         TestEnvironment {
             number,
             config: Arc::new(config),
+            config_json: Arc::new(config_json),
+            temp_dir,
         }
     }
 
@@ -30,4 +39,22 @@ impl TestEnvironment {
     pub fn config(&self) -> &Arc<AppConfig> {
         &self.config
     }
+
+    pub fn config_json(&self) -> &serde_json::Value {
+        &self.config_json
+    }
+
+    pub fn temp_dir(&self) -> &PathBuf {
+        &self.temp_dir
+    }
+}
+
+impl Recycle for TestEnvironment {
+    fn recycle(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.temp_dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("unable to remove {}: {}", self.temp_dir.display(), e);
+            }
+        }
+    }
 }
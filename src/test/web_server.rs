@@ -15,11 +15,20 @@ use tracing::{
     subscriber::with_default,
     Subscriber,
 };
+use tracing_subscriber::layer::SubscriberExt;
 
 use crate::{
     auth::tokens::encoder::TokensEncDec,
     config::app_config::AppConfig,
+    dal::memory::MemoryDal,
+    fs::sources::Sources,
+    health::readiness::Readiness,
+    logging::log_filter::LogFilter,
+    metrics::app_metrics::AppMetrics,
+    rate_limit::{memory_rate_limit_store::MemoryRateLimitStore, rate_limit_rules::RateLimitRules},
+    telemetry::{otlp_exporter::OtlpExporter, otlp_layer::OtlpLayer},
     test::{get_free_port, ports::UsingPort},
+    tls::{client_certificate, server_config::server_config},
     web::{
         app::{self},
         app_data::DefaultAppData,
@@ -53,12 +62,19 @@ impl RunServer for TestContext {
         let port: UsingPort = get_free_port();
         let factory = Factory::from_context(self);
         // let configure = configure;
+        let tls = self.config().tls().cloned();
         let server =
             actix_web::HttpServer::new(move || factory.make_app().configure(|cfg| configure(cfg)))
-                .workers(4)
-                .bind(("127.0.0.1", *port))
-                .unwrap()
-                .run();
+                .on_connect(client_certificate::on_connect)
+                .workers(4);
+        let server = match tls {
+            Some(tls) => {
+                let (tls, _) = server_config(&tls).unwrap();
+                server.bind_rustls_0_23(("127.0.0.1", *port), tls)
+            }
+            None => server.bind(("127.0.0.1", *port)),
+        };
+        let server = server.unwrap().run();
 
         let http_handle = server.handle();
         tokio::task::spawn(server.with_subscriber(self.logs().make_subscriber()));
@@ -120,6 +136,14 @@ struct Factory {
     value_generator: ValueGenerator,
     logs: LogCollector,
     config: Arc<AppConfig>,
+    dal: MemoryDal,
+    rate_limits: MemoryRateLimitStore,
+    sources: Sources,
+    metrics: AppMetrics,
+    otlp: Option<OtlpLayer>,
+    log_filter: LogFilter,
+    readiness: Readiness,
+    rate_limit_rules: RateLimitRules,
 }
 
 impl Factory {
@@ -128,7 +152,19 @@ impl Factory {
             time: ctx.time().clone(),
             value_generator: ctx.value_generator().clone(),
             logs: ctx.logs().clone(),
-            config: ctx.env().config().clone(),
+            config: ctx.config(),
+            dal: ctx.dal().clone(),
+            rate_limits: ctx.rate_limits().clone(),
+            sources: ctx.sources().clone(),
+            metrics: ctx.metrics().clone(),
+            otlp: ctx
+                .config()
+                .tracing()
+                .otlp()
+                .map(|config| OtlpLayer::new(OtlpExporter::start(config.clone()))),
+            log_filter: LogFilter::new(ctx.config().logging().filter()).unwrap(),
+            readiness: ctx.readiness().clone(),
+            rate_limit_rules: RateLimitRules::new(ctx.config().rate_limit().clone()),
         }
     }
 
@@ -147,11 +183,24 @@ impl Factory {
             self.time.clone(),
             self.value_generator.clone(),
             self.value_generator.clone(),
+            self.dal.clone(),
+            self.rate_limits.clone(),
+            self.sources.clone(),
+            self.metrics.clone(),
         );
         let tokens = TokensEncDec::from_config(self.config.secrets().tokens());
 
-        let app = app::create_app(Data::new(data), tokens);
-        let subscriber = self.logs.make_subscriber();
+        let app = app::create_app(
+            Data::new(data),
+            tokens,
+            self.config.clone(),
+            self.log_filter.clone(),
+            self.readiness.clone(),
+            self.rate_limit_rules.clone(),
+        );
+        let subscriber = tracing_subscriber::registry()
+            .with(self.logs.clone())
+            .with(self.otlp.clone());
         app.wrap(SetSubscriberMidlewareFactory(subscriber.into()))
     }
 }
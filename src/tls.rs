@@ -0,0 +1,4 @@
+pub mod certificate_reloader;
+pub mod client_certificate;
+pub mod server_config;
+pub mod tls_error;
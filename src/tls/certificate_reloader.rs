@@ -0,0 +1,205 @@
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, Weak},
+    time::SystemTime,
+};
+
+use chrono::Duration;
+use rustls::{
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+
+use super::tls_error::TlsError;
+
+/// Serves the certificate and key of the files, reloaded once either file is modified.
+/// A pair which doesn't load is logged and the previous one stays in use.
+pub struct CertificateReloader {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Loaded>,
+}
+
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+}
+
+impl CertificateReloader {
+    /// Fails if the files don't load, the server shouldn't start without a certificate
+    pub fn load(
+        cert_path: PathBuf,
+        key_path: PathBuf,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self, TlsError> {
+        let modified = (modified(&cert_path), modified(&key_path));
+        let key = load_certified_key(&cert_path, &key_path, &provider)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            provider,
+            current: RwLock::new(Loaded {
+                key: Arc::new(key),
+                modified,
+            }),
+        })
+    }
+
+    /// Loads the files again if their modification times changed, returns whether a new pair is in use
+    pub fn reload_if_changed(&self) -> bool {
+        let modified = (modified(&self.cert_path), modified(&self.key_path));
+        if self.current.read().unwrap().modified == modified {
+            return false;
+        }
+        let result = load_certified_key(&self.cert_path, &self.key_path, &self.provider);
+        let mut current = self.current.write().unwrap();
+        // a failed pair isn't retried until the files change again
+        current.modified = modified;
+        match result {
+            Ok(key) => {
+                current.key = Arc::new(key);
+                tracing::info!(path = %self.cert_path.display(), "TLS certificate reloaded");
+                true
+            }
+            Err(e) => {
+                tracing::error!(
+                    "TLS certificate not reloaded, the previous one stays in use: {}",
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Checks the files on a thread of its own until the reloader is dropped
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let reloader: Weak<Self> = Arc::downgrade(self);
+        let interval = interval
+            .to_std()
+            .unwrap_or(std::time::Duration::from_secs(30));
+        std::thread::Builder::new()
+            .name("tls-reloader".to_owned())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                match reloader.upgrade() {
+                    Some(reloader) => _ = reloader.reload_if_changed(),
+                    None => break,
+                }
+            })
+            .expect("unable to start the TLS certificate reloader");
+    }
+
+    pub fn certified_key(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().key.clone()
+    }
+}
+
+impl ResolvesServerCert for CertificateReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key())
+    }
+}
+
+impl Debug for CertificateReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateReloader")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load_certified_key(
+    cert_path: &Path,
+    key_path: &Path,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, TlsError> {
+    let certs = load_certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| pem_error(key_path, e))?;
+    Ok(CertifiedKey::from_der(certs, key, provider)?)
+}
+
+/// Every certificate of a PEM file, at least one
+pub fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| pem_error(path, e))?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(path.display().to_string()));
+    }
+    Ok(certs)
+}
+
+fn pem_error(path: &Path, error: rustls_pki_types::pem::Error) -> TlsError {
+    TlsError::Pem {
+        path: path.display().to_string(),
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::test_pki::TestPki;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use std::time::Duration;
+
+    /// Moves the modification time, several writes can happen within the clock resolution
+    fn touch(path: &Path, seconds: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn reloads_changed_files_and_keeps_the_last_good_pair() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        let pki = TestPki::new();
+        pki.write_server_certificate(&cert_path, &key_path);
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let reloader =
+            CertificateReloader::load(cert_path.clone(), key_path.clone(), provider).unwrap();
+        let first = reloader.certified_key();
+
+        // act
+        let unchanged = reloader.reload_if_changed();
+        pki.write_server_certificate(&cert_path, &key_path);
+        touch(&cert_path, 1);
+        let changed = reloader.reload_if_changed();
+        let second = reloader.certified_key();
+        std::fs::write(&key_path, "not a key").unwrap();
+        touch(&key_path, 2);
+        let broken = reloader.reload_if_changed();
+
+        // assert
+        assert!(!unchanged);
+        assert!(changed);
+        assert_ne!(first.cert, second.cert);
+        assert!(!broken);
+        assert_eq!(reloader.certified_key().cert, second.cert);
+    }
+
+    #[test]
+    fn load_fails_without_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        std::fs::write(&cert_path, "").unwrap();
+        std::fs::write(&key_path, "").unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let result = CertificateReloader::load(cert_path, key_path, provider);
+
+        assert!(matches!(result, Err(TlsError::NoCertificate(_))));
+    }
+}
@@ -0,0 +1,96 @@
+use std::any::Any;
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use rustls_pki_types::CertificateDer;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::config::app_config::ClientCertificateIdentity;
+
+/// Leaf certificate the client presented during the handshake, verified against the configured CAs
+#[derive(Debug, Clone)]
+pub struct ClientCertificate(CertificateDer<'static>);
+
+impl ClientCertificate {
+    pub fn new(der: CertificateDer<'static>) -> Self {
+        Self(der)
+    }
+
+    /// Username the certificate stands for, `None` if it lacks the part or can't be parsed
+    pub fn identity(&self, identity: ClientCertificateIdentity) -> Option<String> {
+        let (_, cert) = X509Certificate::from_der(&self.0).ok()?;
+        match identity {
+            ClientCertificateIdentity::SubjectCn => cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())
+                .map(str::to_owned),
+            ClientCertificateIdentity::SanEmail => first_name(&cert, |name| match name {
+                GeneralName::RFC822Name(email) => Some(email),
+                _ => None,
+            }),
+            ClientCertificateIdentity::SanDns => first_name(&cert, |name| match name {
+                GeneralName::DNSName(dns) => Some(dns),
+                _ => None,
+            }),
+        }
+    }
+}
+
+fn first_name<'a>(
+    cert: &'a X509Certificate<'a>,
+    select: impl Fn(&GeneralName<'a>) -> Option<&'a str>,
+) -> Option<String> {
+    let names = cert.subject_alternative_name().ok()??;
+    names
+        .value
+        .general_names
+        .iter()
+        .find_map(select)
+        .map(str::to_owned)
+}
+
+/// For `HttpServer::on_connect`, makes the client certificate available as connection data
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let (_, session) = stream.get_ref();
+    if let Some(leaf) = session.peer_certificates().and_then(|certs| certs.first()) {
+        data.insert(ClientCertificate::new(leaf.clone().into_owned()));
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::test_pki::TestPki;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn identity_from_subject_or_san() {
+        let pki = TestPki::new();
+        let with_email = ClientCertificate::new(
+            pki.client_identity("alice", Some("alice@example.com"))
+                .leaf()
+                .clone(),
+        );
+        let without_san = ClientCertificate::new(pki.client_identity("bob", None).leaf().clone());
+
+        assert_eq!(
+            with_email.identity(ClientCertificateIdentity::SubjectCn),
+            Some("alice".to_owned())
+        );
+        assert_eq!(
+            with_email.identity(ClientCertificateIdentity::SanEmail),
+            Some("alice@example.com".to_owned())
+        );
+        assert_eq!(with_email.identity(ClientCertificateIdentity::SanDns), None);
+        assert_eq!(
+            without_san.identity(ClientCertificateIdentity::SanEmail),
+            None
+        );
+    }
+}
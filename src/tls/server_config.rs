@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use rustls::{crypto::ring, server::WebPkiClientVerifier, RootCertStore, ServerConfig};
+
+use crate::config::app_config::TlsConfig;
+
+use super::{
+    certificate_reloader::{load_certificates, CertificateReloader},
+    tls_error::TlsError,
+};
+
+/// Protocols offered over ALPN, preferred first
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// rustls configuration of the listener and the reloader of its certificate.
+/// The reloader is watched until it's dropped together with the configuration.
+pub fn server_config(
+    config: &TlsConfig,
+) -> Result<(ServerConfig, Arc<CertificateReloader>), TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let reloader = Arc::new(CertificateReloader::load(
+        config.cert_path().clone(),
+        config.key_path().clone(),
+        provider.clone(),
+    )?);
+    reloader.watch(config.reload_interval());
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match config.client_auth() {
+        Some(client_auth) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certificates(client_auth.ca_path())? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if client_auth.required() {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            let verifier = verifier
+                .build()
+                .map_err(|e| TlsError::Rustls(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_cert_resolver(reloader.clone());
+    server_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    Ok((server_config, reloader))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::{test_pki::TestPki, *};
+    use actix_http::{StatusCode, Version};
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn serves_https_over_http2() {
+        test(|ctx| async move {
+            // arrange
+            let pki = TestPki::new();
+            ctx.enable_tls(&pki, None);
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .tls_client(pki.client_config(None))
+                .get("/api/info/v1")
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::OK);
+            assert_eq!(response.version, Version::HTTP_2);
+        });
+    }
+
+    #[test]
+    fn required_client_certificate_fails_handshake_without_one() {
+        test(|ctx| async move {
+            // arrange
+            let pki = TestPki::new();
+            ctx.enable_tls(&pki, Some(true));
+            let server = ctx.run_server().await;
+            let client = |config| {
+                awc::Client::builder()
+                    .connector(awc::Connector::new().rustls_0_23(config))
+                    .finish()
+            };
+            let url = format!("https://127.0.0.1:{}/api/info/v1", server.port());
+
+            // act
+            let without = client(pki.client_config(None)).get(&url).send().await;
+            let with = client(pki.client_config(Some(pki.client_identity("user", None))))
+                .get(&url)
+                .send()
+                .await;
+
+            // assert
+            assert!(without.is_err());
+            assert_eq!(with.unwrap().status(), StatusCode::OK);
+        });
+    }
+}
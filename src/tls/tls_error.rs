@@ -0,0 +1,19 @@
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum TlsError {
+    /// Unreadable or malformed PEM file
+    #[display("{path}: {message}")]
+    Pem { path: String, message: String },
+
+    #[display("{_0}: no certificate found")]
+    NoCertificate(#[error(not(source))] String),
+
+    /// Rejected by rustls, e.g. a key which doesn't match the certificate
+    #[display("rustls: {_0}")]
+    Rustls(#[error(not(source))] String),
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(value: rustls::Error) -> Self {
+        TlsError::Rustls(value.to_string())
+    }
+}
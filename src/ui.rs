@@ -1 +1,26 @@
+//! Browser UI rendered on the server. Pages use the JSON API through the cookie session,
+//! scripts and styles are embedded in the binary, so nothing is loaded from elsewhere.
 
+mod assets;
+mod html;
+mod pages;
+mod ui_error;
+
+use actix_web::web;
+
+use crate::web::app_data::AppData;
+
+pub fn configure<D: AppData + 'static>(cfg: &mut web::ServiceConfig) {
+    cfg.route("/ui", web::get().to(pages::root))
+        .route("/ui/", web::get().to(pages::sources::<D>))
+        .route("/ui/login", web::get().to(pages::login))
+        .route(
+            "/ui/browse/{source_id}/{path:.*}",
+            web::get().to(pages::browse::<D>),
+        )
+        .route(
+            "/ui/shared/{source_id}/{path:.*}",
+            web::get().to(pages::shared),
+        )
+        .route("/ui/assets/{name}", web::get().to(assets::asset));
+}
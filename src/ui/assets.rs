@@ -0,0 +1,29 @@
+use actix_web::{http::header, web, HttpResponse};
+
+use crate::web::common::api_error::ApiError;
+
+/// Name, content type and content of the embedded assets
+const ASSETS: [(&str, &str, &str); 3] = [
+    (
+        "app.css",
+        "text/css; charset=utf-8",
+        include_str!("assets/app.css"),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_str!("assets/app.js"),
+    ),
+    ("icon.svg", "image/svg+xml", include_str!("assets/icon.svg")),
+];
+
+pub async fn asset(name: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let (_, content_type, content) = ASSETS
+        .iter()
+        .find(|(asset, _, _)| *asset == name.as_str())
+        .ok_or_else(|| ApiError::not_found().build())?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, *content_type))
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .body(*content))
+}
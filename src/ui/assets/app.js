@@ -0,0 +1,257 @@
+"use strict";
+
+// Every request goes to the JSON API. The session lives in an HttpOnly cookie,
+// unsafe requests repeat the CSRF cookie in a header.
+
+function csrfHeaders() {
+  const { csrfCookie, csrfHeader } = document.body.dataset;
+  if (!csrfCookie) {
+    return {};
+  }
+  const cookie = document.cookie
+    .split("; ")
+    .find((c) => c.startsWith(csrfCookie + "="));
+  return cookie ? { [csrfHeader]: decodeURIComponent(cookie.slice(csrfCookie.length + 1)) } : {};
+}
+
+async function api(method, url, { json, body, headers } = {}) {
+  const init = {
+    method,
+    credentials: "same-origin",
+    headers: { ...csrfHeaders(), ...headers },
+    body,
+  };
+  if (json !== undefined) {
+    init.headers["content-type"] = "application/json";
+    init.body = JSON.stringify(json);
+  }
+  const response = await fetch(url, init);
+  if (!response.ok) {
+    let message = response.statusText;
+    try {
+      const error = await response.json();
+      message = error.message || error.code || message;
+    } catch (_) {
+      // not an api error
+    }
+    throw new Error(message);
+  }
+  return response.headers.get("content-type")?.startsWith("application/json")
+    ? response.json()
+    : response;
+}
+
+function encodePath(path) {
+  return path.split("/").filter(Boolean).map(encodeURIComponent).join("/");
+}
+
+function fileUrl(source, path) {
+  return `/api/fs/v1/${source}/files/${encodePath(path)}`;
+}
+
+function joinPath(dir, name) {
+  return dir ? `${dir}/${name}` : name;
+}
+
+function parentPath(path) {
+  const index = path.lastIndexOf("/");
+  return index < 0 ? "" : path.slice(0, index);
+}
+
+function setupLogin(form) {
+  const error = form.querySelector(".error");
+  const totp = form.querySelector(".totp");
+  let challenge = null;
+
+  form.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    error.textContent = "";
+    const fields = form.elements;
+    try {
+      let result;
+      if (challenge) {
+        result = await api("POST", "/api/auth/login/totp/v1", {
+          json: { challenge_token: challenge, code: fields.code.value },
+        });
+      } else {
+        result = await api("POST", "/api/auth/login/v1", {
+          json: { username: fields.username.value, password: fields.password.value },
+        });
+      }
+      if (result.type === "totp_required") {
+        challenge = result.challenge_token;
+        totp.hidden = false;
+        fields.code.required = true;
+        fields.code.focus();
+        return;
+      }
+      await api("POST", "/api/auth/cookie/v1", {
+        headers: { authorization: `Bearer ${result.access_token}` },
+      });
+      location.assign(form.dataset.next || "/ui/");
+    } catch (e) {
+      error.textContent = e.message;
+    }
+  });
+}
+
+function setupLogout(button) {
+  button.addEventListener("click", async () => {
+    try {
+      await api("DELETE", "/api/auth/cookie/v1");
+    } finally {
+      location.assign("/ui/login");
+    }
+  });
+}
+
+function setupUpload(zone) {
+  const { source, dir } = zone.dataset;
+  const input = document.getElementById("upload-input");
+
+  async function upload(files) {
+    zone.textContent = "Uploading…";
+    try {
+      for (const file of files) {
+        await api("PUT", fileUrl(source, joinPath(dir, file.name)), { body: file });
+      }
+      location.reload();
+    } catch (e) {
+      zone.textContent = `Upload failed: ${e.message}`;
+    }
+  }
+
+  zone.addEventListener("dragover", (event) => {
+    event.preventDefault();
+    zone.classList.add("over");
+  });
+  zone.addEventListener("dragleave", () => zone.classList.remove("over"));
+  zone.addEventListener("drop", (event) => {
+    event.preventDefault();
+    zone.classList.remove("over");
+    upload(event.dataTransfer.files);
+  });
+  input.addEventListener("change", () => upload(input.files));
+}
+
+async function rename(source, path) {
+  const name = prompt("New name", path.split("/").pop());
+  if (!name) {
+    return;
+  }
+  await api("POST", `/api/fs/v1/${source}/rename`, {
+    json: { from: path, to: joinPath(parentPath(path), name) },
+  });
+  location.reload();
+}
+
+async function remove(source, path) {
+  if (!confirm(`Delete ${path}?`)) {
+    return;
+  }
+  await api("DELETE", fileUrl(source, path));
+  location.reload();
+}
+
+// A share link carries a read only token for the shared path in the fragment
+async function share(source, path) {
+  const days = Number(prompt("Link expires in days", "7"));
+  if (!days || days <= 0) {
+    return;
+  }
+  const created = await api("POST", "/api/auth/tokens/v1", {
+    json: {
+      name: `share ${path}`,
+      rights: ["read"],
+      sources: [source],
+      path,
+      expires_at: Date.now() + days * 24 * 60 * 60 * 1000,
+    },
+  });
+  const link = `${location.origin}/ui/shared/${source}/${encodePath(path)}#${created.token}`;
+  if (navigator.clipboard) {
+    await navigator.clipboard.writeText(link).catch(() => {});
+  }
+  prompt("Share link", link);
+}
+
+function setupActions(table) {
+  table.addEventListener("click", async (event) => {
+    const button = event.target.closest("button");
+    const cell = button?.closest(".actions");
+    if (!cell) {
+      return;
+    }
+    const { source, path } = cell.dataset;
+    try {
+      if (button.classList.contains("rename")) {
+        await rename(source, path);
+      } else if (button.classList.contains("delete")) {
+        await remove(source, path);
+      } else if (button.classList.contains("share")) {
+        await share(source, path);
+      }
+    } catch (e) {
+      alert(e.message);
+    }
+  });
+}
+
+async function setupShared(section) {
+  const { url, name } = section.dataset;
+  const error = section.querySelector(".error");
+  const preview = section.querySelector(".shared-preview");
+  const token = location.hash.slice(1);
+  if (!token) {
+    error.textContent = "The link has no token.";
+    return;
+  }
+  try {
+    const response = await fetch(url, {
+      headers: { authorization: `Bearer ${token}` },
+      credentials: "omit",
+    });
+    if (!response.ok) {
+      throw new Error(response.status === 401 ? "The link has expired." : response.statusText);
+    }
+    const blob = await response.blob();
+    const objectUrl = URL.createObjectURL(blob);
+    if (blob.type.startsWith("image/")) {
+      const image = document.createElement("img");
+      image.src = objectUrl;
+      image.alt = name;
+      preview.append(image);
+    }
+    const download = document.createElement("a");
+    download.className = "button";
+    download.href = objectUrl;
+    download.download = name;
+    download.textContent = "Download";
+    preview.append(download);
+  } catch (e) {
+    error.textContent = e.message;
+  }
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  const login = document.getElementById("login-form");
+  if (login) {
+    setupLogin(login);
+  }
+  const logout = document.getElementById("logout");
+  if (logout) {
+    setupLogout(logout);
+  }
+  const zone = document.getElementById("drop-zone");
+  if (zone) {
+    setupUpload(zone);
+  }
+  const entries = document.querySelector(".entries");
+  if (entries) {
+    setupActions(entries);
+  }
+  const shared = document.getElementById("shared");
+  if (shared) {
+    setupShared(shared);
+  }
+});
@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use uuid::Uuid;
+
+use crate::{config::app_config::CookieAuthConfig, fs::storage_path::StoragePath};
+
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Scripts and styles only come from the server itself, shared files are previewed from blobs
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self'; img-src 'self' blob:; object-src 'none'; frame-ancestors 'none'";
+
+/// Escapes text for element content and quoted attribute values
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encoded segments of the path, separated by `/`
+pub fn encode_path(path: &StoragePath) -> String {
+    path.segments()
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Listing of a directory, ends with a slash
+pub fn browse_url(source_id: Uuid, path: &StoragePath) -> String {
+    match path.is_root() {
+        true => format!("/ui/browse/{}/", source_id),
+        false => format!("/ui/browse/{}/{}/", source_id, encode_path(path)),
+    }
+}
+
+/// Download through the JSON API
+pub fn file_url(source_id: Uuid, path: &StoragePath) -> String {
+    format!("/api/fs/v1/{}/files/{}", source_id, encode_path(path))
+}
+
+/// `1.5 MiB` and alike
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", size),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+/// Whole document around the content of a page
+pub struct Page<'a> {
+    pub title: &'a str,
+    pub body: String,
+    /// Names of the CSRF cookie and header for scripts
+    pub cookie: Option<&'a CookieAuthConfig>,
+    /// Shows the logout button
+    pub signed_in: bool,
+}
+
+impl Page<'_> {
+    pub fn render(&self) -> String {
+        let mut html = String::new();
+        write!(
+            html,
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{} · rusty-http-fs</title>
+<link rel="icon" href="/ui/assets/icon.svg">
+<link rel="stylesheet" href="/ui/assets/app.css">
+<script src="/ui/assets/app.js" defer></script>
+</head>
+"#,
+            escape(self.title)
+        )
+        .unwrap();
+        match self.cookie {
+            Some(cookie) => write!(
+                html,
+                r#"<body data-csrf-cookie="{}" data-csrf-header="{}">"#,
+                escape(cookie.csrf_cookie_name()),
+                escape(cookie.csrf_header_name())
+            )
+            .unwrap(),
+            None => html.push_str("<body>"),
+        }
+        html.push_str(r#"<header class="top"><a class="brand" href="/ui/">rusty-http-fs</a>"#);
+        if self.signed_in {
+            html.push_str(r#"<button id="logout" type="button">Sign out</button>"#);
+        }
+        html.push_str("</header>\n<main>\n");
+        html.push_str(&self.body);
+        html.push_str("\n</main>\n</body>\n</html>\n");
+        html
+    }
+
+    pub fn response(&self, status: StatusCode) -> HttpResponse {
+        HttpResponse::build(status)
+            .insert_header((header::CONTENT_TYPE, "text/html; charset=utf-8"))
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .insert_header((header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY))
+            .body(self.render())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn escapes_markup() {
+        assert_str_eq!(
+            escape(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn urls() {
+        let id = Uuid::nil();
+        let path = StoragePath::parse("my docs/a#1?.txt").unwrap();
+        assert_str_eq!(
+            file_url(id, &path),
+            format!("/api/fs/v1/{}/files/my%20docs/a%231%3F.txt", id)
+        );
+        assert_str_eq!(
+            browse_url(id, &StoragePath::root()),
+            format!("/ui/browse/{}/", id)
+        );
+        assert_str_eq!(
+            browse_url(id, &path),
+            format!("/ui/browse/{}/my%20docs/a%231%3F.txt/", id)
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_str_eq!(format_size(512), "512 B");
+        assert_str_eq!(format_size(1536), "1.5 KiB");
+        assert_str_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,451 @@
+use std::fmt::Write;
+
+use actix_web::{
+    http::{header, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use uuid::Uuid;
+
+use crate::{
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    config::app_config::AppConfig,
+    fs::{storage_error::StorageError, storage_path::StoragePath},
+    web::{
+        app_data::AppData,
+        auth::source_access::{authorize_path, authorize_source, readable_sources, source_rights},
+    },
+};
+
+use super::{
+    html::{browse_url, escape, file_url, format_size, Page},
+    ui_error::UiError,
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct LoginQuery {
+    /// Page to return to after the login, only UI pages are accepted
+    next: Option<String>,
+}
+
+pub async fn root() -> HttpResponse {
+    see_other("/ui/")
+}
+
+/// Password and TOTP steps run in the browser against the login endpoints,
+/// the resulting access token is moved into the session cookie
+pub async fn login(config: web::Data<AppConfig>, query: web::Query<LoginQuery>) -> HttpResponse {
+    let cookie = config.auth().methods().cookie();
+    let next = query
+        .next
+        .as_deref()
+        .filter(|next| next.starts_with("/ui/"))
+        .unwrap_or("/ui/");
+    let body = if cookie.enabled() {
+        format!(
+            r#"<section class="card narrow">
+<h1>Sign in</h1>
+<form id="login-form" data-next="{}">
+<label>Username <input name="username" autocomplete="username" required></label>
+<label>Password <input name="password" type="password" autocomplete="current-password" required></label>
+<label class="totp" hidden>Authentication code <input name="code" autocomplete="one-time-code"></label>
+<button type="submit">Sign in</button>
+<p class="error" role="alert"></p>
+</form>
+</section>"#,
+            escape(next)
+        )
+    } else {
+        r#"<section class="card narrow">
+<h1>Sign in</h1>
+<p class="error">The UI needs cookie authentication, which is disabled in <code>auth.methods.cookie</code>.</p>
+</section>"#
+            .to_owned()
+    };
+    Page {
+        title: "Sign in",
+        body,
+        cookie: Some(cookie),
+        signed_in: false,
+    }
+    .response(StatusCode::OK)
+}
+
+/// Sources the login can read
+pub async fn sources<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Option<Principal>,
+    scope: TokenScope,
+    req: HttpRequest,
+) -> Result<HttpResponse, UiError> {
+    let Some(principal) = principal else {
+        return Ok(login_redirect(&req));
+    };
+    let sources = readable_sources(data.as_ref(), principal, &scope).await?;
+    let mut body = String::from(r#"<section class="card"><h1>Sources</h1>"#);
+    if sources.is_empty() {
+        body.push_str("<p>No sources are available.</p>");
+    } else {
+        body.push_str(r#"<ul class="sources">"#);
+        for (source, rights) in sources {
+            let access = match rights.contains(ContentRight::Write) {
+                true => "read, write",
+                false => "read",
+            };
+            write!(
+                body,
+                r#"<li><a href="{}">{}</a> <span class="muted">{}</span></li>"#,
+                escape(&browse_url(source.id(), &StoragePath::root())),
+                escape(source.name()),
+                access
+            )
+            .unwrap();
+        }
+        body.push_str("</ul>");
+    }
+    body.push_str("</section>");
+    Ok(Page {
+        title: "Sources",
+        body,
+        cookie: Some(config.auth().methods().cookie()),
+        signed_in: true,
+    }
+    .response(StatusCode::OK))
+}
+
+/// Directory listing with breadcrumbs, files are downloaded from the JSON API
+pub async fn browse<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Option<Principal>,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, UiError> {
+    let Some(principal) = principal else {
+        return Ok(login_redirect(&req));
+    };
+    let (source_id, path) = path.into_inner();
+    let source = authorize_source(
+        data.as_ref(),
+        principal,
+        &scope,
+        source_id,
+        ContentRight::Read,
+    )
+    .await?;
+    let path = StoragePath::parse(&path)?;
+    authorize_path(&scope, &path)?;
+    let metadata = source.backend().stat(&path).await?;
+    if !metadata.is_dir() {
+        return Ok(see_other(&file_url(source.id(), &path)));
+    }
+    let writable = !source.backend().is_read_only()
+        && source_rights(data.as_ref(), principal, &scope, source.id())
+            .await?
+            .contains(ContentRight::Write);
+    let mut entries = source.backend().list(&path).await?;
+    entries.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then(a.name().cmp(b.name())));
+
+    let mut body = String::from(r#"<section class="card"><nav class="breadcrumbs">"#);
+    write!(
+        body,
+        r#"<a href="/ui/">Sources</a> / <a href="{}">{}</a>"#,
+        escape(&browse_url(source.id(), &StoragePath::root())),
+        escape(source.name())
+    )
+    .unwrap();
+    let mut crumb = StoragePath::root();
+    for segment in path.segments() {
+        crumb = crumb.join(segment)?;
+        write!(
+            body,
+            r#" / <a href="{}">{}</a>"#,
+            escape(&browse_url(source.id(), &crumb)),
+            escape(segment)
+        )
+        .unwrap();
+    }
+    body.push_str("</nav>");
+
+    if writable {
+        write!(
+            body,
+            r#"<div id="drop-zone" data-source="{}" data-dir="{}">Drop files here or <label class="link">choose them<input id="upload-input" type="file" multiple hidden></label></div>"#,
+            source.id(),
+            escape(path.as_str())
+        )
+        .unwrap();
+    }
+
+    body.push_str(
+        r#"<table class="entries"><thead><tr><th></th><th>Name</th><th>Size</th><th>Modified</th><th></th></tr></thead><tbody>"#,
+    );
+    for entry in &entries {
+        let name = escape(entry.name());
+        let entry_path = escape(entry.path().as_str());
+        let (preview, link, size) = if entry.is_dir() {
+            (
+                r#"<span class="icon dir"></span>"#.to_owned(),
+                browse_url(source.id(), entry.path()),
+                String::new(),
+            )
+        } else {
+            let url = file_url(source.id(), entry.path());
+            let is_image = mime_guess::from_path(entry.name())
+                .first()
+                .is_some_and(|mime| mime.type_() == mime::IMAGE);
+            let preview = match is_image {
+                true => format!(
+                    r#"<img class="preview" loading="lazy" alt="" src="{}">"#,
+                    escape(&url)
+                ),
+                false => r#"<span class="icon file"></span>"#.to_owned(),
+            };
+            (preview, url, format_size(entry.size()))
+        };
+        write!(
+            body,
+            r#"<tr><td>{}</td><td><a href="{}">{}</a></td><td>{}</td><td>{}</td><td class="actions" data-source="{}" data-path="{}">"#,
+            preview,
+            escape(&link),
+            name,
+            size,
+            entry.modified().format("%Y-%m-%d %H:%M"),
+            source.id(),
+            entry_path
+        )
+        .unwrap();
+        if !entry.is_dir() {
+            write!(
+                body,
+                r#"<a class="button" href="{}" download="{}">Download</a><button type="button" class="share">Share</button>"#,
+                escape(&file_url(source.id(), entry.path())),
+                name
+            )
+            .unwrap();
+        }
+        if writable {
+            body.push_str(
+                r#"<button type="button" class="rename">Rename</button><button type="button" class="delete">Delete</button>"#,
+            );
+        }
+        body.push_str("</td></tr>");
+    }
+    body.push_str("</tbody></table>");
+    if entries.is_empty() {
+        body.push_str(r#"<p class="muted">This directory is empty.</p>"#);
+    }
+    body.push_str("</section>");
+
+    let title = match path.name() {
+        Some(name) => name,
+        None => source.name(),
+    };
+    Ok(Page {
+        title,
+        body,
+        cookie: Some(config.auth().methods().cookie()),
+        signed_in: true,
+    }
+    .response(StatusCode::OK))
+}
+
+/// Shell for a shared file. The token stays in the fragment, so it never reaches the server
+/// with the page, the script downloads the file with it.
+pub async fn shared(path: web::Path<(Uuid, String)>) -> Result<HttpResponse, UiError> {
+    let (source_id, path) = path.into_inner();
+    let path = StoragePath::parse(&path)?;
+    let name = path
+        .name()
+        .ok_or_else(|| StorageError::IsADirectory(path.to_string()))?;
+    let body = format!(
+        r#"<section class="card narrow" id="shared" data-url="{}" data-name="{}">
+<h1>{}</h1>
+<div class="shared-preview"></div>
+<p class="error" role="alert"></p>
+</section>"#,
+        escape(&file_url(source_id, &path)),
+        escape(name),
+        escape(name)
+    );
+    Ok(Page {
+        title: name,
+        body,
+        cookie: None,
+        signed_in: false,
+    }
+    .response(StatusCode::OK))
+}
+
+fn login_redirect(req: &HttpRequest) -> HttpResponse {
+    let next = utf8_percent_encode(
+        req.uri()
+            .path_and_query()
+            .map_or("/ui/", |path| path.as_str()),
+        NON_ALPHANUMERIC,
+    );
+    see_other(&format!("/ui/login?next={}", next))
+}
+
+fn see_other(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn login_page_and_assets() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let login = server.client().get("/ui/login?next=/ui/").send().await;
+            let script = server.client().get("/ui/assets/app.js").send().await;
+            let missing = server.client().get("/ui/assets/other.js").send().await;
+
+            // assert
+            assert_eq!(login.status, StatusCode::OK);
+            assert_eq!(
+                login.headers.get(header::CONTENT_TYPE).unwrap(),
+                "text/html; charset=utf-8"
+            );
+            assert!(login
+                .text()
+                .contains(r#"<form id="login-form" data-next="/ui/">"#));
+            assert!(login.text().contains(r#"data-csrf-cookie="rhfs_csrf""#));
+            assert_eq!(script.status, StatusCode::OK);
+            assert_eq!(
+                script.headers.get(header::CONTENT_TYPE).unwrap(),
+                "text/javascript; charset=utf-8"
+            );
+            assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        })
+    }
+
+    #[test]
+    fn pages_require_a_session() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get(&format!("/ui/browse/{}/", source.id()))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::OK);
+            assert!(response
+                .text()
+                .contains(&format!(r#"data-next="/ui/browse/{}/""#, source.id())));
+        })
+    }
+
+    #[test]
+    fn browse_lists_directory() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .memory_source(
+                    "docs",
+                    dir! {
+                        "notes" => dir! {
+                            "<b>.txt" => "b",
+                            "photo.png" => "png",
+                            "archive" => dir! {},
+                        },
+                    },
+                )
+                .await;
+            let reader = ctx.create_login("reader", "password").await;
+            ctx.grant_rights(&reader, ContentRight::Read).await;
+            let writer = ctx.create_login("writer", "password").await;
+            ctx.grant_rights(&writer, ContentRight::Read | ContentRight::Write)
+                .await;
+            let server = ctx.run_server().await;
+            let uri = format!("/ui/browse/{}/notes/", source.id());
+
+            // act
+            let read_only = server
+                .client()
+                .get(&uri)
+                .access_token(&ctx.access_token_for(&reader))
+                .send()
+                .await;
+            let writable = server
+                .client()
+                .get(&uri)
+                .access_token(&ctx.access_token_for(&writer))
+                .send()
+                .await;
+            let sources = server
+                .client()
+                .get("/ui/")
+                .access_token(&ctx.access_token_for(&reader))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(read_only.status, StatusCode::OK);
+            let body = read_only.text();
+            assert!(body.contains(&format!(
+                r#"<a href="/ui/browse/{}/">docs</a> / <a href="/ui/browse/{}/notes/">notes</a>"#,
+                source.id(),
+                source.id()
+            )));
+            assert!(body.contains(&format!(
+                r#"<a href="/ui/browse/{}/notes/archive/">archive</a>"#,
+                source.id()
+            )));
+            assert!(body.contains(&format!(
+                r#"<a href="/api/fs/v1/{}/files/notes/%3Cb%3E.txt">&lt;b&gt;.txt</a>"#,
+                source.id()
+            )));
+            assert!(body.contains(&format!(
+                r#"<img class="preview" loading="lazy" alt="" src="/api/fs/v1/{}/files/notes/photo.png">"#,
+                source.id()
+            )));
+            assert!(body.find("archive").unwrap() < body.find("photo.png").unwrap());
+            assert!(!body.contains("drop-zone"));
+            assert!(!body.contains(r#"class="delete""#));
+            assert!(writable.text().contains("drop-zone"));
+            assert!(writable.text().contains(r#"class="delete""#));
+            assert!(sources.text().contains(">docs</a>"));
+        })
+    }
+
+    #[test]
+    fn browse_requires_read_right() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get(&format!("/ui/browse/{}/", source.id()))
+                .access_token(&ctx.access_token_for(&login))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::FORBIDDEN);
+            assert!(response.text().contains("<h1>Forbidden</h1>"));
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use actix_web::{body::BoxBody, http::StatusCode, HttpResponse, ResponseError};
+
+use crate::{fs::storage_error::StorageError, web::common::api_error::ApiError};
+
+use super::html::{escape, Page};
+
+/// [ApiError] shown to a browser as a page instead of JSON
+#[derive(Debug, derive_more::Display)]
+pub struct UiError(ApiError);
+
+impl ResponseError for UiError {
+    fn status_code(&self) -> StatusCode {
+        self.0.code.http_status()
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let status = self.status_code();
+        let reason = status.canonical_reason().unwrap_or("Error");
+        let mut body = format!(r#"<section class="card"><h1>{}</h1>"#, escape(reason));
+        if let Some(message) = &self.0.message {
+            body.push_str(&format!("<p>{}</p>", escape(message)));
+        }
+        body.push_str(r#"<p><a href="/ui/">Back to sources</a></p></section>"#);
+        Page {
+            title: reason,
+            body,
+            cookie: None,
+            signed_in: false,
+        }
+        .response(status)
+    }
+}
+
+impl From<ApiError> for UiError {
+    fn from(value: ApiError) -> Self {
+        Self(value)
+    }
+}
+
+impl From<StorageError> for UiError {
+    fn from(value: StorageError) -> Self {
+        Self(value.into())
+    }
+}
@@ -1,5 +1,8 @@
+pub mod build_info;
 pub mod id;
 pub mod id_generator;
+pub mod ip_network;
 pub mod secret;
+pub mod span_id;
 pub mod time;
 pub mod trace_id;
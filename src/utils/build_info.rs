@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+
+/// Version of the crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Commit the server was built from, if the build knew it
+pub fn git_commit() -> Option<&'static str> {
+    option_env!("BUILD_GIT_COMMIT")
+}
+
+pub fn built_at() -> Option<DateTime<Utc>> {
+    option_env!("BUILD_TIMESTAMP")
+        .and_then(|seconds| seconds.parse().ok())
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+}
@@ -6,7 +6,17 @@ use uuid::Uuid;
 use super::id_generator::{DefaultIdGenerator, IdGenerator};
 
 #[derive(
-    Debug, Clone, Copy, derive_more::Deref, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord,
+    Debug,
+    Clone,
+    Copy,
+    derive_more::Deref,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(transparent)]
 pub struct Id(Uuid);
@@ -0,0 +1,125 @@
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Range of addresses in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+/// A bare address is a network of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    /// IPv4 addresses mapped to IPv6, as dual stack sockets report them, match IPv4 networks
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                mask(u32::from(network).into(), self.prefix, 32)
+                    == mask(u32::from(ip).into(), self.prefix, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                mask(network.into(), self.prefix, 128) == mask(ip.into(), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mask(bits: u128, prefix: u8, len: u8) -> u128 {
+    match len - prefix {
+        0 => bits,
+        host if host >= 128 => 0,
+        host => bits >> host << host,
+    }
+}
+
+impl Display for IpNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid address in '{}'", s))?;
+        let len = match addr {
+            IpAddr::V4(_) => Ipv4Addr::BITS,
+            IpAddr::V6(_) => Ipv6Addr::BITS,
+        } as u8;
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= len)
+                .ok_or_else(|| format!("invalid prefix length in '{}'", s))?,
+            None => len,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl Serialize for IpNetwork {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for IpNetwork {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let network = String::deserialize(deserializer)?;
+        network.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn contains(network: &str, ip: &str) -> bool {
+        network
+            .parse::<IpNetwork>()
+            .unwrap()
+            .contains(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn matches_addresses_in_range() {
+        assert!(contains("10.0.0.0/8", "10.255.1.2"));
+        assert!(!contains("10.0.0.0/8", "11.0.0.1"));
+        assert!(contains("192.168.1.7", "192.168.1.7"));
+        assert!(!contains("192.168.1.7", "192.168.1.8"));
+        assert!(contains("0.0.0.0/0", "203.0.113.9"));
+        assert!(contains("fd00::/8", "fd12:3456::1"));
+        assert!(!contains("fd00::/8", "fe80::1"));
+        assert!(contains("::/0", "2001:db8::1"));
+        assert!(contains("127.0.0.0/8", "::ffff:127.0.0.1"));
+        assert!(!contains("::/0", "127.0.0.1"));
+    }
+
+    #[test]
+    fn parses_and_formats() {
+        assert_eq!(
+            "10.1.0.0/16".parse::<IpNetwork>().unwrap().to_string(),
+            "10.1.0.0/16"
+        );
+        assert_eq!("::1".parse::<IpNetwork>().unwrap().to_string(), "::1/128");
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/x".parse::<IpNetwork>().is_err());
+    }
+}
@@ -1,20 +1,91 @@
 use std::fmt::Debug;
 
-#[derive(
-    PartialEq,
-    Eq,
-    derive_more::Deref,
-    serde::Serialize,
-    serde::Deserialize,
-    Clone,
-    Copy,
-    PartialOrd,
-    Ord,
-)]
-pub struct Secret<T>(T);
-
-impl<T> Debug for Secret<T> {
+use serde::{Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Serialized in place of a secret
+pub const REDACTED: &str = "[redacted]";
+
+/// Value which mustn't leak. `Debug` and `Serialize` redact it and it's zeroed on drop.
+#[derive(PartialEq, Eq, derive_more::Deref, serde::Deserialize, Clone, PartialOrd, Ord)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Zeroize> Debug for Secret<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("Secret").finish()
     }
 }
+
+impl<T: Zeroize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Serializes the value itself, for request and response bodies which carry a secret:
+/// `#[serde(serialize_with = "expose")]`
+pub fn expose<T, S>(secret: &Secret<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Zeroize + Serialize,
+    S: Serializer,
+{
+    secret.0.serialize(serializer)
+}
+
+/// [expose] for a list of secrets
+pub fn expose_all<T, S>(secrets: &[Secret<T>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Zeroize + Serialize,
+    S: Serializer,
+{
+    serializer.collect_seq(secrets.iter().map(|secret| &secret.0))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[derive(Serialize)]
+    struct Message {
+        redacted: Secret<String>,
+        #[serde(serialize_with = "expose")]
+        exposed: Secret<String>,
+        #[serde(serialize_with = "expose_all")]
+        all: Vec<Secret<String>>,
+    }
+
+    #[test]
+    fn serializes_redacted_unless_exposed() {
+        let message = Message {
+            redacted: Secret::new("a".to_owned()),
+            exposed: Secret::new("b".to_owned()),
+            all: vec![Secret::new("c".to_owned())],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({ "redacted": REDACTED, "exposed": "b", "all": ["c"] })
+        );
+        assert_eq!(format!("{:?}", message.redacted), "Secret");
+    }
+}
@@ -0,0 +1,37 @@
+use std::{fmt::Display, str::FromStr};
+
+/// W3C span id, 8 bytes which aren't all zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanId(u64);
+
+impl SpanId {
+    pub fn random() -> Self {
+        loop {
+            let id = rand::random::<u64>();
+            if id != 0 {
+                return Self(id);
+            }
+        }
+    }
+}
+
+impl Display for SpanId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Exactly 16 lowercase hex digits
+impl FromStr for SpanId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 || !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(());
+        }
+        match u64::from_str_radix(s, 16) {
+            Ok(0) | Err(_) => Err(()),
+            Ok(id) => Ok(Self(id)),
+        }
+    }
+}
@@ -1,6 +1,11 @@
 pub mod app;
 pub mod app_data;
+pub mod audit;
 pub mod auth;
 pub mod common;
+pub mod compression;
+pub mod forwarded;
+pub mod metrics;
+pub mod rate_limit;
 pub mod routes;
 pub mod trace_id;
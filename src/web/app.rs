@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use actix_service::ServiceFactory;
 use actix_web::{
     dev::{ServiceRequest, ServiceResponse},
@@ -5,16 +7,31 @@ use actix_web::{
     App,
 };
 
-use crate::{auth::tokens::encoder::TokensEncDec, web::common::api_error::ApiError};
+use crate::{
+    auth::{oidc::oidc_providers::OidcProviders, tokens::encoder::TokensEncDec},
+    config::app_config::AppConfig,
+    health::readiness::Readiness,
+    logging::log_filter::LogFilter,
+    rate_limit::rate_limit_rules::RateLimitRules,
+    web::common::api_error::ApiError,
+};
 
 use super::{
-    app_data::AppData, auth::jwt_auth_middleware::JwtAuthenticationMiddlewareFactory,
-    trace_id::TraceIdMiddlewareFactory,
+    app_data::AppData, auth::authentication_middleware::AuthenticationMiddlewareFactory,
+    compression::CompressionMiddlewareFactory, metrics::MetricsMiddlewareFactory,
+    rate_limit::RateLimitMiddlewareFactory, trace_id::TraceIdMiddlewareFactory,
 };
 
+/// Larger JSON request bodies are rejected, files are uploaded as raw bodies instead
+pub const MAX_JSON_BODY: usize = 2 * 1024 * 1024;
+
 pub fn create_app<D: AppData + 'static>(
     app_data: Data<D>,
     token_encoders: TokensEncDec,
+    config: Arc<AppConfig>,
+    log_filter: LogFilter,
+    readiness: Readiness,
+    rate_limit_rules: RateLimitRules,
 ) -> App<
     impl ServiceFactory<
         ServiceRequest,
@@ -24,24 +41,47 @@ pub fn create_app<D: AppData + 'static>(
         InitError = (),
     >,
 > {
-    let json_cfg = web::JsonConfig::default().error_handler(|err, req| {
-        tracing::info!("json error: {}, request '{:?}'", err, req);
-        ApiError::bad_reques()
-            .message(err.to_string())
-            .build()
-            .into()
-    });
+    let json_cfg = web::JsonConfig::default()
+        .limit(MAX_JSON_BODY)
+        .error_handler(|err, req| {
+            tracing::info!("json error: {}, request '{:?}'", err, req);
+            ApiError::bad_reques()
+                .message(err.to_string())
+                .build()
+                .into()
+        });
     let access_decoder = Data::new(token_encoders.access.decoder);
+    let oidc_providers = Data::new(OidcProviders::from_config(config.auth().oidc()));
     App::new()
-        .wrap(JwtAuthenticationMiddlewareFactory::new(
+        .wrap(RateLimitMiddlewareFactory::new(
+            (*app_data).clone(),
+            rate_limit_rules,
+        ))
+        .wrap(AuthenticationMiddlewareFactory::new(
+            (*app_data).clone(),
             (*access_decoder).clone(),
+            config.auth().clone(),
+        ))
+        .wrap(CompressionMiddlewareFactory::new(
+            config.compression().clone(),
+        ))
+        .wrap(TraceIdMiddlewareFactory::new(
+            (*app_data).clone(),
+            config.proxies().clone(),
         ))
-        .wrap(TraceIdMiddlewareFactory::new((*app_data).clone()))
+        .wrap(MetricsMiddlewareFactory::new(app_data.metrics().clone()))
         .app_data(app_data)
+        .app_data(Data::from(config))
+        .app_data(oidc_providers)
+        .app_data(Data::new(log_filter))
+        .app_data(Data::new(readiness))
         .app_data(json_cfg)
         .app_data(Data::new(token_encoders.access.encoder))
         .app_data(access_decoder)
         .app_data(Data::new(token_encoders.refresh.encoder))
         .app_data(Data::new(token_encoders.refresh.decoder))
+        .app_data(Data::new(token_encoders.totp_challenge.encoder))
+        .app_data(Data::new(token_encoders.totp_challenge.decoder))
+        .configure(crate::ui::configure::<D>)
         .configure(super::routes::configure::<D>)
 }
@@ -1,29 +1,63 @@
-use crate::utils::{
-    id::Id,
-    id_generator::{self, IdGenerator},
-    time::{self, Time},
-    trace_id::TraceId,
+use crate::{
+    dal,
+    fs::sources::Sources,
+    metrics::app_metrics::AppMetrics,
+    rate_limit::rate_limit_store,
+    utils::{
+        id::Id,
+        id_generator::{self, IdGenerator},
+        time::{self, Time},
+        trace_id::TraceId,
+    },
 };
 
 pub trait AppData {
     type Time: Time;
     type TraceIdGenerator: IdGenerator<TraceId>;
     type IdGenerator: IdGenerator<Id>;
+    type Dal: dal::Dal;
+    type RateLimitStore: rate_limit_store::RateLimitStore;
 
     fn time(&self) -> &Self::Time;
     fn trace_id(&self) -> &Self::TraceIdGenerator;
     fn id(&self) -> &Self::IdGenerator;
+    fn dal(&self) -> &Self::Dal;
+    fn rate_limits(&self) -> &Self::RateLimitStore;
+    fn sources(&self) -> &Sources;
+    fn metrics(&self) -> &AppMetrics;
 }
 
-pub struct DefaultAppData<Time, TraceIdGenerator, IdGenerator> {
+pub struct DefaultAppData<Time, TraceIdGenerator, IdGenerator, Dal, RateLimitStore> {
     time: Time,
     trace_id: TraceIdGenerator,
     id: IdGenerator,
+    dal: Dal,
+    rate_limits: RateLimitStore,
+    sources: Sources,
+    metrics: AppMetrics,
 }
 
-impl<Time, TraceIdGenerator, IdGenerator> DefaultAppData<Time, TraceIdGenerator, IdGenerator> {
-    pub fn new(time: Time, trace_id: TraceIdGenerator, id: IdGenerator) -> Self {
-        Self { time, trace_id, id }
+impl<Time, TraceIdGenerator, IdGenerator, Dal, RateLimitStore>
+    DefaultAppData<Time, TraceIdGenerator, IdGenerator, Dal, RateLimitStore>
+{
+    pub fn new(
+        time: Time,
+        trace_id: TraceIdGenerator,
+        id: IdGenerator,
+        dal: Dal,
+        rate_limits: RateLimitStore,
+        sources: Sources,
+        metrics: AppMetrics,
+    ) -> Self {
+        Self {
+            time,
+            trace_id,
+            id,
+            dal,
+            rate_limits,
+            sources,
+            metrics,
+        }
     }
 }
 
@@ -31,11 +65,15 @@ impl<
         Time: time::Time,
         TraceIdGenerator: id_generator::IdGenerator<TraceId>,
         IdGenerator: id_generator::IdGenerator<Id>,
-    > AppData for DefaultAppData<Time, TraceIdGenerator, IdGenerator>
+        Dal: dal::Dal,
+        RateLimitStore: rate_limit_store::RateLimitStore,
+    > AppData for DefaultAppData<Time, TraceIdGenerator, IdGenerator, Dal, RateLimitStore>
 {
     type Time = Time;
     type TraceIdGenerator = TraceIdGenerator;
     type IdGenerator = IdGenerator;
+    type Dal = Dal;
+    type RateLimitStore = RateLimitStore;
 
     fn time(&self) -> &Self::Time {
         &self.time
@@ -48,4 +86,20 @@ impl<
     fn id(&self) -> &Self::IdGenerator {
         &self.id
     }
+
+    fn dal(&self) -> &Self::Dal {
+        &self.dal
+    }
+
+    fn rate_limits(&self) -> &Self::RateLimitStore {
+        &self.rate_limits
+    }
+
+    fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    fn metrics(&self) -> &AppMetrics {
+        &self.metrics
+    }
 }
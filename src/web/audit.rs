@@ -0,0 +1,73 @@
+use std::{
+    future::{ready, Ready},
+    net::IpAddr,
+};
+
+use actix_http::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+
+use crate::{
+    audit::{
+        audit_event::{AuditAction, AuditEntry},
+        audit_log,
+    },
+    auth::principal::Principal,
+    config::app_config::AppConfig,
+    dal::Dal,
+    utils::{time::Time, trace_id::TraceId},
+    web::common::api_error::ApiError,
+};
+
+use super::{app_data::AppData, forwarded::ClientIp};
+
+/// Who sent the request and from where, extracted by handlers which record [AuditEntry]s
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    enabled: bool,
+    principal: Option<Principal>,
+    trace_id: TraceId,
+    client_ip: Option<IpAddr>,
+}
+
+impl AuditContext {
+    /// Also for middlewares, which have no extractors
+    pub fn of(req: &HttpRequest) -> Self {
+        let extensions = req.extensions();
+        Self {
+            enabled: req
+                .app_data::<web::Data<AppConfig>>()
+                .is_some_and(|config| config.audit().enabled()),
+            principal: extensions.get::<Principal>().copied(),
+            trace_id: extensions.get::<TraceId>().copied().unwrap_or_default(),
+            client_ip: extensions.get::<ClientIp>().map(|ip| ip.0),
+        }
+    }
+
+    /// Entry by the authenticated principal, if there is one
+    pub fn entry(&self, action: AuditAction) -> AuditEntry {
+        AuditEntry::new(action, self.trace_id)
+            .with_principal(self.principal.map(|p| p.id()))
+            .with_client_ip(self.client_ip)
+    }
+
+    /// Failing to record is logged, the request goes on
+    pub async fn record<D: AppData>(&self, data: &D, entry: AuditEntry) {
+        if !self.enabled {
+            return;
+        }
+        let action = entry.action();
+        if let Err(e) = audit_log::append(data.dal().audit_events(), data.time().now(), entry).await
+        {
+            tracing::error!(action = ?action, "unable to record audit event: {}", e);
+        }
+    }
+}
+
+impl FromRequest for AuditContext {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Self::of(req)))
+    }
+}
@@ -1 +1,5 @@
-pub mod jwt_auth_middleware;
+pub mod admin_access;
+pub mod authentication_middleware;
+pub mod interactive;
+pub mod principal_extractor;
+pub mod source_access;
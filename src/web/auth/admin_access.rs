@@ -0,0 +1,21 @@
+use crate::{
+    auth::principal::Principal,
+    dal::{logins_dal::LoginsDal, Dal},
+    web::{app_data::AppData, common::api_error::ApiError},
+};
+
+/// Admins are listed by username in the config section of what they administer
+pub async fn require_admin<D: AppData>(
+    data: &D,
+    admins: &[String],
+    principal: Principal,
+) -> Result<(), ApiError> {
+    let login = data.dal().logins().get(principal.id()).await?;
+    if login.is_some_and(|login| admins.iter().any(|admin| admin == login.username())) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden()
+            .message("only admins may do this".to_owned())
+            .build())
+    }
+}
@@ -0,0 +1,1024 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    str::from_utf8,
+    sync::Arc,
+};
+
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::{
+    body::EitherBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    HttpMessage, ResponseError,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::future::LocalBoxFuture;
+use subtle::ConstantTimeEq;
+use tracing::Instrument;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        basic_auth_cache::BasicAuthCache,
+        login_lockout::LoginLockout,
+        personal_access_token::{is_personal_access_token, PersonalAccessToken, TokenHash},
+        principal::Principal,
+        pwd::Pwd,
+        tokens::{access_token_claims::AccessTokenClaims, encoder::JwtTokenDecoder},
+    },
+    config::app_config::{AuthConfig, CookieAuthConfig},
+    dal::{
+        login_totps_dal::LoginTotpsDal, logins_dal::LoginsDal,
+        personal_access_tokens_dal::PersonalAccessTokensDal, Dal,
+    },
+    tls::client_certificate::ClientCertificate,
+    utils::{secret::Secret, time::Time},
+    web::{
+        app_data::AppData, audit::AuditContext, common::api_error::ApiError, forwarded::ClientIp,
+    },
+};
+
+/// Sets [Principal] from the request credentials. Requests authenticated by a personal access token
+/// also get its [TokenScope] and the [PersonalAccessToken] itself.
+///
+/// Accepted credentials, each one can be switched off in [crate::config::app_config::AuthMethodsConfig]:
+/// - `Authorization: Bearer` with an access JWT or a personal access token
+/// - `Authorization: Basic` with a username and a password
+/// - the access JWT in a cookie, unsafe methods also require the CSRF token
+/// - a client certificate verified during the TLS handshake, if there are no other credentials
+pub struct AuthenticationMiddlewareFactory<D> {
+    data: Arc<D>,
+    decoder: Arc<JwtTokenDecoder<AccessTokenClaims>>,
+    config: Arc<AuthConfig>,
+    basic_cache: Arc<BasicAuthCache>,
+}
+
+impl<D> AuthenticationMiddlewareFactory<D> {
+    pub fn new<T: Into<Arc<JwtTokenDecoder<AccessTokenClaims>>>>(
+        data: Arc<D>,
+        decoder: T,
+        config: AuthConfig,
+    ) -> Self {
+        let basic = config.methods().basic();
+        let basic_cache = BasicAuthCache::new(basic.cache_ttl(), basic.cache_capacity());
+        Self {
+            data,
+            decoder: decoder.into(),
+            config: Arc::new(config),
+            basic_cache: Arc::new(basic_cache),
+        }
+    }
+}
+
+impl<S, B, D: AppData + 'static> Transform<S, ServiceRequest> for AuthenticationMiddlewareFactory<D>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = S::Error;
+    type InitError = ();
+    type Transform = AuthenticationMiddleware<S, D>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+            data: self.data.clone(),
+            decoder: self.decoder.clone(),
+            config: self.config.clone(),
+            basic_cache: self.basic_cache.clone(),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S, D> {
+    service: Rc<S>,
+    data: Arc<D>,
+    decoder: Arc<JwtTokenDecoder<AccessTokenClaims>>,
+    config: Arc<AuthConfig>,
+    basic_cache: Arc<BasicAuthCache>,
+}
+
+impl<S, B, D: AppData + 'static> Service<ServiceRequest> for AuthenticationMiddleware<S, D>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let data = self.data.clone();
+        let decoder = self.decoder.clone();
+        let config = self.config.clone();
+        let basic_cache = self.basic_cache.clone();
+
+        Box::pin(async move {
+            let methods = config.methods();
+            let attempt = match credentials(&req, methods.cookie()) {
+                Some(Credentials::Bearer(token)) if methods.bearer() => {
+                    if is_personal_access_token(&token) {
+                        let result = authenticate_personal_access_token(data.as_ref(), &token);
+                        Some(("personal_access_token", result.await))
+                    } else {
+                        Some(("bearer", authenticate_jwt(data.as_ref(), &decoder, &token)))
+                    }
+                }
+                Some(Credentials::Basic { username, password }) if methods.basic().enabled() => {
+                    let result = authenticate_basic(
+                        data.as_ref(),
+                        &config,
+                        &basic_cache,
+                        &req,
+                        &username,
+                        &password,
+                    )
+                    .await;
+                    if let Err(reason) = result {
+                        audit_failed_login(data.as_ref(), &req, Some(&username), reason).await;
+                    }
+                    Some(("basic", result))
+                }
+                Some(Credentials::Cookie(token)) => {
+                    if !has_valid_csrf_token(&req, methods.cookie()) {
+                        tracing::info!("Missing or invalid CSRF token");
+                        data.metrics().auth_failed("cookie", "invalid_csrf_token");
+                        let error = ApiError::forbidden()
+                            .message("missing or invalid CSRF token".to_owned())
+                            .build();
+                        let res = req.into_response(error.error_response());
+                        return Ok(res.map_into_right_body());
+                    }
+                    Some(("cookie", authenticate_jwt(data.as_ref(), &decoder, &token)))
+                }
+                Some(credentials) => {
+                    tracing::info!("Authentication method is disabled");
+                    Some((credentials.method(), Err("method_disabled")))
+                }
+                None if methods.client_certificate().enabled() => {
+                    let certificate = req.conn_data::<ClientCertificate>().cloned();
+                    match certificate {
+                        Some(certificate) => {
+                            let result = authenticate_client_certificate(
+                                data.as_ref(),
+                                &config,
+                                &certificate,
+                            )
+                            .await;
+                            if let Err(reason) = result {
+                                let identity = methods.client_certificate().identity();
+                                let username = certificate.identity(identity);
+                                audit_failed_login(
+                                    data.as_ref(),
+                                    &req,
+                                    username.as_deref(),
+                                    reason,
+                                )
+                                .await;
+                            }
+                            Some(("client_certificate", result))
+                        }
+                        None => None,
+                    }
+                }
+                None => None,
+            };
+            let authenticated = attempt.and_then(|(method, result)| match result {
+                Ok(authenticated) => {
+                    data.metrics().auth_succeeded(method);
+                    Some(authenticated)
+                }
+                Err(reason) => {
+                    data.metrics().auth_failed(method, reason);
+                    None
+                }
+            });
+
+            let span = match authenticated {
+                Some((principal, token)) => {
+                    tracing::info!("User '{}' has been authenticated", principal.id());
+                    let id = principal.id();
+                    req.extensions_mut().insert(principal);
+                    if let Some(token) = token {
+                        req.extensions_mut().insert(token.scope());
+                        req.extensions_mut().insert(token);
+                    }
+                    tracing::info_span!("principal", id = %id)
+                }
+                None => {
+                    tracing::info!("User hasn't been authenticated");
+                    tracing::info_span!("principal")
+                }
+            };
+            let fut = span.in_scope(|| service.call(req));
+            fut.instrument(span)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+enum Credentials {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Secret<String>,
+    },
+    Cookie(String),
+}
+
+impl Credentials {
+    /// Label of the method in metrics
+    fn method(&self) -> &'static str {
+        match self {
+            Self::Bearer(_) => "bearer",
+            Self::Basic { .. } => "basic",
+            Self::Cookie(_) => "cookie",
+        }
+    }
+}
+
+/// Principal of valid credentials and the personal access token if they were one,
+/// otherwise the reason why they were refused
+type AuthResult = Result<(Principal, Option<PersonalAccessToken>), &'static str>;
+
+/// The `Authorization` header takes precedence over the cookie
+fn credentials(req: &ServiceRequest, cookie: &CookieAuthConfig) -> Option<Credentials> {
+    let authorization = req
+        .headers()
+        .get(actix_http::header::AUTHORIZATION)
+        .map(|h| h.as_bytes())
+        .map(from_utf8)
+        .and_then(Result::ok);
+
+    match authorization {
+        Some(value) => {
+            if let Some(token) = value.strip_prefix("Bearer ") {
+                Some(Credentials::Bearer(token.to_owned()))
+            } else if let Some(encoded) = value.strip_prefix("Basic ") {
+                basic_credentials(encoded)
+            } else {
+                None
+            }
+        }
+        None if cookie.enabled() => req
+            .cookie(cookie.name())
+            .map(|c| Credentials::Cookie(c.value().to_owned())),
+        None => None,
+    }
+}
+
+fn basic_credentials(encoded: &str) -> Option<Credentials> {
+    let decoded = STANDARD.decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some(Credentials::Basic {
+        username: username.to_owned(),
+        password: Secret::new(password.to_owned()),
+    })
+}
+
+/// Double submit check. Safe methods don't need the token.
+fn has_valid_csrf_token(req: &ServiceRequest, config: &CookieAuthConfig) -> bool {
+    if matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    ) {
+        return true;
+    }
+    let Some(cookie) = req.cookie(config.csrf_cookie_name()) else {
+        return false;
+    };
+    let Some(header) = req.headers().get(config.csrf_header_name()) else {
+        return false;
+    };
+    !cookie.value().is_empty() && bool::from(cookie.value().as_bytes().ct_eq(header.as_bytes()))
+}
+
+fn authenticate_jwt<D: AppData>(
+    data: &D,
+    decoder: &JwtTokenDecoder<AccessTokenClaims>,
+    token: &str,
+) -> AuthResult {
+    let token = decoder
+        .decode_at(token, data.time().now())
+        .map_err(|_| "invalid_token")?;
+    Ok((Principal::new(token.claims.sub()), None))
+}
+
+/// Checked like the login endpoint, including the lockout. Logins with TOTP are refused
+/// because Basic credentials can't carry the second factor.
+async fn authenticate_basic<D: AppData>(
+    data: &D,
+    config: &AuthConfig,
+    cache: &BasicAuthCache,
+    req: &ServiceRequest,
+    username: &str,
+    password: &str,
+) -> AuthResult {
+    let now = data.time().now();
+    let ip = req.extensions().get::<ClientIp>().map(|ip| ip.0);
+    let lockout = LoginLockout::new(data.rate_limits(), config.lockout());
+    match lockout.locked_until(username, ip, now).await {
+        Ok(None) => {}
+        Ok(Some(until)) => {
+            tracing::info!("Basic authentication is locked out until {}", until);
+            return Err("locked_out");
+        }
+        Err(e) => {
+            tracing::error!("Lockout check failed: {}", e);
+            return Err("internal_error");
+        }
+    }
+
+    let login = match data.dal().logins().find_by_username(username).await {
+        Ok(login) => login,
+        Err(e) => {
+            tracing::error!("Login lookup failed: {}", e);
+            return Err("internal_error");
+        }
+    };
+    // the cache only saves the password hash computation, everything else is checked every time;
+    // an unknown login or one without a password takes as long as a wrong password
+    let verified = match login.as_ref().and_then(|login| login.password()) {
+        Some(pwd) => cache.verified(username, password, pwd, now) || pwd.verify(password),
+        None => Pwd::verify_missing(password),
+    };
+    let login = match login {
+        Some(login) if verified => login,
+        _ => {
+            tracing::info!("Invalid basic credentials");
+            if let Err(e) = lockout.failed(username, ip, now).await {
+                tracing::error!("Unable to record a failed login: {}", e);
+            }
+            return Err("invalid_credentials");
+        }
+    };
+
+    match data.dal().login_totps().get(login.login_id()).await {
+        Ok(Some(totp)) if totp.is_confirmed() => {
+            tracing::info!(login_id = %login.login_id(), "Basic authentication is not allowed with TOTP");
+            return Err("totp_required");
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("TOTP lookup failed: {}", e);
+            return Err("internal_error");
+        }
+    }
+
+    if let Err(e) = lockout.succeeded(username, now).await {
+        tracing::error!("Unable to reset login failures: {}", e);
+    }
+    if let Some(pwd) = login.password() {
+        cache.insert(username, password, pwd, now);
+    }
+    tracing::info!("Authenticated by basic credentials");
+    Ok((Principal::new(login.login_id()), None))
+}
+
+/// The certificate has been verified by rustls, only its login is looked up
+async fn authenticate_client_certificate<D: AppData>(
+    data: &D,
+    config: &AuthConfig,
+    certificate: &ClientCertificate,
+) -> AuthResult {
+    let identity = config.methods().client_certificate().identity();
+    let Some(username) = certificate.identity(identity) else {
+        tracing::info!("Client certificate has no {:?}", identity);
+        return Err("invalid_credentials");
+    };
+    match data.dal().logins().find_by_username(&username).await {
+        Ok(Some(login)) => {
+            tracing::info!("Authenticated by client certificate");
+            Ok((Principal::new(login.login_id()), None))
+        }
+        Ok(None) => {
+            tracing::info!("No login for the client certificate");
+            Err("invalid_credentials")
+        }
+        Err(e) => {
+            tracing::error!("Login lookup failed: {}", e);
+            Err("internal_error")
+        }
+    }
+}
+
+/// Refused passwords and certificates are audited like failed logins at the login endpoint
+async fn audit_failed_login<D: AppData>(
+    data: &D,
+    req: &ServiceRequest,
+    username: Option<&str>,
+    reason: &str,
+) {
+    let audit = AuditContext::of(req.request());
+    let mut entry = audit.entry(AuditAction::Login);
+    if let Some(username) = username {
+        entry = entry.with_target(username);
+    }
+    audit.record(data, entry.failed(reason)).await;
+}
+
+async fn authenticate_personal_access_token<D: AppData>(data: &D, token: &str) -> AuthResult {
+    let tokens = data.dal().personal_access_tokens();
+    let pat = match tokens.find_by_hash(&TokenHash::of(token)).await {
+        Ok(Some(pat)) => pat,
+        Ok(None) => {
+            tracing::info!("Unknown personal access token");
+            return Err("unknown_token");
+        }
+        Err(e) => {
+            tracing::error!("Personal access token lookup failed: {}", e);
+            return Err("internal_error");
+        }
+    };
+
+    let now = data.time().now();
+    if pat.is_expired(now) {
+        tracing::info!(token_id = %pat.token_id(), "Personal access token has expired");
+        return Err("expired_token");
+    }
+    if let Err(e) = tokens.set_last_used_at(pat.token_id(), now).await {
+        tracing::error!(
+            "Unable to update last use of a personal access token: {}",
+            e
+        );
+    }
+    tracing::info!(token_id = %pat.token_id(), "Authenticated by personal access token");
+    Ok((Principal::new(pat.login_id()), Some(pat)))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        audit::audit_event::AuditOutcome,
+        auth::{
+            content_right::ContentRight, login_totp::LoginTotp, token_scope::TokenScope,
+            totp::TotpSecret,
+        },
+        dal::audit_events_dal::AuditEventsDal,
+        test::{test_pki::TestPki, *},
+        utc,
+        utils::id::Id,
+        web::common::{api_error::ErrorCode, api_result::ApiResult},
+    };
+    use actix_web::web::{self, Json, ServiceConfig};
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+    use test_subscriber::{LogField, SpanData};
+    use uuid::Uuid;
+
+    const LOG_MESSAGE: &str = "fsejfosdenrgviunsdouvnslrzvnsdkjnbvisfdnbds";
+    async fn route(principal: Option<web::ReqData<Principal>>) -> ApiResult<Option<Principal>> {
+        tracing::info!("{}", LOG_MESSAGE);
+        Ok(Json(principal.map(|v| v.into_inner())))
+    }
+
+    async fn scope_route(scope: TokenScope) -> ApiResult<(bool, bool)> {
+        Ok(Json((
+            scope.is_unrestricted(),
+            scope.allows(ContentRight::Write, Uuid::nil()),
+        )))
+    }
+
+    fn configure(cfg: &mut ServiceConfig) {
+        cfg.route("/test", web::get().to(route))
+            .route("/test", web::post().to(route))
+            .route("/test/scope", web::get().to(scope_route));
+    }
+
+    #[test]
+    fn principal_is_set_with_good_jwt() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            ctx.time().set(utc!(2000));
+            let principal_id = Id::from_u128(0x999_999);
+            let token = ctx
+                .access_token_encoder()
+                .encode(&AccessTokenClaims {
+                    sub: principal_id,
+                    exp: utc!(2100).into(),
+                    iat: utc!(1900).into(),
+                })
+                .unwrap();
+
+            // act
+            let response = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            let principal = response.unwrap::<Option<Principal>>();
+            let principal = principal.ok_or("Principal is None").unwrap();
+            let expected = Principal::new(principal_id);
+            assert_eq!(principal, expected);
+
+            let header_trace_id = response.trace_id();
+            let traceparent = response.headers.get("traceparent").unwrap();
+            let span_id = &traceparent.to_str().unwrap()[36..52];
+            let log_entry = ctx.logs().get(|e| e.message() == LOG_MESSAGE);
+            let spans = log_entry.spans();
+            let expected_spans = [
+                SpanData::new(
+                    "principal",
+                    &[LogField::new("id", principal_id.to_string())],
+                ),
+                SpanData::new(
+                    "req",
+                    &[
+                        LogField::new("trace_id", header_trace_id.to_string()),
+                        LogField::new("span_id", span_id),
+                        LogField::new("sampled", "true"),
+                        LogField::new("method", "GET"),
+                        LogField::new("path", "/test"),
+                        LogField::new("client_ip", "127.0.0.1"),
+                    ],
+                ),
+            ];
+            assert_eq!(spans, expected_spans);
+        });
+    }
+
+    #[test]
+    fn principal_is_not_set_if_jwt_has_expired() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+
+            ctx.time().set(utc!(2000));
+            let principal_id = Id::from_u128(1);
+            let token = ctx
+                .access_token_encoder()
+                .encode(&AccessTokenClaims {
+                    sub: principal_id,
+                    exp: utc!(1999, 12, 31, 23, 59, 59).into(),
+                    iat: utc!(1900).into(),
+                })
+                .unwrap();
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert!(
+                principal.is_none(),
+                "Principal is set, but expected none: {:?}",
+                principal
+            );
+        });
+    }
+
+    #[test]
+    fn principal_is_not_set_if_jwt_has_invalid_signature() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+
+            ctx.time().set(utc!(2000));
+            let principal_id = Id::from_u128(1);
+            let token = ctx
+                .refresh_token_encoder()
+                .encode(&AccessTokenClaims {
+                    sub: principal_id,
+                    exp: utc!(2999).into(),
+                    iat: utc!(1900).into(),
+                })
+                .unwrap();
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert!(
+                principal.is_none(),
+                "Principal is set, but expected none: {:?}",
+                principal
+            );
+        });
+    }
+
+    #[test]
+    fn principal_is_set_with_personal_access_token() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let now = utc!(2024);
+            ctx.time().set(now);
+            let login = ctx.create_login("user", "password").await;
+            let (pat, token) = ctx
+                .create_personal_access_token(&login, ContentRight::Read, None, Some(utc!(2025)))
+                .await;
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, Some(Principal::new(login.login_id())));
+            let stored = ctx
+                .dal()
+                .personal_access_tokens()
+                .list_by_login(login.login_id())
+                .await
+                .unwrap();
+            assert_eq!(stored[0].token_id(), pat.token_id());
+            assert_eq!(stored[0].last_used_at(), Some(now));
+        });
+    }
+
+    #[test]
+    fn principal_is_not_set_if_personal_access_token_has_expired() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            ctx.time().set(utc!(2025));
+            let login = ctx.create_login("user", "password").await;
+            let (_, token) = ctx
+                .create_personal_access_token(&login, ContentRight::Read, None, Some(utc!(2025)))
+                .await;
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+        });
+    }
+
+    #[test]
+    fn principal_is_not_set_with_unknown_personal_access_token() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .access_token("rhfs_unknown")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+        });
+    }
+
+    #[test]
+    fn personal_access_token_restricts_scope() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            let (_, pat) = ctx
+                .create_personal_access_token(&login, ContentRight::Read, None, None)
+                .await;
+            let jwt = ctx.access_token_for(&login);
+
+            // act
+            let pat_scope = server
+                .client()
+                .get("/test/scope")
+                .access_token(&pat)
+                .send()
+                .await
+                .unwrap::<(bool, bool)>();
+            let jwt_scope = server
+                .client()
+                .get("/test/scope")
+                .access_token(&jwt)
+                .send()
+                .await
+                .unwrap::<(bool, bool)>();
+
+            // assert
+            assert_eq!(pat_scope, (false, false));
+            assert_eq!(jwt_scope, (true, true));
+        });
+    }
+
+    #[test]
+    fn principal_is_set_with_basic_credentials() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+
+            // act
+            let first = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let cached = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            let expected = Some(Principal::new(login.login_id()));
+            assert_eq!(first, expected);
+            assert_eq!(cached, expected);
+        });
+    }
+
+    #[test]
+    fn principal_is_not_set_with_wrong_basic_password() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            ctx.create_login("user", "password").await;
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "wrong")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+            assert!(!ctx.rate_limits().is_empty(), "failure is not recorded");
+            let event = ctx.dal().audit_events().last().await.unwrap().unwrap();
+            assert_eq!(event.entry().action(), AuditAction::Login);
+            assert_eq!(event.entry().outcome(), AuditOutcome::Failure);
+            assert_eq!(event.entry().reason(), Some("invalid_credentials"));
+            assert_eq!(event.entry().target(), Some("user"));
+        });
+    }
+
+    #[test]
+    fn basic_credentials_are_refused_for_login_with_totp() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            let mut totp = LoginTotp::new(login.login_id(), TotpSecret::generate());
+            totp.confirm(ctx.time().now(), Vec::new());
+            ctx.dal().login_totps().save(totp).await.unwrap();
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+        });
+    }
+
+    #[test]
+    fn cached_basic_credentials_are_refused_after_totp_is_enabled() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let mut totp = LoginTotp::new(login.login_id(), TotpSecret::generate());
+            totp.confirm(ctx.time().now(), Vec::new());
+            ctx.dal().login_totps().save(totp).await.unwrap();
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+        });
+    }
+
+    #[test]
+    fn disabled_methods_are_ignored() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["auth"]["methods"] = json!({
+                    "bearer": false,
+                    "basic": { "enabled": false },
+                    "cookie": { "enabled": false },
+                })
+            });
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+
+            // act
+            let bearer = server
+                .client()
+                .get("/test")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let basic = server
+                .client()
+                .get("/test")
+                .basic_auth("user", "password")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let cookie = server
+                .client()
+                .get("/test")
+                .cookies(&[("rhfs_access", &token)])
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(bearer, None);
+            assert_eq!(basic, None);
+            assert_eq!(cookie, None);
+        });
+    }
+
+    #[test]
+    fn principal_is_set_with_client_certificate() {
+        test(|ctx| async move {
+            // arrange
+            let pki = TestPki::new();
+            ctx.enable_tls(&pki, Some(false));
+            ctx.update_config(|config| {
+                config["auth"]["methods"]["client_certificate"] =
+                    json!({ "enabled": true, "identity": "san_email" });
+            });
+            let login = ctx.create_login("alice@example.com", "password").await;
+            let server = ctx.run_server_with(configure).await;
+            let identity = |cn, email| Some(pki.client_identity(cn, Some(email)));
+
+            // act
+            let known = server
+                .tls_client(pki.client_config(identity("alice", "alice@example.com")))
+                .get("/test")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let unknown = server
+                .tls_client(pki.client_config(identity("bob", "bob@example.com")))
+                .get("/test")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+            let anonymous = server
+                .tls_client(pki.client_config(None))
+                .get("/test")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(known, Some(Principal::new(login.login_id())));
+            assert_eq!(unknown, None);
+            assert_eq!(anonymous, None);
+            let event = ctx.dal().audit_events().last().await.unwrap().unwrap();
+            assert_eq!(event.entry().action(), AuditAction::Login);
+            assert_eq!(event.entry().reason(), Some("invalid_credentials"));
+            assert_eq!(event.entry().target(), Some("bob@example.com"));
+        });
+    }
+
+    #[test]
+    fn client_certificate_is_ignored_when_disabled() {
+        test(|ctx| async move {
+            // arrange
+            let pki = TestPki::new();
+            ctx.enable_tls(&pki, Some(false));
+            ctx.create_login("alice", "password").await;
+            let server = ctx.run_server_with(configure).await;
+
+            // act
+            let principal = server
+                .tls_client(pki.client_config(Some(pki.client_identity("alice", None))))
+                .get("/test")
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, None);
+        });
+    }
+
+    #[test]
+    fn principal_is_set_with_cookie() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+
+            // act
+            let principal = server
+                .client()
+                .get("/test")
+                .cookies(&[("rhfs_access", &token)])
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(principal, Some(Principal::new(login.login_id())));
+        });
+    }
+
+    #[test]
+    fn unsafe_cookie_request_requires_csrf_token() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+            let cookies = [("rhfs_access", token.as_str()), ("rhfs_csrf", "csrf")];
+
+            // act
+            let without_header = server
+                .client()
+                .post("/test")
+                .cookies(&cookies)
+                .send()
+                .await
+                .unwrap_err();
+            let wrong_header = server
+                .client()
+                .post("/test")
+                .cookies(&cookies)
+                .insert_header(("x-csrf-token", "other"))
+                .send()
+                .await
+                .unwrap_err();
+            let principal = server
+                .client()
+                .post("/test")
+                .cookies(&cookies)
+                .insert_header(("x-csrf-token", "csrf"))
+                .send()
+                .await
+                .unwrap::<Option<Principal>>();
+
+            // assert
+            assert_eq!(without_header.code, ErrorCode::Forbidden);
+            assert_eq!(wrong_header.code, ErrorCode::Forbidden);
+            assert_eq!(principal, Some(Principal::new(login.login_id())));
+        });
+    }
+}
@@ -0,0 +1,25 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+
+use crate::{auth::personal_access_token::PersonalAccessToken, web::common::api_error::ApiError};
+
+/// Requires a credential other than a personal access token, whatever its scope.
+/// Credentials created with a token would outlive its revocation.
+#[derive(Debug, Clone, Copy)]
+pub struct Interactive;
+
+impl FromRequest for Interactive {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req.extensions().contains::<PersonalAccessToken>();
+        ready(match token {
+            true => Err(ApiError::forbidden()
+                .message("not allowed with a personal access token".to_owned())
+                .build()),
+            false => Ok(Interactive),
+        })
+    }
+}
@@ -0,0 +1,30 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+
+use crate::{
+    auth::{principal::Principal, token_scope::TokenScope},
+    web::common::api_error::ApiError,
+};
+
+/// Requires the request to be authenticated. Use `Option<Principal>` for optional authentication.
+impl FromRequest for Principal {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let principal = req.extensions().get::<Principal>().copied();
+        ready(principal.ok_or_else(|| ApiError::unauthorized().build()))
+    }
+}
+
+/// Scope of the credential. Unrestricted unless the request has been authenticated by a scoped token.
+impl FromRequest for TokenScope {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let scope = req.extensions().get::<TokenScope>().cloned();
+        ready(Ok(scope.unwrap_or_default()))
+    }
+}
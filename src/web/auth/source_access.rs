@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    dal::{login_rights_dal::LoginRightsDal, Dal},
+    fs::{source::Source, storage_path::StoragePath},
+    web::{app_data::AppData, common::api_error::ApiError},
+};
+
+/// Rights the request has on a source: the rights of the login limited by the scope of the credential
+pub async fn source_rights<D: AppData>(
+    data: &D,
+    principal: Principal,
+    scope: &TokenScope,
+    source_id: Uuid,
+) -> Result<ContentRight, ApiError> {
+    let login_rights = data
+        .dal()
+        .login_rights()
+        .get(principal.id())
+        .await?
+        .map_or(ContentRight::None, |r| r.right());
+    let in_scope = scope
+        .sources()
+        .is_none_or(|sources| sources.contains(&source_id));
+    Ok(if in_scope {
+        login_rights & scope.rights()
+    } else {
+        ContentRight::None
+    })
+}
+
+/// Fails unless the scope of the credential covers the path
+pub fn authorize_path(scope: &TokenScope, path: &StoragePath) -> Result<(), ApiError> {
+    if scope.allows_path(path) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden()
+            .message("path is outside the scope of the token".to_owned())
+            .build())
+    }
+}
+
+/// Source the request may access with the right.
+/// Rights are checked first, so unknown sources and forbidden ones can't be told apart without rights.
+pub async fn authorize_source<D: AppData>(
+    data: &D,
+    principal: Principal,
+    scope: &TokenScope,
+    source_id: Uuid,
+    right: ContentRight,
+) -> Result<Arc<Source>, ApiError> {
+    if !source_rights(data, principal, scope, source_id)
+        .await?
+        .contains(right)
+    {
+        return Err(ApiError::forbidden().build());
+    }
+    data.sources().get(source_id).ok_or_else(|| {
+        ApiError::not_found()
+            .message("source not found".to_owned())
+            .build()
+    })
+}
+
+/// Sources the request can read with its rights on each, without write on read only backends
+pub async fn readable_sources<D: AppData>(
+    data: &D,
+    principal: Principal,
+    scope: &TokenScope,
+) -> Result<Vec<(Arc<Source>, ContentRight)>, ApiError> {
+    let mut sources = Vec::new();
+    for source in data.sources().list() {
+        let mut rights = source_rights(data, principal, scope, source.id()).await?;
+        if !rights.contains(ContentRight::Read) {
+            continue;
+        }
+        if source.backend().is_read_only() {
+            rights.remove(ContentRight::Write);
+        }
+        sources.push((source, rights));
+    }
+    Ok(sources)
+}
@@ -1,3 +1,4 @@
 pub mod api_error;
 pub mod api_result;
+pub mod digest_headers;
 pub mod serde_chrono;
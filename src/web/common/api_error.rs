@@ -1,8 +1,11 @@
-use actix_http::header::TryIntoHeaderValue;
+use actix_http::header::{HeaderValue, TryIntoHeaderValue};
 use actix_web::http::StatusCode;
+use chrono::Duration;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
+use crate::{dal::dal_error::DalError, fs::storage_error::StorageError};
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ErrorCode {
@@ -12,6 +15,8 @@ pub enum ErrorCode {
     Forbidden,
     NotFound,
     Conflict,
+    PayloadTooLarge,
+    RangeNotSatisfiable,
     TooManyRequests,
     UnexpectedError,
 }
@@ -25,6 +30,8 @@ impl ErrorCode {
             ErrorCode::Forbidden => StatusCode::FORBIDDEN,
             ErrorCode::NotFound => StatusCode::NOT_FOUND,
             ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RangeNotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
             ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
             ErrorCode::UnexpectedError => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -46,12 +53,17 @@ pub struct ApiError {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+
+    /// Sent as the `Retry-After` header
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
 }
 
 pub struct ApiErrorBuilder {
     code: ErrorCode,
     message: Option<String>,
     details: Option<String>,
+    retry_after: Option<Duration>,
 }
 
 impl ApiErrorBuilder {
@@ -60,6 +72,7 @@ impl ApiErrorBuilder {
             code,
             message: None,
             details: None,
+            retry_after: None,
         }
     }
 
@@ -73,11 +86,17 @@ impl ApiErrorBuilder {
         self
     }
 
+    pub fn retry_after(mut self, v: Duration) -> Self {
+        self.retry_after = Some(v);
+        self
+    }
+
     pub fn build(self) -> ApiError {
         ApiError {
             code: self.code,
             message: self.message,
             details: self.details,
+            retry_after: self.retry_after,
         }
     }
 }
@@ -117,6 +136,14 @@ impl ApiError {
         Self::builder(ErrorCode::Conflict)
     }
 
+    pub fn payload_too_large() -> ApiErrorBuilder {
+        Self::builder(ErrorCode::PayloadTooLarge)
+    }
+
+    pub fn range_not_satisfiable() -> ApiErrorBuilder {
+        Self::builder(ErrorCode::RangeNotSatisfiable)
+    }
+
     pub fn too_many_requests() -> ApiErrorBuilder {
         Self::builder(ErrorCode::TooManyRequests)
     }
@@ -146,6 +173,12 @@ impl actix_web::ResponseError for ApiError {
         let mime = mime::APPLICATION_JSON.try_into_value().unwrap();
         res.headers_mut()
             .insert(actix_web::http::header::CONTENT_TYPE, mime);
+        if let Some(retry_after) = self.retry_after {
+            res.headers_mut().insert(
+                actix_web::http::header::RETRY_AFTER,
+                HeaderValue::from(ceil_seconds(retry_after)),
+            );
+        }
         let body = match serde_json::to_vec(self) {
             Ok(body) => body,
             Err(e) => {
@@ -156,3 +189,50 @@ impl actix_web::ResponseError for ApiError {
         res.set_body(actix_web::body::BoxBody::new(body))
     }
 }
+
+/// Whole seconds, rounded up, as HTTP headers expect
+pub fn ceil_seconds(duration: Duration) -> i64 {
+    let ms = duration.num_milliseconds().max(0);
+    ms / 1000 + i64::from(ms % 1000 != 0)
+}
+
+impl From<DalError> for ApiError {
+    fn from(value: DalError) -> Self {
+        match value {
+            DalError::Conflict(message) => ApiError::conflict().message(message).build(),
+            DalError::Unexpected(message) => {
+                tracing::error!("dal error: {}", message);
+                ApiError::unexpected().build()
+            }
+        }
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(value: StorageError) -> Self {
+        let message = value.to_string();
+        match value {
+            StorageError::NotFound(_) => ApiError::not_found().message(message).build(),
+            StorageError::AlreadyExists(_) | StorageError::NotEmpty(_) => {
+                ApiError::conflict().message(message).build()
+            }
+            StorageError::NotADirectory(_)
+            | StorageError::IsADirectory(_)
+            | StorageError::InvalidPath(_)
+            | StorageError::DigestMismatch(_) => ApiError::bad_reques().message(message).build(),
+            StorageError::InvalidRange => {
+                ApiError::range_not_satisfiable().message(message).build()
+            }
+            StorageError::TooLarge(_) => ApiError::payload_too_large().message(message).build(),
+            StorageError::ReadOnly => ApiError::forbidden().message(message).build(),
+            StorageError::Io(message) => {
+                tracing::error!("storage error: {}", message);
+                ApiError::unexpected().build()
+            }
+            StorageError::Encryption(message) => {
+                tracing::error!("encryption error: {}", message);
+                ApiError::unexpected().build()
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use actix_web::http::header::{HeaderMap, HeaderName};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::fs::digests::{DigestAlgorithm, ExpectedDigest, FileDigests};
+
+use super::api_error::ApiError;
+
+/// RFC 9530
+pub const CONTENT_DIGEST: HeaderName = HeaderName::from_static("content-digest");
+pub const REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+pub const WANT_CONTENT_DIGEST: HeaderName = HeaderName::from_static("want-content-digest");
+pub const WANT_REPR_DIGEST: HeaderName = HeaderName::from_static("want-repr-digest");
+/// RFC 3230, superseded by RFC 9530 but still sent by many clients
+pub const DIGEST: HeaderName = HeaderName::from_static("digest");
+pub const WANT_DIGEST: HeaderName = HeaderName::from_static("want-digest");
+
+/// Digests of a request body from `Content-Digest` and `Digest`, unknown algorithms are ignored
+pub fn expected_digests(headers: &HeaderMap) -> Result<Vec<ExpectedDigest>, ApiError> {
+    let mut expected = Vec::new();
+    for (name, parse) in [
+        (
+            CONTENT_DIGEST,
+            parse_content_digest as fn(&str) -> Option<_>,
+        ),
+        (DIGEST, parse_legacy_digest),
+    ] {
+        for value in headers.get_all(&name) {
+            let digests = value.to_str().ok().and_then(parse).ok_or_else(|| {
+                ApiError::bad_reques()
+                    .message(format!("invalid {} header", name))
+                    .build()
+            })?;
+            expected.extend(digests);
+        }
+    }
+    Ok(expected)
+}
+
+/// `sha-256=:<base64>:, md5=:<base64>:`
+fn parse_content_digest(value: &str) -> Option<Vec<ExpectedDigest>> {
+    let mut digests = Vec::new();
+    for member in value.split(',') {
+        let (name, value) = member.split_once('=')?;
+        let value = value.trim().strip_prefix(':')?.strip_suffix(':')?;
+        let value = STANDARD.decode(value).ok()?;
+        if let Some(algorithm) = DigestAlgorithm::parse(name) {
+            digests.push(ExpectedDigest { algorithm, value });
+        }
+    }
+    Some(digests)
+}
+
+/// `SHA-256=<base64>,MD5=<base64>`
+fn parse_legacy_digest(value: &str) -> Option<Vec<ExpectedDigest>> {
+    let mut digests = Vec::new();
+    for member in value.split(',') {
+        let (name, value) = member.split_once('=')?;
+        let value = STANDARD.decode(value.trim()).ok()?;
+        if let Some(algorithm) = DigestAlgorithm::parse(name) {
+            digests.push(ExpectedDigest { algorithm, value });
+        }
+    }
+    Some(digests)
+}
+
+/// Supported algorithms of a `Want-*-Digest` (`sha-256=10, md5=1`) or `Want-Digest`
+/// (`SHA-256;q=1, MD5;q=0.1`) preference, most preferred first. Weight 0 means not acceptable.
+pub fn wanted_algorithms(value: &str) -> Vec<DigestAlgorithm> {
+    let mut wanted: Vec<_> = value
+        .split(',')
+        .filter_map(|member| {
+            let (name, weight) = match member.split_once(';') {
+                Some((name, params)) => (name, params.trim().strip_prefix("q=")),
+                None => match member.split_once('=') {
+                    Some((name, weight)) => (name, Some(weight)),
+                    None => (member, None),
+                },
+            };
+            let weight = weight.map_or(Some(1.0), |w| w.trim().parse::<f64>().ok())?;
+            let algorithm = DigestAlgorithm::parse(name)?;
+            (weight > 0.0).then_some((algorithm, weight))
+        })
+        .collect();
+    wanted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    wanted.into_iter().map(|(algorithm, _)| algorithm).collect()
+}
+
+/// Value of `Content-Digest` or `Repr-Digest`
+pub fn format_digests(digests: &FileDigests, algorithms: &[DigestAlgorithm]) -> String {
+    algorithms
+        .iter()
+        .map(|alg| format!("{}=:{}:", alg.name(), STANDARD.encode(digests.get(*alg))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Value of the legacy `Digest`
+pub fn format_legacy_digests(digests: &FileDigests, algorithms: &[DigestAlgorithm]) -> String {
+    algorithms
+        .iter()
+        .map(|alg| {
+            format!(
+                "{}={}",
+                alg.legacy_name(),
+                STANDARD.encode(digests.get(*alg))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn parses_both_digest_headers() {
+        let sha = FileDigests::of(b"hello")
+            .get(DigestAlgorithm::Sha256)
+            .to_vec();
+        let encoded = STANDARD.encode(&sha);
+
+        let content = parse_content_digest(&format!("unknown=:AAAA:, sha-256=:{}:", encoded));
+        let legacy = parse_legacy_digest(&format!("SHA-256={}", encoded));
+
+        let expected = vec![ExpectedDigest {
+            algorithm: DigestAlgorithm::Sha256,
+            value: sha,
+        }];
+        assert_eq!(content, Some(expected.clone()));
+        assert_eq!(legacy, Some(expected));
+        assert_eq!(parse_content_digest("sha-256=not-a-byte-sequence"), None);
+    }
+
+    #[test]
+    fn wanted_algorithms_by_weight() {
+        assert_eq!(
+            wanted_algorithms("md5=1, sha-256=10, blake3=0"),
+            vec![DigestAlgorithm::Sha256, DigestAlgorithm::Md5]
+        );
+        assert_eq!(
+            wanted_algorithms("MD5;q=0.3, SHA-256, unknown"),
+            vec![DigestAlgorithm::Sha256, DigestAlgorithm::Md5]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::future::{ready, Ready};
+
+use actix_http::{
+    body::{BodySize, MessageBody},
+    encoding::Encoder,
+    header::{self, ContentEncoding},
+    HttpMessage, ResponseHead,
+};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{AcceptEncoding, Encoding},
+    Error,
+};
+use futures::future::LocalBoxFuture;
+use mime::Mime;
+
+use crate::config::app_config::CompressionConfig;
+
+use super::common::digest_headers::{CONTENT_DIGEST, DIGEST, REPR_DIGEST};
+
+/// Preferred in this order if the client weights them the same
+const SUPPORTED: [Encoding; 4] = [
+    Encoding::brotli(),
+    Encoding::zstd(),
+    Encoding::gzip(),
+    Encoding::identity(),
+];
+
+/// Compresses responses with the encoding negotiated by `Accept-Encoding`.
+/// Range requests, already compressed content types, responses which are encoded already
+/// and responses with digests of their unencoded content are sent as they are.
+#[derive(Debug, Clone)]
+pub struct CompressionMiddlewareFactory(CompressionConfig);
+
+impl CompressionMiddlewareFactory {
+    pub fn new(config: CompressionConfig) -> Self {
+        Self(config)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CompressionMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<Encoder<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CompressionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionMiddleware {
+            service,
+            config: self.0.clone(),
+        }))
+    }
+}
+
+pub struct CompressionMiddleware<S> {
+    service: S,
+    config: CompressionConfig,
+}
+
+impl<S> CompressionMiddleware<S> {
+    fn negotiate(&self, req: &ServiceRequest) -> ContentEncoding {
+        if !self.config.enabled() || req.headers().contains_key(header::RANGE) {
+            return ContentEncoding::Identity;
+        }
+        match req
+            .get_header::<AcceptEncoding>()
+            .and_then(|accept| accept.negotiate(SUPPORTED.iter()))
+        {
+            Some(Encoding::Known(encoding)) => encoding,
+            _ => ContentEncoding::Identity,
+        }
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<Encoder<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let encoding = self.negotiate(&req);
+        let min_size = self.config.min_size();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            Ok(res.map_body(move |head, body| {
+                let encoding = if is_compressible(head, body.size(), min_size) {
+                    encoding
+                } else {
+                    ContentEncoding::Identity
+                };
+                Encoder::response(encoding, head, body)
+            }))
+        })
+    }
+}
+
+fn is_compressible(head: &ResponseHead, size: BodySize, min_size: u64) -> bool {
+    if matches!(size, BodySize::Sized(size) if size < min_size) {
+        return false;
+    }
+    let headers = head.headers();
+    if [CONTENT_DIGEST, REPR_DIGEST, DIGEST]
+        .iter()
+        .any(|name| headers.contains_key(name))
+    {
+        return false;
+    }
+    match headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Mime>().ok())
+    {
+        Some(mime) => !is_compressed_type(&mime),
+        None => true,
+    }
+}
+
+/// Types which are compressed by their format, compressing them again only costs time
+fn is_compressed_type(mime: &Mime) -> bool {
+    match (mime.type_(), mime.subtype().as_str()) {
+        (mime::IMAGE, subtype) => subtype != mime::SVG.as_str(),
+        (mime::VIDEO | mime::AUDIO, _) => true,
+        (mime::FONT, subtype) => matches!(subtype, "woff" | "woff2"),
+        (mime::APPLICATION, subtype) => {
+            matches!(
+                subtype,
+                "zip"
+                    | "gzip"
+                    | "x-gzip"
+                    | "zstd"
+                    | "x-bzip2"
+                    | "x-xz"
+                    | "x-7z-compressed"
+                    | "vnd.rar"
+                    | "x-rar-compressed"
+                    | "java-archive"
+                    | "epub"
+            ) || subtype.starts_with("vnd.openxmlformats")
+                || subtype.starts_with("vnd.oasis.opendocument")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn compressed_types() {
+        let compressed = ["image/png", "video/mp4", "application/zip", "font/woff2"];
+        let plain = ["image/svg+xml", "text/plain", "application/json"];
+
+        for mime in compressed {
+            assert!(is_compressed_type(&mime.parse().unwrap()), "{}", mime);
+        }
+        for mime in plain {
+            assert!(!is_compressed_type(&mime.parse().unwrap()), "{}", mime);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::net::IpAddr;
+
+use actix_http::header::{self, HeaderMap};
+
+use crate::config::app_config::ProxiesConfig;
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub const X_FORWARDED_FOR: header::HeaderName = header::HeaderName::from_static("x-forwarded-for");
+
+/// Request extension with the result of [client_ip]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Address of the client the request came from. Behind trusted proxies it's taken from
+/// `Forwarded`, or `X-Forwarded-For` without it, walking the chain from the nearest hop
+/// back to the first address which isn't a trusted proxy. Anyone else gets the peer address.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    proxies: &ProxiesConfig,
+) -> Option<IpAddr> {
+    let mut client = peer?;
+    if !proxies.is_trusted(client) {
+        return Some(client);
+    }
+    let forwarded = forwarded_for(headers);
+    let chain = if forwarded.is_empty() {
+        x_forwarded_for(headers)
+    } else {
+        forwarded
+    };
+    for node in chain.iter().rev() {
+        // an obfuscated or unknown hop hides everything before it
+        let Some(ip) = parse_node(node) else {
+            break;
+        };
+        client = ip;
+        if !proxies.is_trusted(ip) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+/// `for` parameters of every element of every `Forwarded` header, see RFC 7239
+fn forwarded_for(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::FORWARDED)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| value.trim().trim_matches('"').to_owned())
+            })
+        })
+        .collect()
+}
+
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|node| node.trim().to_owned())
+        .collect()
+}
+
+/// `192.0.2.43`, `192.0.2.43:47011`, `[2001:db8::17]:4711` or a bare IPv6 address
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(bracketed) = node.strip_prefix('[') {
+        let (ip, _port) = bracketed.split_once(']')?;
+        return ip.parse().ok();
+    }
+    let (ip, _port) = node.split_once(':')?;
+    ip.parse().ok()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use actix_http::header::HeaderValue;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn proxies() -> ProxiesConfig {
+        ProxiesConfig::new(vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "fd00::/8".parse().unwrap(),
+        ])
+    }
+
+    fn client(headers: &[(header::HeaderName, &str)], peer: &str) -> Option<IpAddr> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        client_ip(&map, Some(peer.parse().unwrap()), &proxies())
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_is_the_client() {
+        assert_eq!(
+            client(&[(X_FORWARDED_FOR, "198.51.100.1")], "203.0.113.7"),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn skips_trusted_proxies_of_x_forwarded_for() {
+        assert_eq!(
+            client(
+                &[(X_FORWARDED_FOR, "192.0.2.1, 198.51.100.1, 10.0.0.2")],
+                "10.0.0.1"
+            ),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            client(
+                &[
+                    (X_FORWARDED_FOR, "198.51.100.1"),
+                    (X_FORWARDED_FOR, "10.0.0.2")
+                ],
+                "10.0.0.1"
+            ),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            client(&[(X_FORWARDED_FOR, "10.0.0.3, 10.0.0.2")], "10.0.0.1"),
+            ip("10.0.0.3")
+        );
+        assert_eq!(client(&[], "10.0.0.1"), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn prefers_forwarded() {
+        assert_eq!(
+            client(
+                &[
+                    (X_FORWARDED_FOR, "192.0.2.1"),
+                    (
+                        header::FORWARDED,
+                        r#"for="[2001:db8:cafe::17]:4711";proto=https, For="[fd00::2]""#
+                    ),
+                ],
+                "10.0.0.1"
+            ),
+            ip("2001:db8:cafe::17")
+        );
+        assert_eq!(
+            client(
+                &[(header::FORWARDED, "by=10.0.0.1;for=\"192.0.2.43:47011\"")],
+                "10.0.0.1"
+            ),
+            ip("192.0.2.43")
+        );
+    }
+
+    #[test]
+    fn stops_at_unknown_hops() {
+        assert_eq!(
+            client(
+                &[(
+                    header::FORWARDED,
+                    "for=192.0.2.1, for=_hidden, for=10.0.0.2"
+                )],
+                "10.0.0.1"
+            ),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            client(&[(X_FORWARDED_FOR, "unknown")], "10.0.0.1"),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    future::{ready, Ready},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use actix_http::{
+    body::{BodySize, BoxBody, MessageBody},
+    HttpMessage, Payload,
+};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use bytes::Bytes;
+use futures::{future::LocalBoxFuture, StreamExt};
+
+use crate::metrics::{
+    app_metrics::{AppMetrics, OTHER_ROUTE},
+    transfer_guard::TransferGuard,
+};
+
+/// Response extension of file contents, the download is active until the body is sent
+#[derive(Debug, Clone, Copy)]
+pub struct Download;
+
+/// Records latency by route pattern, method and status, and the body bytes of every request.
+/// Wraps the other middlewares, so bytes are counted as they go over the wire.
+#[derive(Debug, Clone)]
+pub struct MetricsMiddlewareFactory(AppMetrics);
+
+impl MetricsMiddlewareFactory {
+    pub fn new(metrics: AppMetrics) -> Self {
+        Self(metrics)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for MetricsMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<MeteredBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddleware {
+            service,
+            metrics: self.0.clone(),
+        }))
+    }
+}
+
+pub struct MetricsMiddleware<S> {
+    service: S,
+    metrics: AppMetrics,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<MeteredBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| OTHER_ROUTE.to_owned());
+        let method = req.method().as_str().to_owned();
+        let payload = req.take_payload().inspect({
+            let (metrics, route, method) = (self.metrics.clone(), route.clone(), method.clone());
+            move |chunk| {
+                if let Ok(chunk) = chunk {
+                    metrics.received(&route, &method, chunk.len() as u64);
+                }
+            }
+        });
+        req.set_payload(Payload::from(payload.boxed_local()));
+
+        let metrics = self.metrics.clone();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = match fut.await {
+                Ok(res) => res,
+                Err(e) => {
+                    let status = e.as_response_error().status_code().as_u16();
+                    metrics.request_finished(&route, &method, status, started.elapsed());
+                    return Err(e);
+                }
+            };
+            metrics.request_finished(&route, &method, res.status().as_u16(), started.elapsed());
+            let download = res
+                .response()
+                .extensions()
+                .contains::<Download>()
+                .then(|| metrics.download_started());
+            Ok(res.map_body(|_, body| MeteredBody {
+                body: body.boxed(),
+                route,
+                method,
+                metrics,
+                _download: download,
+            }))
+        })
+    }
+}
+
+/// Counts the bytes of the response as they are sent
+pub struct MeteredBody {
+    body: BoxBody,
+    route: String,
+    method: String,
+    metrics: AppMetrics,
+    _download: Option<TransferGuard>,
+}
+
+impl MessageBody for MeteredBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            self.metrics
+                .sent(&self.route, &self.method, chunk.len() as u64);
+        }
+        poll
+    }
+}
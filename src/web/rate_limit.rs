@@ -0,0 +1,373 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+};
+
+use actix_http::{header::HeaderValue, HttpMessage};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, ResponseError,
+};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+    auth::principal::Principal,
+    config::app_config::RouteGroupConfig,
+    rate_limit::{
+        rate_limit_key::RateLimitKey, rate_limit_rules::RateLimitRules,
+        rate_limit_store::RateLimitStore, token_bucket::RateLimitDecision,
+    },
+    utils::time::Time,
+};
+
+use super::{
+    app_data::AppData,
+    common::api_error::{ceil_seconds, ApiError},
+    forwarded::ClientIp,
+};
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub const LIMIT_HEADER_NAME: actix_http::header::HeaderName =
+    actix_http::header::HeaderName::from_static("ratelimit-limit");
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub const REMAINING_HEADER_NAME: actix_http::header::HeaderName =
+    actix_http::header::HeaderName::from_static("ratelimit-remaining");
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub const RESET_HEADER_NAME: actix_http::header::HeaderName =
+    actix_http::header::HeaderName::from_static("ratelimit-reset");
+
+/// Token bucket limits per client IP and per principal for the configured route groups.
+///
+/// It has to be wrapped inside the authentication middleware to see the principal.
+pub struct RateLimitMiddlewareFactory<D> {
+    data: Arc<D>,
+    rules: RateLimitRules,
+}
+
+impl<D> RateLimitMiddlewareFactory<D> {
+    pub fn new(data: Arc<D>, rules: RateLimitRules) -> Self {
+        Self { data, rules }
+    }
+}
+
+impl<S, B, D: AppData + 'static> Transform<S, ServiceRequest> for RateLimitMiddlewareFactory<D>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S, D>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            data: self.data.clone(),
+            rules: self.rules.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S, D> {
+    service: Rc<S>,
+    data: Arc<D>,
+    rules: RateLimitRules,
+}
+
+impl<S, B, D: AppData + 'static> Service<ServiceRequest> for RateLimitMiddleware<S, D>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let data = self.data.clone();
+        let config = self.rules.get();
+
+        Box::pin(async move {
+            let decision = match config.group_for(req.path()) {
+                Some(group) => check(data.as_ref(), group, &req).await,
+                None => None,
+            };
+
+            let Some(decision) = decision else {
+                return service.call(req).await.map(|res| res.map_into_left_body());
+            };
+
+            if !decision.allowed {
+                tracing::info!(
+                    path = req.path(),
+                    limit = decision.limit,
+                    "rate limit exceeded"
+                );
+                let mut error =
+                    ApiError::too_many_requests().message("rate limit exceeded".to_owned());
+                if let Some(retry_after) = decision.retry_after {
+                    error = error.retry_after(retry_after);
+                }
+                let mut res = req.into_response(error.build().error_response());
+                insert_headers(&mut res, &decision);
+                return Ok(res.map_into_right_body());
+            }
+
+            let mut res = service.call(req).await?;
+            insert_headers(&mut res, &decision);
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+/// Takes a token from every applicable bucket and returns the most restrictive decision
+async fn check<D: AppData>(
+    data: &D,
+    group: &RouteGroupConfig,
+    req: &ServiceRequest,
+) -> Option<RateLimitDecision> {
+    let now = data.time().now();
+    let ip = req.extensions().get::<ClientIp>().map(|ip| ip.0);
+    let principal = req.extensions().get::<Principal>().copied();
+
+    let ip_limit = ip.zip(group.per_ip()).map(|(ip, limit)| {
+        let key = RateLimitKey::Ip {
+            group: group.name(),
+            ip,
+        };
+        (key, limit)
+    });
+    let principal_limit = principal.zip(group.per_principal()).map(|(p, limit)| {
+        let key = RateLimitKey::Principal {
+            group: group.name(),
+            id: p.id(),
+        };
+        (key, limit)
+    });
+
+    let mut result: Option<RateLimitDecision> = None;
+    for (key, limit) in ip_limit.into_iter().chain(principal_limit) {
+        let decision = match data.rate_limits().acquire(&key, limit, now).await {
+            Ok(decision) => decision,
+            Err(e) => {
+                // fail open, an unavailable store must not take the whole service down
+                tracing::error!("rate limit store error: {}", e);
+                continue;
+            }
+        };
+        result = match result {
+            Some(r) if (r.allowed, r.remaining) <= (decision.allowed, decision.remaining) => {
+                Some(r)
+            }
+            _ => Some(decision),
+        };
+    }
+    result
+}
+
+fn insert_headers<B>(res: &mut ServiceResponse<B>, decision: &RateLimitDecision) {
+    let headers = res.headers_mut();
+    headers.insert(LIMIT_HEADER_NAME, HeaderValue::from(decision.limit));
+    headers.insert(REMAINING_HEADER_NAME, HeaderValue::from(decision.remaining));
+    headers.insert(
+        RESET_HEADER_NAME,
+        HeaderValue::from(ceil_seconds(decision.reset_after)),
+    );
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::*,
+        utc,
+        web::common::{api_error::ErrorCode, api_result::ApiResult},
+    };
+    use actix_web::web::{self, Json, ServiceConfig};
+    use chrono::Duration;
+    use client::TestHttpResponse;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    async fn route() -> ApiResult<()> {
+        Ok(Json(()))
+    }
+
+    fn configure(cfg: &mut ServiceConfig) {
+        cfg.route("/test/rate_limited", web::get().to(route))
+            .route("/test/unlimited", web::get().to(route));
+    }
+
+    fn header(response: &TestHttpResponse, name: &str) -> Option<String> {
+        response
+            .headers
+            .get(name)
+            .map(|v| v.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn limits_by_ip() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            ctx.time().set(utc!(2024));
+
+            // act
+            let mut responses = vec![];
+            for _ in 0..4 {
+                responses.push(server.client().get("/test/rate_limited").send().await);
+            }
+
+            // assert
+            let remaining: Vec<_> = responses
+                .iter()
+                .map(|r| header(r, "ratelimit-remaining"))
+                .collect();
+            assert_eq!(
+                remaining,
+                vec![
+                    Some("2".to_owned()),
+                    Some("1".to_owned()),
+                    Some("0".to_owned()),
+                    Some("0".to_owned())
+                ]
+            );
+            assert_eq!(
+                header(&responses[0], "ratelimit-limit"),
+                Some("3".to_owned())
+            );
+            assert_eq!(
+                header(&responses[0], "ratelimit-reset"),
+                Some("1".to_owned())
+            );
+
+            let denied = &responses[3];
+            assert_eq!(denied.unwrap_err().code, ErrorCode::TooManyRequests);
+            assert_eq!(header(denied, "retry-after"), Some("1".to_owned()));
+        });
+    }
+
+    async fn get_from(server: &server::TestServer, ip: &str) -> TestHttpResponse {
+        server
+            .client()
+            .get("/test/rate_limited")
+            .insert_header(("x-forwarded-for", ip))
+            .send()
+            .await
+    }
+
+    #[test]
+    fn limits_the_forwarded_client_of_a_trusted_proxy() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["proxies"]["trusted"] = serde_json::json!(["127.0.0.1"])
+            });
+            let server = ctx.run_server_with(configure).await;
+            ctx.time().set(utc!(2024));
+            for _ in 0..3 {
+                get_from(&server, "198.51.100.7").await.unwrap::<()>();
+            }
+
+            // act
+            let same = get_from(&server, "198.51.100.7").await;
+            let other = get_from(&server, "203.0.113.9").await;
+
+            // assert
+            assert_eq!(same.unwrap_err().code, ErrorCode::TooManyRequests);
+            other.unwrap::<()>();
+            assert_eq!(header(&other, "ratelimit-remaining"), Some("2".to_owned()));
+        });
+    }
+
+    #[test]
+    fn bucket_refills_with_time() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+            let now = utc!(2024);
+            ctx.time().set(now);
+            for _ in 0..3 {
+                server.client().get("/test/rate_limited").send().await;
+            }
+
+            // act
+            ctx.time().set(now + Duration::seconds(1));
+            let response = server.client().get("/test/rate_limited").send().await;
+
+            // assert
+            response.unwrap::<()>();
+            assert_eq!(
+                header(&response, "ratelimit-remaining"),
+                Some("0".to_owned())
+            );
+        });
+    }
+
+    #[test]
+    fn limits_by_principal() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server_with(configure).await;
+            ctx.time().set(utc!(2024));
+
+            // act
+            let mut responses = vec![];
+            for _ in 0..3 {
+                let response = server
+                    .client()
+                    .get("/test/rate_limited")
+                    .access_token(&token)
+                    .send()
+                    .await;
+                responses.push(response);
+            }
+
+            // assert
+            assert_eq!(
+                header(&responses[0], "ratelimit-limit"),
+                Some("2".to_owned())
+            );
+            assert_eq!(
+                header(&responses[0], "ratelimit-remaining"),
+                Some("1".to_owned())
+            );
+            assert_eq!(responses[2].unwrap_err().code, ErrorCode::TooManyRequests);
+            assert_eq!(header(&responses[2], "retry-after"), Some("2".to_owned()));
+        });
+    }
+
+    #[test]
+    fn paths_without_group_are_not_limited() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server_with(configure).await;
+
+            // act
+            let mut responses = vec![];
+            for _ in 0..5 {
+                responses.push(server.client().get("/test/unlimited").send().await);
+            }
+
+            // assert
+            for response in responses {
+                response.unwrap::<()>();
+                assert_eq!(header(&response, "ratelimit-limit"), None);
+            }
+            assert!(ctx.rate_limits().is_empty());
+        });
+    }
+}
@@ -1,9 +1,139 @@
+mod audit;
+mod auth;
+mod encryption;
+mod fs;
+mod health;
+mod hosting;
 mod info;
+mod logging;
+mod metrics;
+mod openapi;
 
 use actix_web::web;
 
 use super::app_data::AppData;
 
 pub fn configure<D: AppData + 'static>(cfg: &mut web::ServiceConfig) {
-    cfg.route("/api/info/v1", web::get().to(info::info::<D>));
+    cfg.route("/api/openapi.json", web::get().to(openapi::openapi))
+        .route("/metrics", web::get().to(metrics::metrics::<D>))
+        .route("/health/live", web::get().to(health::live))
+        .route("/health/ready", web::get().to(health::ready::<D>))
+        .route("/api/info/v1", web::get().to(info::info::<D>))
+        .route(
+            "/api/logging/filter/v1",
+            web::get().to(logging::get_filter::<D>),
+        )
+        .route(
+            "/api/logging/filter/v1",
+            web::put().to(logging::set_filter::<D>),
+        )
+        .route("/api/audit/events/v1", web::get().to(audit::list::<D>))
+        .route("/api/audit/export/v1", web::get().to(audit::export::<D>))
+        .route("/api/audit/verify/v1", web::get().to(audit::verify::<D>))
+        .route(
+            "/api/auth/login/v1",
+            web::post().to(auth::login::login::<D>),
+        )
+        .route(
+            "/api/auth/login/totp/v1",
+            web::post().to(auth::login::login_totp::<D>),
+        )
+        .route(
+            "/api/auth/totp/enroll/v1",
+            web::post().to(auth::totp::enroll::<D>),
+        )
+        .route(
+            "/api/auth/totp/confirm/v1",
+            web::post().to(auth::totp::confirm::<D>),
+        )
+        .route(
+            "/api/auth/totp/v1",
+            web::delete().to(auth::totp::disable::<D>),
+        )
+        .route(
+            "/api/auth/tokens/v1",
+            web::post().to(auth::personal_access_tokens::create::<D>),
+        )
+        .route(
+            "/api/auth/tokens/v1",
+            web::get().to(auth::personal_access_tokens::list::<D>),
+        )
+        .route(
+            "/api/auth/tokens/v1/{token_id}",
+            web::delete().to(auth::personal_access_tokens::delete::<D>),
+        )
+        .route(
+            "/api/auth/oidc/{provider}/authorize/v1",
+            web::post().to(auth::oidc::authorize::<D>),
+        )
+        .route(
+            "/api/auth/oidc/{provider}/callback/v1",
+            web::post().to(auth::oidc::callback::<D>),
+        )
+        .route(
+            "/api/auth/cookie/v1",
+            web::post().to(auth::session_cookie::create::<D>),
+        )
+        .route(
+            "/api/auth/cookie/v1",
+            web::delete().to(auth::session_cookie::delete::<D>),
+        )
+        .route(
+            "/api/encryption/rotate/v1",
+            web::post().to(encryption::rotate::<D>),
+        )
+        .route("/api/sources/v1", web::get().to(fs::sources::list::<D>))
+        .route(
+            "/api/sources/v1/{source_id}/dedup",
+            web::get().to(fs::sources::dedup_stats::<D>),
+        )
+        .route(
+            "/api/sources/v1/{source_id}/gc",
+            web::post().to(fs::sources::collect_garbage::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/stat/{path:.*}",
+            web::get().to(fs::files::stat::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/list/{path:.*}",
+            web::get().to(fs::files::list::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/files/{path:.*}",
+            web::get().to(fs::files::download::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/files/{path:.*}",
+            web::put().to(fs::files::upload::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/files/{path:.*}",
+            web::delete().to(fs::files::delete::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/mkdir/{path:.*}",
+            web::post().to(fs::files::mkdir::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/rename",
+            web::post().to(fs::files::rename::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/versions/{path:.*}",
+            web::get().to(fs::versions::list::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/version/{version_id}",
+            web::get().to(fs::versions::download::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/version/{version_id}",
+            web::delete().to(fs::versions::delete::<D>),
+        )
+        .route(
+            "/api/fs/v1/{source_id}/version/{version_id}/restore",
+            web::post().to(fs::versions::restore::<D>),
+        )
+        .default_service(web::to(hosting::serve::<D>));
 }
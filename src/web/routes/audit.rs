@@ -0,0 +1,459 @@
+use std::net::IpAddr;
+
+use actix_web::{http::header, web, HttpResponse};
+use bytes::Bytes;
+use futures::stream;
+use uuid::Uuid;
+
+use crate::{
+    audit::{
+        audit_event::{verify_chain, AuditAction, AuditEvent, AuditOutcome},
+        audit_filter::AuditFilter,
+        chain_hash::ChainHash,
+    },
+    auth::principal::Principal,
+    config::app_config::AppConfig,
+    dal::{audit_events_dal::AuditEventsDal, Dal},
+    utils::{id::Id, trace_id::TraceId},
+    web::{
+        app_data::AppData,
+        auth::admin_access::require_admin,
+        common::{api_error::ApiError, api_result::ApiResult, serde_chrono::ApiDateTime},
+    },
+};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct AuditQuery {
+    /// Sequence number of the last event of the previous page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<Id>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<AuditAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<AuditOutcome>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_prefix: Option<String>,
+    /// Inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<ApiDateTime>,
+    /// Exclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<ApiDateTime>,
+}
+
+impl AuditQuery {
+    fn filter(&self) -> AuditFilter {
+        AuditFilter {
+            principal: self.principal,
+            action: self.action,
+            outcome: self.outcome,
+            source_id: self.source_id,
+            target_prefix: self.target_prefix.clone(),
+            from: self.from.map(|from| *from),
+            to: self.to.map(|to| *to),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct AuditEventInfo {
+    pub sequence: u64,
+    pub timestamp: ApiDateTime,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<Id>,
+    pub trace_id: TraceId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub previous_hash: ChainHash,
+    pub hash: ChainHash,
+}
+
+impl From<&AuditEvent> for AuditEventInfo {
+    fn from(value: &AuditEvent) -> Self {
+        let entry = value.entry();
+        Self {
+            sequence: value.sequence(),
+            timestamp: value.timestamp().into(),
+            action: entry.action(),
+            outcome: entry.outcome(),
+            reason: entry.reason().map(str::to_owned),
+            principal: entry.principal(),
+            trace_id: entry.trace_id(),
+            client_ip: entry.client_ip(),
+            source_id: entry.source_id(),
+            target: entry.target().map(str::to_owned),
+            detail: entry.detail().map(str::to_owned),
+            previous_hash: value.previous_hash(),
+            hash: value.hash(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct AuditEventPage {
+    pub events: Vec<AuditEventInfo>,
+    /// Passed as `after` for the next page, absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_after: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct AuditVerification {
+    pub valid: bool,
+    /// Events checked, up to the first invalid one
+    pub events: u64,
+    /// Sequence number of the first event which was changed or removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_invalid: Option<u64>,
+}
+
+pub async fn list<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    query: web::Query<AuditQuery>,
+) -> ApiResult<AuditEventPage> {
+    require_admin(data.as_ref(), config.audit().admins(), principal).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ApiError::bad_reques()
+            .message(format!("limit must be 1 to {}", MAX_LIMIT))
+            .build());
+    }
+    let events = data
+        .dal()
+        .audit_events()
+        .list(&query.filter(), query.after.unwrap_or(0), limit)
+        .await?;
+    let next_after = events
+        .last()
+        .filter(|_| events.len() == limit)
+        .map(|e| e.sequence());
+    Ok(web::Json(AuditEventPage {
+        events: events.iter().map(Into::into).collect(),
+        next_after,
+    }))
+}
+
+/// Every matching event as JSON lines, streamed page by page
+pub async fn export<D: AppData + 'static>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, ApiError> {
+    require_admin(data.as_ref(), config.audit().admins(), principal).await?;
+    let filter = query.filter();
+    let start = Some(query.after.unwrap_or(0));
+    let pages = stream::unfold(start, move |after| {
+        let data = data.clone();
+        let filter = filter.clone();
+        async move {
+            let after = after?;
+            let page = data
+                .dal()
+                .audit_events()
+                .list(&filter, after, MAX_LIMIT)
+                .await;
+            let events = match page {
+                Ok(events) if events.is_empty() => return None,
+                Ok(events) => events,
+                Err(e) => {
+                    tracing::error!("audit export aborted: {}", e);
+                    return Some((Err(e), None));
+                }
+            };
+            let mut lines = Vec::new();
+            for event in &events {
+                serde_json::to_writer(&mut lines, &AuditEventInfo::from(event))
+                    .expect("audit events always serialize");
+                lines.push(b'\n');
+            }
+            let next = events
+                .last()
+                .filter(|_| events.len() == MAX_LIMIT)
+                .map(|e| e.sequence());
+            Some((Ok(Bytes::from(lines)), next))
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"audit.jsonl\"",
+        ))
+        .streaming(pages))
+}
+
+/// Checks the hash chain of the whole log
+pub async fn verify<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+) -> ApiResult<AuditVerification> {
+    require_admin(data.as_ref(), config.audit().admins(), principal).await?;
+    let all = AuditFilter::default();
+    let mut previous: Option<AuditEvent> = None;
+    let mut count = 0;
+    loop {
+        let after = previous.as_ref().map_or(0, |p| p.sequence());
+        let events = data
+            .dal()
+            .audit_events()
+            .list(&all, after, MAX_LIMIT)
+            .await?;
+        if let Err(sequence) = verify_chain(previous.as_ref(), &events) {
+            tracing::warn!(sequence = sequence, "audit log has been tampered with");
+            return Ok(web::Json(AuditVerification {
+                valid: false,
+                events: sequence - 1,
+                first_invalid: Some(sequence),
+            }));
+        }
+        count += events.len() as u64;
+        if events.len() < MAX_LIMIT {
+            break;
+        }
+        previous = events.into_iter().last();
+    }
+    Ok(web::Json(AuditVerification {
+        valid: true,
+        events: count,
+        first_invalid: None,
+    }))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{auth::content_right::ContentRight, test::*};
+    use actix_http::StatusCode;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    const EVENTS: &str = "/api/audit/events/v1";
+
+    #[test]
+    fn records_logins_and_file_access() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["audit"]["admins"] = json!(["admin"]));
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "a" }).await;
+            let admin = ctx.create_login("admin", "password").await;
+            let user = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&user, ContentRight::Read | ContentRight::Write)
+                .await;
+            let server = ctx.run_server().await;
+            let client = server.client();
+            let token = ctx.access_token_for(&user);
+
+            // act
+            let failed = client
+                .post("/api/auth/login/v1")
+                .json(&json!({ "username": "user", "password": "wrong" }))
+                .send()
+                .await;
+            client
+                .get(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            client
+                .put(&format!("/api/fs/v1/{}/files/b.txt", source.id()))
+                .access_token(&token)
+                .body(Bytes::from("b"))
+                .send()
+                .await;
+            client
+                .post(&format!("/api/fs/v1/{}/rename", source.id()))
+                .access_token(&token)
+                .json(&json!({ "from": "b.txt", "to": "c.txt" }))
+                .send()
+                .await;
+            let admin_token = ctx.access_token_for(&admin);
+            let all = client.get(EVENTS).access_token(&admin_token).send().await;
+            let page = client
+                .get(&format!(
+                    "{}?action=file_moved&limit=1&from=0&to=4102444800000",
+                    EVENTS
+                ))
+                .access_token(&admin_token)
+                .send()
+                .await;
+            let verification = client
+                .get("/api/audit/verify/v1")
+                .access_token(&admin_token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(failed.status, StatusCode::UNAUTHORIZED);
+            let all = all.unwrap::<AuditEventPage>();
+            let summary: Vec<_> = all
+                .events
+                .iter()
+                .map(|e| (e.sequence, e.action, e.outcome, e.target.as_deref()))
+                .collect();
+            assert_eq!(
+                summary,
+                vec![
+                    (1, AuditAction::Login, AuditOutcome::Failure, Some("user")),
+                    (
+                        2,
+                        AuditAction::FileRead,
+                        AuditOutcome::Success,
+                        Some("/a.txt")
+                    ),
+                    (
+                        3,
+                        AuditAction::FileWritten,
+                        AuditOutcome::Success,
+                        Some("/b.txt")
+                    ),
+                    (
+                        4,
+                        AuditAction::FileMoved,
+                        AuditOutcome::Success,
+                        Some("/b.txt")
+                    ),
+                ]
+            );
+            assert_eq!(all.next_after, None);
+            assert_eq!(all.events[0].reason.as_deref(), Some("invalid_credentials"));
+            assert_eq!(all.events[0].principal, None);
+            assert_eq!(all.events[0].trace_id, failed.trace_id());
+            assert_eq!(all.events[0].client_ip, Some("127.0.0.1".parse().unwrap()));
+            assert_eq!(all.events[1].principal, Some(user.login_id()));
+            assert_eq!(all.events[1].source_id, Some(source.id()));
+            assert_eq!(all.events[1].previous_hash, all.events[0].hash);
+
+            let page = page.unwrap::<AuditEventPage>();
+            assert_eq!(page.events.len(), 1);
+            assert_eq!(page.events[0].detail.as_deref(), Some("/c.txt"));
+            assert_eq!(page.next_after, Some(4));
+
+            assert_eq!(
+                verification.unwrap::<AuditVerification>(),
+                AuditVerification {
+                    valid: true,
+                    events: 4,
+                    first_invalid: None,
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn exports_json_lines_page_by_page() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["audit"]["admins"] = json!(["admin"]));
+            let admin = ctx.create_login("admin", "password").await;
+            let server = ctx.run_server().await;
+            let client = server.client();
+            for _ in 0..3 {
+                client
+                    .post("/api/auth/login/v1")
+                    .json(&json!({ "username": "nobody", "password": "wrong" }))
+                    .send()
+                    .await;
+            }
+
+            // act
+            let response = client
+                .get("/api/audit/export/v1?after=1")
+                .access_token(&ctx.access_token_for(&admin))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::OK);
+            assert_eq!(
+                response.headers.get("content-type").unwrap(),
+                "application/x-ndjson"
+            );
+            let events: Vec<AuditEventInfo> = response
+                .text()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(
+                events.iter().map(|e| e.sequence).collect::<Vec<_>>(),
+                vec![2, 3]
+            );
+        });
+    }
+
+    #[test]
+    fn other_logins_are_forbidden() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["audit"]["admins"] = json!(["admin"]));
+            let user = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+            let token = ctx.access_token_for(&user);
+
+            // act
+            let events = server
+                .client()
+                .get(EVENTS)
+                .access_token(&token)
+                .send()
+                .await;
+            let export = server
+                .client()
+                .get("/api/audit/export/v1")
+                .access_token(&token)
+                .send()
+                .await;
+            let anonymous = server.client().get(EVENTS).send().await;
+
+            // assert
+            assert_eq!(events.status, StatusCode::FORBIDDEN);
+            assert_eq!(export.status, StatusCode::FORBIDDEN);
+            assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+        });
+    }
+
+    #[test]
+    fn nothing_is_recorded_if_disabled() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["audit"]["enabled"] = json!(false));
+            let server = ctx.run_server().await;
+
+            // act
+            server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&json!({ "username": "nobody", "password": "wrong" }))
+                .send()
+                .await;
+
+            // assert
+            let last = ctx.dal().audit_events().last().await.unwrap();
+            assert_eq!(last, None);
+        });
+    }
+}
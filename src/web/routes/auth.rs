@@ -0,0 +1,6 @@
+pub mod login;
+pub mod oidc;
+pub mod personal_access_tokens;
+pub mod session_cookie;
+pub mod token_pair;
+pub mod totp;
@@ -0,0 +1,706 @@
+use actix_web::{web, HttpMessage, HttpRequest};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        login_lockout::LoginLockout,
+        pwd::Pwd,
+        tokens::{
+            access_token_claims::AccessTokenClaims,
+            encoder::{JwtTokenDecoder, JwtTokenEncoder},
+            refresh_token_claims::RefreshTokenClaims,
+            totp_challenge_claims::TotpChallengeClaims,
+        },
+    },
+    config::app_config::AppConfig,
+    dal::{login_totps_dal::LoginTotpsDal, logins_dal::LoginsDal, Dal},
+    utils::{
+        id_generator::IdGenerator,
+        secret::{expose, Secret},
+        time::Time,
+    },
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        common::{api_error::ApiError, api_result::ApiResult, serde_chrono::ApiDateTime},
+        forwarded::ClientIp,
+    },
+};
+
+use super::token_pair::{self, encode_error, TokenPair};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LoginRequest {
+    pub username: String,
+    #[serde(serialize_with = "expose")]
+    pub password: Secret<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoginResponse {
+    Tokens(TokenPair),
+    TotpRequired(TotpChallenge),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TotpChallenge {
+    pub challenge_token: String,
+    pub expires_at: ApiDateTime,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LoginTotpRequest {
+    pub challenge_token: String,
+    /// TOTP code or one of the recovery codes
+    #[serde(serialize_with = "expose")]
+    pub code: Secret<String>,
+}
+
+/// Password step. Returns a token pair, or a challenge if the login has TOTP enabled.
+#[allow(clippy::too_many_arguments)]
+pub async fn login<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    access: web::Data<JwtTokenEncoder<AccessTokenClaims>>,
+    refresh: web::Data<JwtTokenEncoder<RefreshTokenClaims>>,
+    challenge: web::Data<JwtTokenEncoder<TotpChallengeClaims>>,
+    req: HttpRequest,
+    audit: AuditContext,
+    request: web::Json<LoginRequest>,
+) -> ApiResult<LoginResponse> {
+    let request = request.into_inner();
+    let auth = config.auth();
+    let now = data.time().now();
+    let ip = req.extensions().get::<ClientIp>().map(|ip| ip.0);
+    let lockout = LoginLockout::new(data.rate_limits(), auth.lockout());
+    if let Some(until) = lockout.locked_until(&request.username, ip, now).await? {
+        tracing::info!("login is locked out until {}", until);
+        data.metrics().auth_failed("password", "locked_out");
+        let entry = audit
+            .entry(AuditAction::Login)
+            .with_target(&request.username);
+        audit
+            .record(data.as_ref(), entry.failed("locked_out"))
+            .await;
+        return Err(locked_out(until, now));
+    }
+
+    let login = data
+        .dal()
+        .logins()
+        .find_by_username(&request.username)
+        .await?;
+    // an unknown login or one without a password takes as long as a wrong password
+    let verified = match login.as_ref().and_then(|login| login.password()) {
+        Some(pwd) => pwd.verify(&request.password),
+        None => Pwd::verify_missing(&request.password),
+    };
+    let login = match login {
+        Some(login) if verified => login,
+        _ => {
+            tracing::info!("invalid username or password");
+            data.metrics()
+                .auth_failed("password", "invalid_credentials");
+            let entry = audit
+                .entry(AuditAction::Login)
+                .with_target(&request.username);
+            audit
+                .record(data.as_ref(), entry.failed("invalid_credentials"))
+                .await;
+            lockout.failed(&request.username, ip, now).await?;
+            return Err(invalid_credentials());
+        }
+    };
+
+    let totp = data.dal().login_totps().get(login.login_id()).await?;
+    if totp.is_some_and(|t| t.is_confirmed()) {
+        tracing::info!(login_id = %login.login_id(), "password accepted, totp required");
+        let expires_at = now + auth.totp().challenge_ttl();
+        let challenge_token = challenge
+            .encode(&TotpChallengeClaims {
+                jti: data.id().next_id(),
+                sub: login.login_id(),
+                exp: expires_at.into(),
+                iat: now.into(),
+            })
+            .map_err(encode_error)?;
+        return Ok(web::Json(LoginResponse::TotpRequired(TotpChallenge {
+            challenge_token,
+            expires_at: expires_at.into(),
+        })));
+    }
+
+    lockout.succeeded(login.username(), now).await?;
+    tracing::info!(login_id = %login.login_id(), "logged in");
+    data.metrics().auth_succeeded("password");
+    let entry = audit
+        .entry(AuditAction::Login)
+        .with_principal(Some(login.login_id()))
+        .with_target(login.username());
+    audit.record(data.as_ref(), entry).await;
+    let tokens = token_pair::issue(data.get_ref(), auth, &access, &refresh, login.login_id())?;
+    Ok(web::Json(LoginResponse::Tokens(tokens)))
+}
+
+/// Second step. Exchanges a challenge and a TOTP (or recovery) code for a token pair.
+#[allow(clippy::too_many_arguments)]
+pub async fn login_totp<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    access: web::Data<JwtTokenEncoder<AccessTokenClaims>>,
+    refresh: web::Data<JwtTokenEncoder<RefreshTokenClaims>>,
+    challenge: web::Data<JwtTokenDecoder<TotpChallengeClaims>>,
+    req: HttpRequest,
+    audit: AuditContext,
+    request: web::Json<LoginTotpRequest>,
+) -> ApiResult<TokenPair> {
+    let request = request.into_inner();
+    let now = data.time().now();
+    let claims = match challenge.decode_at(&request.challenge_token, now) {
+        Ok(token) => token.claims,
+        Err(e) => {
+            tracing::info!("invalid totp challenge: {}", e);
+            data.metrics().auth_failed("totp", "invalid_challenge");
+            let entry = audit.entry(AuditAction::Login);
+            audit
+                .record(data.as_ref(), entry.failed("invalid_challenge"))
+                .await;
+            return Err(ApiError::unauthorized()
+                .message("invalid or expired challenge".to_owned())
+                .build());
+        }
+    };
+
+    let login = data.dal().logins().get(claims.sub()).await?;
+    let totp = data.dal().login_totps().get(claims.sub()).await?;
+    let (login, expected) = match (login, totp) {
+        (Some(login), Some(totp)) if totp.is_confirmed() => (login, totp),
+        _ => {
+            tracing::info!(login_id = %claims.sub(), "login or its totp doesn't exist anymore");
+            data.metrics().auth_failed("totp", "invalid_credentials");
+            let entry = audit
+                .entry(AuditAction::Login)
+                .with_principal(Some(claims.sub()));
+            audit
+                .record(data.as_ref(), entry.failed("invalid_credentials"))
+                .await;
+            return Err(invalid_credentials());
+        }
+    };
+
+    let auth = config.auth();
+    let ip = req.extensions().get::<ClientIp>().map(|ip| ip.0);
+    let lockout = LoginLockout::new(data.rate_limits(), auth.lockout());
+    let entry = audit
+        .entry(AuditAction::Login)
+        .with_principal(Some(login.login_id()))
+        .with_target(login.username());
+    if let Some(until) = lockout.locked_until(login.username(), ip, now).await? {
+        tracing::info!(login_id = %login.login_id(), "login is locked out until {}", until);
+        data.metrics().auth_failed("totp", "locked_out");
+        audit
+            .record(data.as_ref(), entry.failed("locked_out"))
+            .await;
+        return Err(locked_out(until, now));
+    }
+
+    let mut totp = expected.clone();
+    if !totp.verify_code_or_recovery_code(&request.code, now, auth.totp().skew_steps()) {
+        tracing::info!(login_id = %login.login_id(), "invalid totp code");
+        data.metrics().auth_failed("totp", "invalid_code");
+        audit
+            .record(data.as_ref(), entry.failed("invalid_code"))
+            .await;
+        lockout.failed(login.username(), ip, now).await?;
+        return Err(invalid_code());
+    }
+
+    let totps = data.dal().login_totps();
+    if !totps
+        .consume_challenge(claims.jti(), claims.exp(), now)
+        .await?
+    {
+        tracing::info!(login_id = %login.login_id(), "totp challenge already used");
+        data.metrics().auth_failed("totp", "invalid_challenge");
+        audit
+            .record(data.as_ref(), entry.failed("invalid_challenge"))
+            .await;
+        return Err(ApiError::unauthorized()
+            .message("invalid or expired challenge".to_owned())
+            .build());
+    }
+    // the code is accepted only if no concurrent request used it (or a recovery code) first
+    if !totps.compare_and_save(&expected, totp).await? {
+        tracing::info!(login_id = %login.login_id(), "totp code used concurrently");
+        data.metrics().auth_failed("totp", "invalid_code");
+        audit
+            .record(data.as_ref(), entry.failed("invalid_code"))
+            .await;
+        lockout.failed(login.username(), ip, now).await?;
+        return Err(invalid_code());
+    }
+    lockout.succeeded(login.username(), now).await?;
+
+    tracing::info!(login_id = %login.login_id(), "logged in with totp");
+    data.metrics().auth_succeeded("totp");
+    audit.record(data.as_ref(), entry).await;
+    let tokens = token_pair::issue(data.get_ref(), auth, &access, &refresh, login.login_id())?;
+    Ok(web::Json(tokens))
+}
+
+fn invalid_credentials() -> ApiError {
+    ApiError::unauthorized()
+        .message("invalid username or password".to_owned())
+        .build()
+}
+
+pub(super) fn locked_out(until: DateTime<Utc>, now: DateTime<Utc>) -> ApiError {
+    ApiError::too_many_requests()
+        .message("too many failed login attempts".to_owned())
+        .retry_after(until - now)
+        .build()
+}
+
+pub(super) fn invalid_code() -> ApiError {
+    ApiError::unauthorized()
+        .message("invalid code".to_owned())
+        .build()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::{
+            login::Login, login_totp::LoginTotp, recovery_code::RecoveryCodeHash, totp::TotpSecret,
+        },
+        test::*,
+        utc,
+        utils::id_generator::IdGenerator,
+        web::common::api_error::ErrorCode,
+    };
+    use client::TestHttpResponse;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use server::TestServer;
+    use test_context::TestContext;
+
+    const USERNAME: &str = "user";
+    const PASSWORD: &str = "password";
+    const RECOVERY_CODE: &str = "abcde-fghjk";
+
+    fn login_request(password: &str) -> LoginRequest {
+        LoginRequest {
+            username: USERNAME.to_owned(),
+            password: Secret::new(password.to_owned()),
+        }
+    }
+
+    async fn enable_totp(ctx: &TestContext, login: &Login) -> TotpSecret {
+        let secret = TotpSecret::generate();
+        let mut totp = LoginTotp::new(login.login_id(), secret.clone());
+        totp.confirm(ctx.time().now(), vec![RecoveryCodeHash::of(RECOVERY_CODE)]);
+        ctx.dal().login_totps().save(totp).await.unwrap();
+        secret
+    }
+
+    async fn challenge(server: &TestServer) -> TotpChallenge {
+        let response = server
+            .client()
+            .post("/api/auth/login/v1")
+            .json(&login_request(PASSWORD))
+            .send()
+            .await;
+        match response.unwrap::<LoginResponse>() {
+            LoginResponse::TotpRequired(challenge) => challenge,
+            r => panic!("Expected totp challenge, got {:?}", r),
+        }
+    }
+
+    async fn send_code(server: &TestServer, challenge_token: &str, code: &str) -> TestHttpResponse {
+        server
+            .client()
+            .post("/api/auth/login/totp/v1")
+            .json(&LoginTotpRequest {
+                challenge_token: challenge_token.to_owned(),
+                code: Secret::new(code.to_owned()),
+            })
+            .send()
+            .await
+    }
+
+    #[test]
+    fn login_without_totp_returns_tokens() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(PASSWORD))
+                .send()
+                .await;
+
+            // assert
+            let tokens = match response.unwrap::<LoginResponse>() {
+                LoginResponse::Tokens(tokens) => tokens,
+                r => panic!("Expected tokens, got {:?}", r),
+            };
+            let access_ttl = ctx.env().config().auth().access_token_ttl();
+            assert_eq!(*tokens.access_token_expires_at, now + access_ttl);
+
+            let claims = ctx
+                .access_token_decoder()
+                .decode_at(&tokens.access_token, now)
+                .unwrap()
+                .claims;
+            assert_eq!(claims.sub(), login.login_id());
+            assert_eq!(claims.iat(), now);
+        });
+    }
+
+    #[test]
+    fn login_with_wrong_password_is_unauthorized() {
+        test(|ctx| async move {
+            // arrange
+            ctx.create_login(USERNAME, PASSWORD).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request("wrong"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn login_without_password_is_unauthorized() {
+        test(|ctx| async move {
+            // arrange
+            let login =
+                Login::without_password(ctx.value_generator().next_id(), USERNAME.to_owned());
+            ctx.dal().logins().insert(login).await.unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(""))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn login_with_unknown_username_is_unauthorized() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(PASSWORD))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn login_with_totp_requires_code() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let secret = enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+
+            // act
+            let challenge = challenge(&server).await;
+            let response =
+                send_code(&server, &challenge.challenge_token, &secret.code_at(now)).await;
+
+            // assert
+            let ttl = ctx.env().config().auth().totp().challenge_ttl();
+            assert_eq!(*challenge.expires_at, now + ttl);
+
+            let tokens = response.unwrap::<TokenPair>();
+            let claims = ctx
+                .access_token_decoder()
+                .decode_at(&tokens.access_token, now)
+                .unwrap()
+                .claims;
+            assert_eq!(claims.sub(), login.login_id());
+        });
+    }
+
+    #[test]
+    fn totp_code_can_not_be_reused() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let secret = enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            let code = secret.code_at(now);
+            let challenge = challenge(&server).await;
+            send_code(&server, &challenge.challenge_token, &code)
+                .await
+                .unwrap::<TokenPair>();
+
+            // act
+            let response = send_code(&server, &challenge.challenge_token, &code).await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn totp_challenge_can_not_be_reused() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let secret = enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            let challenge = challenge(&server).await;
+            send_code(&server, &challenge.challenge_token, &secret.code_at(now))
+                .await
+                .unwrap::<TokenPair>();
+            let later = now + chrono::Duration::minutes(1);
+            ctx.time().set(later);
+
+            // act
+            let response =
+                send_code(&server, &challenge.challenge_token, &secret.code_at(later)).await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn wrong_totp_code_is_unauthorized() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let secret = enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            let challenge = challenge(&server).await;
+
+            // act
+            let stale_code = secret.code_at(now - chrono::Duration::minutes(5));
+            let response = send_code(&server, &challenge.challenge_token, &stale_code).await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn expired_challenge_is_unauthorized() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            let secret = enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            let challenge = challenge(&server).await;
+
+            let later = *challenge.expires_at + chrono::Duration::seconds(1);
+            ctx.time().set(later);
+
+            // act
+            let response =
+                send_code(&server, &challenge.challenge_token, &secret.code_at(later)).await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn login_is_locked_out_after_failures() {
+        test(|ctx| async move {
+            // arrange
+            ctx.create_login(USERNAME, PASSWORD).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            for _ in 0..3 {
+                let response = server
+                    .client()
+                    .post("/api/auth/login/v1")
+                    .json(&login_request("wrong"))
+                    .send()
+                    .await;
+                assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+            }
+
+            // act
+            let locked = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(PASSWORD))
+                .send()
+                .await;
+
+            ctx.time().set(now + chrono::Duration::seconds(60));
+            let unlocked = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(PASSWORD))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(locked.unwrap_err().code, ErrorCode::TooManyRequests);
+            let retry_after = locked.headers.get("retry-after").unwrap().to_str().unwrap();
+            assert_str_eq!(retry_after, "60");
+            assert!(matches!(
+                unlocked.unwrap::<LoginResponse>(),
+                LoginResponse::Tokens(_)
+            ));
+        });
+    }
+
+    async fn login_from(
+        server: &TestServer,
+        ip: &str,
+        username: &str,
+        password: &str,
+    ) -> TestHttpResponse {
+        server
+            .client()
+            .post("/api/auth/login/v1")
+            .insert_header(("x-forwarded-for", ip))
+            .json(&LoginRequest {
+                username: username.to_owned(),
+                password: Secret::new(password.to_owned()),
+            })
+            .send()
+            .await
+    }
+
+    #[test]
+    fn ip_lockout_behind_a_trusted_proxy_applies_to_the_forwarded_client() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["proxies"]["trusted"] = serde_json::json!(["127.0.0.1"])
+            });
+            ctx.create_login(USERNAME, PASSWORD).await;
+            let server = ctx.run_server().await;
+            ctx.time().set(utc!(2024, 3, 4, 5, 6, 7));
+            let max_failures = ctx.env().config().auth().lockout().max_failures_per_ip();
+            for i in 0..max_failures {
+                let response =
+                    login_from(&server, "198.51.100.7", &format!("unknown{}", i), "wrong").await;
+                assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+            }
+
+            // act
+            let attacker = login_from(&server, "198.51.100.7", USERNAME, PASSWORD).await;
+            let other = login_from(&server, "203.0.113.9", USERNAME, PASSWORD).await;
+
+            // assert
+            assert_eq!(attacker.unwrap_err().code, ErrorCode::TooManyRequests);
+            assert!(matches!(
+                other.unwrap::<LoginResponse>(),
+                LoginResponse::Tokens(_)
+            ));
+        });
+    }
+
+    #[test]
+    fn lockout_grows_with_further_failures() {
+        test(|ctx| async move {
+            // arrange
+            ctx.create_login(USERNAME, PASSWORD).await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+            for _ in 0..3 {
+                server
+                    .client()
+                    .post("/api/auth/login/v1")
+                    .json(&login_request("wrong"))
+                    .send()
+                    .await;
+            }
+            let after_first_lockout = now + chrono::Duration::seconds(60);
+            ctx.time().set(after_first_lockout);
+            server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request("wrong"))
+                .send()
+                .await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&login_request(PASSWORD))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::TooManyRequests);
+            let retry_after = response
+                .headers
+                .get("retry-after")
+                .unwrap()
+                .to_str()
+                .unwrap();
+            assert_str_eq!(retry_after, "120");
+        });
+    }
+
+    #[test]
+    fn recovery_code_can_be_used_once() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login(USERNAME, PASSWORD).await;
+            enable_totp(&ctx, &login).await;
+            let server = ctx.run_server().await;
+            let challenge = challenge(&server).await;
+
+            // act
+            let first = send_code(&server, &challenge.challenge_token, "ABCDE-FGHJK").await;
+            let second = send_code(&server, &challenge.challenge_token, RECOVERY_CODE).await;
+
+            // assert
+            first.unwrap::<TokenPair>();
+            assert_eq!(second.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+}
@@ -0,0 +1,604 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        content_right::ContentRight,
+        login::Login,
+        login_right::LoginRight,
+        oidc::{
+            external_identity::ExternalIdentity,
+            id_token_claims::IdTokenClaims,
+            oidc_auth_request::OidcAuthRequest,
+            oidc_client::{OidcClient, OidcError},
+            oidc_providers::OidcProviders,
+            pkce::{random_token, PkceVerifier},
+        },
+        tokens::{
+            access_token_claims::AccessTokenClaims, encoder::JwtTokenEncoder,
+            refresh_token_claims::RefreshTokenClaims,
+        },
+    },
+    config::app_config::{AppConfig, OidcProviderConfig},
+    dal::{
+        external_identities_dal::ExternalIdentitiesDal, login_rights_dal::LoginRightsDal,
+        logins_dal::LoginsDal, oidc_auth_requests_dal::OidcAuthRequestsDal, Dal,
+    },
+    utils::{id::Id, id_generator::IdGenerator, time::Time},
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        common::{api_error::ApiError, api_result::ApiResult, serde_chrono::ApiDateTime},
+    },
+};
+
+use super::token_pair::{self, TokenPair};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct OidcAuthorization {
+    /// The user agent is sent here, the provider redirects back to the configured `redirect_uri`
+    pub authorization_url: String,
+    pub expires_at: ApiDateTime,
+}
+
+/// Query parameters the provider has added to `redirect_uri`
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+/// First step. Starts the authorization code flow with PKCE.
+pub async fn authorize<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    providers: web::Data<OidcProviders>,
+    provider: web::Path<String>,
+) -> ApiResult<OidcAuthorization> {
+    let client = provider_client(&providers, &provider)?;
+    let now = data.time().now();
+    let expires_at = now + config.auth().oidc().auth_request_ttl();
+
+    let state = random_token();
+    let nonce = random_token();
+    let verifier = PkceVerifier::generate();
+    let authorization_url = client
+        .authorization_url(&state, &nonce, &verifier)
+        .await
+        .map_err(|e| {
+            tracing::error!(provider = %provider, "authorization failed: {}", e);
+            ApiError::unexpected().build()
+        })?;
+
+    let requests = data.dal().oidc_auth_requests();
+    requests.purge_expired(now).await?;
+    requests
+        .insert(OidcAuthRequest::new(
+            state,
+            provider.into_inner(),
+            nonce,
+            verifier,
+            expires_at,
+        ))
+        .await?;
+
+    Ok(web::Json(OidcAuthorization {
+        authorization_url,
+        expires_at: expires_at.into(),
+    }))
+}
+
+/// Second step. Exchanges the authorization code for a token pair of the linked login.
+#[allow(clippy::too_many_arguments)]
+pub async fn callback<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    providers: web::Data<OidcProviders>,
+    access: web::Data<JwtTokenEncoder<AccessTokenClaims>>,
+    refresh: web::Data<JwtTokenEncoder<RefreshTokenClaims>>,
+    provider: web::Path<String>,
+    audit: AuditContext,
+    request: web::Json<OidcCallbackRequest>,
+) -> ApiResult<TokenPair> {
+    let client = provider_client(&providers, &provider)?;
+    let now = data.time().now();
+    let entry = audit
+        .entry(AuditAction::Login)
+        .with_detail(format!("oidc:{}", provider));
+
+    let auth_request = data
+        .dal()
+        .oidc_auth_requests()
+        .take(&request.state)
+        .await?
+        .filter(|r| r.provider() == provider.as_str() && !r.is_expired(now));
+    let Some(auth_request) = auth_request else {
+        data.metrics().auth_failed("oidc", "invalid_state");
+        audit
+            .record(data.as_ref(), entry.failed("invalid_state"))
+            .await;
+        return Err(ApiError::bad_reques()
+            .message("sign-in request is unknown or has expired".to_owned())
+            .build());
+    };
+
+    let claims = match validate_callback(client, &request.code, &auth_request, now).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            data.metrics().auth_failed("oidc", "provider_error");
+            audit
+                .record(data.as_ref(), entry.failed("provider_error"))
+                .await;
+            return Err(sign_in_failed(e));
+        }
+    };
+
+    let login_id = find_or_provision(data.get_ref(), client.config(), &claims).await?;
+    let entry = entry
+        .with_principal(Some(login_id))
+        .with_target(&claims.sub);
+    if let Some(right) = sync_rights(data.get_ref(), client.config(), &claims, login_id).await? {
+        let rights = audit
+            .entry(AuditAction::RightsChanged)
+            .with_principal(Some(login_id))
+            .with_target(login_id)
+            .with_detail(serde_json::to_string(&right).unwrap_or_default());
+        audit.record(data.as_ref(), rights).await;
+    }
+
+    tracing::info!(login_id = %login_id, provider = %provider, "logged in");
+    data.metrics().auth_succeeded("oidc");
+    audit.record(data.as_ref(), entry).await;
+    let tokens = token_pair::issue(data.get_ref(), config.auth(), &access, &refresh, login_id)?;
+    Ok(web::Json(tokens))
+}
+
+fn provider_client<'a>(
+    providers: &'a OidcProviders,
+    name: &str,
+) -> Result<&'a OidcClient, ApiError> {
+    providers.get(name).ok_or_else(|| {
+        ApiError::not_found()
+            .message("unknown identity provider".to_owned())
+            .build()
+    })
+}
+
+async fn validate_callback(
+    client: &OidcClient,
+    code: &str,
+    auth_request: &OidcAuthRequest,
+    now: DateTime<Utc>,
+) -> Result<IdTokenClaims, OidcError> {
+    let id_token = client.exchange_code(code, auth_request.verifier()).await?;
+    client
+        .validate_id_token(&id_token, auth_request.nonce(), now)
+        .await
+}
+
+fn sign_in_failed(err: OidcError) -> ApiError {
+    tracing::warn!("sign-in with the provider failed: {}", err);
+    ApiError::unauthorized()
+        .message("sign-in with the provider failed".to_owned())
+        .build()
+}
+
+async fn find_or_provision<D: AppData>(
+    data: &D,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+) -> Result<Id, ApiError> {
+    let identities = data.dal().external_identities();
+    if let Some(identity) = identities.find(provider.name(), &claims.sub).await? {
+        return Ok(identity.login_id());
+    }
+    if !provider.auto_provision() {
+        tracing::info!(subject = %claims.sub, "no login is linked to the identity");
+        data.metrics().auth_failed("oidc", "unlinked_identity");
+        return Err(ApiError::forbidden()
+            .message("no login is linked to this identity".to_owned())
+            .build());
+    }
+
+    let username = claims
+        .string_claim(provider.username_claim())
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| {
+            ApiError::forbidden()
+                .message(format!(
+                    "identity has no '{}' claim",
+                    provider.username_claim()
+                ))
+                .build()
+        })?;
+    // the provider controls the claim, the prefix keeps it from naming a local login or an admin;
+    // an existing login with the same username is never linked automatically, the conflict
+    // is reported instead
+    let username = format!("{}:{}", provider.name(), username);
+    let login = Login::without_password(data.id().next_id(), username);
+    data.dal().logins().insert(login.clone()).await?;
+    identities
+        .insert(ExternalIdentity::new(
+            provider.name().to_owned(),
+            claims.sub.clone(),
+            login.login_id(),
+        ))
+        .await?;
+    tracing::info!(login_id = %login.login_id(), subject = %claims.sub, "login provisioned");
+    Ok(login.login_id())
+}
+
+/// The right granted by the groups of the identity, if it differs from the stored one
+async fn sync_rights<D: AppData>(
+    data: &D,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+    login_id: Id,
+) -> Result<Option<ContentRight>, ApiError> {
+    let group_rights = provider.group_rights();
+    if group_rights.is_empty() {
+        return Ok(None);
+    }
+    let right = claims
+        .string_list_claim(provider.groups_claim())
+        .into_iter()
+        .filter_map(|group| group_rights.get(group))
+        .fold(ContentRight::None, |acc, right| acc | *right);
+    let previous = data.dal().login_rights().get(login_id).await?;
+    if previous.is_some_and(|r| r.right() == right) {
+        return Ok(None);
+    }
+    data.dal()
+        .login_rights()
+        .save(LoginRight::new(login_id, right))
+        .await?;
+    Ok(Some(right))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{mock_idp::MockIdp, *},
+        utc,
+        web::common::api_error::ErrorCode,
+    };
+    use chrono::Duration;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::{json, Value};
+
+    fn use_provider(ctx: &test_context::TestContext, provider: Value) {
+        ctx.update_config(|config| config["auth"]["oidc"]["providers"] = json!([provider]));
+    }
+
+    async fn sign_in(
+        server: &server::TestServer,
+        idp: &MockIdp,
+        claims: Value,
+    ) -> client::TestHttpResponse {
+        let authorization = server
+            .client()
+            .post("/api/auth/oidc/corp/authorize/v1")
+            .send()
+            .await
+            .unwrap::<OidcAuthorization>();
+        let (code, state) = idp.sign_in(&authorization.authorization_url, claims);
+        server
+            .client()
+            .post("/api/auth/oidc/corp/callback/v1")
+            .json(&OidcCallbackRequest { code, state })
+            .send()
+            .await
+    }
+
+    #[test]
+    fn sign_in_with_linked_identity() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            use_provider(&ctx, idp.provider_config("corp"));
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let login = ctx.create_login("user", "password").await;
+            ctx.dal()
+                .external_identities()
+                .insert(ExternalIdentity::new(
+                    "corp".to_owned(),
+                    "subject-1".to_owned(),
+                    login.login_id(),
+                ))
+                .await
+                .unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            let tokens = sign_in(&server, &idp, idp.claims("subject-1", now))
+                .await
+                .unwrap::<TokenPair>();
+
+            // assert
+            let claims = ctx
+                .access_token_decoder()
+                .decode_at(&tokens.access_token, now)
+                .unwrap()
+                .claims;
+            assert_eq!(claims.sub, login.login_id());
+            assert_eq!(*tokens.access_token_expires_at, now + Duration::minutes(15));
+        });
+    }
+
+    #[test]
+    fn auto_provisioning_creates_login_with_group_rights() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let mut provider = idp.provider_config("corp");
+            provider["auto_provision"] = json!(true);
+            provider["group_rights"] = json!({
+                "readers": ["read"],
+                "writers": ["write"],
+            });
+            use_provider(&ctx, provider);
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let server = ctx.run_server().await;
+            let mut claims = idp.claims("subject-1", now);
+            claims["preferred_username"] = json!("alice");
+            claims["groups"] = json!(["readers", "unmapped"]);
+
+            // act
+            let first = sign_in(&server, &idp, claims.clone())
+                .await
+                .unwrap::<TokenPair>();
+            let login = ctx
+                .dal()
+                .logins()
+                .find_by_username("corp:alice")
+                .await
+                .unwrap()
+                .unwrap();
+            let first_right = ctx
+                .dal()
+                .login_rights()
+                .get(login.login_id())
+                .await
+                .unwrap();
+
+            claims["groups"] = json!(["readers", "writers"]);
+            let second = sign_in(&server, &idp, claims).await.unwrap::<TokenPair>();
+            let second_right = ctx
+                .dal()
+                .login_rights()
+                .get(login.login_id())
+                .await
+                .unwrap();
+
+            // assert
+            assert_eq!(login.password(), None);
+            let decoder = ctx.access_token_decoder();
+            assert_eq!(
+                decoder
+                    .decode_at(&first.access_token, now)
+                    .unwrap()
+                    .claims
+                    .sub,
+                login.login_id()
+            );
+            assert_eq!(
+                decoder
+                    .decode_at(&second.access_token, now)
+                    .unwrap()
+                    .claims
+                    .sub,
+                login.login_id()
+            );
+            assert_eq!(first_right.map(|r| r.right()), Some(ContentRight::Read));
+            assert_eq!(
+                second_right.map(|r| r.right()),
+                Some(ContentRight::Read | ContentRight::Write)
+            );
+        });
+    }
+
+    #[test]
+    fn unknown_identity_is_forbidden_without_provisioning() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            use_provider(&ctx, idp.provider_config("corp"));
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let server = ctx.run_server().await;
+            let mut claims = idp.claims("subject-1", now);
+            claims["preferred_username"] = json!("alice");
+
+            // act
+            let error = sign_in(&server, &idp, claims).await.unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Forbidden);
+            let login = ctx
+                .dal()
+                .logins()
+                .find_by_username("corp:alice")
+                .await
+                .unwrap();
+            assert_eq!(login, None);
+        });
+    }
+
+    #[test]
+    fn provisioning_does_not_take_over_local_login() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            let mut provider = idp.provider_config("corp");
+            provider["auto_provision"] = json!(true);
+            use_provider(&ctx, provider);
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let local = ctx.create_login("alice", "password").await;
+            ctx.create_login("corp:bob", "password").await;
+            let server = ctx.run_server().await;
+            let mut alice = idp.claims("subject-1", now);
+            alice["preferred_username"] = json!("alice");
+            let mut bob = idp.claims("subject-2", now);
+            bob["preferred_username"] = json!("bob");
+
+            // act
+            let provisioned = sign_in(&server, &idp, alice).await.unwrap::<TokenPair>();
+            let conflict = sign_in(&server, &idp, bob).await.unwrap_err();
+
+            // assert
+            let claims = ctx
+                .access_token_decoder()
+                .decode_at(&provisioned.access_token, now)
+                .unwrap()
+                .claims;
+            assert_ne!(claims.sub, local.login_id());
+            let login = ctx.dal().logins().get(claims.sub).await.unwrap().unwrap();
+            assert_str_eq!(login.username(), "corp:alice");
+            assert_eq!(conflict.code, ErrorCode::Conflict);
+            let identity = ctx
+                .dal()
+                .external_identities()
+                .find("corp", "subject-2")
+                .await
+                .unwrap();
+            assert_eq!(identity, None);
+        });
+    }
+
+    #[test]
+    fn expired_id_token_is_rejected() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            use_provider(&ctx, idp.provider_config("corp"));
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let login = ctx.create_login("user", "password").await;
+            ctx.dal()
+                .external_identities()
+                .insert(ExternalIdentity::new(
+                    "corp".to_owned(),
+                    "subject-1".to_owned(),
+                    login.login_id(),
+                ))
+                .await
+                .unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            let error = sign_in(
+                &server,
+                &idp,
+                idp.claims("subject-1", now - Duration::hours(1)),
+            )
+            .await
+            .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn state_is_used_once() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            use_provider(&ctx, idp.provider_config("corp"));
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let server = ctx.run_server().await;
+            let authorization = server
+                .client()
+                .post("/api/auth/oidc/corp/authorize/v1")
+                .send()
+                .await
+                .unwrap::<OidcAuthorization>();
+            let (code, state) = idp.sign_in(
+                &authorization.authorization_url,
+                idp.claims("subject-1", now),
+            );
+            let callback = OidcCallbackRequest { code, state };
+
+            // act
+            let first = server
+                .client()
+                .post("/api/auth/oidc/corp/callback/v1")
+                .json(&callback)
+                .send()
+                .await
+                .unwrap_err();
+            let second = server
+                .client()
+                .post("/api/auth/oidc/corp/callback/v1")
+                .json(&callback)
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(first.code, ErrorCode::Forbidden);
+            assert_eq!(second.code, ErrorCode::BadRequest);
+        });
+    }
+
+    #[test]
+    fn expired_sign_in_request_is_rejected() {
+        test(|ctx| async move {
+            // arrange
+            let idp = MockIdp::start().await;
+            use_provider(&ctx, idp.provider_config("corp"));
+            let now = utc!(2024, 5, 6);
+            ctx.time().set(now);
+            let server = ctx.run_server().await;
+            let authorization = server
+                .client()
+                .post("/api/auth/oidc/corp/authorize/v1")
+                .send()
+                .await
+                .unwrap::<OidcAuthorization>();
+            let (code, state) = idp.sign_in(
+                &authorization.authorization_url,
+                idp.claims("subject-1", now),
+            );
+            ctx.time().set(*authorization.expires_at);
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/oidc/corp/callback/v1")
+                .json(&OidcCallbackRequest { code, state })
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::BadRequest);
+        });
+    }
+
+    #[test]
+    fn unknown_provider_is_not_found() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/oidc/unknown/authorize/v1")
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::NotFound);
+        });
+    }
+}
@@ -0,0 +1,411 @@
+use actix_web::web;
+use uuid::Uuid;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        content_right::ContentRight,
+        personal_access_token::{generate_token, PersonalAccessToken},
+        principal::Principal,
+    },
+    dal::{
+        login_rights_dal::LoginRightsDal, personal_access_tokens_dal::PersonalAccessTokensDal, Dal,
+    },
+    fs::storage_path::StoragePath,
+    utils::{
+        id::Id,
+        id_generator::IdGenerator,
+        secret::{expose, Secret},
+        time::Time,
+    },
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::interactive::Interactive,
+        common::{api_error::ApiError, api_result::ApiResult, serde_chrono::ApiDateTime},
+    },
+};
+
+const MAX_NAME_LEN: usize = 100;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct CreatePersonalAccessTokenRequest {
+    pub name: String,
+    pub rights: ContentRight,
+    /// Limits the token to these sources, all sources if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<Uuid>>,
+    /// Limits the token to this path and everything below it, whole sources if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<StoragePath>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<ApiDateTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct PersonalAccessTokenInfo {
+    pub token_id: Id,
+    pub name: String,
+    pub rights: ContentRight,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<Uuid>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<StoragePath>,
+    pub created_at: ApiDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<ApiDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<ApiDateTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct CreatedPersonalAccessToken {
+    /// Shown only once
+    #[serde(serialize_with = "expose")]
+    pub token: Secret<String>,
+    #[serde(flatten)]
+    pub info: PersonalAccessTokenInfo,
+}
+
+impl From<&PersonalAccessToken> for PersonalAccessTokenInfo {
+    fn from(value: &PersonalAccessToken) -> Self {
+        Self {
+            token_id: value.token_id(),
+            name: value.name().to_owned(),
+            rights: value.rights(),
+            sources: value.sources().map(|s| s.to_vec()),
+            path: value.path().cloned(),
+            created_at: value.created_at().into(),
+            expires_at: value.expires_at().map(Into::into),
+            last_used_at: value.last_used_at().map(Into::into),
+        }
+    }
+}
+
+pub async fn create<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    _interactive: Interactive,
+    audit: AuditContext,
+    request: web::Json<CreatePersonalAccessTokenRequest>,
+) -> ApiResult<CreatedPersonalAccessToken> {
+    let request = request.into_inner();
+    let now = data.time().now();
+
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(ApiError::bad_reques()
+            .message(format!(
+                "name must be 1 to {} characters long",
+                MAX_NAME_LEN
+            ))
+            .build());
+    }
+    if request.rights.is_empty() {
+        return Err(ApiError::bad_reques()
+            .message("rights must not be empty".to_owned())
+            .build());
+    }
+    if request.expires_at.is_some_and(|exp| *exp <= now) {
+        return Err(ApiError::bad_reques()
+            .message("expires_at must be in the future".to_owned())
+            .build());
+    }
+
+    let login_rights = data
+        .dal()
+        .login_rights()
+        .get(principal.id())
+        .await?
+        .map_or(ContentRight::None, |r| r.right());
+    if !login_rights.contains(request.rights) {
+        return Err(ApiError::forbidden()
+            .message("token can't have more rights than its login".to_owned())
+            .build());
+    }
+
+    let token = generate_token();
+    let pat = PersonalAccessToken::new(
+        data.id().next_id(),
+        principal.id(),
+        name.to_owned(),
+        &token,
+        request.rights,
+        request.sources,
+        now,
+        request.expires_at.map(|exp| *exp),
+    )
+    .with_path(request.path);
+    let info = PersonalAccessTokenInfo::from(&pat);
+    data.dal().personal_access_tokens().insert(pat).await?;
+
+    tracing::info!(token_id = %info.token_id, "personal access token created");
+    let entry = audit
+        .entry(AuditAction::TokenCreated)
+        .with_target(info.token_id)
+        .with_detail(serde_json::to_string(&info.rights).unwrap_or_default());
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(CreatedPersonalAccessToken { token, info }))
+}
+
+pub async fn list<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+) -> ApiResult<Vec<PersonalAccessTokenInfo>> {
+    let tokens = data
+        .dal()
+        .personal_access_tokens()
+        .list_by_login(principal.id())
+        .await?;
+    Ok(web::Json(tokens.iter().map(Into::into).collect()))
+}
+
+pub async fn delete<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    _interactive: Interactive,
+    audit: AuditContext,
+    token_id: web::Path<Id>,
+) -> ApiResult<()> {
+    let token_id = token_id.into_inner();
+    let deleted = data
+        .dal()
+        .personal_access_tokens()
+        .delete(principal.id(), token_id)
+        .await?;
+    if !deleted {
+        return Err(ApiError::not_found()
+            .message("token not found".to_owned())
+            .build());
+    }
+    tracing::info!(token_id = %token_id, "personal access token revoked");
+    let entry = audit.entry(AuditAction::TokenRevoked).with_target(token_id);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(()))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{test::*, utc, web::common::api_error::ErrorCode};
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn request(rights: ContentRight) -> CreatePersonalAccessTokenRequest {
+        CreatePersonalAccessTokenRequest {
+            name: "ci".to_owned(),
+            rights,
+            sources: None,
+            path: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn create_list_and_revoke() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let jwt = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            ctx.time().set(utc!(2024));
+            let source = Uuid::from_u128(7);
+
+            // act
+            let created = server
+                .client()
+                .post("/api/auth/tokens/v1")
+                .access_token(&jwt)
+                .json(&CreatePersonalAccessTokenRequest {
+                    name: " ci ".to_owned(),
+                    rights: ContentRight::Read,
+                    sources: Some(vec![source]),
+                    path: None,
+                    expires_at: Some(utc!(2025).into()),
+                })
+                .send()
+                .await
+                .unwrap::<CreatedPersonalAccessToken>();
+            server
+                .client()
+                .get("/api/info/v1")
+                .access_token(&created.token)
+                .send()
+                .await;
+            let listed = server
+                .client()
+                .get("/api/auth/tokens/v1")
+                .access_token(&jwt)
+                .send()
+                .await
+                .unwrap::<Vec<PersonalAccessTokenInfo>>();
+            server
+                .client()
+                .delete(&format!("/api/auth/tokens/v1/{}", created.info.token_id))
+                .access_token(&jwt)
+                .send()
+                .await
+                .unwrap::<()>();
+            let after_revoke = server
+                .client()
+                .get("/api/auth/tokens/v1")
+                .access_token(&jwt)
+                .send()
+                .await
+                .unwrap::<Vec<PersonalAccessTokenInfo>>();
+
+            // assert
+            assert!(created.token.starts_with("rhfs_"));
+            assert_eq!(created.info.name, "ci");
+            assert_eq!(listed.len(), 1);
+            assert_eq!(listed[0].token_id, created.info.token_id);
+            assert_eq!(listed[0].rights, ContentRight::Read);
+            assert_eq!(listed[0].sources, Some(vec![source]));
+            assert_eq!(listed[0].expires_at.map(|e| *e), Some(utc!(2025)));
+            assert_eq!(listed[0].last_used_at.map(|e| *e), Some(utc!(2024)));
+            assert!(after_revoke.is_empty());
+        });
+    }
+
+    #[test]
+    fn revoked_token_is_not_accepted() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let (pat, token) = ctx
+                .create_personal_access_token(&login, ContentRight::Read, None, None)
+                .await;
+            let jwt = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            server
+                .client()
+                .delete(&format!("/api/auth/tokens/v1/{}", pat.token_id()))
+                .access_token(&jwt)
+                .send()
+                .await
+                .unwrap::<()>();
+            let error = server
+                .client()
+                .get("/api/auth/tokens/v1")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn revoke_unknown_or_foreign_token_is_not_found() {
+        test(|ctx| async move {
+            // arrange
+            let owner = ctx.create_login("owner", "password").await;
+            let other = ctx.create_login("other", "password").await;
+            let (pat, _) = ctx
+                .create_personal_access_token(&owner, ContentRight::Read, None, None)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .delete(&format!("/api/auth/tokens/v1/{}", pat.token_id()))
+                .access_token(&ctx.access_token_for(&other))
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::NotFound);
+            let tokens = ctx
+                .dal()
+                .personal_access_tokens()
+                .list_by_login(owner.login_id())
+                .await
+                .unwrap();
+            assert_eq!(tokens.len(), 1);
+        });
+    }
+
+    #[test]
+    fn all_rights_personal_access_token_cannot_create_tokens() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::All).await;
+            let (_, token) = ctx
+                .create_personal_access_token(&login, ContentRight::All, None, None)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/tokens/v1")
+                .access_token(&token)
+                .json(&request(ContentRight::Read))
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Forbidden);
+        });
+    }
+
+    #[test]
+    fn rights_beyond_login_rights_are_forbidden() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/tokens/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .json(&request(ContentRight::Read | ContentRight::Write))
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Forbidden);
+        });
+    }
+
+    #[test]
+    fn expiry_in_the_past_is_rejected() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let server = ctx.run_server().await;
+            ctx.time().set(utc!(2024));
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/tokens/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .json(&CreatePersonalAccessTokenRequest {
+                    expires_at: Some(utc!(2023).into()),
+                    ..request(ContentRight::Read)
+                })
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::BadRequest);
+        });
+    }
+}
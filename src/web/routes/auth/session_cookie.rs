@@ -0,0 +1,241 @@
+use actix_web::{
+    cookie::{time, Cookie, SameSite},
+    web, HttpResponse,
+};
+use chrono::Duration;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        oidc::pkce::random_token,
+        principal::Principal,
+        tokens::{access_token_claims::AccessTokenClaims, encoder::JwtTokenEncoder},
+    },
+    config::app_config::{AppConfig, CookieAuthConfig, CookieSameSite},
+    utils::time::Time,
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::interactive::Interactive,
+        common::{api_error::ApiError, serde_chrono::ApiDateTime},
+    },
+};
+
+use super::token_pair::encode_error;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct SessionCookie {
+    /// Has to be sent in the CSRF header with every unsafe request
+    pub csrf_token: String,
+    pub expires_at: ApiDateTime,
+}
+
+/// Moves the current authentication into an HttpOnly cookie for browsers
+pub async fn create<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    access: web::Data<JwtTokenEncoder<AccessTokenClaims>>,
+    principal: Principal,
+    _interactive: Interactive,
+) -> Result<HttpResponse, ApiError> {
+    let cookie_config = enabled_cookie_config(&config)?;
+
+    let now = data.time().now();
+    let ttl = config.auth().access_token_ttl();
+    let expires_at = now + ttl;
+    let access_token = access
+        .encode(&AccessTokenClaims {
+            sub: principal.id(),
+            exp: expires_at.into(),
+            iat: now.into(),
+        })
+        .map_err(encode_error)?;
+    let csrf_token = random_token();
+
+    Ok(HttpResponse::Ok()
+        .cookie(cookie(
+            cookie_config,
+            cookie_config.name(),
+            access_token,
+            ttl,
+            true,
+        ))
+        .cookie(cookie(
+            cookie_config,
+            cookie_config.csrf_cookie_name(),
+            csrf_token.clone(),
+            ttl,
+            false,
+        ))
+        .json(SessionCookie {
+            csrf_token,
+            expires_at: expires_at.into(),
+        }))
+}
+
+/// Logout of a cookie session
+pub async fn delete<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    audit: AuditContext,
+) -> Result<HttpResponse, ApiError> {
+    let cookie_config = enabled_cookie_config(&config)?;
+    let mut access = cookie(
+        cookie_config,
+        cookie_config.name(),
+        String::new(),
+        Duration::zero(),
+        true,
+    );
+    access.make_removal();
+    let mut csrf = cookie(
+        cookie_config,
+        cookie_config.csrf_cookie_name(),
+        String::new(),
+        Duration::zero(),
+        false,
+    );
+    csrf.make_removal();
+    audit
+        .record(data.as_ref(), audit.entry(AuditAction::Logout))
+        .await;
+    Ok(HttpResponse::Ok().cookie(access).cookie(csrf).json(()))
+}
+
+fn enabled_cookie_config(config: &AppConfig) -> Result<&CookieAuthConfig, ApiError> {
+    let cookie = config.auth().methods().cookie();
+    if cookie.enabled() {
+        Ok(cookie)
+    } else {
+        Err(ApiError::not_found()
+            .message("cookie authentication is disabled".to_owned())
+            .build())
+    }
+}
+
+fn cookie<'a>(
+    config: &CookieAuthConfig,
+    name: &'a str,
+    value: String,
+    max_age: Duration,
+    http_only: bool,
+) -> Cookie<'a> {
+    let same_site = match config.same_site() {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+    };
+    Cookie::build(name, value)
+        .path("/")
+        .http_only(http_only)
+        .secure(config.secure())
+        .same_site(same_site)
+        .max_age(time::Duration::seconds(max_age.num_seconds()))
+        .finish()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::content_right::ContentRight, test::*, utc, web::common::api_error::ErrorCode,
+    };
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn create_and_delete_session_cookie() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+            let now = utc!(2024);
+            ctx.time().set(now);
+
+            // act
+            let created = server
+                .client()
+                .post("/api/auth/cookie/v1")
+                .basic_auth("user", "password")
+                .send()
+                .await;
+            let deleted = server.client().delete("/api/auth/cookie/v1").send().await;
+
+            // assert
+            let session = created.unwrap::<SessionCookie>();
+            assert_eq!(*session.expires_at, now + Duration::minutes(15));
+
+            let access = created.set_cookie("rhfs_access").unwrap();
+            let token = access
+                .strip_prefix("rhfs_access=")
+                .and_then(|v| v.split(';').next())
+                .unwrap();
+            let claims = ctx
+                .access_token_decoder()
+                .decode_at(token, now)
+                .unwrap()
+                .claims;
+            assert_eq!(claims.sub(), login.login_id());
+            for attribute in [
+                "HttpOnly",
+                "Secure",
+                "SameSite=Strict",
+                "Path=/",
+                "Max-Age=900",
+            ] {
+                assert!(access.contains(attribute), "{} in {}", attribute, access);
+            }
+
+            let csrf = created.set_cookie("rhfs_csrf").unwrap();
+            assert!(csrf.starts_with(&format!("rhfs_csrf={};", session.csrf_token)));
+            assert!(!csrf.contains("HttpOnly"));
+
+            let removed = deleted.set_cookie("rhfs_access").unwrap();
+            assert!(removed.starts_with("rhfs_access=;"), "{}", removed);
+            assert!(removed.contains("Max-Age=0"), "{}", removed);
+        });
+    }
+
+    #[test]
+    fn all_rights_personal_access_token_cannot_create_session_cookie() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::All).await;
+            let (_, token) = ctx
+                .create_personal_access_token(&login, ContentRight::All, None, None)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/cookie/v1")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Forbidden);
+        });
+    }
+
+    #[test]
+    fn session_cookie_requires_authentication() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let error = server
+                .client()
+                .post("/api/auth/cookie/v1")
+                .send()
+                .await
+                .unwrap_err();
+
+            // assert
+            assert_eq!(error.code, ErrorCode::Unauthorized);
+        });
+    }
+}
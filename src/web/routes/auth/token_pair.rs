@@ -0,0 +1,63 @@
+use crate::{
+    auth::tokens::{
+        access_token_claims::AccessTokenClaims, encoder::JwtTokenEncoder,
+        refresh_token_claims::RefreshTokenClaims,
+    },
+    config::app_config::AuthConfig,
+    utils::{id::Id, id_generator::IdGenerator, time::Time},
+    web::{
+        app_data::AppData,
+        common::{api_error::ApiError, serde_chrono::ApiDateTime},
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub access_token_expires_at: ApiDateTime,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: ApiDateTime,
+}
+
+/// Starts a new session for the login
+pub fn issue<D: AppData>(
+    data: &D,
+    config: &AuthConfig,
+    access: &JwtTokenEncoder<AccessTokenClaims>,
+    refresh: &JwtTokenEncoder<RefreshTokenClaims>,
+    login_id: Id,
+) -> Result<TokenPair, ApiError> {
+    let now = data.time().now();
+    let access_exp = now + config.access_token_ttl();
+    let refresh_exp = now + config.refresh_token_ttl();
+
+    let access_token = access
+        .encode(&AccessTokenClaims {
+            sub: login_id,
+            exp: access_exp.into(),
+            iat: now.into(),
+        })
+        .map_err(encode_error)?;
+
+    let refresh_token = refresh
+        .encode(&RefreshTokenClaims {
+            sub: login_id,
+            exp: refresh_exp.into(),
+            iat: now.into(),
+            jti: data.id().next_id(),
+            sid: data.id().next_id(),
+        })
+        .map_err(encode_error)?;
+
+    Ok(TokenPair {
+        access_token,
+        access_token_expires_at: access_exp.into(),
+        refresh_token,
+        refresh_token_expires_at: refresh_exp.into(),
+    })
+}
+
+pub fn encode_error(err: jsonwebtoken::errors::Error) -> ApiError {
+    tracing::error!("token encoding error: {}", err);
+    ApiError::unexpected().build()
+}
@@ -0,0 +1,552 @@
+use actix_web::{web, HttpMessage, HttpRequest};
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{
+        login::Login, login_lockout::LoginLockout, login_totp::LoginTotp, principal::Principal,
+        recovery_code, totp::TotpSecret,
+    },
+    config::app_config::AppConfig,
+    dal::{login_totps_dal::LoginTotpsDal, logins_dal::LoginsDal, Dal},
+    utils::{
+        secret::{expose, expose_all, Secret},
+        time::Time,
+    },
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::interactive::Interactive,
+        common::{api_error::ApiError, api_result::ApiResult},
+        forwarded::ClientIp,
+    },
+};
+
+use super::login::{invalid_code, locked_out};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TotpEnrollment {
+    /// Base32 encoded secret for manual entry
+    #[serde(serialize_with = "expose")]
+    pub secret: Secret<String>,
+    #[serde(serialize_with = "expose")]
+    pub otpauth_uri: Secret<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TotpCodeRequest {
+    #[serde(serialize_with = "expose")]
+    pub code: Secret<String>,
+}
+
+/// TOTP can be disabled with a current code, a recovery code or the password
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TotpDisableRequest {
+    Code {
+        #[serde(serialize_with = "expose")]
+        code: Secret<String>,
+    },
+    Password {
+        #[serde(serialize_with = "expose")]
+        password: Secret<String>,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TotpRecoveryCodes {
+    #[serde(serialize_with = "expose_all")]
+    pub recovery_codes: Vec<Secret<String>>,
+}
+
+/// Generates a new secret. TOTP isn't enforced until the enrollment is confirmed.
+pub async fn enroll<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    _interactive: Interactive,
+) -> ApiResult<TotpEnrollment> {
+    let login = get_login(data.as_ref(), principal).await?;
+
+    let existing = data.dal().login_totps().get(login.login_id()).await?;
+    if existing.is_some_and(|t| t.is_confirmed()) {
+        return Err(already_enabled());
+    }
+
+    let secret = TotpSecret::generate();
+    let enrollment = TotpEnrollment {
+        secret: Secret::new(secret.to_base32()),
+        otpauth_uri: Secret::new(
+            secret.otpauth_uri(config.auth().totp().issuer(), login.username()),
+        ),
+    };
+    data.dal()
+        .login_totps()
+        .save(LoginTotp::new(login.login_id(), secret))
+        .await?;
+
+    tracing::info!(login_id = %login.login_id(), "totp enrollment started");
+    Ok(web::Json(enrollment))
+}
+
+/// Enables TOTP after checking the first code. Recovery codes are returned only once.
+#[allow(clippy::too_many_arguments)]
+pub async fn confirm<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    _interactive: Interactive,
+    req: HttpRequest,
+    audit: AuditContext,
+    request: web::Json<TotpCodeRequest>,
+) -> ApiResult<TotpRecoveryCodes> {
+    let login = get_login(data.as_ref(), principal).await?;
+    let expected = match data.dal().login_totps().get(principal.id()).await? {
+        Some(totp) if totp.is_confirmed() => return Err(already_enabled()),
+        Some(totp) => totp,
+        None => {
+            return Err(ApiError::not_found()
+                .message("totp enrollment not found".to_owned())
+                .build())
+        }
+    };
+
+    let now = data.time().now();
+    let totp_config = config.auth().totp();
+    let lockout = CodeLockout::new(data.as_ref(), &config, &req, &login);
+    lockout.check().await?;
+    let mut totp = expected.clone();
+    if !totp.verify_code(&request.code, now, totp_config.skew_steps()) {
+        lockout.failed().await?;
+        return Err(invalid_code());
+    }
+    lockout.succeeded().await?;
+
+    let (codes, hashes) = recovery_code::generate(totp_config.recovery_codes_count())
+        .into_iter()
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    totp.confirm(now, hashes);
+    if !data
+        .dal()
+        .login_totps()
+        .compare_and_save(&expected, totp)
+        .await?
+    {
+        return Err(ApiError::conflict()
+            .message("totp enrollment changed concurrently".to_owned())
+            .build());
+    }
+
+    tracing::info!(login_id = %principal.id(), "totp enabled");
+    let entry = audit.entry(AuditAction::TotpEnabled);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(TotpRecoveryCodes {
+        recovery_codes: codes.into_iter().map(Secret::new).collect(),
+    }))
+}
+
+/// Disables TOTP. Requires a current TOTP code, a recovery code or the password of the login.
+#[allow(clippy::too_many_arguments)]
+pub async fn disable<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    _interactive: Interactive,
+    req: HttpRequest,
+    audit: AuditContext,
+    request: web::Json<TotpDisableRequest>,
+) -> ApiResult<()> {
+    let login = get_login(data.as_ref(), principal).await?;
+    let mut totp = data
+        .dal()
+        .login_totps()
+        .get(principal.id())
+        .await?
+        .ok_or_else(|| ApiError::not_found().message("totp is not enabled".to_owned()))?;
+
+    let now = data.time().now();
+    let skew = config.auth().totp().skew_steps();
+    let lockout = CodeLockout::new(data.as_ref(), &config, &req, &login);
+    lockout.check().await?;
+    let error = match request.into_inner() {
+        TotpDisableRequest::Code { code } => {
+            (!totp.verify_code_or_recovery_code(&code, now, skew)).then(invalid_code)
+        }
+        TotpDisableRequest::Password { password } => {
+            (!login.password().is_some_and(|pwd| pwd.verify(&password))).then(invalid_password)
+        }
+    };
+    if let Some(error) = error {
+        lockout.failed().await?;
+        return Err(error);
+    }
+    lockout.succeeded().await?;
+    data.dal().login_totps().delete(principal.id()).await?;
+
+    tracing::info!(login_id = %principal.id(), "totp disabled");
+    let entry = audit.entry(AuditAction::TotpDisabled);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(()))
+}
+
+async fn get_login<D: AppData>(data: &D, principal: Principal) -> Result<Login, ApiError> {
+    data.dal()
+        .logins()
+        .get(principal.id())
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found()
+                .message("login not found".to_owned())
+                .build()
+        })
+}
+
+/// Failed codes count towards the lockout of the login, like failed passwords
+struct CodeLockout<'a, D: AppData> {
+    data: &'a D,
+    lockout: LoginLockout<'a, D::RateLimitStore>,
+    login: &'a Login,
+    ip: Option<std::net::IpAddr>,
+}
+
+impl<'a, D: AppData> CodeLockout<'a, D> {
+    fn new(data: &'a D, config: &'a AppConfig, req: &HttpRequest, login: &'a Login) -> Self {
+        Self {
+            data,
+            lockout: LoginLockout::new(data.rate_limits(), config.auth().lockout()),
+            login,
+            ip: req.extensions().get::<ClientIp>().map(|ip| ip.0),
+        }
+    }
+
+    async fn check(&self) -> Result<(), ApiError> {
+        let now = self.data.time().now();
+        match self
+            .lockout
+            .locked_until(self.login.username(), self.ip, now)
+            .await?
+        {
+            Some(until) => {
+                tracing::info!(login_id = %self.login.login_id(), "totp is locked out until {}", until);
+                Err(locked_out(until, now))
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn failed(&self) -> Result<(), ApiError> {
+        tracing::info!(login_id = %self.login.login_id(), "invalid totp code or password");
+        let now = self.data.time().now();
+        Ok(self
+            .lockout
+            .failed(self.login.username(), self.ip, now)
+            .await?)
+    }
+
+    async fn succeeded(&self) -> Result<(), ApiError> {
+        let now = self.data.time().now();
+        Ok(self.lockout.succeeded(self.login.username(), now).await?)
+    }
+}
+
+fn invalid_password() -> ApiError {
+    ApiError::unauthorized()
+        .message("invalid password".to_owned())
+        .build()
+}
+
+fn already_enabled() -> ApiError {
+    ApiError::conflict()
+        .message("totp is already enabled".to_owned())
+        .build()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::content_right::ContentRight,
+        test::*,
+        utc,
+        web::{
+            common::api_error::ErrorCode,
+            routes::auth::login::{LoginRequest, LoginResponse},
+        },
+    };
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn code(code: String) -> TotpCodeRequest {
+        TotpCodeRequest {
+            code: Secret::new(code),
+        }
+    }
+
+    #[test]
+    fn enroll_and_confirm() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let now = utc!(2024, 3, 4, 5, 6, 7);
+            ctx.time().set(now);
+
+            // act
+            let enrollment = server
+                .client()
+                .post("/api/auth/totp/enroll/v1")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<TotpEnrollment>();
+            let secret = TotpSecret::from_base32(&enrollment.secret).unwrap();
+            let recovery_codes = server
+                .client()
+                .post("/api/auth/totp/confirm/v1")
+                .access_token(&token)
+                .json(&code(secret.code_at(now)))
+                .send()
+                .await
+                .unwrap::<TotpRecoveryCodes>();
+
+            // assert
+            assert!(enrollment
+                .otpauth_uri
+                .starts_with("otpauth://totp/rusty%2Dhttp%2Dfs%2Dtest:user?secret="));
+            assert_eq!(recovery_codes.recovery_codes.len(), 10);
+
+            let totp = ctx
+                .dal()
+                .login_totps()
+                .get(login.login_id())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(totp.confirmed_at(), Some(now));
+            assert_eq!(totp.recovery_codes_left(), 10);
+
+            let response = server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&LoginRequest {
+                    username: "user".to_owned(),
+                    password: Secret::new("password".to_owned()),
+                })
+                .send()
+                .await
+                .unwrap::<LoginResponse>();
+            assert!(matches!(response, LoginResponse::TotpRequired(_)));
+        });
+    }
+
+    #[test]
+    fn enroll_requires_authentication() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/totp/enroll/v1")
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn confirm_with_wrong_code_keeps_totp_disabled() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            server
+                .client()
+                .post("/api/auth/totp/enroll/v1")
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<TotpEnrollment>();
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/totp/confirm/v1")
+                .access_token(&token)
+                .json(&code("000000".to_owned()))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+            let totp = ctx
+                .dal()
+                .login_totps()
+                .get(login.login_id())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!totp.is_confirmed());
+        });
+    }
+
+    #[test]
+    fn enroll_when_enabled_is_conflict() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let mut totp = LoginTotp::new(login.login_id(), TotpSecret::generate());
+            totp.confirm(ctx.time().now(), vec![]);
+            ctx.dal().login_totps().save(totp).await.unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post("/api/auth/totp/enroll/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::Conflict);
+        });
+    }
+
+    #[test]
+    fn disable_with_code() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let secret = TotpSecret::generate();
+            let mut totp = LoginTotp::new(login.login_id(), secret.clone());
+            totp.confirm(ctx.time().now(), vec![]);
+            ctx.dal().login_totps().save(totp).await.unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            server
+                .client()
+                .delete("/api/auth/totp/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .json(&code(secret.code_at(ctx.time().now())))
+                .send()
+                .await
+                .unwrap::<()>();
+
+            // assert
+            let totp = ctx.dal().login_totps().get(login.login_id()).await.unwrap();
+            assert_eq!(totp, None);
+        });
+    }
+
+    #[test]
+    fn disable_with_password() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let mut totp = LoginTotp::new(login.login_id(), TotpSecret::generate());
+            totp.confirm(ctx.time().now(), vec![]);
+            ctx.dal().login_totps().save(totp).await.unwrap();
+            let server = ctx.run_server().await;
+
+            // act
+            server
+                .client()
+                .delete("/api/auth/totp/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .json(&TotpDisableRequest::Password {
+                    password: Secret::new("password".to_owned()),
+                })
+                .send()
+                .await
+                .unwrap::<()>();
+
+            // assert
+            let totp = ctx.dal().login_totps().get(login.login_id()).await.unwrap();
+            assert_eq!(totp, None);
+        });
+    }
+
+    #[test]
+    fn failed_codes_lock_out_disabling() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let secret = TotpSecret::generate();
+            let mut totp = LoginTotp::new(login.login_id(), secret.clone());
+            totp.confirm(ctx.time().now(), vec![]);
+            ctx.dal().login_totps().save(totp).await.unwrap();
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let max_failures = ctx
+                .env()
+                .config()
+                .auth()
+                .lockout()
+                .max_failures_per_username();
+            for _ in 0..max_failures {
+                let response = server
+                    .client()
+                    .delete("/api/auth/totp/v1")
+                    .access_token(&token)
+                    .json(&code("000000".to_owned()))
+                    .send()
+                    .await;
+                assert_eq!(response.unwrap_err().code, ErrorCode::Unauthorized);
+            }
+
+            // act
+            let response = server
+                .client()
+                .delete("/api/auth/totp/v1")
+                .access_token(&token)
+                .json(&code(secret.code_at(ctx.time().now())))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.unwrap_err().code, ErrorCode::TooManyRequests);
+            let totp = ctx.dal().login_totps().get(login.login_id()).await.unwrap();
+            assert!(totp.is_some());
+        });
+    }
+
+    #[test]
+    fn personal_access_token_cannot_manage_totp() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::All).await;
+            let (_, token) = ctx
+                .create_personal_access_token(&login, ContentRight::All, None, None)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let enroll = server
+                .client()
+                .post("/api/auth/totp/enroll/v1")
+                .access_token(&token)
+                .send()
+                .await;
+            let disable = server
+                .client()
+                .delete("/api/auth/totp/v1")
+                .access_token(&token)
+                .json(&TotpDisableRequest::Password {
+                    password: Secret::new("password".to_owned()),
+                })
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(enroll.unwrap_err().code, ErrorCode::Forbidden);
+            assert_eq!(disable.unwrap_err().code, ErrorCode::Forbidden);
+        });
+    }
+}
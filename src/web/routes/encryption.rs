@@ -0,0 +1,104 @@
+use actix_web::web;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::principal::Principal,
+    config::app_config::AppConfig,
+    fs::encryption::{key_ring::KeyRing, key_rotation::rotate_data_keys},
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::admin_access::require_admin,
+        common::{api_error::ApiError, api_result::ApiResult},
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct KeyRotationInfo {
+    /// Data keys which weren't wrapped with the active master key before
+    pub rotated: usize,
+}
+
+/// Re-wraps the data keys with the active master key, old master keys can be removed afterwards
+pub async fn rotate<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    audit: AuditContext,
+) -> ApiResult<KeyRotationInfo> {
+    require_admin(data.as_ref(), config.encryption().admins(), principal).await?;
+    let secrets = config.secrets().encryption().ok_or_else(|| {
+        ApiError::not_found()
+            .message("no master keys are configured".to_owned())
+            .build()
+    })?;
+    let keys = KeyRing::from_config(secrets)?;
+    let rotated = rotate_data_keys(data.dal(), &keys).await?;
+
+    tracing::warn!(rotated, master_key = keys.active_key(), "data keys rotated");
+    let entry = audit
+        .entry(AuditAction::DataKeysRotated)
+        .with_target(keys.active_key())
+        .with_detail(rotated);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(KeyRotationInfo { rotated }))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::dal::{data_keys_dal::DataKeysDal, Dal};
+    use crate::fs::memory_backend::MemoryBackend;
+    use crate::test::*;
+    use crate::web::common::api_error::ErrorCode;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    const PATH: &str = "/api/encryption/rotate/v1";
+
+    #[test]
+    fn admin_rotates_data_keys() {
+        test(|ctx| async move {
+            // arrange
+            let inner = MemoryBackend::new(ctx.time().clone());
+            let source = ctx
+                .encrypted_source("secret", inner, dir! { "a.txt" => "content" })
+                .await;
+            ctx.update_config(|config| {
+                let encryption = &mut config["secrets"]["encryption"];
+                encryption["active_key"] = json!("new");
+                encryption["master_keys"]["new"] =
+                    json!("OTg3NjU0MzIxMDk4NzY1NDMyMTA5ODc2NTQzMjEwOTg=");
+                config["encryption"]["admins"] = json!(["admin"]);
+            });
+            let admin = ctx.create_login("admin", "password").await;
+            let user = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+
+            // act
+            let forbidden = server
+                .client()
+                .post(PATH)
+                .access_token(&ctx.access_token_for(&user))
+                .send()
+                .await;
+            let token = ctx.access_token_for(&admin);
+            let rotated = server.client().post(PATH).access_token(&token).send().await;
+            let again = server.client().post(PATH).access_token(&token).send().await;
+
+            // assert
+            assert_eq!(forbidden.unwrap_err().code, ErrorCode::Forbidden);
+            assert_eq!(
+                rotated.unwrap::<KeyRotationInfo>(),
+                KeyRotationInfo { rotated: 1 }
+            );
+            assert_eq!(
+                again.unwrap::<KeyRotationInfo>(),
+                KeyRotationInfo { rotated: 0 }
+            );
+            let wrapped = ctx.dal().data_keys().get(source.id()).await.unwrap();
+            assert_eq!(wrapped.unwrap().master_key_id(), "new");
+        });
+    }
+}
@@ -0,0 +1,3 @@
+pub mod files;
+pub mod sources;
+pub mod versions;
@@ -0,0 +1,1204 @@
+use std::{future::Future, ops::Range, sync::Arc};
+
+use actix_web::{
+    body::SizedStream,
+    http::header::{self, AcceptEncoding, Encoding, HeaderValue},
+    http::StatusCode,
+    web, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use super::versions::write_versioned;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    config::app_config::AppConfig,
+    dal::{file_checksums_dal::FileChecksumsDal, Dal},
+    fs::{
+        digests::verified_stream,
+        file_checksum::{file_digests, FileChecksum},
+        source::Source,
+        storage_backend::{limit_stream, EntryKind, EntryMetadata, FileRead},
+        storage_error::StorageError,
+        storage_path::StoragePath,
+    },
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::source_access::{authorize_path, authorize_source},
+        common::{
+            api_error::ApiError,
+            api_result::ApiResult,
+            digest_headers::{
+                expected_digests, format_digests, format_legacy_digests, wanted_algorithms,
+                CONTENT_DIGEST, DIGEST, REPR_DIGEST, WANT_CONTENT_DIGEST, WANT_DIGEST,
+                WANT_REPR_DIGEST,
+            },
+            serde_chrono::ApiDateTime,
+        },
+        metrics::Download,
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct EntryInfo {
+    pub path: StoragePath,
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: ApiDateTime,
+}
+
+impl From<EntryMetadata> for EntryInfo {
+    fn from(value: EntryMetadata) -> Self {
+        Self {
+            name: value.name().to_owned(),
+            kind: value.kind(),
+            size: value.size(),
+            modified: value.modified().into(),
+            path: value.path().clone(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RenameRequest {
+    pub from: StoragePath,
+    pub to: StoragePath,
+}
+
+async fn resolve<D: AppData>(
+    data: &D,
+    principal: Principal,
+    scope: &TokenScope,
+    (source_id, path): (Uuid, String),
+    right: ContentRight,
+) -> Result<(Arc<Source>, StoragePath), ApiError> {
+    let source = authorize_source(data, principal, scope, source_id, right).await?;
+    let path = StoragePath::parse(&path)?;
+    authorize_path(scope, &path)?;
+    Ok((source, path))
+}
+
+pub async fn stat<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+) -> ApiResult<EntryInfo> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Read,
+    )
+    .await?;
+    let metadata = source.backend().stat(&path).await?;
+    Ok(web::Json(metadata.into()))
+}
+
+pub async fn list<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+) -> ApiResult<Vec<EntryInfo>> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Read,
+    )
+    .await?;
+    let entries = source.backend().list(&path).await?;
+    Ok(web::Json(entries.into_iter().map(Into::into).collect()))
+}
+
+/// Supports a single `Range`, other range requests get the whole file
+pub async fn download<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+    audit: AuditContext,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Read,
+    )
+    .await?;
+    let metadata = source.backend().stat(&path).await?;
+    if metadata.is_dir() {
+        return Err(StorageError::IsADirectory(path.to_string()).into());
+    }
+    let mut response = serve_file(data.as_ref(), &source, &metadata, &req).await?;
+    isolate_content(&path, &mut response);
+    if response.status().is_success() {
+        let entry = audit
+            .entry(AuditAction::FileRead)
+            .with_source(source.id())
+            .with_target(&path);
+        audit.record(data.as_ref(), entry).await;
+    }
+    Ok(response)
+}
+
+/// Content of the file with ranges, precompressed siblings and wanted digests
+pub(in crate::web::routes) async fn serve_file<D: AppData>(
+    data: &D,
+    source: &Source,
+    metadata: &EntryMetadata,
+    req: &HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let path = metadata.path();
+    if source.precompressed() {
+        if let Some(response) = precompressed_response(data, source, path, req).await? {
+            return Ok(response);
+        }
+    }
+    let backend = source.backend();
+    let mut response = file_response(req, path, metadata.size(), |range| {
+        backend.read(path, range)
+    })
+    .await?;
+    if response.status().is_success() {
+        add_digest_headers(data, source, metadata, req, &mut response).await?;
+    }
+    if source.precompressed() {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    Ok(response)
+}
+
+/// Serves the `.br` or `.gz` sibling of the file the client prefers, if there is one.
+/// Range requests always get the file itself.
+async fn precompressed_response<D: AppData>(
+    data: &D,
+    source: &Source,
+    path: &StoragePath,
+    req: &HttpRequest,
+) -> Result<Option<HttpResponse>, ApiError> {
+    let Some(accept) = req
+        .get_header::<AcceptEncoding>()
+        .filter(|_| !req.headers().contains_key(header::RANGE))
+    else {
+        return Ok(None);
+    };
+    let mut available = Vec::new();
+    for (encoding, extension) in [(Encoding::brotli(), "br"), (Encoding::gzip(), "gz")] {
+        let name = format!("{}.{}", path.name().unwrap_or_default(), extension);
+        let sibling = path.parent().unwrap_or_default().join(&name)?;
+        match source.backend().stat(&sibling).await {
+            Ok(metadata) if !metadata.is_dir() => available.push((encoding, metadata)),
+            Ok(_) | Err(StorageError::NotFound(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let supported: Vec<_> = available
+        .iter()
+        .map(|(encoding, _)| encoding.clone())
+        .chain([Encoding::identity()])
+        .collect();
+    let chosen = accept.negotiate(supported.iter());
+    let Some((encoding, sibling)) = available
+        .into_iter()
+        .find(|(encoding, _)| Some(encoding) == chosen.as_ref())
+    else {
+        return Ok(None);
+    };
+
+    let read = source.backend().read(sibling.path(), None).await?;
+    let mime = mime_guess::from_path(path.as_str()).first_or_octet_stream();
+    let mut response = HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, mime.to_string()))
+        .insert_header((header::CONTENT_ENCODING, encoding.to_string()))
+        .insert_header((header::VARY, "accept-encoding"))
+        .insert_header(header::LastModified(
+            std::time::SystemTime::from(read.metadata.modified()).into(),
+        ))
+        .body(SizedStream::new(read.metadata.size(), read.stream));
+    response.extensions_mut().insert(Download);
+    // digests of the content as sent, which is the encoded one
+    add_digest_headers(data, source, &sibling, req, &mut response).await?;
+    Ok(Some(response))
+}
+
+/// Answers `Want-Content-Digest`, `Want-Repr-Digest` and `Want-Digest`.
+/// Partial responses only get the digests of the whole file.
+async fn add_digest_headers<D: AppData>(
+    data: &D,
+    source: &Source,
+    metadata: &EntryMetadata,
+    req: &HttpRequest,
+    response: &mut HttpResponse,
+) -> Result<(), ApiError> {
+    let wanted = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(wanted_algorithms)
+            .filter(|algorithms| !algorithms.is_empty())
+    };
+    let full = response.status() == StatusCode::OK;
+    let answers = [
+        (
+            CONTENT_DIGEST,
+            wanted(&WANT_CONTENT_DIGEST).filter(|_| full),
+        ),
+        (REPR_DIGEST, wanted(&WANT_REPR_DIGEST)),
+        (DIGEST, wanted(&WANT_DIGEST)),
+    ];
+    if answers.iter().all(|(_, algorithms)| algorithms.is_none()) {
+        return Ok(());
+    }
+
+    let digests = file_digests(data.dal(), source, metadata).await?;
+    for (name, algorithms) in answers {
+        let Some(algorithms) = algorithms else {
+            continue;
+        };
+        let value = if name == DIGEST {
+            format_legacy_digests(&digests, &algorithms)
+        } else {
+            format_digests(&digests, &algorithms)
+        };
+        response
+            .headers_mut()
+            .insert(name, HeaderValue::from_str(&value).unwrap());
+    }
+    Ok(())
+}
+
+/// Keeps uploaded content from running as a page of this origin, where it would share the
+/// session cookies of `/ui`: nothing is sniffed, documents are sandboxed into an opaque
+/// origin and active types are downloaded rather than shown
+pub(super) fn isolate_content(path: &StoragePath, response: &mut HttpResponse) {
+    let headers = response.headers_mut();
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("sandbox"),
+    );
+    let mime = mime_guess::from_path(path.as_str()).first_or_octet_stream();
+    if ACTIVE_CONTENT_TYPES.contains(&mime.essence_str()) {
+        let disposition = header::ContentDisposition {
+            disposition: header::DispositionType::Attachment,
+            parameters: path
+                .name()
+                .map(|name| header::DispositionParam::Filename(name.to_string()))
+                .into_iter()
+                .collect(),
+        };
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(&disposition.to_string()).unwrap(),
+        );
+    }
+}
+
+/// Types a browser would run scripts from when shown inline
+const ACTIVE_CONTENT_TYPES: &[&str] = &[
+    "text/html",
+    "application/xhtml+xml",
+    "image/svg+xml",
+    "text/xml",
+    "application/xml",
+    "text/javascript",
+    "application/javascript",
+    "application/x-javascript",
+];
+
+/// Content of a file of the given size, read with the requested range if it's satisfiable
+pub(super) async fn file_response<F, Fut>(
+    req: &HttpRequest,
+    path: &StoragePath,
+    size: u64,
+    read: F,
+) -> Result<HttpResponse, ApiError>
+where
+    F: FnOnce(Option<Range<u64>>) -> Fut,
+    Fut: Future<Output = Result<FileRead, StorageError>>,
+{
+    let range = match req
+        .headers()
+        .get(header::RANGE)
+        .map(|v| parse_range(v, size))
+    {
+        Some(RangeHeader::Satisfiable(range)) => Some(range),
+        Some(RangeHeader::Unsatisfiable) => {
+            let mut response = ApiError::range_not_satisfiable().build().error_response();
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{}", size)).unwrap(),
+            );
+            return Ok(response);
+        }
+        Some(RangeHeader::Ignored) | None => None,
+    };
+
+    let read = read(range.clone()).await?;
+    let mut response = match range {
+        Some(_) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header((
+                header::CONTENT_RANGE,
+                format!(
+                    "bytes {}-{}/{}",
+                    read.range.start,
+                    read.range.end - 1,
+                    read.metadata.size()
+                ),
+            ));
+            response
+        }
+        None => HttpResponse::Ok(),
+    };
+    let mime = mime_guess::from_path(path.as_str()).first_or_octet_stream();
+    let mut response = response
+        .insert_header((header::CONTENT_TYPE, mime.to_string()))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header(header::LastModified(
+            std::time::SystemTime::from(read.metadata.modified()).into(),
+        ))
+        .body(SizedStream::new(
+            read.range.end - read.range.start,
+            read.stream,
+        ));
+    response.extensions_mut().insert(Download);
+    Ok(response)
+}
+
+/// Creates or replaces a file with the request body.
+/// Digests sent in `Content-Digest` or `Digest` must match, otherwise nothing is written.
+/// Bodies over `uploads.max_file_size` are rejected, up front if `Content-Length` says so.
+#[allow(clippy::too_many_arguments)]
+pub async fn upload<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+    audit: AuditContext,
+    payload: web::Payload,
+    req: HttpRequest,
+) -> ApiResult<EntryInfo> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    let max_file_size = config.uploads().max_file_size();
+    if let Some(max) = max_file_size {
+        if content_length(&req).is_some_and(|length| length > max) {
+            return Err(StorageError::TooLarge(max).into());
+        }
+    }
+    let _upload = data.metrics().upload_started();
+    let mut content = payload
+        .map(|chunk| chunk.map_err(|e| StorageError::Io(e.to_string())))
+        .boxed_local();
+    if let Some(max) = max_file_size {
+        content = limit_stream(content, max);
+    }
+    let (content, digests) = verified_stream(content, expected_digests(req.headers())?);
+    let metadata = write_versioned(data.as_ref(), &source, &path, principal, content).await?;
+    let digests = *digests.borrow();
+    if let Some(digests) = digests {
+        data.dal()
+            .file_checksums()
+            .save(FileChecksum::new(source.id(), &metadata, digests))
+            .await?;
+    }
+    tracing::info!(source_id = %source.id(), path = %path, size = metadata.size(), "file written");
+    let entry = audit
+        .entry(AuditAction::FileWritten)
+        .with_source(source.id())
+        .with_target(&path);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(metadata.into()))
+}
+
+/// Removes a file or an empty directory
+pub async fn delete<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+    audit: AuditContext,
+) -> ApiResult<()> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    source.backend().delete(&path).await?;
+    data.dal()
+        .file_checksums()
+        .delete_under(source.id(), &path)
+        .await?;
+    tracing::info!(source_id = %source.id(), path = %path, "entry deleted");
+    let entry = audit
+        .entry(AuditAction::FileDeleted)
+        .with_source(source.id())
+        .with_target(&path);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(()))
+}
+
+pub async fn mkdir<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+    audit: AuditContext,
+) -> ApiResult<EntryInfo> {
+    let (source, path) = resolve(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    let metadata = source.backend().mkdir(&path).await?;
+    let entry = audit
+        .entry(AuditAction::DirectoryCreated)
+        .with_source(source.id())
+        .with_target(&path);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(metadata.into()))
+}
+
+pub async fn rename<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    source_id: web::Path<Uuid>,
+    audit: AuditContext,
+    request: web::Json<RenameRequest>,
+) -> ApiResult<()> {
+    let source = authorize_source(
+        data.as_ref(),
+        principal,
+        &scope,
+        source_id.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    authorize_path(&scope, &request.from)?;
+    authorize_path(&scope, &request.to)?;
+    source.backend().rename(&request.from, &request.to).await?;
+    data.dal()
+        .file_checksums()
+        .delete_under(source.id(), &request.from)
+        .await?;
+    tracing::info!(source_id = %source.id(), from = %request.from, to = %request.to, "entry renamed");
+    let entry = audit
+        .entry(AuditAction::FileMoved)
+        .with_source(source.id())
+        .with_target(&request.from)
+        .with_detail(&request.to);
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(()))
+}
+
+fn content_length(req: &HttpRequest) -> Option<u64> {
+    req.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeHeader {
+    Satisfiable(Range<u64>),
+    Unsatisfiable,
+    /// Malformed, multiple ranges or another unit, answered with the whole file
+    Ignored,
+}
+
+/// `bytes=first-last`, `bytes=first-` or `bytes=-suffix_length`, see RFC 9110 section 14.1.2
+fn parse_range(value: &HeaderValue, size: u64) -> RangeHeader {
+    let Some(spec) = value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+    else {
+        return RangeHeader::Ignored;
+    };
+    if spec.contains(',') {
+        return RangeHeader::Ignored;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return RangeHeader::Ignored;
+    };
+    let parse = |v: &str| v.trim().parse::<u64>().ok();
+    match (first.trim().is_empty(), last.trim().is_empty()) {
+        (true, false) => match parse(last) {
+            Some(0) => RangeHeader::Unsatisfiable,
+            Some(suffix) if size > 0 => RangeHeader::Satisfiable(size.saturating_sub(suffix)..size),
+            Some(_) => RangeHeader::Unsatisfiable,
+            None => RangeHeader::Ignored,
+        },
+        (false, true) => match parse(first) {
+            Some(first) if first < size => RangeHeader::Satisfiable(first..size),
+            Some(_) => RangeHeader::Unsatisfiable,
+            None => RangeHeader::Ignored,
+        },
+        (false, false) => match (parse(first), parse(last)) {
+            (Some(first), Some(last)) if first > last => RangeHeader::Ignored,
+            (Some(first), Some(last)) if first < size => {
+                RangeHeader::Satisfiable(first..(last + 1).min(size))
+            }
+            (Some(_), Some(_)) => RangeHeader::Unsatisfiable,
+            _ => RangeHeader::Ignored,
+        },
+        (true, true) => RangeHeader::Ignored,
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::fs::digests::{DigestAlgorithm, FileDigests};
+    use crate::test::*;
+    use crate::utc;
+    use crate::web::common::api_error::ErrorCode;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    fn storage_path(path: &str) -> StoragePath {
+        StoragePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn range_header() {
+        let range = |v: &'static str| parse_range(&HeaderValue::from_static(v), 10);
+        assert_eq!(range("bytes=2-4"), RangeHeader::Satisfiable(2..5));
+        assert_eq!(range("bytes=2-"), RangeHeader::Satisfiable(2..10));
+        assert_eq!(range("bytes=-3"), RangeHeader::Satisfiable(7..10));
+        assert_eq!(range("bytes=-30"), RangeHeader::Satisfiable(0..10));
+        assert_eq!(range("bytes=5-100"), RangeHeader::Satisfiable(5..10));
+        assert_eq!(range("bytes=10-"), RangeHeader::Unsatisfiable);
+        assert_eq!(range("bytes=-0"), RangeHeader::Unsatisfiable);
+        assert_eq!(range("bytes=4-2"), RangeHeader::Ignored);
+        assert_eq!(range("bytes=0-1,4-5"), RangeHeader::Ignored);
+        assert_eq!(range("items=0-1"), RangeHeader::Ignored);
+    }
+
+    #[test]
+    fn upload_and_download() {
+        test(|ctx| async move {
+            // arrange
+            ctx.time().set(utc!(2024, 5, 1));
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let uri = format!("/api/fs/v1/{}/files/notes/a.txt", source.id());
+            server
+                .client()
+                .post(&format!("/api/fs/v1/{}/mkdir/notes", source.id()))
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<EntryInfo>();
+
+            // act
+            let uploaded = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .body(Bytes::from("hello world"))
+                .send()
+                .await;
+            let downloaded = server.client().get(&uri).access_token(&token).send().await;
+            let partial = server
+                .client()
+                .get(&uri)
+                .access_token(&token)
+                .insert_header((header::RANGE, "bytes=6-"))
+                .send()
+                .await;
+
+            // assert
+            let uploaded: EntryInfo = uploaded.unwrap();
+            assert_eq!(uploaded.size, 11);
+            assert_eq!(*uploaded.modified, utc!(2024, 5, 1));
+            assert_eq!(downloaded.status, StatusCode::OK);
+            assert_eq!(downloaded.body, "hello world");
+            assert_eq!(
+                downloaded.headers.get(header::CONTENT_TYPE).unwrap(),
+                "text/plain"
+            );
+            assert_eq!(partial.status, StatusCode::PARTIAL_CONTENT);
+            assert_eq!(partial.body, "world");
+            assert_eq!(
+                partial.headers.get(header::CONTENT_RANGE).unwrap(),
+                "bytes 6-10/11"
+            );
+            ctx.assert_tree(
+                &source,
+                dir! { "notes" => dir! { "a.txt" => "hello world" } },
+            )
+            .await;
+        })
+    }
+
+    #[test]
+    fn downloads_can_not_run_as_pages_of_the_origin() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .memory_source(
+                    "docs",
+                    dir! { "page.html" => "<script>alert(1)</script>", "a.txt" => "text" },
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            let page = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/files/page.html", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            let text = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(page.status, StatusCode::OK);
+            assert_eq!(
+                page.headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+                "nosniff"
+            );
+            assert_eq!(
+                page.headers.get(header::CONTENT_SECURITY_POLICY).unwrap(),
+                "sandbox"
+            );
+            assert_eq!(
+                page.headers.get(header::CONTENT_DISPOSITION).unwrap(),
+                "attachment; filename=\"page.html\""
+            );
+            assert_eq!(
+                text.headers.get(header::CONTENT_SECURITY_POLICY).unwrap(),
+                "sandbox"
+            );
+            assert_eq!(text.headers.get(header::CONTENT_DISPOSITION), None);
+        })
+    }
+
+    #[test]
+    fn upload_replaces_file_on_disk() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .temp_dir_source("disk", dir! { "a.txt" => "old", "b.txt" => "b" })
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .put(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&ctx.access_token_for(&login))
+                .body(Bytes::from("new"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::OK);
+            ctx.assert_tree(&source, dir! { "a.txt" => "new", "b.txt" => "b" })
+                .await;
+        })
+    }
+
+    #[test]
+    fn upload_rejects_files_over_limit() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["uploads"]["max_file_size"] = json!(4));
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "old" }).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let uri = format!("/api/fs/v1/{}/files/a.txt", source.id());
+
+            // act
+            let too_large = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .body(Bytes::from("hello"))
+                .send()
+                .await;
+            let within = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .body(Bytes::from("new"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(too_large.status, StatusCode::PAYLOAD_TOO_LARGE);
+            assert_eq!(too_large.unwrap_err().code, ErrorCode::PayloadTooLarge);
+            assert_eq!(within.status, StatusCode::OK);
+            ctx.assert_tree(&source, dir! { "a.txt" => "new" }).await;
+        })
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "abc" }).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&ctx.access_token_for(&login))
+                .insert_header((header::RANGE, "bytes=3-"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(
+                response.headers.get(header::CONTENT_RANGE).unwrap(),
+                "bytes */3"
+            );
+        })
+    }
+
+    #[test]
+    fn list_rename_delete() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .memory_source("docs", dir! { "a" => dir! { "b.txt" => "b" } })
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            server
+                .client()
+                .post(&format!("/api/fs/v1/{}/rename", source.id()))
+                .access_token(&token)
+                .json(&RenameRequest {
+                    from: storage_path("a/b.txt"),
+                    to: storage_path("c.txt"),
+                })
+                .send()
+                .await
+                .unwrap::<()>();
+            let deleted = server
+                .client()
+                .delete(&format!("/api/fs/v1/{}/files/a", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            let root = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/list/", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(deleted.status, StatusCode::OK);
+            let root: Vec<EntryInfo> = root.unwrap();
+            assert_eq!(
+                root.iter()
+                    .map(|e| (e.name.as_str(), e.kind))
+                    .collect::<Vec<_>>(),
+                vec![("c.txt", EntryKind::File)]
+            );
+            ctx.assert_tree(&source, dir! { "c.txt" => "b" }).await;
+        })
+    }
+
+    #[test]
+    fn rejects_path_escape() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .post(&format!("/api/fs/v1/{}/rename", source.id()))
+                .access_token(&ctx.access_token_for(&login))
+                .json(&serde_json::json!({ "from": "a.txt", "to": "../../etc/a.txt" }))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        })
+    }
+
+    #[test]
+    fn requires_rights_on_source() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let other = ctx.memory_source("other", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let (_, token) = ctx
+                .create_personal_access_token(
+                    &login,
+                    ContentRight::Read | ContentRight::Write,
+                    Some(vec![other.id()]),
+                    None,
+                )
+                .await;
+            let reader = ctx.create_login("reader", "password").await;
+            ctx.grant_rights(&reader, ContentRight::Read).await;
+            let server = ctx.run_server().await;
+            let uri = format!("/api/fs/v1/{}/files/a.txt", source.id());
+
+            // act
+            let out_of_scope = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .body(Bytes::from("a"))
+                .send()
+                .await;
+            let read_only = server
+                .client()
+                .put(&uri)
+                .access_token(&ctx.access_token_for(&reader))
+                .body(Bytes::from("a"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(out_of_scope.status, StatusCode::FORBIDDEN);
+            assert_eq!(read_only.status, StatusCode::FORBIDDEN);
+            ctx.assert_tree(&source, dir! {}).await;
+        })
+    }
+
+    #[test]
+    fn path_scoped_token_reaches_only_its_path() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .memory_source(
+                    "docs",
+                    dir! { "shared" => dir! { "a.txt" => "a" }, "b.txt" => "b" },
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::All).await;
+            let server = ctx.run_server().await;
+            let created = server
+                .client()
+                .post("/api/auth/tokens/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .json(&serde_json::json!({
+                    "name": "share",
+                    "rights": ["read", "write"],
+                    "sources": [source.id()],
+                    "path": "/shared",
+                }))
+                .send()
+                .await
+                .unwrap::<serde_json::Value>();
+            let token = created["token"].as_str().unwrap();
+            let files = format!("/api/fs/v1/{}/files", source.id());
+
+            // act
+            let inside = server
+                .client()
+                .get(&format!("{}/shared/a.txt", files))
+                .access_token(token)
+                .send()
+                .await;
+            let outside = server
+                .client()
+                .get(&format!("{}/b.txt", files))
+                .access_token(token)
+                .send()
+                .await;
+            let listing = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/list/", source.id()))
+                .access_token(token)
+                .send()
+                .await;
+            let moved_out = server
+                .client()
+                .post(&format!("/api/fs/v1/{}/rename", source.id()))
+                .access_token(token)
+                .json(&RenameRequest {
+                    from: StoragePath::parse("shared/a.txt").unwrap(),
+                    to: StoragePath::parse("a.txt").unwrap(),
+                })
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(created["path"], "/shared");
+            assert_eq!(inside.status, StatusCode::OK);
+            assert_eq!(outside.status, StatusCode::FORBIDDEN);
+            assert_eq!(listing.status, StatusCode::FORBIDDEN);
+            assert_eq!(moved_out.status, StatusCode::FORBIDDEN);
+            ctx.assert_tree(
+                &source,
+                dir! { "shared" => dir! { "a.txt" => "a" }, "b.txt" => "b" },
+            )
+            .await;
+        })
+    }
+
+    #[test]
+    fn upload_checks_content_digest() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "old" }).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let uri = format!("/api/fs/v1/{}/files/a.txt", source.id());
+            let sha = |content: &[u8]| {
+                format_digests(&FileDigests::of(content), &[DigestAlgorithm::Sha256])
+            };
+
+            // act
+            let mismatch = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .insert_header((CONTENT_DIGEST, sha(b"something else")))
+                .body(Bytes::from("new"))
+                .send()
+                .await;
+            let legacy = format_legacy_digests(&FileDigests::of(b"new"), &[DigestAlgorithm::Md5]);
+            let matching = server
+                .client()
+                .put(&uri)
+                .access_token(&token)
+                .insert_header((CONTENT_DIGEST, sha(b"new")))
+                .insert_header((DIGEST, legacy))
+                .body(Bytes::from("new"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(mismatch.status, StatusCode::BAD_REQUEST);
+            assert_eq!(matching.status, StatusCode::OK);
+            ctx.assert_tree(&source, dir! { "a.txt" => "new" }).await;
+            let stored = ctx
+                .dal()
+                .file_checksums()
+                .get(source.id(), &storage_path("a.txt"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(*stored.digests(), FileDigests::of(b"new"));
+        })
+    }
+
+    #[test]
+    fn download_returns_wanted_digests() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "hello" }).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let uri = format!("/api/fs/v1/{}/files/a.txt", source.id());
+            let digests = FileDigests::of(b"hello");
+
+            // act
+            let full = server
+                .client()
+                .get(&uri)
+                .access_token(&token)
+                .insert_header((WANT_CONTENT_DIGEST, "md5=1, sha-256=5"))
+                .insert_header((WANT_DIGEST, "MD5"))
+                .send()
+                .await;
+            let partial = server
+                .client()
+                .get(&uri)
+                .access_token(&token)
+                .insert_header((header::RANGE, "bytes=0-1"))
+                .insert_header((WANT_CONTENT_DIGEST, "sha-256=1"))
+                .insert_header((WANT_REPR_DIGEST, "blake3=1"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(
+                full.headers.get(CONTENT_DIGEST).unwrap(),
+                &format_digests(&digests, &[DigestAlgorithm::Sha256, DigestAlgorithm::Md5])
+            );
+            assert_eq!(
+                full.headers.get(DIGEST).unwrap(),
+                &format_legacy_digests(&digests, &[DigestAlgorithm::Md5])
+            );
+            assert_eq!(partial.status, StatusCode::PARTIAL_CONTENT);
+            assert!(partial.headers.get(CONTENT_DIGEST).is_none());
+            assert_eq!(
+                partial.headers.get(REPR_DIGEST).unwrap(),
+                &format_digests(&digests, &[DigestAlgorithm::Blake3])
+            );
+        })
+    }
+
+    #[test]
+    fn compresses_negotiated_responses() {
+        test(|ctx| async move {
+            // arrange
+            let text = "compressible text ".repeat(200);
+            let source = ctx
+                .memory_source(
+                    "docs",
+                    dir! { "a.txt" => text.as_str(), "b.png" => text.as_str() },
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let get = |name: &str, range: Option<&'static str>| {
+                let mut request = server
+                    .client()
+                    .get(&format!("/api/fs/v1/{}/files/{}", source.id(), name))
+                    .access_token(&token)
+                    .insert_header((header::ACCEPT_ENCODING, "gzip;q=0.5, zstd"));
+                if let Some(range) = range {
+                    request = request.insert_header((header::RANGE, range));
+                }
+                request.send()
+            };
+
+            // act
+            let text_response = get("a.txt", None).await;
+            let image = get("b.png", None).await;
+            let partial = get("a.txt", Some("bytes=0-9")).await;
+
+            // assert
+            assert_eq!(
+                text_response.headers.get(header::CONTENT_ENCODING).unwrap(),
+                "zstd"
+            );
+            assert_eq!(
+                zstd::decode_all(text_response.body.as_ref()).unwrap(),
+                text.as_bytes()
+            );
+            assert_eq!(image.headers.get(header::CONTENT_ENCODING), None);
+            assert_eq!(image.body, text);
+            assert_eq!(partial.status, StatusCode::PARTIAL_CONTENT);
+            assert_eq!(partial.headers.get(header::CONTENT_ENCODING), None);
+            assert_eq!(partial.body, "compressib");
+        })
+    }
+
+    #[test]
+    fn serves_precompressed_siblings() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .precompressed_source(
+                    "site",
+                    dir! {
+                        "app.js" => "plain",
+                        "app.js.br" => "brotli bytes",
+                        "app.js.gz" => "gzip bytes",
+                    },
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let get = |accept: &'static str| {
+                server
+                    .client()
+                    .get(&format!("/api/fs/v1/{}/files/app.js", source.id()))
+                    .access_token(&token)
+                    .insert_header((header::ACCEPT_ENCODING, accept))
+                    .send()
+            };
+
+            // act
+            let gzip = get("gzip").await;
+            let preferred = get("gzip;q=0.5, br").await;
+            let identity = get("deflate").await;
+
+            // assert
+            assert_eq!(gzip.body, "gzip bytes");
+            assert_eq!(gzip.headers.get(header::CONTENT_ENCODING).unwrap(), "gzip");
+            assert_eq!(
+                gzip.headers.get(header::CONTENT_TYPE).unwrap(),
+                "text/javascript"
+            );
+            assert_eq!(gzip.headers.get(header::VARY).unwrap(), "accept-encoding");
+            assert_eq!(preferred.body, "brotli bytes");
+            assert_eq!(identity.body, "plain");
+            assert_eq!(identity.headers.get(header::CONTENT_ENCODING), None);
+            assert_eq!(
+                identity.headers.get(header::VARY).unwrap(),
+                "accept-encoding"
+            );
+        })
+    }
+}
@@ -0,0 +1,286 @@
+use actix_web::web;
+use uuid::Uuid;
+
+use crate::{
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    fs::{
+        dedup::dedup_stats::{DedupStats, GarbageReport},
+        storage_path::StoragePath,
+    },
+    web::{
+        app_data::AppData,
+        auth::source_access::{authorize_path, authorize_source, readable_sources},
+        common::{api_error::ApiError, api_result::ApiResult},
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct SourceInfo {
+    pub id: Uuid,
+    pub name: String,
+    /// What the caller may do, limited by the backend for read only sources
+    pub rights: ContentRight,
+}
+
+/// Sources the caller can read
+pub async fn list<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+) -> ApiResult<Vec<SourceInfo>> {
+    let sources = readable_sources(data.as_ref(), principal, &scope).await?;
+    Ok(web::Json(
+        sources
+            .into_iter()
+            .map(|(source, rights)| SourceInfo {
+                id: source.id(),
+                name: source.name().to_owned(),
+                rights,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct DedupStatsInfo {
+    pub files: u64,
+    pub logical_size: u64,
+    pub chunks: u64,
+    pub stored_size: u64,
+    /// Logical size divided by stored size
+    pub ratio: f64,
+}
+
+impl From<DedupStats> for DedupStatsInfo {
+    fn from(value: DedupStats) -> Self {
+        Self {
+            files: value.files,
+            logical_size: value.logical_size,
+            chunks: value.chunks,
+            stored_size: value.stored_size,
+            ratio: value.ratio(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct GarbageReportInfo {
+    pub removed_chunks: u64,
+    pub removed_size: u64,
+}
+
+impl From<GarbageReport> for GarbageReportInfo {
+    fn from(value: GarbageReport) -> Self {
+        Self {
+            removed_chunks: value.removed_chunks,
+            removed_size: value.removed_size,
+        }
+    }
+}
+
+pub async fn dedup_stats<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    source_id: web::Path<Uuid>,
+) -> ApiResult<DedupStatsInfo> {
+    let source = authorize_source(
+        data.as_ref(),
+        principal,
+        &scope,
+        source_id.into_inner(),
+        ContentRight::Read,
+    )
+    .await?;
+    authorize_path(&scope, &StoragePath::root())?;
+    let stats = source.backend().dedup_stats().await?.ok_or_else(|| {
+        ApiError::not_found()
+            .message("source doesn't deduplicate".to_owned())
+            .build()
+    })?;
+    Ok(web::Json(stats.into()))
+}
+
+/// Removes chunks no file refers to anymore, a no-op for other backends
+pub async fn collect_garbage<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    source_id: web::Path<Uuid>,
+) -> ApiResult<GarbageReportInfo> {
+    let source = authorize_source(
+        data.as_ref(),
+        principal,
+        &scope,
+        source_id.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    authorize_path(&scope, &StoragePath::root())?;
+    let report = source.backend().collect_garbage().await?;
+    Ok(web::Json(report.into()))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use actix_http::StatusCode;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn lists_readable_sources() {
+        test(|ctx| async move {
+            // arrange
+            let docs = ctx.memory_source("docs", dir! {}).await;
+            let other = ctx.memory_source("other", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let (_, token) = ctx
+                .create_personal_access_token(
+                    &login,
+                    ContentRight::Read,
+                    Some(vec![docs.id()]),
+                    None,
+                )
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let all = server
+                .client()
+                .get("/api/sources/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .send()
+                .await;
+            let scoped = server
+                .client()
+                .get("/api/sources/v1")
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            let all: Vec<SourceInfo> = all.unwrap();
+            assert_eq!(
+                all.iter().map(|s| s.id).collect::<Vec<_>>(),
+                vec![docs.id(), other.id()]
+            );
+            assert_eq!(all[0].rights, ContentRight::Read | ContentRight::Write);
+            let scoped: Vec<SourceInfo> = scoped.unwrap();
+            assert_eq!(
+                scoped,
+                vec![SourceInfo {
+                    id: docs.id(),
+                    name: "docs".to_owned(),
+                    rights: ContentRight::Read,
+                }]
+            );
+        })
+    }
+
+    #[test]
+    fn requires_read_right() {
+        test(|ctx| async move {
+            // arrange
+            ctx.memory_source("docs", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/sources/v1")
+                .access_token(&ctx.access_token_for(&login))
+                .send()
+                .await;
+
+            // assert
+            assert!(response.unwrap::<Vec<SourceInfo>>().is_empty());
+        })
+    }
+
+    #[test]
+    fn dedup_stats_and_garbage_collection() {
+        test(|ctx| async move {
+            // arrange
+            let content = "0123456789".repeat(100);
+            let source = ctx
+                .dedup_source(
+                    "dedup",
+                    dir! { "a.txt" => content.clone(), "b.txt" => content },
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let client = server.client();
+
+            // act
+            let before = client
+                .get(&format!("/api/sources/v1/{}/dedup", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            client
+                .delete(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&token)
+                .send()
+                .await
+                .unwrap::<()>();
+            let gc = client
+                .post(&format!("/api/sources/v1/{}/gc", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            let before: DedupStatsInfo = before.unwrap();
+            assert_eq!(before.files, 2);
+            assert_eq!(before.logical_size, 2000);
+            assert!(before.stored_size < 2000);
+            assert!(before.ratio > 1.0);
+            assert_eq!(
+                gc.unwrap::<GarbageReportInfo>(),
+                GarbageReportInfo {
+                    removed_chunks: 0,
+                    removed_size: 0
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn dedup_stats_of_plain_source() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            let stats = server
+                .client()
+                .get(&format!("/api/sources/v1/{}/dedup", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            let gc = server
+                .client()
+                .post(&format!("/api/sources/v1/{}/gc", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(stats.status, StatusCode::NOT_FOUND);
+            assert_eq!(gc.status, StatusCode::FORBIDDEN);
+        })
+    }
+}
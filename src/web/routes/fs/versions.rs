@@ -0,0 +1,415 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    dal::{file_versions_dal::FileVersionsDal, Dal},
+    fs::{
+        file_version::FileVersion,
+        source::Source,
+        storage_backend::{ByteStream, EntryMetadata},
+        storage_error::StorageError,
+        storage_path::StoragePath,
+        version_store::VersionStore,
+    },
+    utils::{id::Id, id_generator::IdGenerator, time::Time},
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::source_access::{authorize_path, authorize_source},
+        common::{api_error::ApiError, api_result::ApiResult, serde_chrono::ApiDateTime},
+    },
+};
+
+use super::files::{file_response, isolate_content, EntryInfo};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub id: Id,
+    pub path: StoragePath,
+    /// Login whose write replaced the content
+    pub replaced_by: Option<Id>,
+    /// When the content was replaced
+    pub created: ApiDateTime,
+    pub size: u64,
+    /// Size change from the previous version, the whole size for the oldest one
+    pub size_delta: i64,
+}
+
+impl VersionInfo {
+    fn new(version: &FileVersion, previous: Option<&FileVersion>) -> Self {
+        Self {
+            id: version.id(),
+            path: version.path().clone(),
+            replaced_by: version.replaced_by(),
+            created: version.created().into(),
+            size: version.size(),
+            size_delta: version.size() as i64 - previous.map_or(0, |p| p.size() as i64),
+        }
+    }
+}
+
+/// Writes the file, keeping the content it replaces as a version if the source is versioned.
+/// The version is recorded first, so a failed write never loses the old content.
+pub(super) async fn write_versioned<D: AppData>(
+    data: &D,
+    source: &Source,
+    path: &StoragePath,
+    principal: Principal,
+    content: ByteStream,
+) -> Result<EntryMetadata, StorageError> {
+    let Some(versions) = source.versions() else {
+        return source.backend().write(path, content).await;
+    };
+    let now = data.time().now();
+    versions
+        .record(
+            data.dal(),
+            source,
+            path,
+            data.id().next_id(),
+            Some(principal.id()),
+            now,
+        )
+        .await?;
+    let metadata = source.backend().write(path, content).await?;
+    let removed = versions.prune(data.dal(), source, path, now).await?;
+    if !removed.is_empty() {
+        tracing::debug!(source_id = %source.id(), path = %path, removed = removed.len(), "versions pruned");
+    }
+    Ok(metadata)
+}
+
+fn version_store(source: &Source) -> Result<&VersionStore, ApiError> {
+    source.versions().ok_or_else(|| {
+        ApiError::not_found()
+            .message("source isn't versioned".to_owned())
+            .build()
+    })
+}
+
+async fn resolve_version<D: AppData>(
+    data: &D,
+    principal: Principal,
+    scope: &TokenScope,
+    (source_id, version_id): (Uuid, Id),
+    right: ContentRight,
+) -> Result<(Arc<Source>, FileVersion), ApiError> {
+    let source = authorize_source(data, principal, scope, source_id, right).await?;
+    version_store(&source)?;
+    let version = data
+        .dal()
+        .file_versions()
+        .get(version_id)
+        .await?
+        .filter(|v| v.source_id() == source.id())
+        .ok_or_else(|| {
+            ApiError::not_found()
+                .message("version not found".to_owned())
+                .build()
+        })?;
+    authorize_path(scope, version.path())?;
+    Ok((source, version))
+}
+
+/// Prior contents of the file, newest first
+pub async fn list<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, String)>,
+) -> ApiResult<Vec<VersionInfo>> {
+    let (source_id, path) = path.into_inner();
+    let source = authorize_source(
+        data.as_ref(),
+        principal,
+        &scope,
+        source_id,
+        ContentRight::Read,
+    )
+    .await?;
+    version_store(&source)?;
+    let path = StoragePath::parse(&path)?;
+    authorize_path(&scope, &path)?;
+    let versions = data.dal().file_versions().list(source.id(), &path).await?;
+    Ok(web::Json(
+        versions
+            .iter()
+            .enumerate()
+            .map(|(index, version)| VersionInfo::new(version, versions.get(index + 1)))
+            .collect(),
+    ))
+}
+
+pub async fn download<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, Id)>,
+    audit: AuditContext,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (source, version) = resolve_version(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Read,
+    )
+    .await?;
+    let versions = version_store(&source)?;
+    let mut response = file_response(&req, version.path(), version.size(), |range| {
+        versions.read(&version, range)
+    })
+    .await?;
+    isolate_content(version.path(), &mut response);
+    if response.status().is_success() {
+        let entry = audit
+            .entry(AuditAction::FileRead)
+            .with_source(source.id())
+            .with_target(version.path())
+            .with_detail(version.id());
+        audit.record(data.as_ref(), entry).await;
+    }
+    Ok(response)
+}
+
+/// Writes the content of the version as the current file, the replaced content becomes a new version
+pub async fn restore<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, Id)>,
+    audit: AuditContext,
+) -> ApiResult<EntryInfo> {
+    let (source, version) = resolve_version(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    let read = version_store(&source)?.read(&version, None).await?;
+    let metadata = write_versioned(
+        data.as_ref(),
+        &source,
+        version.path(),
+        principal,
+        read.stream,
+    )
+    .await?;
+    tracing::info!(source_id = %source.id(), path = %version.path(), version_id = %version.id(), "version restored");
+    let entry = audit
+        .entry(AuditAction::VersionRestored)
+        .with_source(source.id())
+        .with_target(version.path())
+        .with_detail(version.id());
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(metadata.into()))
+}
+
+pub async fn delete<D: AppData>(
+    data: web::Data<D>,
+    principal: Principal,
+    scope: TokenScope,
+    path: web::Path<(Uuid, Id)>,
+    audit: AuditContext,
+) -> ApiResult<()> {
+    let (source, version) = resolve_version(
+        data.as_ref(),
+        principal,
+        &scope,
+        path.into_inner(),
+        ContentRight::Write,
+    )
+    .await?;
+    version_store(&source)?.delete(data.dal(), &version).await?;
+    tracing::info!(source_id = %source.id(), path = %version.path(), version_id = %version.id(), "version deleted");
+    let entry = audit
+        .entry(AuditAction::VersionDeleted)
+        .with_source(source.id())
+        .with_target(version.path())
+        .with_detail(version.id());
+    audit.record(data.as_ref(), entry).await;
+    Ok(web::Json(()))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::config::app_config::RetentionConfig;
+    use crate::test::*;
+    use crate::utc;
+    use actix_http::StatusCode;
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    #[test]
+    fn uploads_keep_versions() {
+        test(|ctx| async move {
+            // arrange
+            ctx.time().set(utc!(2024, 1, 1));
+            let source = ctx
+                .versioned_source(
+                    "docs",
+                    dir! { "a.txt" => "seeded" },
+                    RetentionConfig::default(),
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let client = server.client();
+
+            // act
+            for (day, content) in [(2, "new content"), (3, "x")] {
+                ctx.time().set(utc!(2024, 1, day));
+                client
+                    .put(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                    .access_token(&token)
+                    .body(Bytes::from(content))
+                    .send()
+                    .await
+                    .unwrap::<EntryInfo>();
+            }
+            let versions = client
+                .get(&format!("/api/fs/v1/{}/versions/a.txt", source.id()))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            let versions: Vec<VersionInfo> = versions.unwrap();
+            assert_eq!(versions.len(), 2);
+            assert_eq!(versions[0].replaced_by, Some(login.login_id()));
+            assert_eq!(versions[0].created, utc!(2024, 1, 3).into());
+            assert_eq!(versions[0].size, 11);
+            assert_eq!(versions[0].size_delta, 5);
+            assert_eq!(versions[1].created, utc!(2024, 1, 2).into());
+            assert_eq!(versions[1].size, 6);
+            assert_eq!(versions[1].size_delta, 6);
+            ctx.assert_tree(&source, dir! { "a.txt" => "x" }).await;
+
+            let old = client
+                .get(&format!(
+                    "/api/fs/v1/{}/version/{}",
+                    source.id(),
+                    versions[1].id
+                ))
+                .access_token(&token)
+                .send()
+                .await;
+            assert_eq!(old.status, StatusCode::OK);
+            assert_eq!(old.body, "seeded");
+        })
+    }
+
+    #[test]
+    fn restore_and_delete_versions() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .versioned_source(
+                    "docs",
+                    dir! { "a.txt" => "one" },
+                    RetentionConfig::default(),
+                )
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            let client = server.client();
+            client
+                .put(&format!("/api/fs/v1/{}/files/a.txt", source.id()))
+                .access_token(&token)
+                .body(Bytes::from("two"))
+                .send()
+                .await
+                .unwrap::<EntryInfo>();
+            let list = || async {
+                client
+                    .get(&format!("/api/fs/v1/{}/versions/a.txt", source.id()))
+                    .access_token(&token)
+                    .send()
+                    .await
+                    .unwrap::<Vec<VersionInfo>>()
+            };
+            let first = list().await[0].id;
+
+            // act
+            let restored = client
+                .post(&format!(
+                    "/api/fs/v1/{}/version/{}/restore",
+                    source.id(),
+                    first
+                ))
+                .access_token(&token)
+                .send()
+                .await;
+            let deleted = client
+                .delete(&format!("/api/fs/v1/{}/version/{}", source.id(), first))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(restored.unwrap::<EntryInfo>().size, 3);
+            assert_eq!(deleted.status, StatusCode::OK);
+            ctx.assert_tree(&source, dir! { "a.txt" => "one" }).await;
+            let sizes: Vec<_> = list().await.iter().map(|v| v.size).collect();
+            assert_eq!(sizes, vec![3]);
+            let missing = client
+                .get(&format!("/api/fs/v1/{}/version/{}", source.id(), first))
+                .access_token(&token)
+                .send()
+                .await;
+            assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        })
+    }
+
+    #[test]
+    fn unversioned_source_and_rights() {
+        test(|ctx| async move {
+            // arrange
+            let plain = ctx.memory_source("plain", dir! { "a.txt" => "a" }).await;
+            let versioned = ctx
+                .versioned_source("docs", dir! {}, RetentionConfig::default())
+                .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            let plain = server
+                .client()
+                .get(&format!("/api/fs/v1/{}/versions/a.txt", plain.id()))
+                .access_token(&token)
+                .send()
+                .await;
+            let restore = server
+                .client()
+                .post(&format!(
+                    "/api/fs/v1/{}/version/{}/restore",
+                    versioned.id(),
+                    Id::from_u128(1)
+                ))
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(plain.status, StatusCode::NOT_FOUND);
+            assert_eq!(restore.status, StatusCode::FORBIDDEN);
+        })
+    }
+}
@@ -0,0 +1,219 @@
+use actix_web::{http::StatusCode, web, HttpResponse};
+use futures::future::join_all;
+
+use crate::{
+    config::app_config::{AppConfig, HealthConfig},
+    dal::Dal,
+    fs::source::Source,
+    health::{
+        health_check::{CheckStatus, HealthCheck},
+        readiness::Readiness,
+    },
+    web::{
+        app_data::AppData,
+        common::{api_result::ApiResult, serde_chrono::ApiDuration},
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<HealthCheckInfo>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct HealthCheckInfo {
+    pub name: String,
+    pub status: CheckStatus,
+    pub duration: ApiDuration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<HealthCheck> for HealthCheckInfo {
+    fn from(value: HealthCheck) -> Self {
+        Self {
+            name: value.name().to_owned(),
+            status: value.status(),
+            duration: value.duration().into(),
+            message: value.message().map(str::to_owned),
+        }
+    }
+}
+
+/// The process is up. Dependencies aren't checked, a restart wouldn't fix them.
+pub async fn live() -> ApiResult<HealthReport> {
+    Ok(web::Json(HealthReport {
+        status: CheckStatus::Pass,
+        checks: Vec::new(),
+    }))
+}
+
+/// 503 if a check fails or the server is shutting down, with the outcome of every check
+pub async fn ready<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    readiness: web::Data<Readiness>,
+) -> HttpResponse {
+    let checks = if readiness.is_shutting_down() {
+        vec![HealthCheck::failed("shutdown", "server is shutting down")]
+    } else {
+        run_checks(data.as_ref(), config.health()).await
+    };
+    let failed = checks.iter().any(|c| c.status() == CheckStatus::Fail);
+    let report = HealthReport {
+        status: if failed {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Pass
+        },
+        checks: checks.into_iter().map(Into::into).collect(),
+    };
+    let status = if failed {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    HttpResponse::build(status).json(report)
+}
+
+/// Concurrently, so a slow dependency doesn't delay the others
+async fn run_checks<D: AppData>(data: &D, config: &HealthConfig) -> Vec<HealthCheck> {
+    let timeout = config.check_timeout();
+    let database = HealthCheck::run("database", timeout, async {
+        data.dal().ping().await.map_err(|e| e.to_string())?;
+        Ok(None)
+    });
+    let migrations = HealthCheck::run("migrations", timeout, async {
+        let pending = data
+            .dal()
+            .pending_migrations()
+            .await
+            .map_err(|e| e.to_string())?;
+        if pending.is_empty() {
+            Ok(None)
+        } else {
+            Err(format!("pending: {}", pending.join(", ")))
+        }
+    });
+    let sources = data.sources().list();
+    let sources = join_all(sources.iter().map(|source| {
+        HealthCheck::run(
+            format!("source:{}", source.name()),
+            timeout,
+            check_source(source, config.min_free_bytes()),
+        )
+    }));
+    let (database, migrations, sources) = futures::join!(database, migrations, sources);
+    [database, migrations].into_iter().chain(sources).collect()
+}
+
+/// Readable, writable unless it's read only, and with enough space left on a local disk
+async fn check_source(source: &Source, min_free_bytes: u64) -> Result<Option<String>, String> {
+    let check = source.check().await.map_err(|e| e.to_string())?;
+    match check.free_bytes {
+        Some(free) if free < min_free_bytes => Err(format!(
+            "{} bytes free, at least {} required",
+            free, min_free_bytes
+        )),
+        Some(free) => Ok(Some(format!("{} bytes free", free))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    fn summary(report: &HealthReport) -> Vec<(&str, CheckStatus)> {
+        report
+            .checks
+            .iter()
+            .map(|c| (c.name.as_str(), c.status))
+            .collect()
+    }
+
+    #[test]
+    fn ready_checks_dependencies() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["health"]["min_free_bytes"] = json!(0));
+            ctx.memory_source("memory", dir! { "a.txt" => "a" }).await;
+            ctx.temp_dir_source("disk", dir! {}).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let live = server.client().get("/health/live").send().await;
+            let ready = server.client().get("/health/ready").send().await;
+
+            // assert
+            assert_eq!(live.unwrap::<HealthReport>().status, CheckStatus::Pass);
+            assert_eq!(ready.status, StatusCode::OK);
+            let report = ready.unwrap::<HealthReport>();
+            assert_eq!(report.status, CheckStatus::Pass);
+            assert_eq!(
+                summary(&report),
+                vec![
+                    ("database", CheckStatus::Pass),
+                    ("migrations", CheckStatus::Pass),
+                    ("source:disk", CheckStatus::Pass),
+                    ("source:memory", CheckStatus::Pass),
+                ]
+            );
+            assert!(report.checks[2]
+                .message
+                .as_deref()
+                .is_some_and(|m| m.ends_with("bytes free")));
+            assert_eq!(report.checks[3].message, None);
+        });
+    }
+
+    #[test]
+    fn not_ready_without_disk_space() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["health"]["min_free_bytes"] = json!(u64::MAX));
+            ctx.temp_dir_source("disk", dir! {}).await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server.client().get("/health/ready").send().await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+            let report: HealthReport = serde_json::from_str(response.text()).unwrap();
+            assert_eq!(report.status, CheckStatus::Fail);
+            assert_eq!(report.checks[2].name, "source:disk");
+            assert!(report.checks[2]
+                .message
+                .as_deref()
+                .is_some_and(|m| m.contains("required")));
+        });
+    }
+
+    #[test]
+    fn not_ready_during_shutdown() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+            let before = server.client().get("/health/ready").send().await;
+
+            // act
+            ctx.readiness().begin_shutdown();
+            let ready = server.client().get("/health/ready").send().await;
+            let live = server.client().get("/health/live").send().await;
+
+            // assert
+            assert_eq!(before.status, StatusCode::OK);
+            assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+            let report: HealthReport = serde_json::from_str(ready.text()).unwrap();
+            assert_eq!(summary(&report), vec![("shutdown", CheckStatus::Fail)]);
+            assert_eq!(live.status, StatusCode::OK);
+        });
+    }
+}
@@ -0,0 +1,433 @@
+use std::sync::Arc;
+
+use actix_web::{
+    body::SizedStream,
+    http::{header, Method},
+    web, HttpRequest, HttpResponse,
+};
+use percent_encoding::percent_decode_str;
+
+use super::fs::files::serve_file;
+
+use crate::{
+    audit::audit_event::AuditAction,
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    config::app_config::HostingConfig,
+    fs::{
+        source::Source, sources::Sources, storage_backend::EntryMetadata,
+        storage_error::StorageError, storage_path::StoragePath,
+    },
+    web::{
+        app_data::AppData,
+        audit::AuditContext,
+        auth::source_access::{authorize_path, source_rights},
+        common::api_error::ApiError,
+    },
+};
+
+/// Serves hosted sources as static web sites to requests no API route matches.
+///
+/// A path is served from the file itself, the index of a directory, `<path>.html` with clean URLs
+/// and the root index with the SPA fallback, in that order. Directories are redirected to
+/// their path with a trailing slash, so relative links in the index work.
+pub async fn serve<D: AppData>(
+    data: web::Data<D>,
+    principal: Option<Principal>,
+    scope: TokenScope,
+    audit: AuditContext,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return Err(ApiError::not_found().build());
+    }
+    let path = percent_decode_str(req.path()).decode_utf8().map_err(|_| {
+        ApiError::bad_reques()
+            .message("path is not valid UTF-8".to_owned())
+            .build()
+    })?;
+    let host = req.connection_info().host().to_owned();
+    let Some((source, rest)) = find_site(data.sources(), &host, &path) else {
+        return Err(ApiError::not_found().build());
+    };
+    let hosting = source.hosting().expect("sites are hosted sources");
+
+    let requested = StoragePath::parse(rest)?;
+    if !hosting.public() {
+        let principal = principal.ok_or_else(|| ApiError::unauthorized().build())?;
+        if !source_rights(data.as_ref(), principal, &scope, source.id())
+            .await?
+            .contains(ContentRight::Read)
+        {
+            return Err(ApiError::forbidden().build());
+        }
+        authorize_path(&scope, &requested)?;
+    }
+
+    let metadata = match resolve(&source, hosting, &requested, path.ends_with('/')).await? {
+        // the index or the SPA fallback may be outside the requested path
+        Resolved::File(metadata) if !hosting.public() => {
+            authorize_path(&scope, metadata.path())?;
+            metadata
+        }
+        Resolved::File(metadata) => metadata,
+        Resolved::Directory => {
+            let location = match req.query_string() {
+                "" => format!("{}/", req.path()),
+                query => format!("{}/?{}", req.path(), query),
+            };
+            return Ok(HttpResponse::MovedPermanently()
+                .insert_header((header::LOCATION, location))
+                .finish());
+        }
+        Resolved::NotFound => {
+            let mut response = not_found(&source, hosting).await?;
+            isolate_site(hosting, &mut response);
+            return Ok(response);
+        }
+    };
+
+    let mut response = serve_file(data.as_ref(), &source, &metadata, &req).await?;
+    isolate_site(hosting, &mut response);
+    if response.status().is_success() {
+        let entry = audit
+            .entry(AuditAction::FileRead)
+            .with_source(source.id())
+            .with_target(metadata.path());
+        audit.record(data.as_ref(), entry).await;
+        if let Some(value) = hosting.cache_control(extension(metadata.path())) {
+            response.headers_mut().insert(
+                header::CACHE_CONTROL,
+                header::HeaderValue::from_str(value).map_err(|_| {
+                    tracing::error!("invalid cache control '{}'", value);
+                    ApiError::unexpected().build()
+                })?,
+            );
+        }
+    }
+    Ok(response)
+}
+
+/// Sites without a host of their own share the origin of the API and `/ui`, their pages run in
+/// an opaque origin so scripts of the site can't use the session cookies
+fn isolate_site(hosting: &HostingConfig, response: &mut HttpResponse) {
+    if hosting.host().is_none() {
+        response.headers_mut().insert(
+            header::CONTENT_SECURITY_POLICY,
+            header::HeaderValue::from_static("sandbox allow-scripts"),
+        );
+    }
+}
+
+#[derive(Debug)]
+enum Resolved {
+    File(EntryMetadata),
+    /// Requested without a trailing slash
+    Directory,
+    NotFound,
+}
+
+async fn resolve(
+    source: &Source,
+    hosting: &HostingConfig,
+    path: &StoragePath,
+    trailing_slash: bool,
+) -> Result<Resolved, ApiError> {
+    match stat(source, path).await? {
+        Some(metadata) if !metadata.is_dir() => return Ok(Resolved::File(metadata)),
+        Some(_) if !trailing_slash && !path.is_root() => return Ok(Resolved::Directory),
+        Some(_) => {
+            if let Some(index) = file(source, &path.join(hosting.index())?).await? {
+                return Ok(Resolved::File(index));
+            }
+        }
+        None => {}
+    }
+
+    let extensionless = path.name().is_some_and(|name| !name.contains('.'));
+    if hosting.clean_urls() && extensionless && !trailing_slash {
+        let name = format!("{}.html", path.name().unwrap_or_default());
+        let page = path.parent().unwrap_or_default().join(&name)?;
+        if let Some(page) = file(source, &page).await? {
+            return Ok(Resolved::File(page));
+        }
+    }
+    if hosting.spa() && (extensionless || path.is_root()) {
+        if let Some(index) = file(source, &StoragePath::parse(hosting.index())?).await? {
+            return Ok(Resolved::File(index));
+        }
+    }
+    Ok(Resolved::NotFound)
+}
+
+/// The custom page with status 404 if the site has one
+async fn not_found(source: &Source, hosting: &HostingConfig) -> Result<HttpResponse, ApiError> {
+    let page = match hosting.not_found_page() {
+        Some(page) => file(source, &StoragePath::parse(page)?).await?,
+        None => None,
+    };
+    let Some(page) = page else {
+        return Err(ApiError::not_found().build());
+    };
+    let read = source.backend().read(page.path(), None).await?;
+    let mime = mime_guess::from_path(page.path().as_str()).first_or_octet_stream();
+    Ok(HttpResponse::NotFound()
+        .insert_header((header::CONTENT_TYPE, mime.to_string()))
+        .body(SizedStream::new(read.metadata.size(), read.stream)))
+}
+
+async fn stat(source: &Source, path: &StoragePath) -> Result<Option<EntryMetadata>, ApiError> {
+    match source.backend().stat(path).await {
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(StorageError::NotFound(_) | StorageError::NotADirectory(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Metadata of a file, `None` for missing paths and directories
+async fn file(source: &Source, path: &StoragePath) -> Result<Option<EntryMetadata>, ApiError> {
+    Ok(stat(source, path)
+        .await?
+        .filter(|metadata| !metadata.is_dir()))
+}
+
+fn extension(path: &StoragePath) -> Option<&str> {
+    path.name()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+}
+
+/// Site with the longest prefix containing the path, sites bound to the host win over the others.
+/// Returns the source and the path under the prefix.
+fn find_site<'a>(sources: &Sources, host: &str, path: &'a str) -> Option<(Arc<Source>, &'a str)> {
+    let host = host_name(host);
+    sources
+        .list()
+        .into_iter()
+        .filter_map(|source| {
+            let hosting = source.hosting()?;
+            if hosting
+                .host()
+                .is_some_and(|expected| !expected.eq_ignore_ascii_case(host))
+            {
+                return None;
+            }
+            let rest = strip_prefix(path, hosting.prefix())?;
+            let rank = (
+                hosting.prefix().trim_matches('/').len(),
+                hosting.host().is_some(),
+            );
+            Some((rank, source, rest))
+        })
+        .max_by_key(|(rank, _, _)| *rank)
+        .map(|(_, source, rest)| (source, rest))
+}
+
+/// Path under the prefix, `None` if it's outside of it
+fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        return Some(path);
+    }
+    let rest = path.strip_prefix('/')?.strip_prefix(prefix)?;
+    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+}
+
+/// `Host` without the port
+fn host_name(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::dal::{audit_events_dal::AuditEventsDal, Dal};
+    use crate::test::*;
+    use actix_web::http::StatusCode;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+
+    fn hosting(config: serde_json::Value) -> HostingConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(strip_prefix("/a/b", "/"), Some("/a/b"));
+        assert_eq!(strip_prefix("/docs", "/docs"), Some(""));
+        assert_eq!(strip_prefix("/docs/a", "/docs/"), Some("/a"));
+        assert_eq!(strip_prefix("/docs/a", "docs"), Some("/a"));
+        assert_eq!(strip_prefix("/docsa", "/docs"), None);
+        assert_eq!(strip_prefix("/other", "/docs"), None);
+        assert_eq!(host_name("docs.example.com:8080"), "docs.example.com");
+        assert_eq!(host_name("docs.example.com"), "docs.example.com");
+    }
+
+    #[test]
+    fn cache_control_by_extension() {
+        let config = hosting(serde_json::json!({}));
+        assert_eq!(config.cache_control(Some("HTML")), Some("no-cache"));
+        assert_eq!(
+            config.cache_control(Some("js")),
+            Some("public, max-age=3600")
+        );
+        assert_eq!(config.cache_control(None), Some("public, max-age=3600"));
+    }
+
+    #[test]
+    fn serves_indexes_and_clean_urls() {
+        test(|ctx| async move {
+            // arrange
+            ctx.hosted_source(
+                "docs",
+                dir! {
+                    "index.html" => "home",
+                    "about.html" => "about",
+                    "guide" => dir! { "index.html" => "guide" },
+                    "app.js" => "js",
+                },
+                hosting(serde_json::json!({ "public": true })),
+            )
+            .await;
+            let server = ctx.run_server().await;
+            let get = |uri: &'static str| server.client().get(uri).send();
+
+            // act
+            let home = get("/").await;
+            let about = get("/about").await;
+            let guide = get("/guide").await;
+            let script = get("/app.js").await;
+            let missing = get("/missing").await;
+            let post = server.client().post("/about").send().await;
+
+            // assert
+            assert_eq!(home.body, "home");
+            assert_eq!(home.headers.get(header::CACHE_CONTROL).unwrap(), "no-cache");
+            assert_eq!(about.body, "about");
+            assert_eq!(guide.body, "guide");
+            assert_eq!(script.body, "js");
+            assert_eq!(
+                script.headers.get(header::CACHE_CONTROL).unwrap(),
+                "public, max-age=3600"
+            );
+            assert_eq!(missing.status, StatusCode::NOT_FOUND);
+            assert_eq!(post.status, StatusCode::NOT_FOUND);
+        })
+    }
+
+    #[test]
+    fn served_files_are_audited() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx
+                .hosted_source(
+                    "docs",
+                    dir! { "guide" => dir! { "index.html" => "guide" } },
+                    hosting(serde_json::json!({ "public": true })),
+                )
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            server.client().get("/guide/").send().await;
+
+            // assert
+            let event = ctx.dal().audit_events().last().await.unwrap().unwrap();
+            assert_eq!(event.entry().action(), AuditAction::FileRead);
+            assert_eq!(event.entry().source_id(), Some(source.id()));
+            assert_eq!(event.entry().target(), Some("/guide/index.html"));
+        })
+    }
+
+    #[test]
+    fn spa_fallback_and_not_found_page() {
+        test(|ctx| async move {
+            // arrange
+            ctx.hosted_source(
+                "app",
+                dir! { "index.html" => "app", "404.html" => "gone" },
+                hosting(serde_json::json!({
+                    "public": true,
+                    "prefix": "/app",
+                    "spa": true,
+                    "not_found_page": "404.html",
+                })),
+            )
+            .await;
+            let server = ctx.run_server().await;
+            let get = |uri: &'static str| server.client().get(uri).send();
+
+            // act
+            let route = get("/app/users/42").await;
+            let asset = get("/app/missing.png").await;
+            let outside = get("/other").await;
+            let api = get("/api/info/v1").await;
+
+            // assert
+            assert_eq!(route.status, StatusCode::OK);
+            assert_eq!(route.body, "app");
+            assert_eq!(asset.status, StatusCode::NOT_FOUND);
+            assert_eq!(asset.body, "gone");
+            assert_eq!(outside.status, StatusCode::NOT_FOUND);
+            assert_eq!(api.status, StatusCode::OK);
+        })
+    }
+
+    #[test]
+    fn virtual_hosts_and_private_sites() {
+        test(|ctx| async move {
+            // arrange
+            ctx.hosted_source(
+                "public",
+                dir! { "index.html" => "public" },
+                hosting(serde_json::json!({ "public": true })),
+            )
+            .await;
+            ctx.hosted_source(
+                "internal",
+                dir! { "index.html" => "internal" },
+                hosting(serde_json::json!({ "host": "internal.example.com" })),
+            )
+            .await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read).await;
+            let writer = ctx.create_login("writer", "password").await;
+            ctx.grant_rights(&writer, ContentRight::Write).await;
+            let server = ctx.run_server().await;
+            let internal = || {
+                server
+                    .client()
+                    .get("/")
+                    .insert_header((header::HOST, "internal.example.com"))
+            };
+
+            // act
+            let default_host = server.client().get("/").send().await;
+            let anonymous = internal().send().await;
+            let without_read = internal()
+                .access_token(&ctx.access_token_for(&writer))
+                .send()
+                .await;
+            let reader = internal()
+                .access_token(&ctx.access_token_for(&login))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(default_host.body, "public");
+            assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+            assert_eq!(without_read.status, StatusCode::FORBIDDEN);
+            assert_eq!(reader.body, "internal");
+            assert_eq!(
+                default_host
+                    .headers
+                    .get(header::CONTENT_SECURITY_POLICY)
+                    .unwrap(),
+                "sandbox allow-scripts"
+            );
+            assert_eq!(reader.headers.get(header::CONTENT_SECURITY_POLICY), None);
+        })
+    }
+}
@@ -1,9 +1,14 @@
 use actix_web::web;
+use uuid::Uuid;
 
 use crate::{
-    utils::{time::Time, trace_id::TraceId},
+    auth::{content_right::ContentRight, principal::Principal, token_scope::TokenScope},
+    config::app_config::AppConfig,
+    utils::{build_info, time::Time, trace_id::TraceId},
     web::{
+        app::MAX_JSON_BODY,
         app_data::AppData,
+        auth::source_access::readable_sources,
         common::{api_result::ApiResult, serde_chrono::ApiDateTime},
     },
 };
@@ -12,25 +17,141 @@ use crate::{
 pub struct Info {
     pub now: ApiDateTime,
     pub trace_id: TraceId,
+    pub build: BuildInfo,
+    /// Optional features which are switched on, e.g. `audit` or `metrics`
+    pub features: Vec<String>,
+    pub auth: AuthInfo,
+    pub limits: LimitsInfo,
+    /// Sources the caller can read, none for anonymous requests
+    pub sources: Vec<SourceCapabilities>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_at: Option<ApiDateTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct AuthInfo {
+    /// Enabled ways to send credentials: `bearer`, `basic`, `cookie` and `client_certificate`
+    pub methods: Vec<String>,
+    /// Names of the OpenID Connect providers to log in with
+    pub oidc_providers: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct LimitsInfo {
+    pub max_json_body: u64,
+    /// Unlimited if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct SourceCapabilities {
+    pub id: Uuid,
+    pub name: String,
+    pub rights: ContentRight,
+    pub read_only: bool,
+    pub versioning: bool,
+    /// Deleted files can't be restored from a trash yet, always false
+    pub trash: bool,
+    pub encrypted: bool,
+}
+
+/// Public, sources are listed only once the caller is authenticated
 pub async fn info<D: AppData>(
     data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    principal: Option<Principal>,
+    scope: TokenScope,
     trace_id: web::ReqData<TraceId>,
 ) -> ApiResult<Info> {
     let now = data.time().now();
+    let sources = match principal {
+        Some(principal) => readable_sources(data.as_ref(), principal, &scope)
+            .await?
+            .into_iter()
+            .map(|(source, rights)| SourceCapabilities {
+                id: source.id(),
+                name: source.name().to_owned(),
+                rights,
+                read_only: source.backend().is_read_only(),
+                versioning: source.versions().is_some(),
+                trash: false,
+                encrypted: source.encrypted(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
     Ok(web::Json(Info {
         now: now.into(),
         trace_id: trace_id.into_inner(),
+        build: BuildInfo {
+            version: build_info::VERSION.to_owned(),
+            commit: build_info::git_commit().map(str::to_owned),
+            built_at: build_info::built_at().map(Into::into),
+        },
+        features: features(&config),
+        auth: auth(&config),
+        limits: LimitsInfo {
+            max_json_body: MAX_JSON_BODY as u64,
+            max_file_size: config.uploads().max_file_size(),
+        },
+        sources,
     }))
 }
 
+fn features(config: &AppConfig) -> Vec<String> {
+    [
+        ("audit", config.audit().enabled()),
+        ("compression", config.compression().enabled()),
+        ("metrics", config.metrics().enabled()),
+        ("otlp", config.tracing().otlp().is_some()),
+        ("rate_limit", !config.rate_limit().groups().is_empty()),
+        ("tls", config.tls().is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_owned())
+    .collect()
+}
+
+fn auth(config: &AppConfig) -> AuthInfo {
+    let methods = config.auth().methods();
+    let methods = [
+        ("bearer", methods.bearer()),
+        ("basic", methods.basic().enabled()),
+        ("cookie", methods.cookie().enabled()),
+        ("client_certificate", methods.client_certificate().enabled()),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_owned())
+    .collect();
+    AuthInfo {
+        methods,
+        oidc_providers: config
+            .auth()
+            .oidc()
+            .providers()
+            .iter()
+            .map(|p| p.name().to_owned())
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
     use super::*;
-    use crate::{test::*, utc, web::trace_id};
+    use crate::{config::app_config::RetentionConfig, test::*, utc, web::trace_id};
     use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
 
     #[test]
     fn info() {
@@ -57,6 +178,63 @@ mod tests {
 
             assert_eq!(info.trace_id, header_trace_id);
             assert_eq!(*info.now, now);
+            assert_eq!(info.build.version, env!("CARGO_PKG_VERSION"));
+            assert_eq!(info.auth.methods, vec!["bearer", "basic", "cookie"]);
+            assert_eq!(info.limits.max_json_body, MAX_JSON_BODY as u64);
+            assert_eq!(info.sources, vec![]);
+        });
+    }
+
+    #[test]
+    fn info_lists_readable_sources() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["uploads"]["max_file_size"] = json!(1024);
+                config["metrics"]["enabled"] = json!(false);
+            });
+            let docs = ctx
+                .versioned_source("docs", dir! {}, RetentionConfig::default())
+                .await;
+            ctx.memory_source("other", dir! {}).await;
+            let login = ctx.create_login("user", "password").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let (_, token) = ctx
+                .create_personal_access_token(
+                    &login,
+                    ContentRight::Read,
+                    Some(vec![docs.id()]),
+                    None,
+                )
+                .await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .access_token(&token)
+                .send()
+                .await;
+
+            // assert
+            let info = response.unwrap::<Info>();
+            assert_eq!(
+                info.sources,
+                vec![SourceCapabilities {
+                    id: docs.id(),
+                    name: "docs".to_owned(),
+                    rights: ContentRight::Read,
+                    read_only: false,
+                    versioning: true,
+                    trash: false,
+                    encrypted: false,
+                }]
+            );
+            assert_eq!(info.limits.max_file_size, Some(1024));
+            assert!(!info.features.contains(&"metrics".to_owned()));
+            assert!(info.features.contains(&"audit".to_owned()));
         });
     }
 }
@@ -0,0 +1,129 @@
+use actix_web::web;
+
+use crate::{
+    auth::principal::Principal,
+    config::app_config::AppConfig,
+    logging::log_filter::LogFilter,
+    web::{
+        app_data::AppData,
+        auth::admin_access::require_admin,
+        common::{api_error::ApiError, api_result::ApiResult},
+    },
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LogFilterInfo {
+    /// Directives like `info,sqlx=warn`
+    pub filter: String,
+}
+
+pub async fn get_filter<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    log_filter: web::Data<LogFilter>,
+    principal: Principal,
+) -> ApiResult<LogFilterInfo> {
+    require_admin(data.as_ref(), config.logging().admins(), principal).await?;
+    Ok(web::Json(LogFilterInfo {
+        filter: log_filter.directives(),
+    }))
+}
+
+/// Applies until the server restarts, the config isn't changed
+pub async fn set_filter<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+    log_filter: web::Data<LogFilter>,
+    principal: Principal,
+    request: web::Json<LogFilterInfo>,
+) -> ApiResult<LogFilterInfo> {
+    require_admin(data.as_ref(), config.logging().admins(), principal).await?;
+    log_filter.set(&request.filter).map_err(|e| {
+        ApiError::bad_reques()
+            .message(format!("invalid filter: {}", e))
+            .build()
+    })?;
+    let filter = log_filter.directives();
+    tracing::warn!(filter = filter, "log filter changed");
+    Ok(web::Json(LogFilterInfo { filter }))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use actix_http::StatusCode;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    const PATH: &str = "/api/logging/filter/v1";
+
+    #[test]
+    fn admin_changes_filter() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["logging"]["filter"] = json!("info");
+                config["logging"]["admins"] = json!(["admin"]);
+            });
+            let admin = ctx.create_login("admin", "password").await;
+            let server = ctx.run_server().await;
+            let token = ctx.access_token_for(&admin);
+
+            // act
+            let before = server.client().get(PATH).access_token(&token).send().await;
+            let changed = server
+                .client()
+                .put(PATH)
+                .access_token(&token)
+                .json(&json!({ "filter": "warn,rusty_http_fs::web=debug" }))
+                .send()
+                .await;
+            let invalid = server
+                .client()
+                .put(PATH)
+                .access_token(&token)
+                .json(&json!({ "filter": "web=loud" }))
+                .send()
+                .await;
+            let after = server.client().get(PATH).access_token(&token).send().await;
+
+            // assert
+            assert_eq!(before.unwrap::<LogFilterInfo>().filter, "info");
+            assert_eq!(
+                changed.unwrap::<LogFilterInfo>().filter,
+                "warn,rusty_http_fs::web=debug"
+            );
+            assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                after.unwrap::<LogFilterInfo>().filter,
+                "warn,rusty_http_fs::web=debug"
+            );
+        });
+    }
+
+    #[test]
+    fn other_logins_are_forbidden() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["logging"]["admins"] = json!(["admin"]));
+            let user = ctx.create_login("user", "password").await;
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .put(PATH)
+                .access_token(&ctx.access_token_for(&user))
+                .json(&json!({ "filter": "trace" }))
+                .send()
+                .await;
+            let anonymous = server.client().get(PATH).send().await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::FORBIDDEN);
+            assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+        });
+    }
+}
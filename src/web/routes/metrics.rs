@@ -0,0 +1,161 @@
+use actix_web::{http::header, web, HttpResponse};
+
+use crate::{
+    config::app_config::AppConfig,
+    dal::Dal,
+    web::{app_data::AppData, common::api_error::ApiError},
+};
+
+/// Prometheus text format
+pub async fn metrics<D: AppData>(
+    data: web::Data<D>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    if !config.metrics().enabled() {
+        return Err(ApiError::not_found().build());
+    }
+    let text = data.metrics().render(data.dal().pool_stats());
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        ))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(text))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::{auth::content_right::ContentRight, test::*};
+    use bytes::Bytes;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
+
+    /// Value of the sample with exactly these labels
+    fn sample(text: &str, name_and_labels: &str) -> Option<f64> {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name_and_labels)?.strip_prefix(' '))
+            .map(|value| value.parse().unwrap())
+    }
+
+    #[test]
+    fn requests_by_route_pattern() {
+        test(|ctx| async move {
+            // arrange
+            let source = ctx.memory_source("docs", dir! { "a.txt" => "hello" }).await;
+            let login = ctx.create_login("user", "pwd").await;
+            ctx.grant_rights(&login, ContentRight::Read | ContentRight::Write)
+                .await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+            for name in ["a.txt", "missing.txt"] {
+                server
+                    .client()
+                    .get(&format!("/api/fs/v1/{}/files/{}", source.id(), name))
+                    .access_token(&token)
+                    .send()
+                    .await;
+            }
+            server
+                .client()
+                .put(&format!("/api/fs/v1/{}/files/b.txt", source.id()))
+                .access_token(&token)
+                .body(Bytes::from_static(b"uploaded"))
+                .send()
+                .await;
+            server.client().get("/no/such/route").send().await;
+
+            // act
+            let response = server.client().get("/metrics").send().await;
+
+            // assert
+            assert_eq!(response.status, 200);
+            let text = response.text();
+            let route = "/api/fs/v1/{source_id}/files/{path:.*}";
+            let count = |method: &str, status: u16| {
+                sample(
+                    text,
+                    &format!(
+                        "rhfs_http_request_duration_seconds_count{{route=\"{}\",method=\"{}\",status=\"{}\"}}",
+                        route, method, status
+                    ),
+                )
+            };
+            assert_eq!(count("GET", 200), Some(1.0));
+            assert_eq!(count("GET", 404), Some(1.0));
+            assert_eq!(count("PUT", 200), Some(1.0));
+            assert_eq!(
+                sample(
+                    text,
+                    "rhfs_http_request_duration_seconds_count{route=\"other\",method=\"GET\",status=\"404\"}"
+                ),
+                Some(1.0)
+            );
+            assert_eq!(
+                sample(
+                    text,
+                    &format!(
+                        "rhfs_http_received_bytes_total{{route=\"{}\",method=\"PUT\"}}",
+                        route
+                    )
+                ),
+                Some(8.0)
+            );
+            assert!(text.contains("rhfs_auth_successes_total{method=\"bearer\"} 3\n"));
+            assert!(text.contains(
+                "rhfs_fs_operation_duration_seconds_count{source=\"docs\",operation=\"write\",result=\"ok\"} 1\n"
+            ));
+            assert!(!text.contains("rhfs_db_pool"));
+        })
+    }
+
+    #[test]
+    fn auth_failures_by_reason() {
+        test(|ctx| async move {
+            // arrange
+            ctx.create_login("user", "pwd").await;
+            let server = ctx.run_server().await;
+            server
+                .client()
+                .post("/api/auth/login/v1")
+                .json(&json!({ "username": "user", "password": "wrong" }))
+                .send()
+                .await;
+            server
+                .client()
+                .get("/api/sources/v1")
+                .access_token("not a jwt")
+                .send()
+                .await;
+
+            // act
+            let response = server.client().get("/metrics").send().await;
+
+            // assert
+            let text = response.text();
+            assert!(text.contains(
+                "rhfs_auth_failures_total{method=\"password\",reason=\"invalid_credentials\"} 1\n"
+            ));
+            assert!(text.contains(
+                "rhfs_auth_failures_total{method=\"bearer\",reason=\"invalid_token\"} 1\n"
+            ));
+        })
+    }
+
+    #[test]
+    fn disabled() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["metrics"]["enabled"] = json!(false));
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server.client().get("/metrics").send().await;
+
+            // assert
+            assert_eq!(response.status, 404);
+        })
+    }
+}
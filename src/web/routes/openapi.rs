@@ -0,0 +1,1250 @@
+use actix_web::web;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    audit::audit_event::AuditAction,
+    config::app_config::{AppConfig, AuthConfig},
+    web::common::{api_error::ErrorCode, api_result::ApiResult},
+};
+
+/// OpenAPI 3.1 document describing [super::configure], see `tests::documents_every_route`
+pub async fn openapi(config: web::Data<AppConfig>) -> ApiResult<Value> {
+    Ok(web::Json(document(config.auth())))
+}
+
+/// Only the enabled authentication methods are listed as security schemes
+pub fn document(auth: &AuthConfig) -> Value {
+    let methods = auth.methods();
+    let mut security_schemes = Map::new();
+    let mut security = Vec::new();
+    if methods.bearer() {
+        security_schemes.insert(
+            "bearer".to_owned(),
+            json!({
+                "type": "http",
+                "scheme": "bearer",
+                "description": "Access JWT from the login endpoints or a personal access token",
+            }),
+        );
+        security.push(json!({ "bearer": [] }));
+    }
+    if methods.basic().enabled() {
+        security_schemes.insert(
+            "basic".to_owned(),
+            json!({
+                "type": "http",
+                "scheme": "basic",
+                "description": "Username and password of a login without TOTP",
+            }),
+        );
+        security.push(json!({ "basic": [] }));
+    }
+    if methods.cookie().enabled() {
+        security_schemes.insert(
+            "cookie".to_owned(),
+            json!({
+                "type": "apiKey",
+                "in": "cookie",
+                "name": methods.cookie().name(),
+                "description": format!(
+                    "Session cookie, unsafe methods also need the `{}` header",
+                    methods.cookie().csrf_header_name()
+                ),
+            }),
+        );
+        security.push(json!({ "cookie": [] }));
+    }
+    if methods.client_certificate().enabled() {
+        security_schemes.insert(
+            "client_certificate".to_owned(),
+            json!({
+                "type": "mutualTLS",
+                "description": "Client certificate whose subject or SAN is the username of a login",
+            }),
+        );
+        security.push(json!({ "client_certificate": [] }));
+    }
+
+    let mut paths = Map::new();
+    for (method, path, operation) in operations() {
+        let item = paths
+            .entry(path.to_owned())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        item.insert(method.to_owned(), operation.into_value());
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "rusty-http-fs",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": security,
+        "paths": paths,
+        "components": {
+            "securitySchemes": security_schemes,
+            "schemas": schemas(),
+            "responses": {
+                "Error": {
+                    "description": "Error envelope, `code` matches the status",
+                    "content": { "application/json": { "schema": schema_ref("ApiError") } },
+                },
+            },
+        },
+    })
+}
+
+/// Method, path and operation of every route
+fn operations() -> Vec<(&'static str, &'static str, Operation)> {
+    let source_id = ("source_id", json!({ "type": "string", "format": "uuid" }));
+    let path = ("path", schema_ref("StoragePath"));
+    let version_id = ("version_id", schema_ref("Id"));
+    vec![
+        (
+            "get",
+            "/api/openapi.json",
+            Operation::new("meta", "This document")
+                .public()
+                .json(json!({ "type": "object" })),
+        ),
+        (
+            "get",
+            "/metrics",
+            Operation::new("meta", "Prometheus metrics, unless they are switched off")
+                .public()
+                .text(),
+        ),
+        (
+            "get",
+            "/health/live",
+            Operation::new("meta", "Liveness probe, the process is up")
+                .public()
+                .json(schema_ref("HealthReport")),
+        ),
+        (
+            "get",
+            "/health/ready",
+            Operation::new(
+                "meta",
+                "Readiness probe, checks the database, migrations and every source",
+            )
+            .public()
+            .json(schema_ref("HealthReport"))
+            .unavailable(schema_ref("HealthReport")),
+        ),
+        (
+            "get",
+            "/api/info/v1",
+            Operation::new(
+                "meta",
+                "Server time, build, features, auth methods, limits and the caller's sources",
+            )
+            .public()
+            .json(schema_ref("Info")),
+        ),
+        (
+            "get",
+            "/api/logging/filter/v1",
+            Operation::new("meta", "Log filter, for logging admins only")
+                .json(schema_ref("LogFilterInfo")),
+        ),
+        (
+            "put",
+            "/api/logging/filter/v1",
+            Operation::new("meta", "Replaces the log filter until the server restarts")
+                .body(schema_ref("LogFilterInfo"))
+                .json(schema_ref("LogFilterInfo")),
+        ),
+        (
+            "get",
+            "/api/audit/events/v1",
+            audit_query(Operation::new(
+                "audit",
+                "Page of audit events, for audit admins only",
+            ))
+            .query(
+                "after",
+                json!({ "type": "integer", "format": "int64", "minimum": 0 }),
+            )
+            .query(
+                "limit",
+                json!({ "type": "integer", "minimum": 1, "maximum": 1000 }),
+            )
+            .json(schema_ref("AuditEventPage")),
+        ),
+        (
+            "get",
+            "/api/audit/export/v1",
+            audit_query(Operation::new(
+                "audit",
+                "Every matching audit event as JSON lines",
+            ))
+            .query(
+                "after",
+                json!({ "type": "integer", "format": "int64", "minimum": 0 }),
+            )
+            .ndjson("AuditEventInfo"),
+        ),
+        (
+            "get",
+            "/api/audit/verify/v1",
+            Operation::new("audit", "Checks the hash chain of the audit log")
+                .json(schema_ref("AuditVerification")),
+        ),
+        (
+            "post",
+            "/api/auth/login/v1",
+            Operation::new("auth", "Password step of a login")
+                .public()
+                .body(schema_ref("LoginRequest"))
+                .json(schema_ref("LoginResponse")),
+        ),
+        (
+            "post",
+            "/api/auth/login/totp/v1",
+            Operation::new("auth", "TOTP step of a login")
+                .public()
+                .body(schema_ref("LoginTotpRequest"))
+                .json(schema_ref("TokenPair")),
+        ),
+        (
+            "post",
+            "/api/auth/totp/enroll/v1",
+            Operation::new("auth", "Starts the TOTP enrollment").json(schema_ref("TotpEnrollment")),
+        ),
+        (
+            "post",
+            "/api/auth/totp/confirm/v1",
+            Operation::new("auth", "Enables TOTP with the first code")
+                .body(schema_ref("TotpCodeRequest"))
+                .json(schema_ref("TotpRecoveryCodes")),
+        ),
+        (
+            "delete",
+            "/api/auth/totp/v1",
+            Operation::new("auth", "Disables TOTP")
+                .body(schema_ref("TotpDisableRequest"))
+                .json(null()),
+        ),
+        (
+            "post",
+            "/api/auth/tokens/v1",
+            Operation::new("auth", "Creates a personal access token")
+                .body(schema_ref("CreatePersonalAccessTokenRequest"))
+                .json(schema_ref("CreatedPersonalAccessToken")),
+        ),
+        (
+            "get",
+            "/api/auth/tokens/v1",
+            Operation::new("auth", "Personal access tokens of the login")
+                .json(array(schema_ref("PersonalAccessTokenInfo"))),
+        ),
+        (
+            "delete",
+            "/api/auth/tokens/v1/{token_id}",
+            Operation::new("auth", "Revokes a personal access token")
+                .param("token_id", schema_ref("Id"))
+                .json(null()),
+        ),
+        (
+            "post",
+            "/api/auth/oidc/{provider}/authorize/v1",
+            Operation::new("auth", "Starts a login with an OpenID Connect provider")
+                .public()
+                .param("provider", json!({ "type": "string" }))
+                .json(schema_ref("OidcAuthorization")),
+        ),
+        (
+            "post",
+            "/api/auth/oidc/{provider}/callback/v1",
+            Operation::new("auth", "Finishes a login with an OpenID Connect provider")
+                .public()
+                .param("provider", json!({ "type": "string" }))
+                .body(schema_ref("OidcCallbackRequest"))
+                .json(schema_ref("TokenPair")),
+        ),
+        (
+            "post",
+            "/api/auth/cookie/v1",
+            Operation::new(
+                "auth",
+                "Moves the current authentication into a session cookie",
+            )
+            .json(schema_ref("SessionCookie")),
+        ),
+        (
+            "delete",
+            "/api/auth/cookie/v1",
+            Operation::new("auth", "Removes the session cookie")
+                .public()
+                .json(null()),
+        ),
+        (
+            "post",
+            "/api/encryption/rotate/v1",
+            Operation::new(
+                "sources",
+                "Re-wraps the data keys of encrypted sources with the active master key, \
+                 for encryption admins only",
+            )
+            .json(schema_ref("KeyRotationInfo")),
+        ),
+        (
+            "get",
+            "/api/sources/v1",
+            Operation::new("sources", "Sources the caller can read")
+                .json(array(schema_ref("SourceInfo"))),
+        ),
+        (
+            "get",
+            "/api/sources/v1/{source_id}/dedup",
+            Operation::new("sources", "Deduplication statistics")
+                .param(source_id.0, source_id.1.clone())
+                .json(schema_ref("DedupStatsInfo")),
+        ),
+        (
+            "post",
+            "/api/sources/v1/{source_id}/gc",
+            Operation::new("sources", "Removes unreferenced chunks")
+                .param(source_id.0, source_id.1.clone())
+                .json(schema_ref("GarbageReportInfo")),
+        ),
+        (
+            "get",
+            "/api/fs/v1/{source_id}/stat/{path}",
+            Operation::new("files", "Metadata of an entry")
+                .param(source_id.0, source_id.1.clone())
+                .param(path.0, path.1.clone())
+                .json(schema_ref("EntryInfo")),
+        ),
+        (
+            "get",
+            "/api/fs/v1/{source_id}/list/{path}",
+            Operation::new("files", "Entries of a directory")
+                .param(source_id.0, source_id.1.clone())
+                .param(path.0, path.1.clone())
+                .json(array(schema_ref("EntryInfo"))),
+        ),
+        (
+            "get",
+            "/api/fs/v1/{source_id}/files/{path}",
+            Operation::new(
+                "files",
+                "Content of a file, a single `Range` is supported. \
+                 `Want-Content-Digest`, `Want-Repr-Digest` and `Want-Digest` are answered.",
+            )
+            .param(source_id.0, source_id.1.clone())
+            .param(path.0, path.1.clone())
+            .content(),
+        ),
+        (
+            "put",
+            "/api/fs/v1/{source_id}/files/{path}",
+            Operation::new(
+                "files",
+                "Creates or replaces a file. Digests in `Content-Digest` or `Digest` are verified.",
+            )
+            .param(source_id.0, source_id.1.clone())
+            .param(path.0, path.1.clone())
+            .binary_body()
+            .json(schema_ref("EntryInfo")),
+        ),
+        (
+            "delete",
+            "/api/fs/v1/{source_id}/files/{path}",
+            Operation::new("files", "Removes a file or an empty directory")
+                .param(source_id.0, source_id.1.clone())
+                .param(path.0, path.1.clone())
+                .json(null()),
+        ),
+        (
+            "post",
+            "/api/fs/v1/{source_id}/mkdir/{path}",
+            Operation::new("files", "Creates a directory")
+                .param(source_id.0, source_id.1.clone())
+                .param(path.0, path.1.clone())
+                .json(schema_ref("EntryInfo")),
+        ),
+        (
+            "post",
+            "/api/fs/v1/{source_id}/rename",
+            Operation::new("files", "Moves an entry")
+                .param(source_id.0, source_id.1.clone())
+                .body(schema_ref("RenameRequest"))
+                .json(null()),
+        ),
+        (
+            "get",
+            "/api/fs/v1/{source_id}/versions/{path}",
+            Operation::new("versions", "Prior contents of a file, newest first")
+                .param(source_id.0, source_id.1.clone())
+                .param(path.0, path.1.clone())
+                .json(array(schema_ref("VersionInfo"))),
+        ),
+        (
+            "get",
+            "/api/fs/v1/{source_id}/version/{version_id}",
+            Operation::new(
+                "versions",
+                "Content of a version, a single `Range` is supported",
+            )
+            .param(source_id.0, source_id.1.clone())
+            .param(version_id.0, version_id.1.clone())
+            .content(),
+        ),
+        (
+            "delete",
+            "/api/fs/v1/{source_id}/version/{version_id}",
+            Operation::new("versions", "Removes a version")
+                .param(source_id.0, source_id.1.clone())
+                .param(version_id.0, version_id.1.clone())
+                .json(null()),
+        ),
+        (
+            "post",
+            "/api/fs/v1/{source_id}/version/{version_id}/restore",
+            Operation::new(
+                "versions",
+                "Writes the content of a version as the current file",
+            )
+            .param(source_id.0, source_id.1)
+            .param(version_id.0, version_id.1)
+            .json(schema_ref("EntryInfo")),
+        ),
+    ]
+}
+
+struct Operation {
+    value: Map<String, Value>,
+    parameters: Vec<Value>,
+    responses: Map<String, Value>,
+}
+
+impl Operation {
+    fn new(tag: &str, summary: &str) -> Self {
+        let mut value = Map::new();
+        value.insert("tags".to_owned(), json!([tag]));
+        value.insert("summary".to_owned(), json!(summary));
+        let mut responses = Map::new();
+        responses.insert("4XX".to_owned(), schema_ref_response("Error"));
+        responses.insert("5XX".to_owned(), schema_ref_response("Error"));
+        Self {
+            value,
+            parameters: Vec::new(),
+            responses,
+        }
+    }
+
+    /// Works without credentials
+    fn public(mut self) -> Self {
+        self.value.insert("security".to_owned(), json!([]));
+        self
+    }
+
+    fn param(mut self, name: &str, schema: Value) -> Self {
+        self.parameters.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": schema,
+        }));
+        self
+    }
+
+    fn query(mut self, name: &str, schema: Value) -> Self {
+        self.parameters.push(json!({
+            "name": name,
+            "in": "query",
+            "schema": schema,
+        }));
+        self
+    }
+
+    fn body(mut self, schema: Value) -> Self {
+        self.value.insert(
+            "requestBody".to_owned(),
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+        self
+    }
+
+    fn binary_body(mut self) -> Self {
+        self.value.insert(
+            "requestBody".to_owned(),
+            json!({
+                "required": true,
+                "content": { "application/octet-stream": { "schema": {} } },
+            }),
+        );
+        self
+    }
+
+    fn json(mut self, schema: Value) -> Self {
+        self.responses.insert(
+            "200".to_owned(),
+            json!({
+                "description": "OK",
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+        self
+    }
+
+    /// 503 with a body describing why
+    fn unavailable(mut self, schema: Value) -> Self {
+        self.responses.insert(
+            "503".to_owned(),
+            json!({
+                "description": "Service Unavailable",
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+        self
+    }
+
+    fn text(mut self) -> Self {
+        self.responses.insert(
+            "200".to_owned(),
+            json!({
+                "description": "OK",
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            }),
+        );
+        self
+    }
+
+    /// JSON lines, each an object of the named schema
+    fn ndjson(mut self, item: &str) -> Self {
+        self.responses.insert(
+            "200".to_owned(),
+            json!({
+                "description": format!("One `{}` per line", item),
+                "content": { "application/x-ndjson": { "schema": { "type": "string" } } },
+            }),
+        );
+        self
+    }
+
+    /// File content with its guessed content type
+    fn content(mut self) -> Self {
+        self.parameters.push(json!({
+            "name": "Range",
+            "in": "header",
+            "schema": { "type": "string", "examples": ["bytes=0-1023"] },
+        }));
+        self.responses.insert(
+            "200".to_owned(),
+            json!({ "description": "Whole content", "content": { "*/*": { "schema": {} } } }),
+        );
+        self.responses.insert(
+            "206".to_owned(),
+            json!({ "description": "Requested range", "content": { "*/*": { "schema": {} } } }),
+        );
+        self
+    }
+
+    fn into_value(mut self) -> Value {
+        if !self.parameters.is_empty() {
+            self.value
+                .insert("parameters".to_owned(), Value::Array(self.parameters));
+        }
+        self.value
+            .insert("responses".to_owned(), Value::Object(self.responses));
+        Value::Object(self.value)
+    }
+}
+
+/// Filters shared by listing and exporting audit events
+fn audit_query(operation: Operation) -> Operation {
+    operation
+        .query("principal", schema_ref("Id"))
+        .query("action", schema_ref("AuditAction"))
+        .query("outcome", schema_ref("AuditOutcome"))
+        .query("source_id", json!({ "type": "string", "format": "uuid" }))
+        .query("target_prefix", json!({ "type": "string" }))
+        .query("from", schema_ref("ApiDateTime"))
+        .query("to", schema_ref("ApiDateTime"))
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn schema_ref_response(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/responses/{}", name) })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// `()` is sent as JSON `null`
+fn null() -> Value {
+    json!({ "type": "null" })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+fn schemas() -> Value {
+    let string = json!({ "type": "string" });
+    let size = json!({ "type": "integer", "format": "int64", "minimum": 0 });
+    let error_codes: Vec<Value> = [
+        ErrorCode::BadRequest,
+        ErrorCode::Unauthorized,
+        ErrorCode::PaymentRequired,
+        ErrorCode::Forbidden,
+        ErrorCode::NotFound,
+        ErrorCode::Conflict,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::RangeNotSatisfiable,
+        ErrorCode::TooManyRequests,
+        ErrorCode::UnexpectedError,
+    ]
+    .iter()
+    .map(|code| serde_json::to_value(code).unwrap())
+    .collect();
+
+    let mut schemas = json!({
+        "ApiDateTime": {
+            "type": "integer",
+            "format": "int64",
+            "description": "Milliseconds since the Unix epoch",
+        },
+        "ApiDurationSeconds": {
+            "type": "integer",
+            "format": "int64",
+            "description": "Duration in whole seconds",
+        },
+        "Id": { "type": "string", "format": "uuid" },
+        "TraceId": { "type": "string", "format": "uuid" },
+        "StoragePath": {
+            "type": "string",
+            "description": "Path inside a source, `..` segments are rejected",
+            "examples": ["/docs/readme.md"],
+        },
+        "ContentRight": {
+            "type": "array",
+            "items": { "type": "string", "enum": ["read", "write", "all"] },
+            "description": "Rights by name, `all` is accepted on input only",
+        },
+        "ErrorCode": { "type": "string", "enum": error_codes },
+        "ApiError": object(&["code"], json!({
+            "code": schema_ref("ErrorCode"),
+            "message": string,
+            "details": string,
+        })),
+        "LogFilterInfo": object(&["filter"], json!({
+            "filter": {
+                "type": "string",
+                "description": "Comma separated `target=level` directives and a default level",
+                "examples": ["info,sqlx=warn"],
+            },
+        })),
+        "LoginRequest": object(&["username", "password"], json!({
+            "username": string,
+            "password": string,
+        })),
+        "LoginResponse": {
+            "oneOf": [
+                {
+                    "allOf": [
+                        schema_ref("TokenPair"),
+                        object(&["type"], json!({ "type": { "const": "tokens" } })),
+                    ],
+                },
+                {
+                    "allOf": [
+                        schema_ref("TotpChallenge"),
+                        object(&["type"], json!({ "type": { "const": "totp_required" } })),
+                    ],
+                },
+            ],
+            "discriminator": { "propertyName": "type" },
+        },
+        "TotpChallenge": object(&["challenge_token", "expires_at"], json!({
+            "challenge_token": string,
+            "expires_at": schema_ref("ApiDateTime"),
+        })),
+        "LoginTotpRequest": object(&["challenge_token", "code"], json!({
+            "challenge_token": string,
+            "code": { "type": "string", "description": "TOTP code or one of the recovery codes" },
+        })),
+        "TokenPair": object(
+            &["access_token", "access_token_expires_at", "refresh_token", "refresh_token_expires_at"],
+            json!({
+                "access_token": string,
+                "access_token_expires_at": schema_ref("ApiDateTime"),
+                "refresh_token": string,
+                "refresh_token_expires_at": schema_ref("ApiDateTime"),
+            }),
+        ),
+        "TotpEnrollment": object(&["secret", "otpauth_uri"], json!({
+            "secret": { "type": "string", "description": "Base32 encoded secret for manual entry" },
+            "otpauth_uri": string,
+        })),
+        "TotpCodeRequest": object(&["code"], json!({ "code": string })),
+        "TotpDisableRequest": {
+            "oneOf": [
+                object(&["code"], json!({
+                    "code": { "type": "string", "description": "TOTP code or one of the recovery codes" },
+                })),
+                object(&["password"], json!({ "password": string })),
+            ],
+        },
+        "TotpRecoveryCodes": object(&["recovery_codes"], json!({
+            "recovery_codes": array(string.clone()),
+        })),
+        "CreatePersonalAccessTokenRequest": object(&["name", "rights"], json!({
+            "name": string,
+            "rights": schema_ref("ContentRight"),
+            "sources": {
+                "type": "array",
+                "items": { "type": "string", "format": "uuid" },
+                "description": "Limits the token to these sources, all sources if absent",
+            },
+            "path": schema_ref("StoragePath"),
+            "expires_at": schema_ref("ApiDateTime"),
+        })),
+        "PersonalAccessTokenInfo": object(&["token_id", "name", "rights", "created_at"], json!({
+            "token_id": schema_ref("Id"),
+            "name": string,
+            "rights": schema_ref("ContentRight"),
+            "sources": array(json!({ "type": "string", "format": "uuid" })),
+            "path": schema_ref("StoragePath"),
+            "created_at": schema_ref("ApiDateTime"),
+            "expires_at": schema_ref("ApiDateTime"),
+            "last_used_at": schema_ref("ApiDateTime"),
+        })),
+        "CreatedPersonalAccessToken": {
+            "allOf": [
+                schema_ref("PersonalAccessTokenInfo"),
+                object(&["token"], json!({
+                    "token": { "type": "string", "description": "Shown only once" },
+                })),
+            ],
+        },
+        "OidcAuthorization": object(&["authorization_url", "expires_at"], json!({
+            "authorization_url": { "type": "string", "format": "uri" },
+            "expires_at": schema_ref("ApiDateTime"),
+        })),
+        "OidcCallbackRequest": object(&["code", "state"], json!({
+            "code": string,
+            "state": string,
+        })),
+        "SessionCookie": object(&["csrf_token", "expires_at"], json!({
+            "csrf_token": {
+                "type": "string",
+                "description": "Has to be sent in the CSRF header with every unsafe request",
+            },
+            "expires_at": schema_ref("ApiDateTime"),
+        })),
+        "SourceInfo": object(&["id", "name", "rights"], json!({
+            "id": { "type": "string", "format": "uuid" },
+            "name": string,
+            "rights": schema_ref("ContentRight"),
+        })),
+        "DedupStatsInfo": object(
+            &["files", "logical_size", "chunks", "stored_size", "ratio"],
+            json!({
+                "files": size,
+                "logical_size": size,
+                "chunks": size,
+                "stored_size": size,
+                "ratio": { "type": "number", "description": "Logical size divided by stored size" },
+            }),
+        ),
+        "GarbageReportInfo": object(&["removed_chunks", "removed_size"], json!({
+            "removed_chunks": size,
+            "removed_size": size,
+        })),
+        "KeyRotationInfo": object(&["rotated"], json!({
+            "rotated": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Data keys which weren't wrapped with the active master key before",
+            },
+        })),
+        "EntryKind": { "type": "string", "enum": ["file", "directory"] },
+        "EntryInfo": object(&["path", "name", "kind", "size", "modified"], json!({
+            "path": schema_ref("StoragePath"),
+            "name": string,
+            "kind": schema_ref("EntryKind"),
+            "size": size,
+            "modified": schema_ref("ApiDateTime"),
+        })),
+        "RenameRequest": object(&["from", "to"], json!({
+            "from": schema_ref("StoragePath"),
+            "to": schema_ref("StoragePath"),
+        })),
+        "VersionInfo": object(
+            &["id", "path", "replaced_by", "created", "size", "size_delta"],
+            json!({
+                "id": schema_ref("Id"),
+                "path": schema_ref("StoragePath"),
+                "replaced_by": {
+                    "oneOf": [schema_ref("Id"), { "type": "null" }],
+                    "description": "Login whose write replaced the content",
+                },
+                "created": schema_ref("ApiDateTime"),
+                "size": size,
+                "size_delta": { "type": "integer", "format": "int64" },
+            }),
+        ),
+    });
+    for group in [audit_schemas(), health_schemas(), info_schemas()] {
+        if let (Some(schemas), Value::Object(group)) = (schemas.as_object_mut(), group) {
+            schemas.extend(group);
+        }
+    }
+    schemas
+}
+
+/// Kept apart from [schemas], a single `json!` would exceed the recursion limit
+fn audit_schemas() -> Value {
+    let string = json!({ "type": "string" });
+    let size = json!({ "type": "integer", "format": "int64", "minimum": 0 });
+    let audit_actions: Vec<Value> = [
+        AuditAction::Login,
+        AuditAction::Logout,
+        AuditAction::TotpEnabled,
+        AuditAction::TotpDisabled,
+        AuditAction::TokenCreated,
+        AuditAction::TokenRevoked,
+        AuditAction::RightsChanged,
+        AuditAction::FileRead,
+        AuditAction::FileWritten,
+        AuditAction::FileDeleted,
+        AuditAction::FileMoved,
+        AuditAction::DirectoryCreated,
+        AuditAction::VersionRestored,
+        AuditAction::VersionDeleted,
+        AuditAction::DataKeysRotated,
+    ]
+    .iter()
+    .map(|action| serde_json::to_value(action).unwrap())
+    .collect();
+
+    json!({
+        "AuditAction": { "type": "string", "enum": audit_actions },
+        "AuditOutcome": { "type": "string", "enum": ["success", "failure"] },
+        "ChainHash": {
+            "type": "string",
+            "pattern": "^[0-9a-f]{64}$",
+            "description": "Hex SHA-256, zeros before the first event",
+        },
+        "AuditEventInfo": object(
+            &["sequence", "timestamp", "action", "outcome", "trace_id", "previous_hash", "hash"],
+            json!({
+                "sequence": size,
+                "timestamp": schema_ref("ApiDateTime"),
+                "action": schema_ref("AuditAction"),
+                "outcome": schema_ref("AuditOutcome"),
+                "reason": { "type": "string", "examples": ["invalid_credentials"] },
+                "principal": schema_ref("Id"),
+                "trace_id": schema_ref("TraceId"),
+                "client_ip": string,
+                "source_id": { "type": "string", "format": "uuid" },
+                "target": {
+                    "type": "string",
+                    "description": "Path, username, token or login the action is about",
+                },
+                "detail": {
+                    "type": "string",
+                    "description": "Destination of a move, version id or rights granted",
+                },
+                "previous_hash": schema_ref("ChainHash"),
+                "hash": schema_ref("ChainHash"),
+            }),
+        ),
+        "AuditEventPage": object(&["events"], json!({
+            "events": array(schema_ref("AuditEventInfo")),
+            "next_after": {
+                "type": "integer",
+                "format": "int64",
+                "description": "`after` of the next page, absent on the last one",
+            },
+        })),
+        "AuditVerification": object(&["valid", "events"], json!({
+            "valid": { "type": "boolean" },
+            "events": size,
+            "first_invalid": {
+                "type": "integer",
+                "format": "int64",
+                "description": "Sequence number of the first changed or removed event",
+            },
+        })),
+    })
+}
+
+fn health_schemas() -> Value {
+    json!({
+        "ApiDuration": {
+            "type": "integer",
+            "format": "int64",
+            "description": "Duration in milliseconds",
+        },
+        "CheckStatus": { "type": "string", "enum": ["pass", "fail"] },
+        "HealthCheckInfo": object(&["name", "status", "duration"], json!({
+            "name": {
+                "type": "string",
+                "examples": ["database", "migrations", "source:docs", "shutdown"],
+            },
+            "status": schema_ref("CheckStatus"),
+            "duration": schema_ref("ApiDuration"),
+            "message": { "type": "string", "description": "Why it failed, or what was found" },
+        })),
+        "HealthReport": object(&["status"], json!({
+            "status": schema_ref("CheckStatus"),
+            "checks": array(schema_ref("HealthCheckInfo")),
+        })),
+    })
+}
+
+fn info_schemas() -> Value {
+    let string = json!({ "type": "string" });
+    let size = json!({ "type": "integer", "format": "int64", "minimum": 0 });
+    json!({
+        "Info": object(
+            &["now", "trace_id", "build", "features", "auth", "limits", "sources"],
+            json!({
+                "now": schema_ref("ApiDateTime"),
+                "trace_id": schema_ref("TraceId"),
+                "build": schema_ref("BuildInfo"),
+                "features": array(json!({
+                    "type": "string",
+                    "enum": ["audit", "compression", "metrics", "otlp", "rate_limit", "tls"],
+                })),
+                "auth": schema_ref("AuthInfo"),
+                "limits": schema_ref("LimitsInfo"),
+                "sources": {
+                    "type": "array",
+                    "items": schema_ref("SourceCapabilities"),
+                    "description": "Sources the caller can read, empty for anonymous requests",
+                },
+            }),
+        ),
+        "BuildInfo": object(&["version"], json!({
+            "version": string,
+            "commit": { "type": "string", "description": "Git commit, if known at build time" },
+            "built_at": schema_ref("ApiDateTime"),
+        })),
+        "AuthInfo": object(&["methods", "oidc_providers"], json!({
+            "methods": array(json!({
+                "type": "string",
+                "enum": ["bearer", "basic", "cookie", "client_certificate"],
+            })),
+            "oidc_providers": array(string.clone()),
+        })),
+        "LimitsInfo": object(&["max_json_body"], json!({
+            "max_json_body": size,
+            "max_file_size": {
+                "type": "integer",
+                "format": "int64",
+                "minimum": 0,
+                "description": "Unlimited if absent",
+            },
+        })),
+        "SourceCapabilities": object(
+            &["id", "name", "rights", "read_only", "versioning", "trash", "encrypted"],
+            json!({
+                "id": { "type": "string", "format": "uuid" },
+                "name": string,
+                "rights": schema_ref("ContentRight"),
+                "read_only": { "type": "boolean" },
+                "versioning": { "type": "boolean" },
+                "trash": {
+                    "type": "boolean",
+                    "description": "Deleted files can be restored, not supported yet",
+                },
+                "encrypted": { "type": "boolean" },
+            }),
+        ),
+    })
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        audit::{audit_event::AuditOutcome, chain_hash::ChainHash},
+        auth::content_right::ContentRight,
+        fs::{storage_backend::EntryKind, storage_path::StoragePath},
+        health::health_check::CheckStatus,
+        test::*,
+        utc,
+        utils::{id::Id, trace_id::TraceId},
+        web::{
+            common::{api_error::ApiError, serde_chrono::ApiDateTime},
+            routes::{
+                audit::{AuditEventInfo, AuditEventPage, AuditVerification},
+                encryption::KeyRotationInfo,
+                fs::files::EntryInfo,
+                health::{HealthCheckInfo, HealthReport},
+                info::{AuthInfo, BuildInfo, Info, LimitsInfo, SourceCapabilities},
+                logging::LogFilterInfo,
+            },
+        },
+    };
+    use actix_web::http::StatusCode;
+    use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use uuid::Uuid;
+
+    fn document_schema(name: &str) -> Value {
+        document(&AuthConfig::default())["components"]["schemas"][name].clone()
+    }
+
+    /// Every serialized field is described and every required field is serialized
+    fn assert_schema_fields(name: &str, value: impl serde::Serialize) {
+        let schema = document_schema(name);
+        let value = serde_json::to_value(value).unwrap();
+        let fields: BTreeSet<_> = value.as_object().unwrap().keys().cloned().collect();
+        let properties: BTreeSet<_> = schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        assert!(fields.is_subset(&properties), "{}: {:?}", name, fields);
+        for required in schema["required"].as_array().unwrap() {
+            assert!(
+                fields.contains(required.as_str().unwrap()),
+                "{}: {}",
+                name,
+                required
+            );
+        }
+    }
+
+    /// Documented path with its parameters filled in
+    fn example_uri(path: &str) -> String {
+        path.replace("{source_id}", &Uuid::nil().to_string())
+            .replace("{version_id}", &Id::from_u128(1).to_string())
+            .replace("{token_id}", &Id::from_u128(1).to_string())
+            .replace("{provider}", "corp")
+            .replace("{path}", "a.txt")
+    }
+
+    #[test]
+    fn documented_routes_exist() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| {
+                config["auth"]["oidc"]["providers"] = json!([{
+                    "name": "corp",
+                    "issuer": "http://127.0.0.1:1",
+                    "client_id": "client",
+                    "redirect_uri": "http://localhost/oidc/callback",
+                }]);
+            });
+            let server = ctx.run_server().await;
+            let document = document(&AuthConfig::default());
+            let operations: Vec<(String, String)> = document["paths"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .flat_map(|(path, item)| {
+                    item.as_object()
+                        .unwrap()
+                        .keys()
+                        .map(|method| (method.to_uppercase(), path.clone()))
+                })
+                .collect();
+
+            // act
+            let unknown = server.client().get("/api/unknown/v1").send().await;
+            let mut statuses = Vec::new();
+            for (method, path) in &operations {
+                let response = server
+                    .client()
+                    .request(method.parse().unwrap(), &example_uri(path))
+                    .send()
+                    .await;
+                statuses.push((method, path, response.status));
+            }
+
+            // assert
+            assert_eq!(unknown.status, StatusCode::NOT_FOUND);
+            let missing: Vec<_> = statuses
+                .into_iter()
+                .filter(|(_, _, status)| {
+                    matches!(
+                        *status,
+                        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+                    )
+                })
+                .collect();
+            assert_eq!(missing, Vec::new());
+        })
+    }
+
+    #[test]
+    fn references_resolve() {
+        let document = document(&AuthConfig::default());
+        let text = document.to_string();
+        for reference in text.split("\"$ref\":\"").skip(1) {
+            let pointer = reference.split('"').next().unwrap();
+            assert!(
+                document.pointer(pointer.trim_start_matches('#')).is_some(),
+                "{}",
+                pointer
+            );
+        }
+    }
+
+    #[test]
+    fn schemas_match_types() {
+        assert_schema_fields(
+            "Info",
+            Info {
+                now: utc!(2024, 1, 1).into(),
+                trace_id: TraceId::default(),
+                build: BuildInfo {
+                    version: "1.0.0".to_owned(),
+                    commit: Some("abc".to_owned()),
+                    built_at: Some(utc!(2024, 1, 1).into()),
+                },
+                features: vec!["audit".to_owned()],
+                auth: AuthInfo {
+                    methods: vec!["bearer".to_owned()],
+                    oidc_providers: Vec::new(),
+                },
+                limits: LimitsInfo {
+                    max_json_body: 1,
+                    max_file_size: Some(1),
+                },
+                sources: Vec::new(),
+            },
+        );
+        assert_schema_fields(
+            "BuildInfo",
+            BuildInfo {
+                version: "1.0.0".to_owned(),
+                commit: Some("abc".to_owned()),
+                built_at: Some(utc!(2024, 1, 1).into()),
+            },
+        );
+        assert_schema_fields(
+            "LimitsInfo",
+            LimitsInfo {
+                max_json_body: 1,
+                max_file_size: Some(1),
+            },
+        );
+        assert_schema_fields(
+            "SourceCapabilities",
+            SourceCapabilities {
+                id: uuid::Uuid::nil(),
+                name: "docs".to_owned(),
+                rights: ContentRight::Read,
+                read_only: false,
+                versioning: true,
+                trash: false,
+                encrypted: true,
+            },
+        );
+        assert_schema_fields(
+            "EntryInfo",
+            EntryInfo {
+                path: StoragePath::parse("a.txt").unwrap(),
+                name: "a.txt".to_owned(),
+                kind: EntryKind::File,
+                size: 1,
+                modified: utc!(2024, 1, 1).into(),
+            },
+        );
+        assert_schema_fields("KeyRotationInfo", KeyRotationInfo { rotated: 1 });
+        assert_schema_fields(
+            "LogFilterInfo",
+            LogFilterInfo {
+                filter: "info".to_owned(),
+            },
+        );
+        let event = AuditEventInfo {
+            sequence: 1,
+            timestamp: utc!(2024, 1, 1).into(),
+            action: AuditAction::FileMoved,
+            outcome: AuditOutcome::Failure,
+            reason: Some("x".to_owned()),
+            principal: Some(Id::from_u128(1)),
+            trace_id: TraceId::default(),
+            client_ip: Some("127.0.0.1".parse().unwrap()),
+            source_id: Some(uuid::Uuid::nil()),
+            target: Some("/a.txt".to_owned()),
+            detail: Some("/b.txt".to_owned()),
+            previous_hash: ChainHash::GENESIS,
+            hash: ChainHash::GENESIS,
+        };
+        assert_schema_fields("AuditEventInfo", &event);
+        assert_schema_fields(
+            "AuditEventPage",
+            AuditEventPage {
+                events: vec![event],
+                next_after: Some(1),
+            },
+        );
+        assert_schema_fields(
+            "AuditVerification",
+            AuditVerification {
+                valid: false,
+                events: 1,
+                first_invalid: Some(2),
+            },
+        );
+        let check = HealthCheckInfo {
+            name: "database".to_owned(),
+            status: CheckStatus::Fail,
+            duration: chrono::Duration::milliseconds(3).into(),
+            message: Some("down".to_owned()),
+        };
+        assert_schema_fields("HealthCheckInfo", &check);
+        assert_schema_fields(
+            "HealthReport",
+            HealthReport {
+                status: CheckStatus::Fail,
+                checks: vec![check],
+            },
+        );
+        assert_schema_fields(
+            "ApiError",
+            ApiError::not_found().message("x".to_owned()).build(),
+        );
+        assert_eq!(
+            serde_json::to_value(ApiDateTime::from(utc!(2024, 1, 1))).unwrap(),
+            json!(1_704_067_200_000i64)
+        );
+    }
+
+    #[test]
+    fn serves_document() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server.client().get("/api/openapi.json").send().await;
+
+            // assert
+            assert_eq!(response.status, StatusCode::OK);
+            let document: Value = response.unwrap();
+            assert_eq!(document["openapi"], json!("3.1.0"));
+            assert_eq!(
+                document["components"]["securitySchemes"]["bearer"]["scheme"],
+                json!("bearer")
+            );
+            assert_eq!(
+                document["components"]["securitySchemes"]["cookie"]["name"],
+                json!("rhfs_access")
+            );
+        })
+    }
+}
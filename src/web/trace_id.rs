@@ -1,26 +1,47 @@
 use std::{
     future::{ready, Ready},
+    rc::Rc,
+    str::FromStr,
     sync::Arc,
 };
 
-use actix_http::{header::TryIntoHeaderValue, HttpMessage};
+use actix_http::{
+    header::{HeaderValue, TryIntoHeaderValue},
+    HttpMessage,
+};
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error,
 };
 use futures::future::LocalBoxFuture;
-use tracing::Instrument;
+use tracing::{field, Instrument};
 
-use crate::utils::id_generator::IdGenerator;
+use crate::{
+    config::app_config::ProxiesConfig,
+    logging::redacted_headers::RedactedHeaders,
+    telemetry::trace_context::{traceparent, TraceContext, TRACEPARENT, TRACESTATE},
+    utils::{id_generator::IdGenerator, span_id::SpanId, trace_id::TraceId},
+};
 
-use super::app_data::AppData;
+use super::{
+    app_data::AppData,
+    forwarded::{client_ip, ClientIp},
+};
 
-#[derive(Debug, Default)]
-pub struct TraceIdMiddlewareFactory<D>(Arc<D>);
+/// Trace ids come from `traceparent`, then from `x-traceid` of a trusted proxy,
+/// and are generated otherwise
+#[derive(Debug)]
+pub struct TraceIdMiddlewareFactory<D> {
+    data: Arc<D>,
+    proxies: Rc<ProxiesConfig>,
+}
 
 impl<D> TraceIdMiddlewareFactory<D> {
-    pub fn new(data: Arc<D>) -> Self {
-        Self(data)
+    pub fn new(data: Arc<D>, proxies: ProxiesConfig) -> Self {
+        Self {
+            data,
+            proxies: Rc::new(proxies),
+        }
     }
 }
 
@@ -39,7 +60,8 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(TraceIdMiddleware {
             service,
-            app_data: self.0.clone(),
+            app_data: self.data.clone(),
+            proxies: self.proxies.clone(),
         }))
     }
 }
@@ -47,6 +69,7 @@ where
 pub struct TraceIdMiddleware<S, D> {
     service: S,
     app_data: Arc<D>,
+    proxies: Rc<ProxiesConfig>,
 }
 
 #[allow(clippy::declare_interior_mutable_const)]
@@ -66,16 +89,43 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let trace_id = self.app_data.trace_id().next_id();
+        let peer = req.peer_addr().map(|a| a.ip());
+        let client_ip = client_ip(req.headers(), peer, &self.proxies);
+        let context = TraceContext::from_headers(req.headers());
+        let trace_id = match &context {
+            Some(context) => context.trace_id,
+            None => peer
+                .filter(|peer| self.proxies.is_trusted(*peer))
+                .and_then(|_| req.headers().get(HEADER_NAME))
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| TraceId::from_str(value).ok())
+                .unwrap_or_else(|| self.app_data.trace_id().next_id()),
+        };
+        let span_id = SpanId::random();
+        let sampled = context.as_ref().is_none_or(|c| c.sampled);
         req.extensions_mut().insert(trace_id);
-        let span = tracing::info_span!("req", trace_id = %trace_id);
+        if let Some(ip) = client_ip {
+            req.extensions_mut().insert(ClientIp(ip));
+        }
         let path = req.path().to_owned();
         let method = req.method().as_ref().to_owned();
+        let span = tracing::info_span!(
+            "req",
+            trace_id = %trace_id,
+            span_id = %span_id,
+            parent_span_id = context.as_ref().map(|c| field::display(c.parent_id)),
+            sampled = sampled,
+            method = method,
+            path = path,
+            client_ip = client_ip.map(field::display),
+            status = field::Empty,
+        );
 
         let started = std::time::Instant::now();
         let fut = {
             let _guard = span.enter();
             tracing::info!(path = path, method = method, "request starting...");
+            tracing::debug!(headers = ?RedactedHeaders(req.headers()), "request headers");
             self.service.call(req)
         }
         .instrument(span.clone());
@@ -83,9 +133,19 @@ where
             async move {
                 let mut res = fut.await?;
                 let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
-                res.headers_mut()
-                    .insert(HEADER_NAME, trace_id.try_into_value().unwrap());
+                let headers = res.headers_mut();
+                headers.insert(HEADER_NAME, trace_id.try_into_value().unwrap());
+                headers.insert(
+                    TRACEPARENT,
+                    HeaderValue::from_str(&traceparent(trace_id, span_id, sampled)).unwrap(),
+                );
+                if let Some(state) = context.and_then(|c| c.state) {
+                    if let Ok(state) = HeaderValue::from_str(&state) {
+                        headers.insert(TRACESTATE, state);
+                    }
+                }
                 let status = res.status().as_u16();
+                tracing::Span::current().record("status", status);
                 tracing::info!(
                     path = path,
                     method = method,
@@ -113,6 +173,7 @@ mod tests {
     };
     use actix_web::web;
     use pretty_assertions::{assert_eq, assert_ne, assert_str_eq};
+    use serde_json::json;
 
     #[test]
     fn trace_id() {
@@ -151,4 +212,265 @@ mod tests {
             assert_eq!(response_trace_id, log_trace_id);
         });
     }
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    fn header<'a>(response: &'a client::TestHttpResponse, name: &str) -> Option<&'a str> {
+        response.headers.get(name).map(|v| v.to_str().unwrap())
+    }
+
+    #[test]
+    fn continues_incoming_trace() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("traceparent", format!("00-{}-{}-01", TRACE_ID, PARENT_ID)))
+                .insert_header(("tracestate", "congo=t61rcWkgMzE"))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.trace_id().to_string(), TRACE_ID);
+            let traceparent = header(&response, "traceparent").unwrap();
+            let fields: Vec<_> = traceparent.split('-').collect();
+            assert_eq!(fields[0], "00");
+            assert_eq!(fields[1], TRACE_ID);
+            assert_ne!(fields[2], PARENT_ID);
+            assert_eq!(fields[3], "01");
+            assert_eq!(header(&response, "tracestate"), Some("congo=t61rcWkgMzE"));
+        });
+    }
+
+    #[test]
+    fn invalid_traceparent_starts_a_trace() {
+        test(|ctx| async move {
+            // arrange
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header((
+                    "traceparent",
+                    format!("00-{}-{}-01", "0".repeat(32), PARENT_ID),
+                ))
+                .insert_header(("tracestate", "congo=t61rcWkgMzE"))
+                .send()
+                .await;
+
+            // assert
+            let trace_id = response.trace_id().to_string();
+            assert_ne!(trace_id, "0".repeat(32));
+            assert!(header(&response, "traceparent")
+                .unwrap()
+                .starts_with(&format!("00-{}-", trace_id)));
+            assert_eq!(header(&response, "tracestate"), None);
+        });
+    }
+
+    #[test]
+    fn exports_spans() {
+        test(|ctx| async move {
+            // arrange
+            let collector = mock_otlp::MockOtlp::start().await;
+            ctx.update_config(|config| config["tracing"]["otlp"] = collector.config());
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("traceparent", format!("00-{}-{}-01", TRACE_ID, PARENT_ID)))
+                .send()
+                .await;
+
+            // assert
+            let span_id = header(&response, "traceparent").unwrap()[36..52].to_owned();
+            let req = collector.wait_for_span("req").await;
+            let principal = collector.wait_for_span("principal").await;
+            assert_eq!(req["traceId"], TRACE_ID);
+            assert_eq!(req["spanId"], span_id);
+            assert_eq!(req["parentSpanId"], PARENT_ID);
+            assert_eq!(req["kind"], 2);
+            assert!(req["attributes"].as_array().unwrap().contains(
+                &serde_json::json!({ "key": "path", "value": { "stringValue": "/api/info/v1" } })
+            ));
+            assert_eq!(principal["traceId"], TRACE_ID);
+            assert_eq!(principal["parentSpanId"], span_id);
+            assert_eq!(principal["kind"], 1);
+
+            let (headers, body) = collector.requests().remove(0);
+            assert_eq!(headers.get("x-api-key").unwrap(), "secret");
+            assert_eq!(
+                body["resourceSpans"][0]["resource"]["attributes"][0],
+                serde_json::json!({ "key": "service.name", "value": { "stringValue": "rusty-http-fs" } })
+            );
+        });
+    }
+
+    #[test]
+    fn unsampled_traces_are_not_exported() {
+        test(|ctx| async move {
+            // arrange
+            let collector = mock_otlp::MockOtlp::start().await;
+            ctx.update_config(|config| config["tracing"]["otlp"] = collector.config());
+            let server = ctx.run_server().await;
+
+            // act
+            let unsampled = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("traceparent", format!("00-{}-{}-00", TRACE_ID, PARENT_ID)))
+                .send()
+                .await;
+            server.client().get("/api/info/v1").send().await;
+
+            // assert
+            assert!(header(&unsampled, "traceparent").unwrap().ends_with("-00"));
+            collector.wait_for_span("req").await;
+            assert!(collector
+                .spans()
+                .iter()
+                .all(|span| span["traceId"] != TRACE_ID));
+        });
+    }
+
+    const PROXY_TRACE_ID: &str = "0192f6a3b1c27d3e8f4a5b6c7d8e9fa0";
+
+    #[test]
+    fn reuses_trace_id_of_trusted_proxy() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["proxies"]["trusted"] = json!(["127.0.0.0/8"]));
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("x-traceid", PROXY_TRACE_ID))
+                .send()
+                .await;
+            let invalid = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("x-traceid", "not-a-trace-id"))
+                .send()
+                .await;
+            let with_traceparent = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("x-traceid", PROXY_TRACE_ID))
+                .insert_header(("traceparent", format!("00-{}-{}-01", TRACE_ID, PARENT_ID)))
+                .send()
+                .await;
+
+            // assert
+            assert_eq!(response.trace_id().to_string(), PROXY_TRACE_ID);
+            assert!(header(&response, "traceparent")
+                .unwrap()
+                .starts_with(&format!("00-{}-", PROXY_TRACE_ID)));
+            assert_ne!(invalid.trace_id().to_string(), PROXY_TRACE_ID);
+            assert_eq!(with_traceparent.trace_id().to_string(), TRACE_ID);
+        });
+    }
+
+    #[test]
+    fn ignores_trace_id_of_untrusted_peer() {
+        test(|ctx| async move {
+            // arrange
+            ctx.update_config(|config| config["proxies"]["trusted"] = json!(["10.0.0.0/8"]));
+            let server = ctx.run_server().await;
+
+            // act
+            let response = server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("x-traceid", PROXY_TRACE_ID))
+                .send()
+                .await;
+
+            // assert
+            assert_ne!(response.trace_id().to_string(), PROXY_TRACE_ID);
+        });
+    }
+
+    #[test]
+    fn records_client_ip() {
+        test(|ctx| async move {
+            // arrange
+            ctx.logs().write_always();
+            ctx.update_config(|config| config["proxies"]["trusted"] = json!(["127.0.0.1"]));
+            let server = ctx.run_server().await;
+
+            // act
+            ctx.logs().clear();
+            server
+                .client()
+                .get("/api/info/v1")
+                .insert_header(("x-forwarded-for", "198.51.100.7, 127.0.0.1"))
+                .send()
+                .await;
+
+            // assert
+            let client_ip = ctx
+                .logs()
+                .get(|e| e.message() == "request starting...")
+                .must_have_span_field_value::<String>("req", "client_ip");
+            assert_eq!(client_ip, "198.51.100.7");
+        });
+    }
+
+    #[test]
+    fn request_headers_are_logged_without_credentials() {
+        test(|ctx| async move {
+            // arrange
+            let login = ctx.create_login("user", "password").await;
+            let token = ctx.access_token_for(&login);
+            let server = ctx.run_server().await;
+
+            // act
+            ctx.logs().clear();
+            server
+                .client()
+                .get("/api/info/v1")
+                .access_token(&token)
+                .insert_header(("accept-language", "de"))
+                .send()
+                .await;
+
+            // assert
+            let headers = ctx
+                .logs()
+                .get(|e| e.message() == "request headers")
+                .field("headers")
+                .unwrap()
+                .value()
+                .to_owned();
+            assert!(
+                headers.contains(r#""accept-language": "de""#),
+                "{}",
+                headers
+            );
+            assert!(
+                headers.contains(r#""authorization": "[redacted]""#),
+                "{}",
+                headers
+            );
+            // the test client logs what it sends
+            assert!(!ctx
+                .logs()
+                .get_all()
+                .iter()
+                .filter(|e| !e.target().starts_with("rusty_http_fs::test"))
+                .any(|e| e.fields().iter().any(|f| f.value().contains(&token))));
+        });
+    }
 }